@@ -14,11 +14,23 @@ pub fn explain(feature: &Option<String>, extra: &Option<String>) {
         println!("    bibliography-title    #[bibliography-title] New title");
         println!("    toc                   #[toc] table of contents title here");
         println!("    image                 [[path/to/image] alt text here]");
+        println!("    media                 ![[path/to/video.mp4] title here]");
+        println!("    transclusion          ![[other-post.md#Some Heading]]");
         println!("    link                  [[http://example.link]]");
+        println!("    wiki link             [[other-post|some label]]");
         println!("    header                # header text here");
         println!("    codeblock             ```lang_name\\n text here ```");
+        println!("    math                  $x^2$ or $$\\frac{{a}}{{b}}$$");
+        println!("    callout               > [!warning] title here");
+        println!("    definition            term here\\n: definition here");
+        println!("    figures               see [@fig:image-id]");
         println!("    bold                  **bold text here**");
         println!("    italics               *italics text here*");
+        println!("    strikethrough         ~~struck text here~~");
+        println!("    underline             ++underlined text here++");
+        println!("    highlight             ==highlighted text here==");
+        println!("    superscript           x^2^");
+        println!("    subscript             H~2~O");
         println!("    hover                 %[base text](hovered text)");
         println!("    style                 £{{css_style: here;}}(text)");
         println!("    link                  [link text](https://link.here)");
@@ -164,9 +176,61 @@ pub fn explain(feature: &Option<String>, extra: &Option<String>) {
                 println!();
                 println!("Example: ");
                 println!("    [[path/to/image.png] an image, with alt text]");
+                println!("    [[path/to/image.png|600x400] an image that is 600 by 400 pixels]");
                 println!();
                 println!("Note: ");
                 println!("    uses Open Graph or Twitter meta tags to get embedding");
+                println!("    the size after '|' is a width, or a width and a height split by 'x'.");
+                println!("    numbers without a unit are pixels, any css unit like '50%' or '20em' works,");
+                println!("    and either side can be 'auto' or left out, like '600x' or 'x400'");
+                println!();
+            },
+            "wiki" | "wiki-link" | "wiki-links" | "backlink" | "backlinks" => {
+                println!("Wiki links:");
+                println!();
+                println!("Example: ");
+                println!("    [[other-post]]");
+                println!("    [[other-post|some label]]");
+                println!("    [[Other Post#Some Heading]]");
+                println!("    [[#Some Heading]]");
+                println!();
+                println!("Note: ");
+                println!("    links to another post by its file name without the extension, or by its title");
+                println!("    after the '#' is a heading in that post, without a post it's a heading in this one");
+                println!("    only the posts given to the same command can be linked to, their urls are made from");
+                println!("    the 'url' and 'blog_dir' in their frontmatter and links to posts that aren't there are reported");
+                println!("    posts that are linked to get a list of the posts linking to them at the bottom,");
+                println!("    its title is set with 'backlinks-title' in the frontmatter");
+                println!();
+            },
+            "transclusion" | "transclusions" | "include" | "includes" => {
+                println!("Transclusion:");
+                println!();
+                println!("Example: ");
+                println!("    ![[shared/disclaimer.md]]");
+                println!("    ![[other-post.pmd#Some Heading]]");
+                println!();
+                println!("Note: ");
+                println!("    puts another .md or .pmd document where the embed is, after the '#' is the text or id of");
+                println!("    a heading to only include that section, which runs until the next heading that isn't under it");
+                println!("    paths are relative to the file doing the including, and a document can't end up including itself");
+                println!("    the frontmatter and table of contents of the included document are left out,");
+                println!("    its notes and references are added to the ones of the document including it");
+                println!();
+            },
+            "media" | "video" | "videos" | "audio" | "iframe" | "iframes" | "youtube" | "peertube" => {
+                println!("Media:");
+                println!();
+                println!("Example: ");
+                println!("    ![[clips/intro.mp4|640x360] the intro]");
+                println!("    ![[podcast/episode-1.mp3] the first episode]");
+                println!("    ![[https://www.youtube.com/watch?v=dQw4w9WgXcQ] a video from youtube]");
+                println!();
+                println!("Note: ");
+                println!("    the same embeds as images, mp4, webm, ogv, mov and m4v files are videos,");
+                println!("    mp3, ogg, oga, wav, flac, m4a, opus and aac files are audio");
+                println!("    youtube and peertube links are shown in a sandboxed iframe, youtube through youtube-nocookie.com");
+                println!("    rss, text and pdf output can't play anything so they link to what was embedded instead");
                 println!();
             },
             "header" | "headers" => {
@@ -187,7 +251,81 @@ pub fn explain(feature: &Option<String>, extra: &Option<String>) {
                 println!("    code here");
                 println!("    ```");
                 println!();
+                println!("    ```rust title=\"main.rs\" showLineNumbers=10 {{2,4-5}} diff");
+                println!("    code here");
+                println!("    ```");
+                println!();
+                println!("Note: ");
+                println!("    everything after the language is optional and can come in any order");
+                println!("    title=\"...\"        a title shown above the code");
+                println!("    showLineNumbers    number the lines, starting from 1 or from showLineNumbers=N");
+                println!("    {{1,3-5}}            highlight lines, counted from the top of the block");
+                println!("    diff               lines starting with + or - are shown as added or removed");
+                println!();
+            },
+            "math" => {
+                println!("Math:");
+                println!();
+                println!("Example: ");
+                println!("    the area is $\\pi r^2$ and");
+                println!("    $$");
+                println!("    x = \\frac{{-b \\pm \\sqrt{{b^2 - 4ac}}}}{{2a}}");
+                println!("    $$");
+                println!();
+                println!("Note: ");
+                println!("    formulas are written in tex and turned into mathml, or typeset in pdf output");
+                println!("    inline math can't start or end with a space and the closing $ can't be followed by a digit,");
+                println!("    so \"$5 and $10\" stays text, write \\$ for a dollar sign that should never start math");
+                println!("    supported: \\frac, \\sqrt[n]{{}}, ^ and _, \\left( \\right), \\text, \\mathbf, \\mathrm, \\mathbb,");
+                println!("    greek letters, \\sum, \\int, \\prod, \\sin and other functions, accents like \\hat and \\vec,");
+                println!("    and the usual operators, arrows and relations");
+                println!();
+            },
+            "callout" | "callouts" | "admonition" | "admonitions" => {
+                println!("Callouts:");
+                println!();
+                println!("Example: ");
+                println!("    > [!warning] Mind the gap");
+                println!("    > the body is a quote, so it can hold");
+                println!("    > - lists, code and other blocks");
+                println!();
+                println!("Note: ");
+                println!("    the kind goes between [! and ], note, tip, warning and danger get their own colour in pdf output");
+                println!("    without a title the kind is used, \"[!tip]-\" starts out collapsed in html");
+                println!("    callouts are left out of the table of contents unless the frontmatter has toc-callouts");
+                println!();
+            },
+            "definition" | "definitions" | "glossary" => {
+                println!("Definitions:");
+                println!();
+                println!("Example: ");
+                println!("    Latency");
+                println!("    : how long one request takes");
+                println!("      from start to end");
+                println!("    : not the same as throughput");
+                println!();
                 println!("Note: ");
+                println!("    a blank line and another term keep the list going");
+                println!("    every term is also put in a glossary at the end, sorted and with the definitions of a term merged,");
+                println!("    glossary-title in the frontmatter renames it and hide-glossary leaves it out");
+                println!();
+            },
+            "figure" | "figures" | "cross-reference" | "cross-references" => {
+                println!("Figures:");
+                println!();
+                println!("Example: ");
+                println!("    ---");
+                println!("    figures: true");
+                println!("    ---");
+                println!("    the results are in [@fig:Latency-over-time] and [@tbl:table-0]");
+                println!();
+                println!("    ![[latency.png] Latency over time]");
+                println!();
+                println!("Note: ");
+                println!("    with figures in the frontmatter images, tables and code blocks are numbered,");
+                println!("    the text after an image becomes its caption, like \"Figure 1: Latency over time\"");
+                println!("    [@fig:id], [@tbl:id] and [@lst:id] are replaced with the number of whatever has that id,");
+                println!("    an image gets its id from the caption, the others count up from table-0 and codeblock");
                 println!();
             },
             "bold"      => {
@@ -208,6 +346,17 @@ pub fn explain(feature: &Option<String>, extra: &Option<String>) {
                 println!("Note: ");
                 println!();
             },
+            "strikethrough" | "underline" | "highlight" | "superscript" | "subscript" => {
+                println!("Strikethrough, Underline, Highlight, Superscript and Subscript:");
+                println!();
+                println!("Example: ");
+                println!("    ~~struck~~ ++underlined++ ==highlighted== x^2^ H~2~O");
+                println!();
+                println!("Note: ");
+                println!("    the text can't start or end with a space, so \"a == b\" and \"C++\" are left alone");
+                println!("    superscripts and subscripts can't contain spaces at all, escape them like x^a\\ b^");
+                println!();
+            },
             "hover"     => {
                 println!("Hover:");
                 println!();
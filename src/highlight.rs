@@ -0,0 +1,514 @@
+use std::ops::Range;
+
+// a small syntax highlighter so code blocks get their colours when the post is built instead of
+// from a script in the reader's browser, it only knows the rough shape of a handful of languages
+// but that's enough for keywords, strings, numbers and comments
+
+pub const DEFAULT_THEME: &str = "light";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    Type,
+    String,
+    Number,
+    Comment,
+    Function,
+}
+
+impl TokenKind {
+    pub const ALL: [TokenKind; 7] = [
+        TokenKind::Plain, TokenKind::Keyword, TokenKind::Type, TokenKind::String,
+        TokenKind::Number, TokenKind::Comment, TokenKind::Function,
+    ];
+
+    pub fn class(self) -> &'static str {
+        match self {
+            TokenKind::Plain    => "hl-plain",
+            TokenKind::Keyword  => "hl-keyword",
+            TokenKind::Type     => "hl-type",
+            TokenKind::String   => "hl-string",
+            TokenKind::Number   => "hl-number",
+            TokenKind::Comment  => "hl-comment",
+            TokenKind::Function => "hl-function",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+}
+
+struct Language {
+    names: &'static [&'static str],
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    // identifiers starting with an uppercase letter are types
+    capitalised_types: bool,
+    // `name!(..)` is a call
+    macros: bool,
+    // `'a` is a lifetime and not the start of a character
+    lifetimes: bool,
+}
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "const", "continue", "default", "do", "else", "enum", "extern", "for",
+    "goto", "if", "inline", "register", "restrict", "return", "sizeof", "static", "struct", "switch",
+    "typedef", "union", "volatile", "while", "true", "false", "NULL",
+    "include", "define", "ifdef", "ifndef", "endif", "pragma",
+];
+const C_TYPES: &[&str] = &[
+    "void", "char", "short", "int", "long", "float", "double", "signed", "unsigned", "bool", "size_t",
+    "int8_t", "int16_t", "int32_t", "int64_t", "uint8_t", "uint16_t", "uint32_t", "uint64_t",
+];
+const CPP_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "const", "continue", "default", "do", "else", "enum", "extern", "for",
+    "goto", "if", "inline", "register", "return", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "volatile", "while", "true", "false", "NULL", "include", "define", "ifdef", "ifndef",
+    "endif", "pragma", "class", "namespace", "template", "typename", "public", "private", "protected",
+    "virtual", "override", "new", "delete", "this", "using", "nullptr", "operator", "friend", "try",
+    "catch", "throw", "constexpr", "noexcept",
+];
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        names: &["rust", "rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+            "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true",
+            "type", "unsafe", "use", "where", "while",
+        ],
+        types: &[
+            "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize",
+            "i8", "i16", "i32", "i64", "i128", "isize", "f32", "f64",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        capitalised_types: true,
+        macros: true,
+        lifetimes: true,
+    },
+    Language {
+        names: &["c", "h"],
+        keywords: C_KEYWORDS,
+        types: C_TYPES,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        capitalised_types: false,
+        macros: false,
+        lifetimes: false,
+    },
+    Language {
+        names: &["cpp", "c++", "cc", "cxx", "hpp"],
+        keywords: CPP_KEYWORDS,
+        types: C_TYPES,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        capitalised_types: true,
+        macros: false,
+        lifetimes: false,
+    },
+    Language {
+        names: &["javascript", "js", "jsx", "typescript", "ts", "tsx"],
+        keywords: &[
+            "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "debugger",
+            "default", "delete", "do", "else", "enum", "export", "extends", "false", "finally", "for",
+            "from", "function", "if", "implements", "import", "in", "instanceof", "interface", "let",
+            "new", "null", "of", "readonly", "return", "static", "super", "switch", "this", "throw",
+            "true", "try", "type", "typeof", "undefined", "var", "void", "while", "yield",
+        ],
+        types: &["any", "boolean", "never", "number", "object", "string", "unknown"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        capitalised_types: true,
+        macros: false,
+        lifetimes: false,
+    },
+    Language {
+        names: &["python", "py"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+            "else", "except", "False", "finally", "for", "from", "global", "if", "import", "in", "is",
+            "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "self", "True", "try",
+            "while", "with", "yield",
+        ],
+        types: &["bool", "bytes", "dict", "float", "int", "list", "set", "str", "tuple"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+        capitalised_types: true,
+        macros: false,
+        lifetimes: false,
+    },
+    Language {
+        names: &["go", "golang"],
+        keywords: &[
+            "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough",
+            "false", "for", "func", "go", "goto", "if", "import", "interface", "map", "nil", "package",
+            "range", "return", "select", "struct", "switch", "true", "type", "var",
+        ],
+        types: &[
+            "any", "bool", "byte", "error", "float32", "float64", "int", "int8", "int16", "int32",
+            "int64", "rune", "string", "uint", "uint8", "uint16", "uint32", "uint64", "uintptr",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        capitalised_types: false,
+        macros: false,
+        lifetimes: false,
+    },
+    Language {
+        names: &["bash", "sh", "shell", "zsh", "console"],
+        keywords: &[
+            "case", "cd", "do", "done", "echo", "elif", "else", "esac", "export", "fi", "for",
+            "function", "if", "in", "local", "return", "then", "until", "while",
+        ],
+        types: &[],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+        capitalised_types: false,
+        macros: false,
+        lifetimes: false,
+    },
+    Language {
+        names: &["json"],
+        keywords: &["true", "false", "null"],
+        types: &[],
+        line_comments: &[],
+        block_comment: None,
+        quotes: &['"'],
+        capitalised_types: false,
+        macros: false,
+        lifetimes: false,
+    },
+    Language {
+        names: &["toml", "ini"],
+        keywords: &["true", "false"],
+        types: &[],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+        capitalised_types: false,
+        macros: false,
+        lifetimes: false,
+    },
+];
+
+fn language(name: &str) -> Option<&'static Language> {
+    let name = name.to_lowercase();
+    LANGUAGES.iter().find(|x| x.names.contains(&name.as_str()))
+}
+
+pub fn is_supported(lang: &str) -> bool {
+    language(lang).is_some()
+}
+
+// length of a string starting at `text`, None if it doesn't end on the same line
+fn string_length(text: &str, quote: char) -> Option<usize> {
+    let triple: String = [quote; 3].iter().collect();
+    if quote != '`' && text.starts_with(&triple) {
+        let end = text[3..].find(&triple).map_or(text.len(), |x| x + 6);
+        return Some(end);
+    }
+
+    let mut escaped = false;
+    for (index, character) in text.char_indices().skip(1) {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\n' if quote != '`' => return None,
+            _ if character == quote => return Some(index + 1),
+            _ => {},
+        }
+    }
+    None
+}
+
+fn number_length(text: &str) -> usize {
+    let mut previous = '\0';
+    for (index, character) in text.char_indices() {
+        let decimal_point = character == '.' && text[index + 1..].starts_with(|c: char| c.is_ascii_digit());
+        let exponent_sign = (character == '-' || character == '+') && (previous == 'e' || previous == 'E')
+            && !text.starts_with("0x");
+        if !(character.is_ascii_alphanumeric() || character == '_' || decimal_point || exponent_sign) {
+            return index;
+        }
+        previous = character;
+    }
+    text.len()
+}
+
+fn identifier_length(text: &str) -> usize {
+    text.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(text.len())
+}
+
+fn next_token(language: &Language, text: &str) -> (TokenKind, usize) {
+    let Some(first) = text.chars().next() else { return (TokenKind::Plain, 0) };
+
+    for prefix in language.line_comments {
+        if text.starts_with(prefix) {
+            return (TokenKind::Comment, text.find('\n').unwrap_or(text.len()));
+        }
+    }
+    if let Some((open, close)) = language.block_comment && text.starts_with(open) {
+        let end = text[open.len()..].find(close).map_or(text.len(), |x| open.len() + x + close.len());
+        return (TokenKind::Comment, end);
+    }
+
+    if first == '\'' && language.lifetimes {
+        let rest = &text[1..];
+        let is_char = rest.starts_with('\\') || rest.chars().nth(1) == Some('\'');
+        if !is_char && rest.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            return (TokenKind::Type, 1 + identifier_length(rest));
+        }
+    }
+    if language.quotes.contains(&first) && let Some(length) = string_length(text, first) {
+        return (TokenKind::String, length);
+    }
+
+    if first.is_ascii_digit() {
+        return (TokenKind::Number, number_length(text));
+    }
+
+    if first.is_alphabetic() || first == '_' {
+        let length = identifier_length(text);
+        let word = &text[..length];
+        let next = text[length..].chars().next();
+        let kind = if language.keywords.contains(&word) {
+            TokenKind::Keyword
+        } else if language.types.contains(&word) || (language.capitalised_types && word.starts_with(char::is_uppercase)) {
+            TokenKind::Type
+        } else if next == Some('(') || (language.macros && next == Some('!')) {
+            TokenKind::Function
+        } else {
+            TokenKind::Plain
+        };
+        return (kind, length);
+    }
+
+    (TokenKind::Plain, first.len_utf8())
+}
+
+/// splits `code` into coloured tokens, languages we don't know come back as a single plain token
+pub fn highlight<'a>(lang: &str, code: &'a str) -> Vec<Token<'a>> {
+    let Some(language) = language(lang) else {
+        return vec![Token { kind: TokenKind::Plain, text: code }];
+    };
+
+    let mut ranges: Vec<(TokenKind, Range<usize>)> = vec![];
+    let mut index = 0;
+    while index < code.len() {
+        let (kind, length) = next_token(language, &code[index..]);
+        match ranges.last_mut() {
+            Some((last, range)) if *last == kind => range.end += length,
+            _ => ranges.push((kind, index..index + length)),
+        }
+        index += length;
+    }
+    ranges.into_iter().map(|(kind, range)| Token { kind, text: &code[range] }).collect()
+}
+
+/// the same tokens but one list per line, tokens spanning lines like block comments get cut up
+pub fn split_lines<'a>(tokens: &[Token<'a>]) -> Vec<Vec<Token<'a>>> {
+    let mut lines = vec![vec![]];
+    for token in tokens {
+        let mut parts = token.text.split('\n');
+        if let Some(first) = parts.next() && !first.is_empty() {
+            lines.last_mut().expect("there is always a line").push(Token { text: first, ..*token });
+        }
+        for part in parts {
+            lines.push(if part.is_empty() { vec![] } else { vec![Token { text: part, ..*token }] });
+        }
+    }
+    lines
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl Color {
+    pub fn hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+
+    pub fn rgb(self) -> (f32, f32, f32) {
+        (self.0 as f32 / 255.0, self.1 as f32 / 255.0, self.2 as f32 / 255.0)
+    }
+
+    /// `amount` of the way from this colour to `other`
+    pub fn mix(self, other: Color, amount: f32) -> Color {
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+        Color(mix(self.0, other.0), mix(self.1, other.1), mix(self.2, other.2))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Theme {
+    pub name: &'static str,
+    pub background: Color,
+    pub foreground: Color,
+    pub keyword: Color,
+    pub type_: Color,
+    pub string: Color,
+    pub number: Color,
+    pub comment: Color,
+    pub function: Color,
+}
+
+// the colours inserted and deleted lines of a diff are tinted with, the same in every theme
+const INSERTED: Color = Color(0x2e, 0xa0, 0x43);
+const DELETED: Color  = Color(0xcf, 0x22, 0x2e);
+
+pub const THEMES: [Theme; 2] = [
+    Theme {
+        name: "light",
+        background: Color(0xf6, 0xf8, 0xfa),
+        foreground: Color(0x24, 0x29, 0x2e),
+        keyword:    Color(0xd7, 0x3a, 0x49),
+        type_:      Color(0x6f, 0x42, 0xc1),
+        string:     Color(0x03, 0x2f, 0x62),
+        number:     Color(0x00, 0x5c, 0xc5),
+        comment:    Color(0x6a, 0x73, 0x7d),
+        function:   Color(0x6f, 0x42, 0xc1),
+    },
+    Theme {
+        name: "dark",
+        background: Color(0x28, 0x2c, 0x34),
+        foreground: Color(0xab, 0xb2, 0xbf),
+        keyword:    Color(0xc6, 0x78, 0xdd),
+        type_:      Color(0xe5, 0xc0, 0x7b),
+        string:     Color(0x98, 0xc3, 0x79),
+        number:     Color(0xd1, 0x9a, 0x66),
+        comment:    Color(0x7f, 0x84, 0x8e),
+        function:   Color(0x61, 0xaf, 0xef),
+    },
+];
+
+pub fn theme(name: &str) -> Option<&'static Theme> {
+    THEMES.iter().find(|x| x.name == name)
+}
+
+impl Theme {
+    pub fn color(&self, kind: TokenKind) -> Color {
+        match kind {
+            TokenKind::Plain    => self.foreground,
+            TokenKind::Keyword  => self.keyword,
+            TokenKind::Type     => self.type_,
+            TokenKind::String   => self.string,
+            TokenKind::Number   => self.number,
+            TokenKind::Comment  => self.comment,
+            TokenKind::Function => self.function,
+        }
+    }
+
+    /// the inline style for a token, for places that drop stylesheets like rss readers
+    pub fn style(&self, kind: TokenKind) -> String {
+        let italic = if kind == TokenKind::Comment { " font-style: italic;" } else { "" };
+        format!("color: {};{italic}", self.color(kind).hex())
+    }
+
+    /// rules for the `hl-*` classes and the code block around them
+    pub fn css(&self) -> String {
+        let mut css = format!(".code-block pre {{ background: {}; color: {}; }}\n", self.background.hex(), self.foreground.hex());
+        css += format!(".code-block .line.highlighted {{ background: {}; }}\n", self.background.mix(self.foreground, 0.12).hex()).as_str();
+        css += format!(".code-block .line.inserted {{ background: {}; }}\n", self.background.mix(INSERTED, 0.2).hex()).as_str();
+        css += format!(".code-block .line.deleted {{ background: {}; }}\n", self.background.mix(DELETED, 0.2).hex()).as_str();
+        // the numbers come from `data-line` so copying the code doesn't copy them too
+        css += format!(".code-block .line[data-line]::before {{ content: attr(data-line); display: inline-block; min-width: 3ch; \
+            margin-right: 1ch; text-align: right; color: {}; user-select: none; }}\n", self.comment.hex()).as_str();
+        for kind in TokenKind::ALL.into_iter().skip(1) {
+            css += format!(".{} {{ {} }}\n", kind.class(), self.style(kind)).as_str();
+        }
+        css
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds<'a>(tokens: &[Token<'a>]) -> Vec<(TokenKind, &'a str)> {
+        tokens.iter().map(|x| (x.kind, x.text)).collect()
+    }
+
+    #[test]
+    fn rust() {
+        let tokens = highlight("rust", "fn get<'a>(x: &'a str) -> Option<u8> { println!(\"hi {}\", 1.5); } // done");
+        assert_eq!(kinds(&tokens), vec![
+            (TokenKind::Keyword, "fn"),
+            (TokenKind::Plain, " get<"),
+            (TokenKind::Type, "'a"),
+            (TokenKind::Plain, ">(x: &"),
+            (TokenKind::Type, "'a"),
+            (TokenKind::Plain, " "),
+            (TokenKind::Type, "str"),
+            (TokenKind::Plain, ") -> "),
+            (TokenKind::Type, "Option"),
+            (TokenKind::Plain, "<"),
+            (TokenKind::Type, "u8"),
+            (TokenKind::Plain, "> { "),
+            (TokenKind::Function, "println"),
+            (TokenKind::Plain, "!("),
+            (TokenKind::String, "\"hi {}\""),
+            (TokenKind::Plain, ", "),
+            (TokenKind::Number, "1.5"),
+            (TokenKind::Plain, "); } "),
+            (TokenKind::Comment, "// done"),
+        ]);
+        assert_eq!(kinds(&highlight("rs", "'x' '\\n'")), vec![
+            (TokenKind::String, "'x'"), (TokenKind::Plain, " "), (TokenKind::String, "'\\n'"),
+        ]);
+    }
+
+    #[test]
+    fn unknown_language() {
+        assert_eq!(kinds(&highlight("brainfuck", "+[-->-]")), vec![(TokenKind::Plain, "+[-->-]")]);
+        assert_eq!(kinds(&highlight("", "")), vec![(TokenKind::Plain, "")]);
+    }
+
+    #[test]
+    fn unterminated() {
+        assert_eq!(kinds(&highlight("c", "x = \"oops\ny /* never")), vec![
+            (TokenKind::Plain, "x = \"oops\ny "),
+            (TokenKind::Comment, "/* never"),
+        ]);
+        assert_eq!(kinds(&highlight("python", "\"\"\"doc\nstring\"\"\"")), vec![(TokenKind::String, "\"\"\"doc\nstring\"\"\"")]);
+    }
+
+    #[test]
+    fn lines() {
+        let tokens = highlight("c", "/* a\nb */ int\n\nx;");
+        let lines = split_lines(&tokens);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(kinds(&lines[0]), vec![(TokenKind::Comment, "/* a")]);
+        assert_eq!(kinds(&lines[1]), vec![(TokenKind::Comment, "b */"), (TokenKind::Plain, " "), (TokenKind::Type, "int")]);
+        assert!(lines[2].is_empty());
+    }
+
+    #[test]
+    fn themes() {
+        let theme = theme(DEFAULT_THEME).unwrap();
+        assert_eq!(theme.color(TokenKind::Plain), theme.foreground);
+        assert!(theme.css().contains(".hl-keyword { color: #d73a49; }"));
+        for theme in &THEMES {
+            let css = theme.css();
+            assert!(css.contains(".code-block .line[data-line]::before { content: attr(data-line);"), "{} has no line numbers", theme.name);
+            assert!(css.contains(".code-block .line.inserted {"), "{} has no inserted lines", theme.name);
+            assert!(css.contains(".code-block .line.deleted {"), "{} has no deleted lines", theme.name);
+        }
+        assert_eq!(Color(0, 0, 0).mix(Color(255, 255, 255), 0.5), Color(128, 128, 128));
+    }
+}
@@ -4,6 +4,7 @@
 #![feature(string_remove_matches)]
 #![feature(box_patterns)]
 #![feature(pattern)]
+#![cfg_attr(test, feature(test))]
 use std::{collections::HashMap, fs};
 use anyhow::{Context, Result};
 
@@ -23,7 +24,12 @@ mod parser;
 // mod paws_markdown;
 // mod pmd_serializer;
 // mod config;
+#[cfg(any(feature = "pdf", feature = "wasm"))]
 mod pdf;
+#[cfg(any(feature = "html", feature = "rss", feature = "pdf", feature = "wasm"))]
+mod highlight;
+#[cfg(feature = "wasm")]
+mod serializer;
 // mod ordered_map;
 // #[cfg(feature = "text")]
 // mod pmd_pure_text;
@@ -51,3 +57,26 @@ mod pdf;
 // use pmd_rss::*;
 // #[cfg(feature = "pdf")]
 // use pmd_pdf::*;
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn generate_output(source: &str, pdf: bool) -> std::result::Result<Vec<u8>, JsError> {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    let (result, _) = parser::parse(&source.to_string(), None).map_err(|e| JsError::new(&e.to_string()))?;
+    serializer::PMDWASMSerializer::new(pdf).convert(&result).map_err(|e| JsError::new(&e.to_string()))
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn generate_diagnostics(source: &str) -> std::result::Result<Vec<String>, JsError> {
+    let (_, diagnostics) = parser::parse(&source.to_string(), None).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(diagnostics.iter().map(|x| x.to_string()).collect())
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn generate_json(source: &str) -> std::result::Result<String, JsError> {
+    let (result, _) = parser::parse(&source.to_string(), None).map_err(|e| JsError::new(&e.to_string()))?;
+    serializer::to_json(&result).map_err(|e| JsError::new(&e.to_string()))
+}
@@ -4,6 +4,7 @@
 #![feature(string_remove_matches)]
 #![feature(box_patterns)]
 #![feature(pattern)]
+#![cfg_attr(test, feature(test))]
 #[allow(dead_code)]
 #[allow(unused)]
 use std::{collections::HashMap, fs};
@@ -18,12 +19,16 @@ extern crate console_error_panic_hook;
 // mod frontmatter;
 mod parser;
 // mod toplevel;
+#[cfg(feature = "pdf")]
 mod pdf;
+#[cfg(any(feature = "html", feature = "rss", feature = "pdf"))]
+mod highlight;
+mod serializer;
 
 // use frontmatter::*;
 use parser::*;
 // use paws_markdown::*;
-// use pmd_serializer::*;
+use serializer::*;
 
 use clap::{Parser, Subcommand};
 use std::path::{Path, PathBuf};
@@ -51,6 +56,9 @@ enum Commands {
 
     #[cfg(feature = "pdf")]
     Pdf   {files: Vec<PathBuf> },
+
+    #[cfg(feature = "json")]
+    Json  {files: Vec<PathBuf> },
     
     #[cfg(feature = "text")]
     Paragraph {file: PathBuf },
@@ -68,6 +76,31 @@ enum Commands {
 
 /////////////////////////////////////////////////////////////////////////////////////////////
 
+fn parse_and_report(file: &PathBuf) -> Result<AssDownDocument> {
+    let path = file.to_str().context("expected a file")?.to_string();
+    let (result, diagnostics) = parse_file(&path)?;
+    for diagnostic in &diagnostics {
+        diagnostic.print(Some(&path));
+    }
+    Ok(result)
+}
+
+// every file is parsed before anything is written so wiki links can point between them
+fn parse_blog(files: &[PathBuf]) -> Result<Vec<AssDownDocument>> {
+    let mut documents = vec![];
+    for file in files {
+        let stem = file.as_path().file_stem().context("expected file name")?;
+        documents.push((stem.to_str().context("converting OsStr to str")?.to_string(), parse_and_report(file)?));
+    }
+    let diagnostics = wiki_links::link_documents(&mut documents);
+    for (file, diagnostics) in files.iter().zip(diagnostics) {
+        for diagnostic in &diagnostics {
+            diagnostic.print(file.to_str());
+        }
+    }
+    Ok(documents.into_iter().map(|(_, document)| document).collect())
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
     let dir = cli.out_dir.unwrap_or("./out".into());
@@ -78,89 +111,95 @@ fn main() -> Result<()> {
     match &cli.command {
         #[cfg(feature = "text")]
         Commands::Paragraph { file } => {
-            let result = parse_file(&file.to_str().context("expected a file")?.to_string())?;
-            let paragraph = result.body.iter().find(|(x, _)| match x { Element::Paragraph(_) => true, _ => false});
-            if let Some((Element::Paragraph(content), _)) = paragraph {
-                // let mut serialiser = PMDPureTextSerializer::new();
-                // serialiser.references = result.references.clone();
-                // let text = serialiser.convert_paragraph(&content, &String::new())?;
-                // println!("{text}");
+            let result = parse_and_report(file)?;
+            let paragraph = result.body.iter().find(|(x, _, _)| match x { Element::Paragraph(_) => true, _ => false});
+            if let Some((Element::Paragraph(content), _, _)) = paragraph {
+                let text = PMDPureTextSerializer::new().visit_paragraph(&serializer::Context::new(&result), content, &String::new())?;
+                println!("{text}");
             } else {
             }
         },
         #[cfg(feature = "text")]
         Commands::Subtitle { file } => {
-            let result = parse_file(&file.to_str().context("expected a file")?.to_string())?;
+            let result = parse_and_report(file)?;
             let text = result.meta.subtitle;
             println!("{text}");
         },
         #[cfg(feature = "html")]
         Commands::Html{files} => {
             let out_dir = Path::new(dir.as_str());
-            for file in files {
+            for (file, result) in files.iter().zip(parse_blog(files)?) {
                 let stem = file.as_path().file_stem().context("expected file name")?;
                 let mut out_file = out_dir.join(stem);
                 out_file.set_extension("html");
                 println!("outputting to file {}", out_file.to_str().expect("whatever"));
 
-                let result = parse_file(&file.to_str().context("expected a file")?.to_string())?;
-                println!("{:?}", result);
-                // let html = to_string_from_boxed(&result, PMDHTMLSerializer::new(stem.to_str().context("converting OsStr to str")?))?;
-                // if out_file.exists() {
-                //     fs::remove_file(&out_file)?;
-                // }
-                // fs::write(out_file, html)?;
+                let html = to_string(&result, PMDHTMLSerializer::new(stem.to_str().context("converting OsStr to str")?))?;
+                if out_file.exists() {
+                    fs::remove_file(&out_file)?;
+                }
+                fs::write(out_file, html)?;
             }
         }, 
         #[cfg(feature = "pdf")]
         Commands::Pdf{files} => {
             let out_dir = Path::new(dir.as_str());
-            for file in files {
+            for (file, result) in files.iter().zip(parse_blog(files)?) {
                 let stem = file.as_path().file_stem().context("expected file name")?;
                 let stem = format!("pdf-{}", stem.to_str().expect("could not convert filename to str"));
                 let mut out_file = out_dir.join(stem.clone());
                 out_file.set_extension("pdf");
                 println!("outputting to file {}", out_file.to_str().expect("whatever"));
 
-                let result = parse_file(&file.to_str().context("expected a file")?.to_string())?;
-                println!("{:?}", result);
-                // let html   = to_string_from_boxed(&result, PMDPDFSerializer::new(stem.as_str()))?;
-                // if out_file.exists() {
-                //     fs::remove_file(&out_file)?;
-                // }
-                // fs::write(out_file, html)?;
+                let pdf    = PMDPDFSerializer::new().convert(&result)?;
+                if out_file.exists() {
+                    fs::remove_file(&out_file)?;
+                }
+                fs::write(out_file, pdf)?;
+            }
+        }, 
+        #[cfg(feature = "json")]
+        Commands::Json{files} => {
+            let out_dir = Path::new(dir.as_str());
+            for (file, result) in files.iter().zip(parse_blog(files)?) {
+                let stem = file.as_path().file_stem().context("expected file name")?;
+                let mut out_file = out_dir.join(stem);
+                out_file.set_extension("json");
+                println!("outputting to file {}", out_file.to_str().expect("whatever"));
+
+                let json   = to_json(&result)?;
+                if out_file.exists() {
+                    fs::remove_file(&out_file)?;
+                }
+                fs::write(out_file, json)?;
             }
         }, 
         #[cfg(feature = "rss")]
         Commands::Rss{files}  => {
             let out_dir = Path::new(dir.as_str());
-            for file in files {
+            for (file, result) in files.iter().zip(parse_blog(files)?) {
                 let stem = file.as_path().file_stem().context("expected file name")?;
                 let mut out_file = out_dir.join(stem);
                 out_file.set_extension("rss");
 
-                let result = parse_file(&file.to_str().context("expected a file")?.to_string())?;
-                println!("{:?}", result);
-                // let html   = to_string(&result, PMDRSSSerializer::new(stem.to_str().context("converting OsStr to str")?))?;
-                // if out_file.exists() {
-                //     fs::remove_file(&out_file)?;
-                // }
-                // fs::write(out_file, html)?;
+                let rss    = to_string(&result, PMDRSSSerializer::new(stem.to_str().context("converting OsStr to str")?))?;
+                if out_file.exists() {
+                    fs::remove_file(&out_file)?;
+                }
+                fs::write(out_file, rss)?;
             }
         }, 
         #[cfg(feature = "text")]
         Commands::Text{files} => {
-            for file in files {
+            for (file, result) in files.iter().zip(parse_blog(files)?) {
                 let file_path_string = file.as_path().to_str().expect("expected a valid path");
-                // println!("// {} //////////////////////////////////////////////////////////////////////////", file_path_string);
-
-                let result = parse_file(&file.to_str().context("expected a file")?.to_string())?;
-                println!("{:?}", result);
-                // let text   = to_string(&result, PMDPureTextSerializer::new())?;
-                // 
-                // println!("{text}");
-                // 
-                // println!("////////////////////////////////////////////////////////////////////////////////");
+                println!("// {} //////////////////////////////////////////////////////////////////////////", file_path_string);
+
+                let text   = to_string(&result, PMDPureTextSerializer::new())?;
+
+                println!("{text}");
+
+                println!("////////////////////////////////////////////////////////////////////////////////");
             }
         }
         Commands::Explain{feature, extra}  => {
@@ -227,7 +266,7 @@ fn main() -> Result<()> {
                         println!("    pdf-no-first-page removes the first page and adds title/subtitle to the document");
                         println!("    pdf-text-size     sets the font size for paragraphs");
                         println!("    pdf-line-height   sets the line height");
-                        println!("    pdf-font          changes the font");
+                        println!("    pdf-font          changes the font (helvetica, times or courier)");
                         println!("    ");
                         println!("    pdf-header        inserts text into the header, centered");
                         println!("    pdf-header-left   inserts text into the header, left aligned");
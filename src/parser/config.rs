@@ -4,6 +4,8 @@ pub const DEFAULT_DATA_DIR: &'static str = "data";
 pub const DEFAULT_BLOG_DIR: &'static str = "blog";
 pub const DEFAULT_BIBLIOGRAPHY_TITLE: &'static str = "References";
 pub const DEFAULT_NOTES_TITLE: &'static str = "Notes";
+pub const DEFAULT_GLOSSARY_TITLE: &'static str = "Glossary";
+pub const DEFAULT_BACKLINKS_TITLE: &'static str = "Linked from";
 pub const DEFAULT_FACTBOX_TITLE: &'static str = "Facts";
 
 pub const MAX_ID_LENGTH: usize = 64;
@@ -22,3 +24,15 @@ pub const FRONTMATTER_HIDE_REFERENCES: [&'static str; 9] = [
 pub const FRONTMATTER_HIDE_CONTACTS: [&'static str; 2] = [
     "hide-contacts", "no-contacts"
 ];
+
+pub const FRONTMATTER_HIDE_GLOSSARY: [&'static str; 2] = [
+    "hide-glossary", "no-glossary"
+];
+
+pub const FRONTMATTER_TOC_CALLOUTS: [&'static str; 2] = [
+    "toc-callouts", "callouts-in-toc"
+];
+
+pub const FRONTMATTER_NUMBER_FIGURES: [&'static str; 3] = [
+    "figures", "number-figures", "figure-numbers"
+];
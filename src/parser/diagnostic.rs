@@ -0,0 +1,96 @@
+use std::fmt;
+use super::source::SourceSpan;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error   => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// something the parser wants to tell the author about, collected instead of printed so the
+/// cli, the wasm editor and ci can each decide how to show it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// short stable identifier like `missing-title`, safe to match on
+    pub code: &'static str,
+    pub message: String,
+    /// where in the source file the problem is, if we know
+    pub span: Option<SourceSpan>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error<S: Into<String>>(code: &'static str, message: S) -> Self {
+        Self { severity: Severity::Error, code, message: message.into(), span: None, help: None }
+    }
+
+    pub fn warning<S: Into<String>>(code: &'static str, message: S) -> Self {
+        Self { severity: Severity::Warning, code, message: message.into(), span: None, help: None }
+    }
+
+    pub fn with_span(mut self, span: SourceSpan) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_help<S: Into<String>>(mut self, help: S) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// prints the diagnostic the way the cli always has, `file` is prefixed when known
+    #[cfg(feature = "exe")]
+    pub fn print(&self, file: Option<&str>) {
+        use color_print::cprintln;
+        let location = match (file, &self.span) {
+            (Some(file), Some(span)) => format!("{file}:{}:{}: ", span.start.line, span.start.column),
+            (Some(file), None)       => format!("{file}: "),
+            (None, Some(span))       => format!("{}:{}: ", span.start.line, span.start.column),
+            (None, None)             => String::new(),
+        };
+        match self.severity {
+            Severity::Error   => cprintln!("<r>error[{}]:</> {}{}", self.code, location, self.message),
+            Severity::Warning => cprintln!("<y>warning[{}]:</> {}{}", self.code, location, self.message),
+        }
+        if let Some(help) = &self.help {
+            cprintln!("  <cyan>help:</> {}", help);
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        if let Some(help) = &self.help {
+            write!(f, " ({help})")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let diagnostic = Diagnostic::warning("missing-date", "document is missing a date")
+            .with_help("see 'pmd explain frontmatter'");
+        assert_eq!(diagnostic.to_string(), "warning[missing-date]: document is missing a date (see 'pmd explain frontmatter')");
+        assert!(!diagnostic.is_error());
+    }
+}
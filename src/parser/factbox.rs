@@ -1,5 +1,4 @@
 use crate::parser::inline::parse_inline;
-use crate::parser::parser::check_id;
 
 use super::{parser_util::generate_id, toplevel::TopLevelSyntax};
 use super::structs::*;
@@ -7,8 +6,9 @@ use super::util::ordered_map::OrderedMap;
 use std::collections::HashSet;
 use super::parser_util::{is_valid_id, ParserInfo};
 use anyhow::{Result, Context};
-use color_print::cformat;
 use super::parser::*;
+use super::diagnostic::Diagnostic;
+use super::source::{LineIndex, Spanned};
 
 
 pub fn parse_factbox_element(info: &mut ParserInfo, elem: &TopLevelSyntax)  -> Result<MaybeElement> {
@@ -16,9 +16,10 @@ pub fn parse_factbox_element(info: &mut ParserInfo, elem: &TopLevelSyntax)  -> R
     let TopLevelSyntax::FactBox{ title, body} = elem else {
         return Ok(MaybeElement::No)
     };
-    let factbox_parsed = parse_factbox_body(body)?;
+    let mut factbox_parsed = parse_factbox_body(info.index, body);
+    info.diagnostics.append(&mut factbox_parsed.diagnostics);
     let mut factbox = FactBox {
-        title: title.clone(),
+        title: title.to_string(),
         notes: OrderedMap::new(),
         body: factbox_parsed.body,
     };
@@ -28,8 +29,11 @@ pub fn parse_factbox_element(info: &mut ParserInfo, elem: &TopLevelSyntax)  -> R
             info.references.insert(key.clone(), def.clone());
         }
     }
+    for (key, definition) in &factbox_parsed.glossary {
+        add_to_glossary(&mut info.glossary, key.clone(), definition.clone());
+    }
     let id = if let Some(id) = generate_id(title) { id } else { format!("factbox-{}", info.num_factboxes) };
-    for (_, object_id) in &mut factbox.body {
+    for (_, object_id, _) in &mut factbox.body {
         if is_valid_id(object_id) {
             let last_length = info.body.len();
             *object_id = format!("{id}-{object_id}");
@@ -51,81 +55,51 @@ pub fn parse_factbox_element(info: &mut ParserInfo, elem: &TopLevelSyntax)  -> R
     )))
 }
 
-fn parse_factbox_body(toplevel_syntax: &Vec<TopLevelSyntax>) -> Result<AssDownDocument> {
-    let mut info = ParserInfo::new();
-
-    for elem in toplevel_syntax {
-        let last_length = info.body.len();
+fn parse_factbox_body<'a>(index: &'a LineIndex<'a>, toplevel_syntax: &Vec<Spanned<TopLevelSyntax>>) -> ParserInfo<'a> {
+    let mut info = ParserInfo::new(index);
 
+    for Spanned { node: elem, span } in toplevel_syntax {
         if let TopLevelSyntax::FactBox{title: _, body: _} = elem {
-            error!("fact boxes inside of fact boxes is not allowed");
+            info.diagnostics.push(Diagnostic::error("nested-factbox", "fact boxes inside of fact boxes is not allowed")
+                .with_span(*span));
             continue;
         }
         if let TopLevelSyntax::FrontMatter(frontmatter) = elem {
-            error!("fact boxes frontmatter of fact boxes is not allowed");
+            info.diagnostics.push(Diagnostic::error("factbox-frontmatter", "fact boxes can't have frontmatter")
+                .with_span(*span));
             continue;
         }
         if let TopLevelSyntax::ReferenceDefinition(reference) = elem {
-            info.references.insert(reference.id.clone(), reference.clone());
+            info.define_reference(reference.clone(), *span);
             continue;
         }
         if let TopLevelSyntax::NoteDefinition { id, text } = elem {
-            let (object, _) = parse_inline(&text)?;
-            info.notes.insert(id.clone(), Box::into_inner(object));
+            parse_note_definition(&mut info, id, text, *span);
             continue;
         }
         if let TopLevelSyntax::TOC(title) = elem {
             if info.metadata.toc.is_none() {
-                info.metadata.toc = Some(TableOfContent{ title: title.clone(), index: info.body.len(), headers: vec![], max_depth: 1,});
-                info.body.push((Element::TOCLocationMarker, String::new()));
+                info.metadata.toc = Some(TableOfContent{ title: title.to_string(), index: info.body.len(), headers: vec![], max_depth: 1,});
+                info.body.push((Element::TOCLocationMarker, String::new(), *span));
             }
             continue;
         }
 
-        if let MaybeElement::Yes(element) = parse_factbox_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-
-        if let MaybeElement::Yes(element) = parse_pagebreak_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-
-        if let MaybeElement::Yes(element) = parse_codeblock_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-        
-        if let MaybeElement::Yes(element) = parse_image_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-        
-        if let MaybeElement::Yes(element) = parse_list_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-
-        if let MaybeElement::Yes(element) = parse_paragraph_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-
-        if let MaybeElement::Yes(element) = parse_quote_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-        
-        panic!("element was not parsed {:?}", elem);
+        // headings would break up the box so they aren't parsed in here
+        parse_element(&mut info, elem, *span, &[
+            parse_pagebreak_element,
+            parse_codeblock_element,
+            parse_math_block_element,
+            parse_image_element,
+            parse_list_element,
+            parse_paragraph_element,
+            parse_quote_element,
+            parse_callout_element,
+            parse_table_element,
+            parse_definition_list_element,
+            parse_transclusion_element,
+        ]);
     }
 
-        Ok(info.build(String::new(), String::new()))
+    info
 }
@@ -1,10 +1,11 @@
 use std::{collections::{btree_map::Keys, BTreeMap}, ops::Index};
 
+use serde::{Deserialize, Serialize};
 use serde_yaml::{value::TaggedValue, Mapping, Value};
 
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Frontmatter {
     text: String,
     data: BTreeMap<String, Value>,
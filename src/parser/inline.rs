@@ -1,13 +1,48 @@
 
 use anyhow::{Result, Context};
 use super::structs::*;
-use super::parser_util::{gather_link, get_citation, generate_id};
+use super::parser_util::{gather_link, get_citation, generate_id, PeekableChars};
+use super::wiki_links::is_wiki_link;
+use super::source::TextOrigin;
 
+// the text up to the closing `delimiter` and the characters after it. nothing can start or end
+// with a space so "a == b" stays text, and the single character ones like ~sub~ can't have any.
+// the first closer ends the scan even when it can't be used, so an opener that never gets closed
+// only looks as far as the next one instead of to the end of the paragraph
+fn delimited<'a>(mut chars: PeekableChars<'a>, delimiter: &str, allow_spaces: bool) -> Option<(String, PeekableChars<'a>)> {
+    let mut text = String::new();
+    loop {
+        if !text.is_empty() && chars.clone().take(delimiter.len()).eq(delimiter.chars()) {
+            if text.ends_with(char::is_whitespace) { return None }
+            for _ in 0..delimiter.len() { chars.next(); }
+            return Some((text, chars));
+        }
+        let character = chars.next()?;
+        if character.is_whitespace() && (!allow_spaces || text.is_empty()) { return None }
+        text.push(character);
+        if character == '\\' {
+            text.push(chars.next()?);
+        }
+    }
+}
 
-pub fn parse_inline(text: &String) -> Result<(Box<Element>, String)> {
+// how far into `text` the characters left in `chars` start
+fn position(text: &str, chars: &PeekableChars) -> usize {
+    text.len() - chars.remaining()
+}
+
+/// the inline elements in `text` and the id they would give the block they're in, for text that
+/// isn't from the file like a generated title
+pub fn parse_inline(text: &str) -> Result<(Box<Element>, String)> {
+    parse_inline_at(text, TextOrigin::UNKNOWN)
+}
+
+/// like `parse_inline`, with `origin` saying where in the file the text came from so the elements
+/// that can end up in a diagnostic know where they are
+pub fn parse_inline_at(text: &str, origin: TextOrigin) -> Result<(Box<Element>, String)> {
     let mut body = Vec::<Element>::new();
     let mut buffer = String::new();
-    let mut peekable = text.chars().peekable();
+    let mut peekable = PeekableChars::new(text);
     let mut tmp_id = String::new();
     while let Some(character) = peekable.peek() {
         match character {
@@ -43,20 +78,24 @@ pub fn parse_inline(text: &String) -> Result<(Box<Element>, String)> {
                 }
 
                 let start_char = *character;
-                let mut make_object = |base: &String, alt: &String|
+                // the base starts after "%[" and the alternative after the "](" that ends it
+                let base_start = position(text, &peekable) + 2;
+                let mut make_object = |base: &String, alt: &String| {
+                    let alt_start = base_start + base.len() + 2;
                     anyhow::Ok(if start_char == '%' { 
-                        let (base, id) = parse_inline(base)?;
-                        let (alt, _) = parse_inline(alt)?;
+                        let (base, id) = parse_inline_at(base, origin.shifted(base_start))?;
+                        let (alt, _) = parse_inline_at(alt, origin.shifted(alt_start))?;
                         tmp_id += id.as_str();
                         Element::Hoverable(Alternative{base, alt})
                     } else {
-                        let (base, _) = parse_inline(base)?;
-                        let (alt,  id) = parse_inline(alt)?;
+                        let (base, _) = parse_inline_at(base, origin.shifted(base_start))?;
+                        let (alt,  id) = parse_inline_at(alt, origin.shifted(alt_start))?;
                         tmp_id.push(' ');
                         tmp_id += id.as_str();
                         tmp_id.push(' ');
                         Element::Styled(Alternative{base, alt})
-                    });
+                    })
+                };
                 
                 let search_begin_char = if start_char == '%' { '[' } else { '{' };
                 let search_end_char   = if start_char == '%' { ']' } else { '}' };
@@ -108,6 +147,7 @@ pub fn parse_inline(text: &String) -> Result<(Box<Element>, String)> {
                     buffer = String::new();
                 }
 
+                let start = position(text, &peekable);
                 let mut base = String::new(); 
                 peekable.next();
 
@@ -133,6 +173,37 @@ pub fn parse_inline(text: &String) -> Result<(Box<Element>, String)> {
                 }
                 
                 
+                if let Some((prefix, id)) = base.strip_prefix('@').and_then(|x| x.split_once(':'))
+                    && let Some(kind) = FigureKind::from_prefix(prefix) && !id.trim().is_empty() {
+                    // a reference to a numbered figure, table or listing
+                    let span = origin.span(start..start + base.len() + 2);
+                    body.push(Element::CrossReference(kind, id.trim().to_string(), span));
+                    end.next();
+                    peekable = end.clone();
+                    continue;
+                }
+
+                if let Some(inner) = base.strip_prefix('[').and_then(|x| x.strip_suffix(']'))
+                    && !inner.contains(['[', ']']) && is_wiki_link(inner) {
+                    let mut after = end.clone();
+                    after.next();
+                    // "[[text]](url)" is still a link with brackets around its text
+                    if after.peek() != Some(&'(') {
+                        let (target, label) = inner.split_once('|').unwrap_or((inner, ""));
+                        // the label starts after "[[", the target and the '|'
+                        let label_start = start + 2 + inner.len() - label.trim_start().len();
+                        let (target, label) = (target.trim(), label.trim());
+                        tmp_id.push(' ');
+                        tmp_id += if label.is_empty() { target } else { label };
+                        tmp_id.push(' ');
+                        let label = if label.is_empty() { None } else { Some(parse_inline_at(label, origin.shifted(label_start))?.0) };
+                        let span = origin.span(start..start + base.len() + 2);
+                        body.push(Element::WikiLink(WikiLink { target: target.to_string(), label, span }));
+                        peekable = after;
+                        continue;
+                    }
+                }
+
                 if base.starts_with('^') && base.len() > 1 {
                     // this is a citation
                     let citation : String = base.chars().skip(1).collect();
@@ -143,16 +214,17 @@ pub fn parse_inline(text: &String) -> Result<(Box<Element>, String)> {
                 }
 
                 end.next();
+                let base_length = base.len();
                 let alt;
                 (alt, peekable) = gather_link(end, &mut depth)?;
                 if let Some(element) = get_citation(&alt) {
-                    let (base, _) = parse_inline(&base)?;
+                    let (base, _) = parse_inline_at(&base, origin.shifted(start + 1))?;
                     body.push(Element::Link(
                         Alternative { base, alt: Box::new(element) }
                     ))
                 } else {
-                    let (base, id) = parse_inline(&base)?;
-                    let (alt, _) = parse_inline(&alt)?;
+                    let (base, id) = parse_inline_at(&base, origin.shifted(start + 1))?;
+                    let (alt, _) = parse_inline_at(&alt, origin.shifted(start + base_length + 3))?;
                     tmp_id.push(' ');
                     tmp_id += id.as_str();
                     tmp_id.push(' ');
@@ -161,6 +233,81 @@ pub fn parse_inline(text: &String) -> Result<(Box<Element>, String)> {
                 }
                 continue;
             },
+            '~'|'='|'^'|'+' => {
+                let start_char = *character;
+                let mut after = peekable.clone();
+                after.next();
+                let double = after.peek() == Some(&start_char);
+                if double { after.next(); }
+                let inner_start = position(text, &after);
+
+                let found = match (start_char, double) {
+                    ('~', true)  => delimited(after, "~~", true).map(|found| (found, Element::Strikethrough as fn(Box<Element>) -> Element)),
+                    ('=', true)  => delimited(after, "==", true).map(|found| (found, Element::Highlight as fn(Box<Element>) -> Element)),
+                    ('+', true)  => delimited(after, "++", true).map(|found| (found, Element::Underline as fn(Box<Element>) -> Element)),
+                    ('~', false) => delimited(after, "~", false).map(|found| (found, Element::Subscript as fn(Box<Element>) -> Element)),
+                    ('^', false) => delimited(after, "^", false).map(|found| (found, Element::Superscript as fn(Box<Element>) -> Element)),
+                    _ => None,
+                };
+
+                let Some(((inner, end), make_object)) = found else {
+                    buffer.push(start_char);
+                    tmp_id.push(start_char);
+                    peekable.next();
+                    continue;
+                };
+
+                if buffer.len() != 0 {
+                    body.push(Element::Text(buffer));
+                    buffer = String::new();
+                }
+                let (text, id) = parse_inline_at(&inner, origin.shifted(inner_start))?;
+                tmp_id += id.as_str();
+                body.push(make_object(text));
+                peekable = end;
+                continue;
+            },
+            '$' => {
+                // "$5 and $10" shouldn't turn into math, so like pandoc the opening dollar can't
+                // be followed by a space and the closing one can't follow a space or come before
+                // a digit, escapes are kept for the tex and '\$' is a dollar inside the math
+                let mut end = peekable.clone();
+                end.next();
+                let mut tex = String::new();
+                let mut closed = false;
+                if end.peek().is_some_and(|c| !c.is_whitespace() && *c != '$') {
+                    while let Some(character) = end.next() {
+                        match character {
+                            '\\' => {
+                                tex.push('\\');
+                                if let Some(escaped_character) = end.next() { tex.push(escaped_character) }
+                            },
+                            '$' => {
+                                closed = !tex.ends_with(char::is_whitespace)
+                                    && !end.peek().is_some_and(char::is_ascii_digit);
+                                break;
+                            },
+                            _ => tex.push(character),
+                        }
+                    }
+                }
+
+                if !closed {
+                    buffer.push('$');
+                    tmp_id.push('$');
+                    peekable.next();
+                    continue;
+                }
+
+                if buffer.len() != 0 {
+                    body.push(Element::Text(buffer));
+                    buffer = String::new();
+                }
+                peekable = end;
+                tmp_id.push(' ');
+                body.push(Element::InlineMath(tex));
+                continue;
+            },
             '`' => {
                 if buffer.len() != 0 {
                     body.push(Element::Text(buffer));
@@ -192,6 +339,7 @@ pub fn parse_inline(text: &String) -> Result<(Box<Element>, String)> {
                 peekable.next();
                 if peekable.peek() == Some(&'*') {
                     peekable.next();
+                    let inner_start = position(text, &peekable);
                     let mut depth = 0;
                     let mut result = String::new();
                     while peekable.peek().is_some() {
@@ -210,10 +358,11 @@ pub fn parse_inline(text: &String) -> Result<(Box<Element>, String)> {
                     if peekable.peek() == Some(&'*') {
                         peekable.next();
                     }
-                    let (text, id) = parse_inline(&result)?;
+                    let (text, id) = parse_inline_at(&result, origin.shifted(inner_start))?;
                     tmp_id += id.as_str();
                     body.push(Element::Bold(text))
                 } else {
+                    let inner_start = position(text, &peekable);
                     let mut result = String::new();
                     while peekable.peek() != Some(&'*') {
                         if peekable.peek().is_none() { break }
@@ -222,7 +371,7 @@ pub fn parse_inline(text: &String) -> Result<(Box<Element>, String)> {
                     if peekable.peek() == Some(&'*') {
                         peekable.next();
                     }
-                    let (text, id) = parse_inline(&result)?;
+                    let (text, id) = parse_inline_at(&result, origin.shifted(inner_start))?;
                     tmp_id += id.as_str();
                     body.push(Element::Italics(text))
                 }
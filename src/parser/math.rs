@@ -0,0 +1,454 @@
+// a parser for the bit of tex that shows up in posts, enough for formulas with fractions, roots,
+// scripts, greek letters and the usual operators. the serializers turn the tree into mathml or
+// lay it out for the pdf, so there's no javascript needed to show any of it
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MathVariant {
+    Bold,
+    Roman,
+    Blackboard,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MathNode {
+    /// a single letter, shown in italics
+    Identifier(String),
+    Number(String),
+    Operator(String),
+    /// sums, products and integrals, their scripts go above and below in display math
+    LargeOperator(String),
+    /// `\sin`, `\log` and friends, shown upright
+    Function(String),
+    Text(String),
+    /// horizontal space in em
+    Space(f32),
+    Group(Vec<MathNode>),
+    Scripts { base: Box<MathNode>, sub: Option<Box<MathNode>>, sup: Option<Box<MathNode>> },
+    Fraction(Box<MathNode>, Box<MathNode>),
+    Root { radicand: Box<MathNode>, index: Option<Box<MathNode>> },
+    /// `\left( .. \right)`, either side can be empty for `\left.`
+    Fenced { open: String, close: String, body: Vec<MathNode> },
+    Accent { base: Box<MathNode>, accent: String },
+    Styled(MathVariant, Box<MathNode>),
+    /// something we didn't understand, kept as it was written
+    Error(String),
+}
+
+enum Symbol {
+    Identifier(&'static str),
+    Operator(&'static str),
+    Large(&'static str),
+    Function,
+    Space(f32),
+}
+
+fn symbol(command: &str) -> Option<Symbol> {
+    use Symbol::*;
+    Some(match command {
+        "alpha" => Identifier("α"), "beta" => Identifier("β"), "gamma" => Identifier("γ"),
+        "delta" => Identifier("δ"), "epsilon" => Identifier("ϵ"), "varepsilon" => Identifier("ε"),
+        "zeta" => Identifier("ζ"), "eta" => Identifier("η"), "theta" => Identifier("θ"),
+        "vartheta" => Identifier("ϑ"), "iota" => Identifier("ι"), "kappa" => Identifier("κ"),
+        "lambda" => Identifier("λ"), "mu" => Identifier("μ"), "nu" => Identifier("ν"),
+        "xi" => Identifier("ξ"), "pi" => Identifier("π"), "rho" => Identifier("ρ"),
+        "sigma" => Identifier("σ"), "tau" => Identifier("τ"), "upsilon" => Identifier("υ"),
+        "phi" => Identifier("ϕ"), "varphi" => Identifier("φ"), "chi" => Identifier("χ"),
+        "psi" => Identifier("ψ"), "omega" => Identifier("ω"),
+        "Gamma" => Identifier("Γ"), "Delta" => Identifier("Δ"), "Theta" => Identifier("Θ"),
+        "Lambda" => Identifier("Λ"), "Xi" => Identifier("Ξ"), "Pi" => Identifier("Π"),
+        "Sigma" => Identifier("Σ"), "Upsilon" => Identifier("Υ"), "Phi" => Identifier("Φ"),
+        "Psi" => Identifier("Ψ"), "Omega" => Identifier("Ω"),
+        "infty" => Identifier("∞"), "partial" => Identifier("∂"), "nabla" => Identifier("∇"),
+        "emptyset" => Identifier("∅"), "aleph" => Identifier("ℵ"), "ell" => Identifier("ℓ"),
+
+        "pm" => Operator("±"), "mp" => Operator("∓"), "times" => Operator("×"), "div" => Operator("÷"),
+        "cdot" => Operator("·"), "ast" => Operator("∗"), "circ" => Operator("∘"),
+        "leq" | "le" => Operator("≤"), "geq" | "ge" => Operator("≥"), "neq" | "ne" => Operator("≠"),
+        "approx" => Operator("≈"), "equiv" => Operator("≡"), "sim" => Operator("∼"),
+        "propto" => Operator("∝"), "ll" => Operator("≪"), "gg" => Operator("≫"),
+        "in" => Operator("∈"), "notin" => Operator("∉"), "subset" => Operator("⊂"),
+        "subseteq" => Operator("⊆"), "supset" => Operator("⊃"), "cup" => Operator("∪"),
+        "cap" => Operator("∩"), "setminus" => Operator("∖"), "forall" => Operator("∀"),
+        "exists" => Operator("∃"), "neg" => Operator("¬"), "land" | "wedge" => Operator("∧"),
+        "lor" | "vee" => Operator("∨"), "perp" => Operator("⊥"), "angle" => Operator("∠"),
+        "to" | "rightarrow" => Operator("→"), "leftarrow" | "gets" => Operator("←"),
+        "leftrightarrow" => Operator("↔"), "Rightarrow" | "implies" => Operator("⇒"),
+        "Leftarrow" => Operator("⇐"), "Leftrightarrow" | "iff" => Operator("⇔"),
+        "mapsto" => Operator("↦"), "ldots" | "dots" => Operator("…"), "cdots" => Operator("⋯"),
+        "langle" => Operator("⟨"), "rangle" => Operator("⟩"), "mid" => Operator("|"),
+        "lfloor" => Operator("⌊"), "rfloor" => Operator("⌋"), "lceil" => Operator("⌈"), "rceil" => Operator("⌉"),
+        "prime" => Operator("′"), "degree" => Operator("°"),
+
+        "sum" => Large("∑"), "prod" => Large("∏"), "int" => Large("∫"), "oint" => Large("∮"),
+        "bigcup" => Large("⋃"), "bigcap" => Large("⋂"),
+
+        "sin" | "cos" | "tan" | "cot" | "sec" | "csc" | "arcsin" | "arccos" | "arctan" |
+        "sinh" | "cosh" | "tanh" | "log" | "ln" | "exp" | "lim" | "max" | "min" | "sup" |
+        "inf" | "det" | "gcd" | "deg" | "dim" | "ker" | "arg" | "Pr" => Function,
+
+        "quad" => Space(1.0), "qquad" => Space(2.0),
+        _ => return None,
+    })
+}
+
+fn accent(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "hat" | "widehat" => "^",
+        "bar" | "overline" => "¯",
+        "vec" => "→",
+        "tilde" | "widetilde" => "~",
+        "dot" => "˙",
+        "ddot" => "¨",
+        _ => return None,
+    })
+}
+
+// the characters that can follow `\left` and `\right`
+fn delimiter(text: &str) -> Option<&'static str> {
+    Some(match text {
+        "(" => "(", ")" => ")", "[" => "[", "]" => "]", "|" => "|", "." => "",
+        "\\{" | "\\lbrace" => "{", "\\}" | "\\rbrace" => "}", "\\|" => "‖",
+        "\\langle" => "⟨", "\\rangle" => "⟩", "\\lfloor" => "⌊", "\\rfloor" => "⌋",
+        "\\lceil" => "⌈", "\\rceil" => "⌉",
+        _ => return None,
+    })
+}
+
+struct TexParser<'a> {
+    text: &'a str,
+    index: usize,
+    errors: Vec<String>,
+}
+
+impl<'a> TexParser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.index..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let character = self.peek()?;
+        self.index += character.len_utf8();
+        Some(character)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    // `\name` or `\` followed by a single symbol, without the backslash
+    fn command(&mut self) -> &'a str {
+        let start = self.index;
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() => {
+                while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.bump();
+                }
+            },
+            Some(_) => { self.bump(); },
+            None => {},
+        }
+        &self.text[start..self.index]
+    }
+
+    // everything up to the matching '}', taken as it was written
+    fn raw_group(&mut self) -> Option<&'a str> {
+        self.skip_whitespace();
+        if self.peek() != Some('{') { return None }
+        self.bump();
+        let start = self.index;
+        let mut depth = 0;
+        while let Some(character) = self.bump() {
+            match character {
+                '\\' => { self.bump(); },
+                '{' => depth += 1,
+                '}' if depth == 0 => return Some(&self.text[start..self.index - 1]),
+                '}' => depth -= 1,
+                _ => {},
+            }
+        }
+        self.errors.push("expected '}'".into());
+        Some(&self.text[start..])
+    }
+
+    fn delimiter(&mut self, after: &str) -> String {
+        self.skip_whitespace();
+        let start = self.index;
+        if self.peek() == Some('\\') {
+            self.bump();
+            self.command();
+        } else {
+            self.bump();
+        }
+        let text = &self.text[start..self.index];
+        match delimiter(text) {
+            Some(delimiter) => delimiter.to_string(),
+            None => {
+                self.errors.push(format!("'{text}' can't be used after \\{after}"));
+                text.to_string()
+            },
+        }
+    }
+
+    /// a sequence of nodes up to the end of the text, a closing brace or `\right`
+    fn sequence(&mut self, inside_group: bool) -> Vec<MathNode> {
+        let mut nodes = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => {
+                    if inside_group { self.errors.push("expected '}'".into()) }
+                    return nodes;
+                },
+                Some('}') if inside_group => { self.bump(); return nodes },
+                Some('}') => {
+                    self.bump();
+                    self.errors.push("unexpected '}'".into());
+                    nodes.push(MathNode::Error("}".into()));
+                },
+                Some('\\') if self.text[self.index..].starts_with("\\right") && !self.text[self.index + 6..].starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    return nodes;
+                },
+                Some('^' | '_') => {
+                    // a script with nothing in front of it attaches to an empty group
+                    let base = nodes.pop().unwrap_or(MathNode::Group(vec![]));
+                    nodes.push(self.scripts(base));
+                },
+                Some(_) => {
+                    let Some(node) = self.atom() else { continue };
+                    nodes.push(node);
+                },
+            }
+        }
+    }
+
+    fn scripts(&mut self, base: MathNode) -> MathNode {
+        let (mut base, mut sub, mut sup) = match base {
+            MathNode::Scripts { base, sub, sup } => (base, sub, sup),
+            base => (Box::new(base), None, None),
+        };
+        while let Some(marker @ ('^' | '_')) = { self.skip_whitespace(); self.peek() } {
+            self.bump();
+            let script = self.argument();
+            let slot = if marker == '^' { &mut sup } else { &mut sub };
+            if slot.is_some() {
+                self.errors.push(format!("double {}", if marker == '^' { "superscript" } else { "subscript" }));
+                // x^a^b is ambiguous in tex, we nest instead of giving up
+                base = Box::new(MathNode::Scripts { base, sub: sub.take(), sup: sup.take() });
+            }
+            let slot = if marker == '^' { &mut sup } else { &mut sub };
+            *slot = Some(Box::new(script));
+        }
+        MathNode::Scripts { base, sub, sup }
+    }
+
+    /// the argument of a command or script, a group or a single atom
+    fn argument(&mut self) -> MathNode {
+        self.skip_whitespace();
+        if self.peek() == Some('{') {
+            self.bump();
+            return group(self.sequence(true));
+        }
+        // only a single character is taken for arguments like x^12
+        if let Some(c) = self.peek() && c.is_ascii_digit() {
+            self.bump();
+            return MathNode::Number(c.to_string());
+        }
+        match self.atom() {
+            Some(node) => node,
+            None => {
+                self.errors.push("expected an argument".into());
+                MathNode::Group(vec![])
+            },
+        }
+    }
+
+    fn atom(&mut self) -> Option<MathNode> {
+        let character = self.peek()?;
+        match character {
+            '{' => {
+                self.bump();
+                Some(group(self.sequence(true)))
+            },
+            '\\' => {
+                self.bump();
+                self.command_node()
+            },
+            '0'..='9' | '.' => {
+                let start = self.index;
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.bump();
+                }
+                Some(MathNode::Number(self.text[start..self.index].to_string()))
+            },
+            '&' | '~' => {
+                self.bump();
+                Some(MathNode::Space(if character == '~' { 0.28 } else { 0.0 }))
+            },
+            c if c.is_alphabetic() => {
+                self.bump();
+                Some(MathNode::Identifier(c.to_string()))
+            },
+            _ => {
+                self.bump();
+                let operator = match character {
+                    '-' => "−".to_string(),
+                    '*' => "∗".to_string(),
+                    '\'' => "′".to_string(),
+                    c => c.to_string(),
+                };
+                Some(MathNode::Operator(operator))
+            },
+        }
+    }
+
+    fn command_node(&mut self) -> Option<MathNode> {
+        let command = self.command();
+        if let Some(symbol) = symbol(command) {
+            return Some(match symbol {
+                Symbol::Identifier(text) => MathNode::Identifier(text.into()),
+                Symbol::Operator(text)   => MathNode::Operator(text.into()),
+                Symbol::Large(text)      => MathNode::LargeOperator(text.into()),
+                Symbol::Function         => MathNode::Function(command.into()),
+                Symbol::Space(width)     => MathNode::Space(width),
+            });
+        }
+        if let Some(accent) = accent(command) {
+            return Some(MathNode::Accent { base: Box::new(self.argument()), accent: accent.into() });
+        }
+
+        Some(match command {
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.argument();
+                MathNode::Fraction(Box::new(numerator), Box::new(self.argument()))
+            },
+            "sqrt" => {
+                self.skip_whitespace();
+                let index = if self.peek() == Some('[') {
+                    self.bump();
+                    let start = self.index;
+                    while self.peek().is_some_and(|c| c != ']') { self.bump(); }
+                    let index = &self.text[start..self.index];
+                    if self.bump().is_none() { self.errors.push("expected ']'".into()) }
+                    Some(Box::new(group(parse_tex(index).0)))
+                } else {
+                    None
+                };
+                MathNode::Root { radicand: Box::new(self.argument()), index }
+            },
+            "left" => {
+                let open = self.delimiter("left");
+                let body = self.sequence(false);
+                let close = if self.text[self.index..].starts_with("\\right") {
+                    self.index += "\\right".len();
+                    self.delimiter("right")
+                } else {
+                    self.errors.push("\\left without a matching \\right".into());
+                    String::new()
+                };
+                MathNode::Fenced { open, close, body }
+            },
+            "right" => {
+                self.errors.push("\\right without a matching \\left".into());
+                self.delimiter("right");
+                return None;
+            },
+            "text" | "mbox" | "textrm" | "mathrm" | "operatorname" if command.starts_with("text") || command == "mbox" => {
+                MathNode::Text(self.raw_group().unwrap_or_default().to_string())
+            },
+            "operatorname" => MathNode::Function(self.raw_group().unwrap_or_default().to_string()),
+            "mathrm" => MathNode::Styled(MathVariant::Roman, Box::new(self.argument())),
+            "mathbf" | "boldsymbol" => MathNode::Styled(MathVariant::Bold, Box::new(self.argument())),
+            "mathbb" => MathNode::Styled(MathVariant::Blackboard, Box::new(self.argument())),
+            "," => MathNode::Space(0.17),
+            ":" | ">" => MathNode::Space(0.22),
+            ";" => MathNode::Space(0.28),
+            " " => MathNode::Space(0.33),
+            "!" => MathNode::Space(-0.17),
+            "{" | "}" | "$" | "%" | "#" | "&" | "_" | "|" => MathNode::Operator(if command == "|" { "‖" } else { command }.into()),
+            "\\" => MathNode::Space(1.0),
+            "" => {
+                self.errors.push("expected a command after '\\'".into());
+                MathNode::Error("\\".into())
+            },
+            _ => {
+                self.errors.push(format!("unknown command '\\{command}'"));
+                MathNode::Error(format!("\\{command}"))
+            },
+        })
+    }
+}
+
+fn group(mut nodes: Vec<MathNode>) -> MathNode {
+    if nodes.len() == 1 { nodes.remove(0) } else { MathNode::Group(nodes) }
+}
+
+/// the tree for `tex` along with everything that went wrong on the way, which never stops the
+/// parse, the broken parts show up as `MathNode::Error`
+pub fn parse_tex(tex: &str) -> (Vec<MathNode>, Vec<String>) {
+    let mut parser = TexParser { text: tex, index: 0, errors: vec![] };
+    let mut nodes = parser.sequence(false);
+    while parser.index < tex.len() {
+        // a `\right` that isn't closing anything
+        parser.bump();
+        parser.command();
+        parser.errors.push("\\right without a matching \\left".into());
+        nodes.extend(parser.sequence(false));
+    }
+    (nodes, parser.errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use MathNode::*;
+
+    fn id(text: &str) -> MathNode { Identifier(text.into()) }
+    fn num(text: &str) -> MathNode { Number(text.into()) }
+
+    #[test]
+    fn scripts_and_fractions() {
+        let (nodes, errors) = parse_tex("x_1^2 + \\frac{a}{b12}");
+        assert!(errors.is_empty());
+        assert_eq!(nodes, vec![
+            Scripts { base: Box::new(id("x")), sub: Some(Box::new(num("1"))), sup: Some(Box::new(num("2"))) },
+            Operator("+".into()),
+            Fraction(Box::new(id("a")), Box::new(Group(vec![id("b"), num("12")]))),
+        ]);
+    }
+
+    #[test]
+    fn commands() {
+        let (nodes, errors) = parse_tex("\\sqrt[3]{\\alpha} \\leq \\sin x\\,\\text{if } \\mathbb{R}");
+        assert!(errors.is_empty());
+        assert_eq!(nodes, vec![
+            Root { radicand: Box::new(id("α")), index: Some(Box::new(num("3"))) },
+            Operator("≤".into()),
+            Function("sin".into()),
+            id("x"),
+            Space(0.17),
+            Text("if ".into()),
+            Styled(MathVariant::Blackboard, Box::new(id("R"))),
+        ]);
+    }
+
+    #[test]
+    fn fences() {
+        let (nodes, errors) = parse_tex("\\left( x \\right]");
+        assert!(errors.is_empty());
+        assert_eq!(nodes, vec![Fenced { open: "(".into(), close: "]".into(), body: vec![id("x")] }]);
+    }
+
+    #[test]
+    fn errors() {
+        let (nodes, errors) = parse_tex("\\nope{x} + {y");
+        assert_eq!(nodes[0], Error("\\nope".into()));
+        assert_eq!(errors, vec!["unknown command '\\nope'".to_string(), "expected '}'".to_string()]);
+
+        let (_, errors) = parse_tex("\\left( x");
+        assert_eq!(errors, vec!["\\left without a matching \\right".to_string()]);
+        let (_, errors) = parse_tex("x \\right)");
+        assert_eq!(errors, vec!["\\right without a matching \\left".to_string()]);
+    }
+}
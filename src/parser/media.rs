@@ -0,0 +1,131 @@
+// works out what `![[...]]` embeds from the path alone. video and audio files go by their
+// extension, youtube and peertube links become iframes pointing at their embed pages, and
+// everything else is still an image
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MediaKind {
+    Image,
+    Video,
+    Audio,
+    /// the url the iframe shows, which isn't the one that was written
+    Iframe(String),
+}
+
+const VIDEO_EXTENSIONS: [&str; 5] = ["mp4", "webm", "ogv", "mov", "m4v"];
+const AUDIO_EXTENSIONS: [&str; 8] = ["mp3", "ogg", "oga", "wav", "flac", "m4a", "opus", "aac"];
+
+pub fn media_kind(src: &str) -> MediaKind {
+    if let Some(url) = embed_url(src) {
+        return MediaKind::Iframe(url);
+    }
+    // "clip.mp4?raw=true" and "clip.mp4#t=10" are still videos
+    let path = src.split(['?', '#']).next().unwrap_or(src);
+    let Some((_, extension)) = path.rsplit_once('.') else { return MediaKind::Image };
+    let extension = extension.to_lowercase();
+    if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        MediaKind::Video
+    } else if AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+        MediaKind::Audio
+    } else {
+        MediaKind::Image
+    }
+}
+
+// "https://www.example.com/a/b?c=d" into ("example.com", "/a/b", "c=d")
+fn split_url(url: &str) -> Option<(&str, &str, &str)> {
+    let rest = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"))?;
+    let (rest, _) = rest.split_once('#').unwrap_or((rest, ""));
+    let (rest, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (host, path) = rest.find('/').map_or((rest, ""), |index| rest.split_at(index));
+    let host = host.trim_start_matches("www.").trim_start_matches("m.");
+    Some((host, path, query))
+}
+
+fn query_value<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query.split('&').find_map(|pair| pair.strip_prefix(key)?.strip_prefix('='))
+}
+
+// youtube's `t=90`, `t=1m30s` or `t=90s` in seconds
+fn start_time(text: &str) -> Option<usize> {
+    if let Ok(seconds) = text.parse() { return Some(seconds) }
+    let mut total = 0;
+    let mut number = String::new();
+    for character in text.chars() {
+        match character {
+            '0'..='9' => number.push(character),
+            'h' | 'm' | 's' => {
+                let scale = match character { 'h' => 3600, 'm' => 60, _ => 1 };
+                total += number.parse::<usize>().ok()? * scale;
+                number.clear();
+            },
+            _ => return None,
+        }
+    }
+    number.is_empty().then_some(total)
+}
+
+fn is_video_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// the privacy friendly embed page for a youtube or peertube link, youtube goes through
+/// youtube-nocookie.com so nothing is stored until the video is played
+pub fn embed_url(src: &str) -> Option<String> {
+    let (host, path, query) = split_url(src)?;
+    let segments: Vec<&str> = path.split('/').filter(|x| !x.is_empty()).collect();
+
+    let youtube = match (host, segments.as_slice()) {
+        ("youtube.com" | "youtube-nocookie.com", ["watch"]) => query_value(query, "v"),
+        ("youtube.com" | "youtube-nocookie.com", ["embed" | "shorts" | "live", id]) => Some(*id),
+        ("youtu.be", [id]) => Some(*id),
+        _ => None,
+    };
+    if let Some(id) = youtube.filter(|id| is_video_id(id)) {
+        let start = query_value(query, "t").or_else(|| query_value(query, "start")).and_then(start_time);
+        return Some(match start {
+            Some(start) => format!("https://www.youtube-nocookie.com/embed/{id}?start={start}"),
+            None => format!("https://www.youtube-nocookie.com/embed/{id}"),
+        });
+    }
+
+    // peertube runs on anybody's server, so it can only be recognized by its paths
+    let peertube = match segments.as_slice() {
+        ["w", id] | ["videos", "watch" | "embed", id] => Some(*id),
+        _ => None,
+    };
+    let scheme = if src.starts_with("http://") { "http" } else { "https" };
+    let domain = src.split("://").nth(1)?.split(['/', '?', '#']).next()?;
+    peertube.filter(|id| is_video_id(id)).map(|id| format!("{scheme}://{domain}/videos/embed/{id}?warningTitle=0&p2p=0"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files() {
+        assert_eq!(media_kind("clips/intro.MP4"), MediaKind::Video);
+        assert_eq!(media_kind("song.ogg?raw=true"), MediaKind::Audio);
+        assert_eq!(media_kind("banner.png"), MediaKind::Image);
+        assert_eq!(media_kind("https://example.com/some/page"), MediaKind::Image);
+    }
+
+    #[test]
+    fn youtube() {
+        let embed = Some("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ".to_string());
+        assert_eq!(embed_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ"), embed);
+        assert_eq!(embed_url("https://youtu.be/dQw4w9WgXcQ"), embed);
+        assert_eq!(embed_url("https://m.youtube.com/shorts/dQw4w9WgXcQ"), embed);
+        assert_eq!(embed_url("https://youtu.be/dQw4w9WgXcQ?t=1m30s").as_deref(), Some("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ?start=90"));
+        assert_eq!(embed_url("https://www.youtube.com/watch?list=abc"), None);
+    }
+
+    #[test]
+    fn peertube() {
+        assert_eq!(embed_url("https://framatube.org/w/9c9de5e8-0a1e-484a-b099-e80766180a6d").as_deref(),
+            Some("https://framatube.org/videos/embed/9c9de5e8-0a1e-484a-b099-e80766180a6d?warningTitle=0&p2p=0"));
+        assert_eq!(embed_url("https://tube.example/videos/watch/abc123").as_deref(),
+            Some("https://tube.example/videos/embed/abc123?warningTitle=0&p2p=0"));
+        assert_eq!(embed_url("https://example.com/w/"), None);
+    }
+}
@@ -3,11 +3,20 @@ mod frontmatter;
 mod config;
 mod paws_markdown;
 mod parser_util;
-#[macro_use]
 mod util;
 pub mod structs;
 pub mod inline;
 pub mod factbox;
 pub mod parser;
+pub mod diagnostic;
+pub mod source;
+pub mod math;
+pub mod media;
+pub mod wiki_links;
 pub use structs::*;
+pub use diagnostic::{Diagnostic, Severity};
+pub use source::{Location, SourceSpan, Spanned};
 pub use paws_markdown::{parse, parse_file};
+pub use toplevel::{PmdDate, to_citation, to_bibliography, bibliograph_name, any_non_empty};
+pub use util::ordered_map::OrderedMap;
+pub use frontmatter::{Frontmatter, FrontmatterHelper};
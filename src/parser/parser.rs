@@ -1,17 +1,15 @@
-#[cfg(not(feature = "wasm"))]
 use anyhow::{Result, Context};
-use color_print::{cprintln,cformat};
+use std::borrow::Cow;
 use super::config::*;
 use super::factbox::parse_factbox_element;
-use super::inline::parse_inline;
 use super::parser_util::{check_frontmatter, generate_id, get_bibliography_title, get_blog_dir, get_data_dir, get_date, get_last_update, get_url, is_valid_id, ParserInfo};
 use super::util::ordered_map::OrderedMap;
-use super::toplevel::{toplevel_parse, PmdDate, ReferenceDefinition, TopLevelSyntax};
+use super::toplevel::{toplevel_parse, PmdDate, ReferenceDefinition, TopLevelSyntax, TransclusionError};
 use super::frontmatter::*;
 use super::structs::*;
-
-// this is so stupid, why can't I just have macros be scoped like ever other fucking thing?
-use crate::{error, warning};
+use super::diagnostic::Diagnostic;
+use super::media::{media_kind, MediaKind};
+use super::source::{SourceSpan, Spanned};
 
 pub fn parse_pagebreak_element(_: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
     let TopLevelSyntax::PageBreak = elem else { return Ok(MaybeElement::No) };
@@ -23,11 +21,64 @@ pub fn parse_frontmatter_element(_: &mut ParserInfo, elem: &TopLevelSyntax) -> R
     Ok(MaybeElement::Yes((Element::PageBreak, String::new())))
 }
 
+// splits on whitespace, except inside of quotes so `title="a b"` stays together
+fn split_info_string(text: &str) -> Vec<&str> {
+    let mut words = vec![];
+    let mut start = None;
+    let mut quoted = false;
+    for (index, character) in text.char_indices() {
+        if character == '"' { quoted = !quoted }
+        if character.is_whitespace() && !quoted {
+            if let Some(start) = start.take() { words.push(&text[start..index]) }
+        } else if start.is_none() {
+            start = Some(index);
+        }
+    }
+    if let Some(start) = start { words.push(&text[start..]) }
+    words
+}
+
+// `{1,3-5}` into [(1, 1), (3, 5)]
+fn parse_line_ranges(text: &str) -> Option<Vec<(usize, usize)>> {
+    let inner = text.strip_prefix('{')?.strip_suffix('}')?;
+    inner.split(',').map(str::trim).filter(|x| !x.is_empty()).map(|range| {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let (start, end) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+        if start == 0 || end < start { return None }
+        Some((start, end))
+    }).collect()
+}
+
+pub fn parse_codeblock_info(info: &mut ParserInfo, text: &str, code: &str) -> CodeBlock {
+    let mut block = CodeBlock { code: code.trim_end().replace("\r\n", "\n"), ..CodeBlock::default() };
+    for (index, word) in split_info_string(text).into_iter().enumerate() {
+        let (key, value) = match word.split_once('=') {
+            Some((key, value)) => (key, Some(value.trim_matches('"'))),
+            None => (word, None),
+        };
+        match (key, value) {
+            ("title", Some(title)) => block.title = Some(title.to_string()),
+            ("showLineNumbers", None) => block.start_line = Some(1),
+            ("showLineNumbers", Some(start)) if start.parse::<usize>().is_ok() => block.start_line = start.parse().ok(),
+            ("diff", None) => block.diff = true,
+            _ if word.starts_with('{') => match parse_line_ranges(word) {
+                Some(ranges) => block.highlights.extend(ranges),
+                None => info.diagnostics.push(Diagnostic::warning("invalid-codeblock-option", format!("'{word}' isn't a list of lines like {{1,3-5}}"))),
+            },
+            (lang, None) if index == 0 => block.lang = lang.to_string(),
+            _ => info.diagnostics.push(Diagnostic::warning("unknown-codeblock-option", format!("unknown codeblock option '{word}'"))
+                .with_help("see 'pmd explain codeblock'")),
+        }
+    }
+    block
+}
+
 pub fn parse_codeblock_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
-    let TopLevelSyntax::CodeBlock(block) = elem else { return Ok(MaybeElement::No) };
+    let TopLevelSyntax::CodeBlock { info: text, code } = elem else { return Ok(MaybeElement::No) };
+    let block = parse_codeblock_info(info, text, code);
     let id = info.num_codeblocks;
     info.num_codeblocks +=1;
-    Ok(MaybeElement::Yes((Element::CodeBlock(block.to_string()),
+    Ok(MaybeElement::Yes((Element::CodeBlock(block),
         if id == 0 {
             format!("codeblock")
         } else {
@@ -41,93 +92,226 @@ use super::toplevel as toplevel;
 pub fn parse_image_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
     let TopLevelSyntax::Image(img) = elem else { return Ok(MaybeElement::No) };
     let size = match &img.size {
-        toplevel::ImageSize::None => ImageSize::None,
-        toplevel::ImageSize::Single(x) => ImageSize::Single(x.parse()?),
-        toplevel::ImageSize::Double(x, y) => ImageSize::Double(x.parse()?, y.parse()?),
+        toplevel::ImageSize::None => Ok(ImageSize::None),
+        toplevel::ImageSize::Single(x) => x.parse().map(ImageSize::Single),
+        toplevel::ImageSize::Double(x, y) => x.parse().and_then(|x| Ok(ImageSize::Double(x, y.parse()?))),
     };
-    if let toplevel::ImageSize::None = img.size {} else { warning!("image sizing is currently unimplemented") };
+    // a size that doesn't parse only loses the size, the image is still shown
+    let size = size.unwrap_or_else(|error| {
+        info.diagnostics.push(Diagnostic::warning("invalid-image-size", format!("invalid size for '{}': {error}", img.image))
+            .with_help("sizes look like '600', '600x400', 'x400' or '50%xauto', see 'pmd explain image'"));
+        ImageSize::None
+    });
 
-    let num_image = info.num_image;
-    info.num_image += 1;
-
-    let (alt, mut id) = parse_inline(&img.alt)?;
+    let (alt, mut id) = info.parse_inline(&img.alt)?;
+    let src = img.image.to_string();
+    // the same syntax embeds videos, sounds and youtube links, the alt text is their title
+    let (element, kind, count) = match media_kind(&src) {
+        MediaKind::Image => (Element::Image(Image{src, alt, size, caption: None}), "image", &mut info.num_image),
+        MediaKind::Video => (Element::Video(Media { link: src.clone(), src, title: alt, size }), "video", &mut info.num_videos),
+        MediaKind::Audio => (Element::Audio(Media { link: src.clone(), src, title: alt, size }), "audio", &mut info.num_audio),
+        MediaKind::Iframe(embed) => (Element::Iframe(Media { src: embed, link: src, title: alt, size }), "embed", &mut info.num_embeds),
+    };
     if id.is_empty() {
-        id = format!("image-{num_image}");
+        id = format!("{kind}-{count}");
     }
+    *count += 1;
 
-    Ok(MaybeElement::Yes((
-            Element::Image(Image{src: img.image.to_string(), alt, size}),
-            id
-    )))
+    Ok(MaybeElement::Yes((element, id)))
 }
 
 pub fn parse_heading_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
     let TopLevelSyntax::Heading(text, level) = elem else { return Ok(MaybeElement::No) };
-    let (object, id) = parse_inline(&text)?;
+    let (object, id) = info.parse_inline(text)?;
     Ok(MaybeElement::Yes((Element::Header(object, *level), id)))
 }
 
-fn parse_list_element_inner(vec: &Vec<toplevel::ListItem>) -> Result<ListItem> {
+// the blocks in list items and quotes go through the same parsers as the ones in a fact box
+fn parse_block_body(info: &mut ParserInfo, body: &[Spanned<TopLevelSyntax>]) -> Vec<Spanned<Element>> {
+    let mut inner = ParserInfo::new(info.index);
+    for Spanned { node: elem, span } in body {
+        parse_element(&mut inner, elem, *span, &[
+            parse_pagebreak_element,
+            parse_codeblock_element,
+            parse_math_block_element,
+            parse_image_element,
+            parse_list_element,
+            parse_paragraph_element,
+            parse_quote_element,
+            parse_callout_element,
+            parse_table_element,
+            parse_definition_list_element,
+            parse_transclusion_element,
+        ]);
+    }
+    info.diagnostics.append(&mut inner.diagnostics);
+    for (key, definition) in &inner.glossary {
+        add_to_glossary(&mut info.glossary, key.clone(), definition.clone());
+    }
+    // whatever comes after the body is somewhere after it in the source too
+    if let Some((_, _, span)) = inner.body.last() {
+        info.cursor = info.cursor.max(span.end.offset);
+    }
+    inner.body.into_iter().map(|(node, _, span)| Spanned { node, span }).collect()
+}
+
+fn parse_list_items(info: &mut ParserInfo, vec: &[toplevel::ListItem]) -> Result<Vec<ListItem>> {
     let mut result = Vec::new();
     for elem in vec {
         let object = match elem {
-            toplevel::ListItem::Unordered(text)                        => ListItem::Unordered(parse_inline(&text)?.0),
-            toplevel::ListItem::Numbered(number, text)         => ListItem::Numbered(*number, parse_inline(&text)?.0),
-            toplevel::ListItem::Alphabetical(id, text)        => ListItem::Alphabetical(id.clone(), parse_inline(&text)?.0),
-            toplevel::ListItem::NumberedRounded(number, text)  => ListItem::NumberedRounded(*number, parse_inline(&text)?.0),
-            toplevel::ListItem::AlphabeticalRounded(id, text) => ListItem::AlphabeticalRounded(id.clone(), parse_inline(&text)?.0),
-            toplevel::ListItem::InnerList(vec) => parse_list_element_inner(vec)?,
+            toplevel::ListItem::Unordered(text, body)                  => ListItem::Unordered(info.parse_inline(text)?.0, parse_block_body(info, body)),
+            toplevel::ListItem::Numbered(number, text, body)           => ListItem::Numbered(*number, info.parse_inline(text)?.0, parse_block_body(info, body)),
+            toplevel::ListItem::Alphabetical(id, text, body)           => ListItem::Alphabetical(id.to_string(), info.parse_inline(text)?.0, parse_block_body(info, body)),
+            toplevel::ListItem::NumberedRounded(number, text, body)    => ListItem::NumberedRounded(*number, info.parse_inline(text)?.0, parse_block_body(info, body)),
+            toplevel::ListItem::AlphabeticalRounded(id, text, body)    => ListItem::AlphabeticalRounded(id.to_string(), info.parse_inline(text)?.0, parse_block_body(info, body)),
+            toplevel::ListItem::Task { checked, text, body }           => ListItem::Task { checked: *checked, text: info.parse_inline(text)?.0, body: parse_block_body(info, body) },
+            toplevel::ListItem::InnerList(vec) => ListItem::List(parse_list_items(info, vec)?),
         };
-        // let (object, _) = parse_inline(&elem)?;
         result.push(object)
     }
-
-    Ok(ListItem::List(result))
+    Ok(result)
 }
 
 pub fn parse_list_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
     let TopLevelSyntax::List(list) = elem else { return Ok(MaybeElement::No) };
 
-    let mut result = Vec::new();
-    for elem in list {
-        let object = match elem {
-            toplevel::ListItem::Unordered(text)                        => ListItem::Unordered(parse_inline(&text)?.0),
-            toplevel::ListItem::Numbered(number, text)         => ListItem::Numbered(*number, parse_inline(&text)?.0),
-            toplevel::ListItem::Alphabetical(id, text)        => ListItem::Alphabetical(id.clone(), parse_inline(&text)?.0),
-            toplevel::ListItem::NumberedRounded(number, text)  => ListItem::NumberedRounded(*number, parse_inline(&text)?.0),
-            toplevel::ListItem::AlphabeticalRounded(id, text) => ListItem::AlphabeticalRounded(id.clone(), parse_inline(&text)?.0),
-            toplevel::ListItem::InnerList(vec) => parse_list_element_inner(vec)?,
-        };
-        result.push(object)
-    }
+    let result = parse_list_items(info, list)?;
     let id = info.num_lists;
     info.num_lists += 1;
     let result = Element::List(result);
     Ok(MaybeElement::Yes((result, format!("list-{id}"))))
 }
 
+// broken tex still renders, with the broken parts marked, so these are only warnings
+fn check_math(info: &mut ParserInfo, tex: &str) {
+    for error in super::math::parse_tex(tex).1 {
+        info.diagnostics.push(Diagnostic::warning("invalid-math", format!("{error} in '{tex}'"))
+            .with_help("see 'pmd explain math'"));
+    }
+}
+
+fn check_inline_math(info: &mut ParserInfo, element: &Element) {
+    match element {
+        Element::InlineMath(tex) => check_math(info, tex),
+        Element::Span(span) => span.elements.iter().for_each(|element| check_inline_math(info, element)),
+        Element::Paragraph(element) | Element::Bold(element) | Element::Italics(element) | Element::Header(element, _) |
+        Element::Strikethrough(element) | Element::Underline(element) | Element::Highlight(element) |
+        Element::Superscript(element) | Element::Subscript(element) => check_inline_math(info, element),
+        Element::Link(alternative) | Element::Hoverable(alternative) | Element::Styled(alternative) => check_inline_math(info, &alternative.base),
+        _ => {},
+    }
+}
+
+pub fn parse_math_block_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
+    let TopLevelSyntax::MathBlock(tex) = elem else { return Ok(MaybeElement::No) };
+    check_math(info, tex);
+    let id = info.num_math;
+    info.num_math += 1;
+    Ok(MaybeElement::Yes((Element::MathBlock(tex.to_string()), format!("math-{id}"))))
+}
+
 pub fn parse_paragraph_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
     let TopLevelSyntax::Paragraph(text) = elem else { return Ok(MaybeElement::No) };
 
-    let (object, id) = parse_inline(&text)?;
+    let (object, id) = info.parse_inline(text)?;
+    check_inline_math(info, &object);
     Ok(MaybeElement::Yes((Element::Paragraph(object), id)))
 }
 
 pub fn parse_quote_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
-    let TopLevelSyntax::Quote(list) = elem else { return Ok(MaybeElement::No) };
-    let mut result = Vec::<Element>::new();
-    for elem in list {
-        let (object, _) = parse_inline(&elem)?;
-        result.push(Box::into_inner(object));
-    }
+    let TopLevelSyntax::Quote { body, attribution } = elem else { return Ok(MaybeElement::No) };
+    let body = parse_block_body(info, body);
+    let attribution = match attribution {
+        Some(text) => Some(info.parse_inline(text)?.0),
+        None => None,
+    };
     let id = info.num_quotes;
     info.num_quotes += 1;
-    Ok(MaybeElement::Yes((Element::Quote(result), format!("quote-{id}"))))
+    Ok(MaybeElement::Yes((Element::Quote(Quote { body, attribution }), format!("quote-{id}"))))
+}
+
+pub fn parse_callout_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
+    let TopLevelSyntax::Callout { kind, title, collapsed, body } = elem else { return Ok(MaybeElement::No) };
+    let kind = kind.to_lowercase();
+    let title = if title.trim().is_empty() {
+        // "> [!warning]" on its own is titled "Warning"
+        let mut chars = kind.chars();
+        let default = chars.next().map(|c| c.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default();
+        Box::new(Element::Text(default))
+    } else {
+        info.parse_inline(title)?.0
+    };
+    let body = parse_block_body(info, body);
+    let id = info.num_callouts;
+    info.num_callouts += 1;
+    Ok(MaybeElement::Yes((Element::Callout(Callout { kind, title, collapsed: *collapsed, body }), format!("callout-{id}"))))
+}
+
+pub fn parse_definition_list_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
+    let TopLevelSyntax::DefinitionList(entries) = elem else { return Ok(MaybeElement::No) };
+    let mut list = vec![];
+    for (term, definitions) in entries {
+        let definition = Definition {
+            term: info.parse_inline(term)?.0,
+            definitions: definitions.iter().map(|text| Ok(Box::into_inner(info.parse_inline(text)?.0))).collect::<Result<_>>()?,
+        };
+        add_to_glossary(&mut info.glossary, term.trim().to_lowercase(), definition.clone());
+        list.push(definition);
+    }
+    let id = info.num_definition_lists;
+    info.num_definition_lists += 1;
+    Ok(MaybeElement::Yes((Element::DefinitionList(list), format!("definitions-{id}"))))
+}
+
+// a term defined twice ends up in the glossary once, with the definitions of both
+pub fn add_to_glossary(glossary: &mut OrderedMap<String, Definition>, key: String, definition: Definition) {
+    let Some(entry) = glossary.get_mut(&key) else {
+        glossary.insert(key, definition);
+        return;
+    };
+    for text in definition.definitions {
+        if !entry.definitions.contains(&text) {
+            entry.definitions.push(text);
+        }
+    }
+}
+
+pub fn parse_table_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
+    let TopLevelSyntax::Table { header, alignments, rows } = elem else { return Ok(MaybeElement::No) };
+    let parse_row = |info: &mut ParserInfo, row: &[Cow<str>]| -> Result<Vec<Element>> {
+        row.iter().map(|cell| Ok(Box::into_inner(info.parse_inline(cell)?.0))).collect()
+    };
+    let header = parse_row(info, header)?;
+    let rows = rows.iter().map(|row| parse_row(info, row)).collect::<Result<Vec<_>>>()?;
+    let id = info.num_tables;
+    info.num_tables += 1;
+    Ok(MaybeElement::Yes((Element::Table(Table { header, alignments: alignments.clone(), rows }), format!("table-{id}"))))
+}
+
+// transclusions are swapped out for what they include while parsing the toplevel syntax, the
+// ones still here couldn't be included
+pub fn parse_transclusion_element(info: &mut ParserInfo, elem: &TopLevelSyntax) -> Result<MaybeElement> {
+    let TopLevelSyntax::Transclusion { path, section, error } = elem else { return Ok(MaybeElement::No) };
+    let source = match section {
+        Some(section) => format!("{path}#{section}"),
+        None => path.to_string(),
+    };
+    let diagnostic = match error {
+        Some(TransclusionError::MissingSection) => Diagnostic::error("unknown-section", format!("'{path}' has no section called '{}'", section.as_deref().unwrap_or_default()))
+            .with_help("the section is the text of a heading or its id"),
+        Some(TransclusionError::Cycle(files)) => Diagnostic::error("transclusion-cycle", format!("'{source}' ends up including itself"))
+            .with_help(files.join(" -> ")),
+        Some(TransclusionError::Unreadable(error)) => Diagnostic::error("missing-transclusion", format!("could not include '{source}': {error}"))
+            .with_help("paths are relative to the file doing the including"),
+        None => Diagnostic::error("missing-transclusion", format!("could not include '{source}'")),
+    };
+    info.diagnostics.push(diagnostic);
+    Ok(MaybeElement::Yes((Element::Error(format!("![[{source}]]")), String::new())))
 }
 
 pub fn check_id(info: &mut ParserInfo, last_length: usize) {
     if info.body.len() != last_length {
-        if let Some((_, id)) = info.body.last_mut() {
+        if let Some((_, id, _)) = info.body.last_mut() {
             if is_valid_id(id) {
                 while info.ids.contains(id) {
                     *id += format!("-{last_length}").as_str();
@@ -137,3 +321,40 @@ pub fn check_id(info: &mut ParserInfo, last_length: usize) {
         }
     }
 }
+
+pub type ElementParser = fn(&mut ParserInfo, &TopLevelSyntax) -> Result<MaybeElement>;
+
+/// runs `elem` through `parsers` in order and pushes the first element one of them produces,
+/// if a parser fails or none of them apply the source is kept as an `Element::Error` instead
+pub fn parse_element(info: &mut ParserInfo, elem: &TopLevelSyntax, span: SourceSpan, parsers: &[ElementParser]) {
+    let last_length = info.body.len();
+    info.enter(span);
+    for parser in parsers {
+        match parser(info, elem) {
+            Ok(MaybeElement::No) => continue,
+            Ok(MaybeElement::Yes(element)) => {
+                info.push(element, span);
+                check_id(info, last_length);
+            },
+            Err(error) => {
+                info.push_error(span, Diagnostic::error("invalid-element", format!("{error:#}")));
+            },
+        }
+        return;
+    }
+    info.push_error(span, Diagnostic::error("unexpected-element", "this element isn't allowed here"));
+}
+
+pub fn parse_note_definition(info: &mut ParserInfo, id: &str, text: &str, span: SourceSpan) {
+    info.enter(span);
+    match info.parse_inline(text) {
+        Ok((object, _)) => {
+            info.define_note(id, Box::into_inner(object), span);
+        },
+        Err(error) => {
+            info.define_note(id, Element::Error(text.to_string()), span);
+            info.diagnostics.push(Diagnostic::error("invalid-note", format!("note '{id}' could not be parsed: {error:#}"))
+                .with_span(span));
+        },
+    }
+}
@@ -1,67 +1,211 @@
-#[cfg(not(feature = "wasm"))]
 use anyhow::{Result, Context};
 use super::structs::*;
 use super::frontmatter::*;
 use super::config::*;
+use super::diagnostic::Diagnostic;
+use super::inline::parse_inline_at;
+use super::source::{find_lines, LineIndex, SourceSpan, TextOrigin};
 use super::util::ordered_map::OrderedMap;
 use std::collections::HashSet;
 
-pub(super) struct ParserInfo {
+pub(super) struct ParserInfo<'a> {
+    // the whole file, spans index into this
+    pub(super) source: &'a str,
+    // lines and columns of the whole file, so spans found while parsing don't index it again
+    pub(super) index: &'a LineIndex<'a>,
     pub(super) notes: OrderedMap<String, Element>,
+    pub(super) glossary: OrderedMap<String, Definition>,
+    pub(super) figures: OrderedMap<String, FigureNumber>,
     pub(super) references: OrderedMap<String, ReferenceDefinition>,
     pub(super) metadata: MetaData,
-    pub(super) body: Vec::<(Element, String)>,
+    pub(super) body: Vec::<(Element, String, SourceSpan)>,
+    pub(super) diagnostics: Vec<Diagnostic>,
+    // the element being parsed and how far into it we've found its text, see `parse_inline`
+    pub(super) span: SourceSpan,
+    pub(super) cursor: usize,
 
     pub(super) ids: HashSet<String>,
     pub(super) num_codeblocks: usize,
+    pub(super) num_math: usize,
     pub(super) num_image: usize,
+    pub(super) num_videos: usize,
+    pub(super) num_audio: usize,
+    pub(super) num_embeds: usize,
     pub(super) num_lists: usize,
     pub(super) num_quotes: usize,
+    pub(super) num_callouts: usize,
+    pub(super) num_tables: usize,
+    pub(super) num_definition_lists: usize,
     pub(super) num_factboxes: usize,
 }
 
-impl ParserInfo {
-    pub(super) fn new() -> Self {
+impl<'a> ParserInfo<'a> {
+    pub(super) fn new(index: &'a LineIndex<'a>) -> Self {
         let mut notes      = OrderedMap::<String, Element>::new();
+        let mut glossary   = OrderedMap::<String, Definition>::new();
+        let mut figures    = OrderedMap::<String, FigureNumber>::new();
         let mut references = OrderedMap::<String, ReferenceDefinition>::new();
         let mut metadata = MetaData::default();
-        let mut body = Vec::<(Element, String)>::new();
+        let mut body = Vec::<(Element, String, SourceSpan)>::new();
+        let mut diagnostics = Vec::<Diagnostic>::new();
         
         let mut ids = HashSet::<String>::new();
         let mut num_codeblocks = 0usize;
+        let mut num_math = 0usize;
         let mut num_image = 0usize;
+        let mut num_videos = 0usize;
+        let mut num_audio = 0usize;
+        let mut num_embeds = 0usize;
         let mut num_lists = 0usize;
         let mut num_quotes = 0usize;
+        let mut num_callouts = 0usize;
+        let mut num_tables = 0usize;
+        let mut num_definition_lists = 0usize;
         let mut num_factboxes = 0usize;
         Self {
+            source: index.text(),
+            index,
             notes,
+            glossary,
+            figures,
             references,
             metadata,
             body,
+            diagnostics,
+            span: SourceSpan::default(),
+            cursor: 0,
             
             ids,
             num_codeblocks,
+            num_math,
             num_image,
+            num_videos,
+            num_audio,
+            num_embeds,
             num_lists,
             num_quotes,
+            num_callouts,
+            num_tables,
+            num_definition_lists,
             num_factboxes,
         }
     } 
 
-    pub(super) fn build(self, notes_id: String, bibliography_id: String) -> AssDownDocument {
-        AssDownDocument { 
+    /// starts parsing the element at `span`, the text of it is looked for from the start again
+    pub(super) fn enter(&mut self, span: SourceSpan) {
+        self.span = span;
+        self.cursor = span.start.offset;
+    }
+
+    /// `parse_inline` for a piece of text of the element being parsed. pieces are looked for in the
+    /// source in the order they're parsed in, so the cells of a table find their own text
+    pub(super) fn parse_inline(&mut self, text: &str) -> Result<(Box<Element>, String)> {
+        let end = self.span.end.offset.min(self.source.len());
+        let lines = self.source.get(..end).and_then(|source| find_lines(source, text, self.cursor)).unwrap_or_default();
+        if let Some((start, origin)) = lines.last() {
+            self.cursor = (origin + text.len() - start).min(end);
+        }
+        parse_inline_at(text, TextOrigin::new(self.index, &lines))
+    }
+
+    /// a note defined again, like one with the same id in an included post, is kept under a key of
+    /// its own so its text isn't lost, references to the id keep going to the first one
+    pub(super) fn define_note(&mut self, id: &str, note: Element, span: SourceSpan) {
+        let key = if self.notes.contains_key(id) {
+            let key = free_key(&self.notes, id);
+            self.diagnostics.push(Diagnostic::warning("duplicate-note", format!("note '{id}' is defined more than once"))
+                .with_span(span)
+                .with_help(format!("[^{id}] points at the first one, this one is kept as [^{key}], rename one of them")));
+            key
+        } else { id.to_string() };
+        self.notes.insert(key, note);
+    }
+
+    /// like `define_note`, gives back the id the reference ended up with
+    pub(super) fn define_reference(&mut self, mut reference: ReferenceDefinition, span: SourceSpan) -> String {
+        let id = reference.id.clone();
+        if self.references.contains_key(&id) {
+            reference.id = free_key(&self.references, &id);
+            self.diagnostics.push(Diagnostic::warning("duplicate-reference", format!("reference '{id}' is defined more than once"))
+                .with_span(span)
+                .with_help(format!("£{id} points at the first one, this one is kept as £{}, rename one of them", reference.id)));
+        }
+        let key = reference.id.clone();
+        self.references.insert(key.clone(), reference);
+        key
+    }
+
+    pub(super) fn push(&mut self, (element, id): (Element, String), span: SourceSpan) {
+        // anything reported while parsing this element without a location of its own points at the element
+        for diagnostic in self.diagnostics.iter_mut().rev() {
+            if diagnostic.span.is_some() { break }
+            diagnostic.span = Some(span);
+        }
+        self.body.push((element, id, span));
+    }
+
+    /// keeps the source of something we couldn't parse in the body so the document still builds
+    pub(super) fn push_error(&mut self, span: SourceSpan, diagnostic: Diagnostic) {
+        let raw = self.source.get(span.range()).unwrap_or_default().to_string();
+        self.diagnostics.push(diagnostic);
+        self.push((Element::Error(raw), String::new()), span);
+    }
+
+    pub(super) fn build(self, notes_id: String, bibliography_id: String, glossary_id: String) -> (AssDownDocument, Vec<Diagnostic>) {
+        (AssDownDocument { 
             meta: self.metadata, 
             bibliography_id,
             notes_id,
+            glossary_id,
             references: self.references, 
             notes: self.notes,
+            glossary: self.glossary,
+            figures: self.figures,
+            wiki_links: OrderedMap::new(),
+            backlinks: vec![],
             body: self.body
-        }
+        }, self.diagnostics)
     }
 }
 
 
-type PeekableChars<'l> = std::iter::Peekable<std::str::Chars<'l>>;
+// the first of "id-2", "id-3", ... that isn't in `map` yet
+fn free_key<V>(map: &OrderedMap<String, V>, id: &str) -> String {
+    (2..).map(|number| format!("{id}-{number}")).find(|key| !map.contains_key(key)).unwrap()
+}
+
+/// a peekable `Chars` that knows how much of the text is left without walking the rest of it,
+/// `std::iter::Peekable` hides the iterator it wraps
+#[derive(Clone)]
+pub(super) struct PeekableChars<'l> {
+    chars: std::str::Chars<'l>,
+    peeked: Option<char>,
+}
+
+impl<'l> PeekableChars<'l> {
+    pub(super) fn new(text: &'l str) -> Self {
+        Self { chars: text.chars(), peeked: None }
+    }
+
+    pub(super) fn peek(&mut self) -> Option<&char> {
+        if self.peeked.is_none() { self.peeked = self.chars.next() }
+        self.peeked.as_ref()
+    }
+
+    /// how many bytes of the text haven't been taken yet
+    pub(super) fn remaining(&self) -> usize {
+        self.chars.as_str().len() + self.peeked.map_or(0, char::len_utf8)
+    }
+}
+
+impl Iterator for PeekableChars<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        self.peeked.take().or_else(|| self.chars.next())
+    }
+}
+
 pub(super) fn gather_link<'l>(mut end: PeekableChars<'l>, depth: &mut i32) -> Result<(String, PeekableChars<'l>)> {
     let mut alt = String::new();
     if end.peek() == Some(&'(') {
@@ -97,7 +241,7 @@ pub(super) fn get_citation(text: &String) -> Option<Element> {
     }
 }
 
-pub(super) fn generate_id(text: &String) -> Option<String> {
+pub(super) fn generate_id(text: &str) -> Option<String> {
     if text.split_whitespace().collect::<String>().is_empty() {
         None
     } else {
@@ -112,9 +256,13 @@ pub(super) fn generate_id(text: &String) -> Option<String> {
             id.push('-');
         }
         if id.len() > MAX_ID_LENGTH {
-            Some(id[..MAX_ID_LENGTH].to_string())
+            // cut on a character, a byte in the middle of one would panic
+            let mut end = MAX_ID_LENGTH;
+            while !id.is_char_boundary(end) { end -= 1 }
+            id.truncate(end);
+            Some(id)
         } else {
-            id.remove(id.len() - 1);
+            if id.ends_with('-') { id.pop(); }
             Some(id)
         }
     }
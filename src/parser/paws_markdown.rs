@@ -1,8 +1,7 @@
 use std::collections::HashSet;
+use std::path::Path;
 
-#[cfg(not(feature = "wasm"))]
 use anyhow::{Result, Context};
-use color_print::{cprintln,cformat};
 use super::config::*;
 use super::factbox::parse_factbox_element;
 use super::inline::parse_inline;
@@ -12,108 +11,72 @@ use super::toplevel::{toplevel_parse, PmdDate, ReferenceDefinition, TopLevelSynt
 use super::frontmatter::*;
 use super::structs::*;
 use super::parser::*;
-// this is so stupid, why can't I just have macros be scoped like ever other fucking thing?
-use crate::{error, warning};
+use super::diagnostic::Diagnostic;
+use super::source::{LineIndex, SourceSpan, Spanned};
 
 macro_rules! no_id {
     ($e: expr) => { ($e, &String::new()) }
 }
 
 
-pub fn parse_file(file_path: &String) -> Result<AssDownDocument> {
+pub fn parse_file(file_path: &String) -> Result<(AssDownDocument, Vec<Diagnostic>)> {
     parse(&std::fs::read_to_string(file_path)?, Some(file_path))
 }
 
-pub fn parse(file_content: &String, file_path: Option<&String>) -> Result<AssDownDocument> {
-    let toplevel_syntax = toplevel_parse(file_content)?;
-    println!("parsed toplevel!");
+pub fn parse(file_content: &String, file_path: Option<&String>) -> Result<(AssDownDocument, Vec<Diagnostic>)> {
+    let toplevel_syntax = toplevel_parse(file_content, file_path.map(Path::new))?;
 
-    let mut info = ParserInfo::new();
-
-    for elem in &toplevel_syntax {
-        let last_length = info.body.len();
+    let index = LineIndex::new(file_content);
+    let mut info = ParserInfo::new(&index);
+    let mut reference_spans = OrderedMap::new();
 
+    for Spanned { node: elem, span } in &toplevel_syntax {
         if let TopLevelSyntax::FrontMatter(frontmatter) = elem {
             info.metadata.frontmatter = Some(frontmatter.clone());
             continue;
         }
         if let TopLevelSyntax::ReferenceDefinition(reference) = elem {
-            info.references.insert(reference.id.clone(), reference.clone());
+            // a second definition already got a diagnostic, it isn't reported as unused as well
+            if info.define_reference(reference.clone(), *span) == reference.id {
+                reference_spans.insert(reference.id.clone(), *span);
+            }
             continue;
         }
         if let TopLevelSyntax::NoteDefinition { id, text } = elem {
-            let (object, _) = parse_inline(&text)?;
-            info.notes.insert(id.clone(), Box::into_inner(object));
+            parse_note_definition(&mut info, id, text, *span);
             continue;
         }
         if let TopLevelSyntax::TOC(title) = elem {
             if info.metadata.toc.is_none() {
-                info.metadata.toc = Some(TableOfContent{ title: title.clone(), index: info.body.len(), headers: vec![], max_depth: 1,});
-                info.body.push((Element::TOCLocationMarker, String::new()));
+                info.metadata.toc = Some(TableOfContent{ title: title.to_string(), index: info.body.len(), headers: vec![], max_depth: 1,});
+                info.body.push((Element::TOCLocationMarker, String::new(), *span));
             }
             continue;
         }
 
-        if let MaybeElement::Yes(element) = parse_factbox_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-
-        if let MaybeElement::Yes(element) = parse_pagebreak_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-
-        if let MaybeElement::Yes(element) = parse_codeblock_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-        
-        if let MaybeElement::Yes(element) = parse_image_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-        
-        if let MaybeElement::Yes(element) = parse_list_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-
-        if let MaybeElement::Yes(element) = parse_paragraph_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-        
-        if let MaybeElement::Yes(element) = parse_heading_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-        
-        if let MaybeElement::Yes(element) = parse_quote_element(&mut info, elem)? {
-            info.body.push(element);
-            check_id(&mut info, last_length);
-            continue;
-        }
-
-
-        panic!("element was not parsed {:?}", elem);
+        parse_element(&mut info, elem, *span, &[
+            parse_factbox_element,
+            parse_pagebreak_element,
+            parse_codeblock_element,
+            parse_math_block_element,
+            parse_image_element,
+            parse_list_element,
+            parse_paragraph_element,
+            parse_heading_element,
+            parse_quote_element,
+            parse_callout_element,
+            parse_table_element,
+            parse_definition_list_element,
+            parse_transclusion_element,
+        ]);
     }
 
     if let Some(frontmatter) = &info.metadata.frontmatter {
         if let Some(title) = frontmatter["title"].as_string() {
             info.metadata.title = title;
         } else {
-            if let Some(file_path) = file_path {
-                #[cfg(not(feature = "wasm"))]
-                cprintln!("<r>error:</> Document '{}' is missing a title, see 'pmd explain frontmatter'", file_path);
-            }
+            info.diagnostics.push(Diagnostic::error("missing-title", "document is missing a title")
+                .with_help("see 'pmd explain frontmatter'"));
         }
         
         if let Some(subtitle) = frontmatter["subtitle"].as_string() {
@@ -128,6 +91,14 @@ pub fn parse(file_content: &String, file_path: Option<&String>) -> Result<AssDow
             info.metadata.notes_title = title;
         }
 
+        if let Some(title) = frontmatter["glossary-title"].as_string() {
+            info.metadata.glossary_title = title;
+        }
+
+        if let Some(title) = frontmatter["backlinks-title"].as_string() {
+            info.metadata.backlinks_title = title;
+        }
+
         if let Some(title) = get_bibliography_title(frontmatter) {
             info.metadata.bibliography_title = title;
         }
@@ -135,10 +106,8 @@ pub fn parse(file_content: &String, file_path: Option<&String>) -> Result<AssDow
         if let Some(date) = get_date(frontmatter) {
             info.metadata.date_written = PmdDate::String(date);
         } else {
-            if let Some(file_path) = file_path {
-                #[cfg(not(feature = "wasm"))]
-                cprintln!("<r>error:</> Document '{}' is missing a date, see 'pmd explain frontmatter'", file_path);
-            }
+            info.diagnostics.push(Diagnostic::error("missing-date", "document is missing a date")
+                .with_help("see 'pmd explain frontmatter'"));
         }
         
         if let Some(update) = get_last_update(frontmatter) {
@@ -158,12 +127,26 @@ pub fn parse(file_content: &String, file_path: Option<&String>) -> Result<AssDow
         info.metadata.hide_notes      = check_frontmatter(frontmatter, &FRONTMATTER_HIDE_NOTES);
         info.metadata.hide_references = check_frontmatter(frontmatter, &FRONTMATTER_HIDE_REFERENCES);
         info.metadata.hide_contacts   = check_frontmatter(frontmatter, &FRONTMATTER_HIDE_CONTACTS);
-    } else {
-        //TODO(Paw): this should really be a warning
-        if let Some(file_path) = file_path {
-            #[cfg(not(feature = "wasm"))]
-            cprintln!("<r>error:</> Document '{}' is missing frontmatter, see 'pmd explain frontmatter'", file_path);
+        info.metadata.hide_glossary   = check_frontmatter(frontmatter, &FRONTMATTER_HIDE_GLOSSARY);
+        info.metadata.toc_callouts    = check_frontmatter(frontmatter, &FRONTMATTER_TOC_CALLOUTS);
+        info.metadata.number_figures  = check_frontmatter(frontmatter, &FRONTMATTER_NUMBER_FIGURES);
+
+        // only checked here, the serializers quietly fall back to the defaults
+        #[cfg(any(feature = "html", feature = "rss", feature = "pdf", feature = "wasm"))]
+        if let Some(name) = frontmatter["code-theme"].as_string() && name != "none" && crate::highlight::theme(&name).is_none() {
+            use crate::highlight::{DEFAULT_THEME, THEMES};
+            let names: Vec<&str> = THEMES.iter().map(|theme| theme.name).collect();
+            info.diagnostics.push(Diagnostic::warning("unknown-code-theme", format!("code theme '{name}' doesn't exist, falling back to '{DEFAULT_THEME}'"))
+                .with_help(format!("the themes are {}, or 'none' to leave code blocks uncoloured", names.join(", "))));
+        }
+        #[cfg(any(feature = "pdf", feature = "wasm"))]
+        if let Some(name) = frontmatter["pdf-font"].as_string() && crate::pdf::FontFamily::from_name(&name).is_none() {
+            info.diagnostics.push(Diagnostic::warning("unknown-pdf-font", format!("font '{name}' is not available in pdf output, falling back to Helvetica"))
+                .with_help("pdf output only has helvetica, times and courier"));
         }
+    } else {
+        info.diagnostics.push(Diagnostic::warning("missing-frontmatter", "document is missing frontmatter")
+            .with_help("see 'pmd explain frontmatter'"));
     }
 
     let notes_id     = if let Some(id) = generate_id(&info.metadata.notes_title) { id } else {
@@ -175,54 +158,35 @@ pub fn parse(file_content: &String, file_path: Option<&String>) -> Result<AssDow
         default_id
     };
 
+    // the glossary is read alphabetically, not in the order the terms came up in
+    let mut entries: Vec<(&String, &Definition)> = info.glossary.iter().collect();
+    entries.sort_by_cached_key(|(term, _)| term.to_lowercase());
+    let mut glossary = OrderedMap::new();
+    for (term, definition) in entries {
+        glossary.insert(term.clone(), definition.clone());
+    }
+    info.glossary = glossary;
+
+    let glossary_id = if let Some(id) = generate_id(&info.metadata.glossary_title) { id } else {
+        let default_id = generate_id(&DEFAULT_GLOSSARY_TITLE.to_string()).unwrap();
+        default_id
+    };
+
     if !info.notes.is_empty() {
-        if info.ids.contains(&notes_id) {
-            'outer: for (elem, id) in &mut info.body.iter_mut() {
-                if let Element::FactBox(factbox) = elem {
-                    for (_, factbox_id) in &mut factbox.body {
-                        if factbox_id != &notes_id { continue }
-        
-                        while info.ids.contains(factbox_id) {
-                            *factbox_id = format!("{factbox_id}-disass");
-                        }
-                        break 'outer;
-                    }
-                }
-                if id != &notes_id { continue }
-                while info.ids.contains(id) {
-                    *id = format!("{id}-disass");
-                }
-        
-                break;
-            }
-        }
+        free_id(&mut info, &notes_id);
     }
-    
     if !info.references.is_empty() {
-        if info.ids.contains(&bibliography_id) {
-            'outer: for (elem, id) in &mut info.body.iter_mut() {
-                if let Element::FactBox(factbox) = elem {
-                    for (_, factbox_id) in &mut factbox.body {
-                        if factbox_id != &bibliography_id { continue }
-        
-                        while info.ids.contains(factbox_id) {
-                            *factbox_id = format!("{factbox_id}-disass");
-                        }
-                        break 'outer;
-                    }
-                }
-                if id != &bibliography_id { continue }
-                while info.ids.contains(id) {
-                    *id = format!("{id}-disass");
-                }
-        
-                break;
-            }
-        }
+        free_id(&mut info, &bibliography_id);
     }
-    
+    if !info.glossary.is_empty() {
+        free_id(&mut info, &glossary_id);
+    }
+
+    number_figures(&mut info);
+    check_citations(&mut info, &reference_spans);
+
     if let Some(toc) = info.metadata.toc.as_mut() {
-        for (i, (item, id)) in info.body.iter().enumerate() {
+        for (i, (item, id, _)) in info.body.iter().enumerate() {
             if i < toc.index { continue; }
             if let &Element::Header(text, depth) = &item {
                 if depth > &toc.max_depth {
@@ -236,20 +200,128 @@ pub fn parse(file_content: &String, file_path: Option<&String>) -> Result<AssDow
                 }
                 toc.headers.push((Box::new(item.clone()), 2, id.clone()));
             }
+            else if let Element::Callout(callout) = &item && info.metadata.toc_callouts {
+                if 2 > toc.max_depth {
+                    toc.max_depth = 2;
+                }
+                toc.headers.push((callout.title.clone(), 2, id.clone()));
+            }
         }
 
         if !info.notes.is_empty() {
             toc.headers.push((Box::new(Element::Text(info.metadata.notes_title.clone())), 1, notes_id.clone()))
         }
 
+        if !(info.glossary.is_empty() || info.metadata.hide_glossary) {
+            toc.headers.push((Box::new(Element::Text(info.metadata.glossary_title.clone())), 1, glossary_id.clone()))
+        }
+
         if !info.references.is_empty() {
             toc.headers.push((Box::new(Element::Text(info.metadata.bibliography_title.clone())), 1, bibliography_id.clone()))
         }
     }
 
-    Ok(info.build(notes_id, bibliography_id))
+    Ok(info.build(notes_id, bibliography_id, glossary_id))
 }
 
+// renames whatever in the body already uses the id of a section added at the end, like the notes
+fn free_id(info: &mut ParserInfo, reserved: &String) {
+    if !info.ids.contains(reserved) { return }
+    'outer: for (elem, id, _) in &mut info.body.iter_mut() {
+        if let Element::FactBox(factbox) = elem {
+            for (_, factbox_id, _) in &mut factbox.body {
+                if factbox_id != reserved { continue }
+
+                while info.ids.contains(factbox_id) {
+                    *factbox_id = format!("{factbox_id}-disass");
+                }
+                break 'outer;
+            }
+        }
+        if id != reserved { continue }
+        while info.ids.contains(id) {
+            *id = format!("{id}-disass");
+        }
+
+        break;
+    }
+}
+
+// images, tables and code blocks are counted separately in the order they show up, this runs
+// after the ids are final since that's what `[@fig:id]` is resolved with
+fn number_figures(info: &mut ParserInfo) {
+    if info.metadata.number_figures {
+        let mut counts = [0usize; 3];
+        for (element, id, _) in info.body.iter_mut() {
+            let kind = match element {
+                Element::Image(image) => {
+                    if !matches!(&*image.alt, Element::Span(span) if span.elements.is_empty()) {
+                        image.caption = Some(image.alt.clone());
+                    }
+                    FigureKind::Figure
+                },
+                Element::Table(_) => FigureKind::Table,
+                Element::CodeBlock(_) => FigureKind::Listing,
+                _ => continue,
+            };
+            let count = &mut counts[kind as usize];
+            *count += 1;
+            info.figures.insert(id.clone(), FigureNumber { kind, number: *count });
+        }
+    }
+
+    for (element, _, span) in &info.body {
+        let mut references = vec![];
+        element.walk_spanned(*span, &mut |element, block| if let Element::CrossReference(kind, id, span) = element {
+            // the reference itself if we know where it is, otherwise the block it's in
+            references.push((*kind, id.clone(), if span.is_located() { *span } else { block }));
+        });
+        for (kind, id, span) in references {
+            let reference = format!("[@{}:{id}]", kind.prefix());
+            let diagnostic = match info.figures.get(&id) {
+                Some(figure) if figure.kind == kind => continue,
+                Some(figure) => Diagnostic::warning("wrong-figure-kind",
+                    format!("{reference} points at {}, not a {}", figure.label(), kind.name().to_lowercase())),
+                None => Diagnostic::warning("unknown-figure", format!("{reference} doesn't point at anything that is numbered"))
+                    .with_help("figures are only numbered with 'figures: true' in the frontmatter, see 'pmd explain figures'"),
+            };
+            info.diagnostics.push(diagnostic.with_span(span));
+        }
+    }
+}
+
+// a citation without a reference is rendered as a missing source and a reference nobody cites is
+// left out of the bibliography, usually one of them is a typo
+fn check_citations(info: &mut ParserInfo, reference_spans: &OrderedMap<String, SourceSpan>) {
+    let mut citations: Vec<(String, Option<SourceSpan>)> = vec![];
+    let mut collect = |element: &Element, span: Option<SourceSpan>| element.walk_spanned(span.unwrap_or_default(), &mut |element, block| {
+        if let Element::Citation(id) = element {
+            citations.push((id.clone(), span.map(|_| block)));
+        }
+    });
+    for (element, _, span) in &info.body {
+        collect(element, Some(*span));
+        if let Element::FactBox(factbox) = element {
+            factbox.notes.iter().for_each(|(_, (note, _))| collect(note, Some(*span)));
+        }
+    }
+    info.notes.iter().for_each(|(_, note)| collect(note, None));
+
+    for (id, span) in &citations {
+        if info.references.contains_key(id) { continue }
+        let diagnostic = Diagnostic::warning("unknown-citation", format!("£{id} has no source"))
+            .with_help(format!("define it with £{id} {{ title: ... }}, see 'pmd explain citation'"));
+        info.diagnostics.push(match span {
+            Some(span) => diagnostic.with_span(*span),
+            None => diagnostic,
+        });
+    }
+    for (id, span) in reference_spans {
+        if citations.iter().any(|(cited, _)| cited == id) { continue }
+        info.diagnostics.push(Diagnostic::warning("unused-reference", format!("reference '{id}' is not used and will not be included"))
+            .with_span(*span));
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -378,6 +450,43 @@ mod tests {
         assert!(inner == Element::Bold(Box::new(Element::Text("bold".into()))))
     }
     
+    #[test]
+    fn test_parse_inline_styles() {
+        let text = |value: &str| Box::new(Element::Text(value.into()));
+        let (element, id) = parse_inline("~~gone~~ ++under++ ==marked== x^2^ H~2~O").unwrap();
+        assert_eq!(*element, Element::Span(Span { elements: vec![
+            Element::Strikethrough(text("gone")),
+            Element::Text(" ".into()),
+            Element::Underline(text("under")),
+            Element::Text(" ".into()),
+            Element::Highlight(text("marked")),
+            Element::Text(" x".into()),
+            Element::Superscript(text("2")),
+            Element::Text(" H".into()),
+            Element::Subscript(text("2")),
+            Element::Text("O".into()),
+        ]}));
+        assert_eq!(id, "gone-under-marked-x2-H2O");
+
+        // spaces around the delimiters, or inside the single character ones, keep them as text
+        let (element, _) = parse_inline("a == b, C++ and ~ some text~ or 2^ 3^").unwrap();
+        assert_eq!(*element, Element::Text("a == b, C++ and ~ some text~ or 2^ 3^".into()));
+    }
+
+    #[test]
+    fn test_unmatched_delimiters() {
+        let text = "==a ==b ~~c ++d ^e ".repeat(2000);
+        let (element, _) = parse_inline(&text).unwrap();
+        assert_eq!(*element, Element::Text(text.clone()));
+
+        // where the styled text is counted in bytes, past characters that take more than one
+        let text: String = "==é== ~~ü~~ and [@fig:gone]\n\n".into();
+        let (_, diagnostics) = parse(&text, None).unwrap();
+        let span = diagnostics.iter().find(|x| x.code == "unknown-figure").unwrap().span.unwrap();
+        assert_eq!(&text[span.range()], "[@fig:gone]");
+        assert_eq!(span.start.column, 17);
+    }
+
     #[test]
     fn test_parse_bold_and_italics() {
         let text: String = "***italics and bold***".into();
@@ -436,6 +545,298 @@ mod tests {
         let inner = Box::into_inner(result.unwrap().0);
         assert!(inner == Element::Citation("-other-example".into()))
     }
-}
 
+    #[test]
+    fn test_recover_from_invalid_element() {
+        let text: String = "---\ntitle: broken\ndate: 2024-01-01\n---\n\n[[image.png|10]\n%[alt]\n\nafter\n\n".into();
+        let (document, diagnostics) = parse(&text, None).unwrap();
+        assert_eq!(document.body[0].0, Element::Error("[[image.png|10]\n%[alt]".into()));
+        assert!(matches!(document.body[1].0, Element::Paragraph(_)));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "invalid-element");
+        assert_eq!(diagnostics[0].span.map(|x| x.start.line), Some(6));
+    }
 
+    #[test]
+    fn test_recover_from_unexpected_element() {
+        let text: String = "[[fact] title\n    # heading\n\n    body\n\n]\n".into();
+        let (document, diagnostics) = parse(&text, None).unwrap();
+        let Element::FactBox(factbox) = &document.body[0].0 else { panic!("expected a factbox") };
+        assert_eq!(factbox.body[0].0, Element::Error("# heading".into()));
+        assert!(diagnostics.iter().any(|x| x.code == "unexpected-element"));
+    }
+
+    #[test]
+    fn test_image_size() {
+        let text: String = "![[a.png|600x400] alt]\n\n![[b.png|x50%]]\n\n![[c.png|2emxauto]]\n\n![[d.png|300]]\n\n![[e.png|12parsecs]]\n\n".into();
+        let (document, diagnostics) = parse(&text, None).unwrap();
+        let size = |index: usize| match &document.body[index].0 {
+            Element::Image(image) => image.size.clone(),
+            _ => panic!("expected an image"),
+        };
+        assert_eq!(size(0), ImageSize::Double(Unit::Px(600), Unit::Px(400)));
+        assert_eq!(size(1), ImageSize::Double(Unit::Auto, Unit::Percentage(50)));
+        assert_eq!(size(2), ImageSize::Single(Unit::Em(2)));
+        assert_eq!(size(3), ImageSize::Single(Unit::Px(300)));
+        assert_eq!(size(4), ImageSize::None);
+        assert_eq!(size(1).dimensions(), (None, Some(&Unit::Percentage(50))));
+        assert_eq!(diagnostics.iter().filter(|x| x.code == "invalid-image-size").count(), 1);
+    }
+
+    #[test]
+    fn test_figures() {
+        let text: String = "---\nfigures: true\n---\nsee [@fig:A-cat] and [@tbl:table-0], not [@lst:A-cat] or [@fig:nothing]\n\n![[cat.png] A cat]\n\n| a |\n|---|\n\n```rust\nfn main() {}\n```\n\n![[dog.png]]\n\n".into();
+        let (document, diagnostics) = parse(&text, None).unwrap();
+        let Element::Paragraph(paragraph) = &document.body[0].0 else { panic!("expected a paragraph") };
+        let Element::Span(span) = &**paragraph else { panic!("expected a span") };
+        assert!(matches!(&span.elements[1], Element::CrossReference(FigureKind::Figure, id, at) if id == "A-cat" && &text[at.range()] == "[@fig:A-cat]"));
+        assert!(matches!(&span.elements[3], Element::CrossReference(FigureKind::Table, id, _) if id == "table-0"));
+
+        let Element::Image(image) = &document.body[1].0 else { panic!("expected an image") };
+        assert_eq!(image.caption, Some(Box::new(Element::Text("A cat".into()))));
+        let label = |id: &str| document.figures.get(id).map(FigureNumber::label);
+        assert_eq!(label("A-cat").as_deref(), Some("Figure 1"));
+        assert_eq!(label("table-0").as_deref(), Some("Table 1"));
+        assert_eq!(label("codeblock").as_deref(), Some("Listing 1"));
+        assert_eq!(label("image-1").as_deref(), Some("Figure 2"));
+
+        assert_eq!(diagnostics.iter().filter(|x| x.code == "wrong-figure-kind").count(), 1);
+        assert_eq!(diagnostics.iter().filter(|x| x.code == "unknown-figure").count(), 1);
+
+        // without the frontmatter nothing is numbered or captioned
+        let (document, _) = parse(&"![[cat.png] A cat]\n\n".to_string(), None).unwrap();
+        let Element::Image(image) = &document.body[0].0 else { panic!("expected an image") };
+        assert!(image.caption.is_none() && document.figures.is_empty());
+    }
+
+    #[test]
+    fn test_non_ascii() {
+        // ids are cut short at MAX_ID_LENGTH bytes, which can be in the middle of a character
+        let long = "日本".repeat(20);
+        let text = format!("# Ça coûte 5£ à Zürich\n\n# {long}\n\n[[fact] Faits sur le café\nLe café est né en Éthiopie, 日本 aussi\n]\n\n");
+        let (document, _) = parse(&text, None).unwrap();
+        assert_eq!(document.body[0].1, "Ça-coûte-5£-à-Zürich");
+        assert!(document.body[1].1.len() <= MAX_ID_LENGTH && long.starts_with(&document.body[1].1));
+        let Element::FactBox(factbox) = &document.body[2].0 else { panic!("expected a fact box") };
+        assert_eq!(factbox.title.trim(), "Faits sur le café");
+        assert_eq!(factbox.body.len(), 1);
+    }
+
+    #[test]
+    fn test_transclusion_duplicates() {
+        let directory = std::env::temp_dir().join(format!("pmd-duplicates-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("other.md"), "from the other post[^1][£doe]\n\n[^1]: the other note\n\n£doe {\n    title: Another Book,\n}\n").unwrap();
+        let main = directory.join("main.pmd").display().to_string();
+        std::fs::write(&main, "![[other.md]]\n\nhere[^1][£doe]\n\n[^1]: this note\n\n£doe {\n    title: A Book,\n}\n").unwrap();
+
+        let (document, diagnostics) = parse_file(&main).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let notes: Vec<(&String, &Element)> = document.notes.iter().collect();
+        assert_eq!(notes, [
+            (&"1".to_string(), &Element::Text("the other note".into())),
+            (&"1-2".to_string(), &Element::Text("this note".into())),
+        ]);
+        assert_eq!(document.references.get("doe-2").unwrap().id, "doe-2");
+        let duplicates: Vec<_> = diagnostics.iter().filter(|x| x.code.starts_with("duplicate-")).collect();
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].span.unwrap().start.line, 5);
+        assert!(!diagnostics.iter().any(|x| x.code == "unused-reference"));
+    }
+
+    #[test]
+    fn test_media() {
+        let text: String = "![[https://youtu.be/dQw4w9WgXcQ] A song]\n\n![[clip.webm|640x360]]\n\n![[talk.mp3] The talk]\n\n".into();
+        let (document, _) = parse(&text, None).unwrap();
+        let Element::Iframe(iframe) = &document.body[0].0 else { panic!("expected an iframe") };
+        assert_eq!(iframe.src, "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ");
+        assert_eq!(iframe.link, "https://youtu.be/dQw4w9WgXcQ");
+        assert_eq!(document.body[0].1, "A-song");
+        let Element::Video(video) = &document.body[1].0 else { panic!("expected a video") };
+        assert_eq!(video.size, ImageSize::Double(Unit::Px(640), Unit::Px(360)));
+        assert_eq!(document.body[1].1, "video-0");
+        assert!(matches!(&document.body[2].0, Element::Audio(audio) if audio.src == "talk.mp3"));
+    }
+
+    #[test]
+    fn test_transclusion() {
+        let directory = std::env::temp_dir().join(format!("pmd-transclusion-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("posts")).unwrap();
+        std::fs::write(directory.join("posts/other.md"), "---\ntitle: other\n---\nintro[^a]\n\n# Some Heading\n\nincluded\n\n## Below\n\nalso included\n\n# Next\n\nleft out\n\n[^a]: a note\n").unwrap();
+        std::fs::write(directory.join("posts/loop.pmd"), "![[../main.pmd]]\n").unwrap();
+        let main = directory.join("main.pmd").display().to_string();
+        std::fs::write(&main, "![[posts/other.md#Some Heading]]\n\n![[posts/loop.pmd]]\n\n![[posts/other.md#Nowhere]]\n\n![[missing.md]]\n").unwrap();
+
+        let (document, diagnostics) = parse_file(&main).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let body = format!("{:?}", document.body);
+        assert!(body.contains("Some Heading") && body.contains("also included"));
+        assert!(!body.contains("intro") && !body.contains("left out"));
+        assert!(matches!(&document.body[0].0, Element::Header(_, 1)));
+        assert!(document.notes.contains_key("a"));
+        // the included heading points at the line that included it
+        assert_eq!(document.body[1].2.start.line, 1);
+
+        let codes: Vec<&str> = diagnostics.iter().map(|x| x.code).filter(|x| *x != "missing-frontmatter").collect();
+        assert_eq!(codes, ["transclusion-cycle", "unknown-section", "missing-transclusion"]);
+        assert_eq!(diagnostics.iter().find(|x| x.code == "unknown-section").unwrap().span.unwrap().start.line, 5);
+    }
+
+    #[test]
+    fn test_codeblock_info_string() {
+        let text: String = "```rust title=\"src/main.rs\" showLineNumbers=3 {1,3-4} diff\nfn main() {}\r\n```\n\n```\nplain\n```\n\n```c nonsense {x}\n```\n".into();
+        let (document, diagnostics) = parse(&text, None).unwrap();
+        let Element::CodeBlock(block) = &document.body[0].0 else { panic!("expected a codeblock") };
+        assert_eq!(block.lang, "rust");
+        assert_eq!(block.title.as_deref(), Some("src/main.rs"));
+        assert_eq!(block.start_line, Some(3));
+        assert_eq!(block.highlights, vec![(1, 1), (3, 4)]);
+        assert!(block.diff && block.is_highlighted(4) && !block.is_highlighted(2));
+        assert_eq!(block.code, "fn main() {}");
+
+        let Element::CodeBlock(block) = &document.body[1].0 else { panic!("expected a codeblock") };
+        assert_eq!(block, &CodeBlock { code: "plain".into(), ..CodeBlock::default() });
+
+        assert!(diagnostics.iter().any(|x| x.code == "unknown-codeblock-option" && x.message.contains("nonsense")));
+        assert!(diagnostics.iter().any(|x| x.code == "invalid-codeblock-option"));
+    }
+
+    #[test]
+    fn test_pdf_font() {
+        let parse_font = |font: &str| parse(&format!("---\ntitle: t\ndate: 2024-01-01\npdf-font: {font}\n---\n"), None).unwrap().1;
+        assert!(parse_font("Times New Roman").is_empty());
+        let diagnostics = parse_font("Comic Sans");
+        assert!(diagnostics.is_empty(), "sans falls back to helvetica on purpose");
+        let diagnostics = parse_font("Papyrus");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "unknown-pdf-font");
+    }
+
+    #[test]
+    fn test_nested_spans() {
+        let text: String = "> quoted\n> see [@fig:gone] here\n\n- item with [@tbl:lost]\n\n  body of *the [@lst:missing]* item\n\n".into();
+        let (_, diagnostics) = parse(&text, None).unwrap();
+        let locations: Vec<(usize, usize)> = diagnostics.iter().filter(|x| x.code == "unknown-figure")
+            .map(|x| x.span.unwrap()).map(|span| (span.start.line, span.start.column)).collect();
+        assert_eq!(locations, vec![(2, 7), (4, 13), (6, 16)]);
+    }
+
+    #[test]
+    fn test_citation_diagnostics() {
+        let text: String = "see [£doe] and [£nobody][^1]\n\n[^1]: also [£doe]\n\n£doe {\n    title: A Book,\n}\n\n£unused {\n    title: Another Book,\n}\n".into();
+        let (_, diagnostics) = parse(&text, None).unwrap();
+        let unknown: Vec<_> = diagnostics.iter().filter(|x| x.code == "unknown-citation").collect();
+        assert_eq!(unknown.len(), 1);
+        assert!(unknown[0].message.contains("nobody"));
+        assert_eq!(unknown[0].span.unwrap().start.line, 1);
+        let unused: Vec<_> = diagnostics.iter().filter(|x| x.code == "unused-reference").collect();
+        assert_eq!(unused.len(), 1);
+        assert!(unused[0].message.contains("'unused'"));
+        assert_eq!(unused[0].span.unwrap().start.line, 9);
+    }
+
+    #[test]
+    fn test_code_theme() {
+        let parse_theme = |theme: &str| parse(&format!("---\ntitle: t\ndate: 2024-01-01\ncode-theme: {theme}\n---\n"), None).unwrap().1;
+        assert!(parse_theme("dark").is_empty() && parse_theme("none").is_empty());
+        let diagnostics = parse_theme("solarized");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "unknown-code-theme");
+        assert!(diagnostics[0].help.as_ref().unwrap().contains("light, dark"));
+    }
+
+    #[test]
+    fn test_table() {
+        let text: String = "| name | **size** |\n| :--- | ---: |\n| a | 1 |\n| b |\n\n| x |\n|---|\n".into();
+        let (document, _) = parse(&text, None).unwrap();
+        let Element::Table(table) = &document.body[0].0 else { panic!("expected a table") };
+        assert_eq!(document.body[0].1, "table-0");
+        assert_eq!(document.body[1].1, "table-1");
+        assert_eq!(table.alignments, vec![Alignment::Left, Alignment::Right]);
+        assert_eq!(table.header[1], Element::Bold(Box::new(Element::Text("size".into()))));
+        assert_eq!(table.rows[1][1], Element::Span(Span { elements: vec![] }));
+        assert_eq!(&text[document.body[0].2.range()], "| name | **size** |\n| :--- | ---: |\n| a | 1 |\n| b |");
+    }
+
+    #[test]
+    fn test_math() {
+        let text: String = "costs $5 or \\$6, $x^2 \\$$ is $ not math $\n\n$$\n\\frac{1}{\\nope}\n$$\n".into();
+        let (document, diagnostics) = parse(&text, None).unwrap();
+        let Element::Paragraph(paragraph) = &document.body[0].0 else { panic!("expected a paragraph") };
+        assert_eq!(**paragraph, Element::Span(Span { elements: vec![
+            Element::Text("costs $5 or $6, ".into()),
+            Element::InlineMath("x^2 \\$".into()),
+            Element::Text(" is $ not math $\n".into()),
+        ]}));
+        assert_eq!(document.body[1], (Element::MathBlock("\\frac{1}{\\nope}".into()), "math-0".into(), document.body[1].2));
+        assert_eq!(diagnostics.iter().filter(|x| x.code == "invalid-math").count(), 1);
+    }
+
+    #[test]
+    fn test_list_body() {
+        let text: String = "1. build it\n   ```sh\n   cargo build\n   ```\n\n   then run it\n2. done\n\n".into();
+        let (document, _) = parse(&text, None).unwrap();
+        let Element::List(list) = &document.body[0].0 else { panic!("expected a list") };
+        let ListItem::Numbered(1, step, body) = &list[0] else { panic!("expected a numbered item") };
+        assert_eq!(**step, Element::Text("build it".into()));
+        assert!(matches!(&body[0].node, Element::CodeBlock(block) if block.lang == "sh" && block.code == "cargo build"));
+        assert_eq!(body[1].node, Element::Paragraph(Box::new(Element::Text("then run it\n".into()))));
+        assert_eq!((body[1].span.start.line, body[1].span.start.column), (6, 4));
+        assert_eq!(list[1], ListItem::Numbered(2, Box::new(Element::Text("done".into())), vec![]));
+    }
+
+    #[test]
+    fn test_task_list() {
+        let text: String = "- [x] **done**\n- [ ] not yet\n\n".into();
+        let (document, _) = parse(&text, None).unwrap();
+        let Element::List(list) = &document.body[0].0 else { panic!("expected a list") };
+        assert_eq!(list[0], ListItem::Task { checked: true, text: Box::new(Element::Bold(Box::new(Element::Text("done".into())))), body: vec![] });
+        assert_eq!(list[1], ListItem::Task { checked: false, text: Box::new(Element::Text("not yet".into())), body: vec![] });
+    }
+
+    #[test]
+    fn test_quote() {
+        let text: String = "> said\n>\n> ```\n> code\n> ```\n> -- *Someone*\n\n".into();
+        let (document, _) = parse(&text, None).unwrap();
+        let Element::Quote(quote) = &document.body[0].0 else { panic!("expected a quote") };
+        assert_eq!(quote.body[0].node, Element::Paragraph(Box::new(Element::Text("said\n".into()))));
+        assert!(matches!(&quote.body[1].node, Element::CodeBlock(block) if block.code == "code"));
+        assert_eq!(quote.attribution, Some(Box::new(Element::Italics(Box::new(Element::Text("Someone".into()))))));
+    }
+
+    #[test]
+    fn test_glossary() {
+        let text: String = "Zebra\n: striped\n\nApple\n: a *fruit*\n\n> Zebra\n> : striped\n> : a horse\n\n".into();
+        let (document, _) = parse(&text, None).unwrap();
+        let Element::DefinitionList(list) = &document.body[0].0 else { panic!("expected a definition list") };
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[1].definitions, vec![Element::Span(Span { elements: vec![
+            Element::Text("a ".into()),
+            Element::Italics(Box::new(Element::Text("fruit".into()))),
+        ]})]);
+
+        assert_eq!(document.glossary.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), vec!["apple", "zebra"]);
+        let zebra = document.glossary.get("zebra").unwrap();
+        assert_eq!(zebra.definitions, vec![Element::Text("striped".into()), Element::Text("a horse".into())]);
+        assert_eq!(document.glossary_id, "Glossary");
+    }
+
+    #[test]
+    fn test_callout() {
+        let text: String = "#[toc] Contents\n\n> [!WARNING]-\n> careful\n\n# After\n\n".into();
+        let (document, _) = parse(&text, None).unwrap();
+        let Element::Callout(callout) = &document.body[1].0 else { panic!("expected a callout") };
+        assert_eq!(callout.kind, "warning");
+        assert_eq!(*callout.title, Element::Text("Warning".into()));
+        assert!(callout.collapsed);
+        assert_eq!(callout.body.len(), 1);
+        assert_eq!(callout.body[0].node, Element::Paragraph(Box::new(Element::Text("careful\n".into()))));
+        assert_eq!(document.meta.toc.as_ref().unwrap().headers.len(), 1);
+
+        let text: String = "---\ntoc-callouts: true\n---\n#[toc] Contents\n\n> [!tip] A *title*\n> body\n\n".into();
+        let (document, _) = parse(&text, None).unwrap();
+        let headers = &document.meta.toc.as_ref().unwrap().headers;
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].2, document.body[1].1);
+    }
+}
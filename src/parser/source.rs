@@ -0,0 +1,188 @@
+use std::ops::Range;
+use serde::{Deserialize, Serialize};
+
+/// a position in the source, `line` and `column` start at 1 and `column` counts characters
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Location {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// where in the source file a piece of syntax came from
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SourceSpan {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl SourceSpan {
+    /// a span that only knows its byte offsets, lines and columns get filled in by `LineIndex::locate`
+    pub fn from_range(range: Range<usize>) -> Self {
+        Self {
+            start: Location { offset: range.start, line: 0, column: 0 },
+            end:   Location { offset: range.end,   line: 0, column: 0 },
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.range().contains(&offset)
+    }
+
+    /// whether the line and column are filled in, text we couldn't find in the source (like
+    /// something transcluded from another file) never gets them
+    pub fn is_located(&self) -> bool {
+        self.start.line != 0
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: SourceSpan,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, range: Range<usize>) -> Self {
+        Self { node, span: SourceSpan::from_range(range) }
+    }
+}
+
+/// where `offset` into a text ends up in the source, `lines` has where every line starts in the
+/// text and where that line came from
+pub fn map_offset(lines: &[(usize, usize)], offset: usize) -> usize {
+    let line = lines.partition_point(|(start, _)| *start <= offset).saturating_sub(1);
+    let Some((start, origin)) = lines.get(line) else { return offset };
+    origin + (offset - start)
+}
+
+/// where every line of `text` is in `source`, looking from `start` on. the lines have to show up
+/// in the source in order but can be missing whatever came before them, like the "> " of a quote.
+/// `None` when one of them can't be found
+pub fn find_lines(source: &str, text: &str, start: usize) -> Option<Vec<(usize, usize)>> {
+    let mut lines = vec![];
+    let mut cursor = start;
+    let mut text_offset = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim();
+        if trimmed.is_empty() {
+            lines.push((text_offset, cursor));
+        } else {
+            let found = cursor + source.get(cursor..)?.find(trimmed)?;
+            let indent = content.len() - content.trim_start().len();
+            lines.push((text_offset, found - indent.min(found - cursor)));
+            cursor = found + trimmed.len();
+        }
+        text_offset += line.len();
+    }
+    Some(lines)
+}
+
+/// where a piece of text that was parsed on its own came from, so whatever is parsed out of it can
+/// point back at the source file
+#[derive(Debug, Clone, Copy)]
+pub struct TextOrigin<'a> {
+    /// the index of the whole file, built once per parse
+    index: Option<&'a LineIndex<'a>>,
+    /// what `find_lines` found for the whole text, empty when we don't know where it is
+    lines: &'a [(usize, usize)],
+    /// where the piece being parsed starts in that text
+    base: usize,
+}
+
+impl<'a> TextOrigin<'a> {
+    /// for text that isn't from the source, spans out of it are left without a location
+    pub const UNKNOWN: TextOrigin<'static> = TextOrigin { index: None, lines: &[], base: 0 };
+
+    pub fn new(index: &'a LineIndex<'a>, lines: &'a [(usize, usize)]) -> Self {
+        Self { index: Some(index), lines, base: 0 }
+    }
+
+    /// the origin of whatever starts `offset` bytes into this piece
+    pub fn shifted(self, offset: usize) -> Self {
+        Self { base: self.base + offset, ..self }
+    }
+
+    /// the span of `range` in this piece
+    pub fn span(&self, range: Range<usize>) -> SourceSpan {
+        let Some(index) = self.index.filter(|_| !self.lines.is_empty()) else { return SourceSpan::default() };
+        let mut span = SourceSpan::from_range(map_offset(self.lines, self.base + range.start)..map_offset(self.lines, self.base + range.end));
+        index.locate(&mut span);
+        span
+    }
+}
+
+/// maps byte offsets into a source file onto lines and columns
+#[derive(Debug)]
+pub struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        for (index, character) in text.char_indices() {
+            if character == '\n' {
+                line_starts.push(index + 1);
+            }
+        }
+        Self { text, line_starts }
+    }
+
+    /// the file this indexes
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    pub fn location(&self, offset: usize) -> Location {
+        let offset = offset.min(self.text.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let line_start = self.line_starts[line];
+        let column = self.text.get(line_start..offset).map_or(offset - line_start, |x| x.chars().count());
+        Location { offset, line: line + 1, column: column + 1 }
+    }
+
+    pub fn locate(&self, span: &mut SourceSpan) {
+        span.start = self.location(span.start.offset);
+        span.end   = self.location(span.end.offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locations() {
+        let index = LineIndex::new("first\nsécond\n\nfourth");
+        assert_eq!(index.location(0), Location { offset: 0, line: 1, column: 1 });
+        assert_eq!(index.location(5), Location { offset: 5, line: 1, column: 6 });
+        assert_eq!(index.location(6), Location { offset: 6, line: 2, column: 1 });
+        // é is two bytes but a single column
+        assert_eq!(index.location(9), Location { offset: 9, line: 2, column: 3 });
+        assert_eq!(index.location(15), Location { offset: 15, line: 4, column: 1 });
+        assert_eq!(index.location(100).line, 4);
+    }
+
+    #[test]
+    fn origins() {
+        let source = "> first line\n> second [here]\n";
+        let lines = find_lines(source, "first line\nsecond [here]\n", 0).unwrap();
+        assert_eq!(lines, vec![(0, 2), (11, 15)]);
+        let index = LineIndex::new(source);
+        let span = TextOrigin::new(&index, &lines).shifted(11).span(7..13);
+        assert_eq!(&source[span.range()], "[here]");
+        assert_eq!((span.start.line, span.start.column), (2, 10));
+        assert!(find_lines(source, "missing", 0).is_none());
+        assert!(!TextOrigin::UNKNOWN.span(0..1).is_located());
+    }
+}
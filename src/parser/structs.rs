@@ -1,14 +1,18 @@
 
 use std::num::ParseIntError;
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 use super::util::ordered_map::OrderedMap;
 use super::frontmatter::*;
 use super::toplevel::*;
 use super::config::*;
+use super::source::{SourceSpan, Spanned};
 
 pub use super::toplevel::ReferenceDefinition;
+pub use super::toplevel::Alignment;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct TableOfContent {
     pub title:   String,
     pub index:   usize,
@@ -16,7 +20,7 @@ pub struct TableOfContent {
     pub headers: Vec<(Box<Element>, /*depth: */ usize, /*id: */ String)>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct MetaData {
     pub title: String,
     pub subtitle: String,
@@ -29,9 +33,16 @@ pub struct MetaData {
     pub hide_references: bool,
     pub hide_notes: bool,
     pub hide_contacts: bool,
+    pub hide_glossary: bool,
+    /// callouts only show up in the table of contents when asked for
+    pub toc_callouts: bool,
+    /// images, tables and code blocks get numbers and captions so `[@fig:id]` can point at them
+    pub number_figures: bool,
     pub toc: Option<TableOfContent>,
     pub bibliography_title: String,
     pub notes_title: String,
+    pub glossary_title: String,
+    pub backlinks_title: String,
     pub frontmatter: Option<Frontmatter>,
 }
 
@@ -50,40 +61,64 @@ impl MetaData {
             hide_references: false,
             hide_notes: false,
             hide_contacts: false,
+            hide_glossary: false,
+            toc_callouts: false,
+            number_figures: false,
             bibliography_title: DEFAULT_BIBLIOGRAPHY_TITLE.into(),
             notes_title: DEFAULT_NOTES_TITLE.into(),
+            glossary_title: DEFAULT_GLOSSARY_TITLE.into(),
+            backlinks_title: DEFAULT_BACKLINKS_TITLE.into(),
             frontmatter: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Alternative {
     pub base: Box<Element>,
     pub alt:  Box<Element>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Span {
     pub elements: Vec<Element>
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum ListKind {
     Unordered, Numbered,
     // we still need to do '1)' lists
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum ListItem {
-    Unordered(Box<Element>),                   // -
-    Numbered(usize, Box<Element>),             // 1.
-    Alphabetical(String, Box<Element>),        // a.
-    NumberedRounded(usize, Box<Element>),      // 1)
-    AlphabeticalRounded(String, Box<Element>), // a)
+    // the text on the line of the marker, then the blocks indented under it
+    Unordered(Box<Element>, Vec<Spanned<Element>>),                       // -
+    Numbered(usize, Box<Element>, Vec<Spanned<Element>>),                 // 1.
+    Alphabetical(String, Box<Element>, Vec<Spanned<Element>>),            // a.
+    NumberedRounded(usize, Box<Element>, Vec<Spanned<Element>>),          // 1)
+    AlphabeticalRounded(String, Box<Element>, Vec<Spanned<Element>>),     // a)
+    Task{checked: bool, text: Box<Element>, body: Vec<Spanned<Element>>}, // - [ ] and - [x]
     List(Vec<ListItem>)
 }
 
+impl ListItem {
+    /// the text and body of the item, `None` for a nested list
+    pub fn content(&self) -> Option<(&Element, &[Spanned<Element>])> {
+        match self {
+            ListItem::List(_) => None,
+            ListItem::Unordered(text, body)              |
+            ListItem::Numbered(_, text, body)            |
+            ListItem::Alphabetical(_, text, body)        |
+            ListItem::NumberedRounded(_, text, body)     |
+            ListItem::AlphabeticalRounded(_, text, body) |
+            ListItem::Task { text, body, .. }            => Some((text, body)),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Unit {
     Cap(isize),
@@ -115,7 +150,9 @@ pub enum Unit {
     In(isize),
     Pc(isize),
     Pt(isize),
-    Percentage(isize)
+    Percentage(isize),
+    /// let the other side (or the image itself) decide, like in `x400`
+    Auto,
 }
 
 #[derive(Debug, Clone)]
@@ -128,10 +165,14 @@ impl std::str::FromStr for Unit {
     type Err = ParseUnitError;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let string = string.trim();
+        if string == "auto" { return Ok(Self::Auto) }
         let num: String = string.chars().take_while(|c| c.is_digit(10)).collect();
         let text: String = string.chars().skip_while(|c| c.is_digit(10)).collect();
         let value = num.parse::<isize>()?;
         match text.as_str() {
+            // a bare number like the ones in `|600x400` is in pixels
+            "" => Ok(Self::Px(value)),
             "cap" => Ok(Self::Cap(value)),
             "ch" => Ok(Self::Ch(value)),
             "em" => Ok(Self::Em(value)),
@@ -167,6 +208,20 @@ impl std::str::FromStr for Unit {
     }
 }
 
+// units are written the way they would be in css, like "600px"
+impl Serialize for Unit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Unit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl std::string::ToString for Unit {
     fn to_string(&self) -> String {
         match self {
@@ -200,14 +255,17 @@ impl std::string::ToString for Unit {
             Unit::Pc(n) => format!("{n}pc"),
             Unit::Pt(n) => format!("{n}pt"),
             Unit::Percentage(n) => format!("{n}%"),
+            Unit::Auto => "auto".to_string(),
         }
     }
 }
 
 impl std::fmt::Display for ParseUnitError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let text = self.to_string();
-        write!(f, "{text}")
+        match self {
+            ParseUnitError::NotANumber => write!(f, "expected a number"),
+            ParseUnitError::NotAUnit   => write!(f, "expected a unit like 'px' or '%'"),
+        }
     }
 }
 
@@ -219,21 +277,141 @@ impl std::convert::From<ParseIntError> for ParseUnitError {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum ImageSize {
     Single(Unit),
     Double(Unit, Unit),
     None
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl ImageSize {
+    /// the width and height that were given, `auto` counts as not given
+    pub fn dimensions(&self) -> (Option<&Unit>, Option<&Unit>) {
+        fn given(unit: &Unit) -> Option<&Unit> { (unit != &Unit::Auto).then_some(unit) }
+        match self {
+            ImageSize::Single(width)         => (given(width), None),
+            ImageSize::Double(width, height) => (given(width), given(height)),
+            ImageSize::None                  => (None, None),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Image {
     pub src: String,
     pub alt: Box<Element>,
     pub size: ImageSize,
+    /// the text after the embed shown under the image, only set when figures are numbered
+    pub caption: Option<Box<Element>>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// `[[post-name#Some Heading|label]]`, only the target is required
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct WikiLink {
+    /// everything before the `|`, this is what `AssDownDocument::wiki_links` is keyed with
+    pub target: String,
+    pub label: Option<Box<Element>>,
+    /// where the whole `[[...]]` is, unlocated when the text wasn't parsed out of a file
+    pub span: SourceSpan,
+}
+
+/// another post with a wiki link to this one
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Backlink {
+    pub title: String,
+    pub url: String,
+}
+
+/// a video, a sound or a page embedded with `![[...]]`, picked by `media::media_kind`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Media {
+    /// what the player or iframe loads, for youtube and peertube that's their embed page
+    pub src: String,
+    /// what was written in the post, outputs that can't play anything link here instead
+    pub link: String,
+    pub title: Box<Element>,
+    pub size: ImageSize,
+}
+
+/// what `[@fig:id]`, `[@tbl:id]` and `[@lst:id]` point at, every kind is counted on its own
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FigureKind {
+    Figure,
+    Table,
+    Listing,
+}
+
+impl FigureKind {
+    pub fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "fig" => Some(Self::Figure),
+            "tbl" => Some(Self::Table),
+            "lst" => Some(Self::Listing),
+            _ => None,
+        }
+    }
+
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Self::Figure  => "fig",
+            Self::Table   => "tbl",
+            Self::Listing => "lst",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Figure  => "Figure",
+            Self::Table   => "Table",
+            Self::Listing => "Listing",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct FigureNumber {
+    pub kind: FigureKind,
+    pub number: usize,
+}
+
+impl FigureNumber {
+    /// how references to it are written, like "Figure 3"
+    pub fn label(&self) -> String {
+        format!("{} {}", self.kind.name(), self.number)
+    }
+}
+
+/// a fenced code block along with the options from its info string,
+/// e.g. ```` ```rust title="main.rs" showLineNumbers {3-5} diff ````
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct CodeBlock {
+    /// empty when the fence didn't name a language
+    pub lang: String,
+    pub title: Option<String>,
+    /// the number shown next to the first line, None if the lines aren't numbered
+    pub start_line: Option<usize>,
+    /// inclusive ranges of lines counting from 1, regardless of `start_line`
+    pub highlights: Vec<(usize, usize)>,
+    /// lines starting with '+' or '-' are shown as added or removed
+    pub diff: bool,
+    pub code: String,
+}
+
+impl CodeBlock {
+    pub fn is_highlighted(&self, line: usize) -> bool {
+        self.highlights.iter().any(|&(start, end)| start <= line && line <= end)
+    }
+
+    /// whether the lines have to be rendered one by one instead of as a single block of text
+    pub fn has_line_markup(&self) -> bool {
+        self.start_line.is_some() || !self.highlights.is_empty() || self.diff
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum Element {
     Hoverable(Alternative),
     Styled(Alternative),
@@ -241,37 +419,153 @@ pub enum Element {
     Header(Box<Element>, usize),
     Italics(Box<Element>),
     Bold(Box<Element>),
+    Strikethrough(Box<Element>),
+    Underline(Box<Element>),
+    Highlight(Box<Element>),
+    Superscript(Box<Element>),
+    Subscript(Box<Element>),
     InlineCode(String),
-    CodeBlock(String),
+    CodeBlock(CodeBlock),
+    /// the tex between single dollars, see `math::parse_tex`
+    InlineMath(String),
+    /// the tex between `$$` on their own lines
+    MathBlock(String),
     Image(Image),
+    Video(Media),
+    Audio(Media),
+    /// a page from another site in a sandboxed iframe, like a youtube video
+    Iframe(Media),
     // EmbeddedLink(String, String),
     FactBox(FactBox),
-    Quote(Vec<Element>),
+    Quote(Quote),
+    Callout(Callout),
     List(Vec<ListItem>),
+    Table(Table),
+    DefinitionList(Vec<Definition>),
     Paragraph(Box<Element>),
     Text(String),
     Span(Span),
     Citation(String),
     Note(String),
+    /// `[@fig:id]`, the number of whatever has that id once figures are numbered, and where it is
+    CrossReference(FigureKind, String, SourceSpan),
+    /// `[[post-name]]`, where it goes is only known once the other posts are parsed, see `wiki_links`
+    WikiLink(WikiLink),
     PageBreak,
     TOCLocationMarker,
+    /// the raw source of something that could not be parsed, a diagnostic says why
+    Error(String),
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl Element {
+    /// calls `f` with this element and everything inside of it, in the order they show up
+    pub fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a Element)) {
+        self.walk_spanned(SourceSpan::default(), &mut |element, _| f(element));
+    }
+
+    /// like `walk`, but `f` also gets the span of the innermost block the element is in, `span`
+    /// being the one of this element
+    pub fn walk_spanned<'a>(&'a self, span: SourceSpan, f: &mut dyn FnMut(&'a Element, SourceSpan)) {
+        f(self, span);
+        let mut walk_all = |elements: &mut dyn Iterator<Item = &'a Element>| elements.for_each(|element| element.walk_spanned(span, f));
+        match self {
+            Element::Span(inner) => walk_all(&mut inner.elements.iter()),
+            Element::Paragraph(element) | Element::Bold(element) | Element::Italics(element) | Element::Header(element, _) |
+            Element::Strikethrough(element) | Element::Underline(element) | Element::Highlight(element) |
+            Element::Superscript(element) | Element::Subscript(element) => element.walk_spanned(span, f),
+            Element::Link(alternative) | Element::Hoverable(alternative) | Element::Styled(alternative) => {
+                walk_all(&mut [&*alternative.base, &*alternative.alt].into_iter())
+            },
+            Element::WikiLink(link) => walk_all(&mut link.label.as_deref().into_iter()),
+            Element::Image(image) => image.alt.walk_spanned(span, f),
+            Element::Video(media) | Element::Audio(media) | Element::Iframe(media) => media.title.walk_spanned(span, f),
+            Element::List(list) => walk_list(list, span, f),
+            Element::Quote(quote) => {
+                walk_body(&quote.body, f);
+                quote.attribution.iter().for_each(|element| element.walk_spanned(span, f));
+            },
+            Element::Callout(callout) => {
+                callout.title.walk_spanned(span, f);
+                walk_body(&callout.body, f);
+            },
+            Element::Table(table) => walk_all(&mut table.header.iter().chain(table.rows.iter().flatten())),
+            Element::DefinitionList(list) => walk_all(&mut list.iter()
+                .flat_map(|definition| std::iter::once(&*definition.term).chain(&definition.definitions))),
+            Element::FactBox(factbox) => factbox.body.iter().for_each(|(element, _, span)| element.walk_spanned(*span, f)),
+            _ => {},
+        }
+    }
+}
+
+fn walk_body<'a>(body: &'a [Spanned<Element>], f: &mut dyn FnMut(&'a Element, SourceSpan)) {
+    body.iter().for_each(|Spanned { node, span }| node.walk_spanned(*span, f));
+}
+
+fn walk_list<'a>(list: &'a [ListItem], span: SourceSpan, f: &mut dyn FnMut(&'a Element, SourceSpan)) {
+    for item in list {
+        if let ListItem::List(inner) = item {
+            walk_list(inner, span, f);
+        } else if let Some((text, body)) = item.content() {
+            text.walk_spanned(span, f);
+            walk_body(body, f);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Quote {
+    pub body: Vec<Spanned<Element>>,
+    /// from a last line starting with a dash, like "> — Author, [£cite]"
+    pub attribution: Option<Box<Element>>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Callout {
+    /// lowercase, "note", "warning", "tip" or whatever the post made up
+    pub kind: String,
+    pub title: Box<Element>,
+    pub collapsed: bool,
+    pub body: Vec<Spanned<Element>>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Table {
+    pub header: Vec<Element>,
+    /// one per column, the same length as `header` and every row
+    pub alignments: Vec<Alignment>,
+    pub rows: Vec<Vec<Element>>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Definition {
+    pub term: Box<Element>,
+    pub definitions: Vec<Element>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct AssDownDocument {
     pub meta: MetaData,
     pub bibliography_id: String,
     pub notes_id: String,
+    pub glossary_id: String,
     pub references: OrderedMap<String, ReferenceDefinition>,
     pub notes: OrderedMap<String, Element>,
-    pub body: Vec<(Element, String)>,
+    /// every term defined in the post, keyed by its lowercased source text
+    pub glossary: OrderedMap<String, Definition>,
+    /// the numbered images, tables and code blocks keyed by their id, empty unless the frontmatter has `figures`
+    pub figures: OrderedMap<String, FigureNumber>,
+    /// the url of every wiki link that could be resolved, keyed by its target
+    pub wiki_links: OrderedMap<String, String>,
+    /// the posts linking here, both of these are empty until `wiki_links::link_documents` runs
+    pub backlinks: Vec<Backlink>,
+    pub body: Vec<(Element, /*id: */ String, /*span: */ SourceSpan)>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct FactBox {
     pub title: String,
     pub notes: OrderedMap::<String, (Element, String)>,
-    pub body: Vec<(Element, String)>
+    pub body: Vec<(Element, /*id: */ String, /*span: */ SourceSpan)>
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -2,30 +2,31 @@
 use super::list_pattern::ListPattern;
 use super::structs::*;
 use super::parser_object::*;
+use super::parser::parse_dedented;
 
 #[derive(Clone)]
-enum MarkerKind {
+enum MarkerKind<'a> {
     Unordered,
-    Number(String),
-    Alphabetic(String),
+    Number(&'a str),
+    Alphabetic(&'a str),
 }
 
-#[derive(Clone)]
-enum ListMetaItem {
+enum ListMetaItem<'a> {
     Base{
         depth: usize,
-        marker: MarkerKind,
+        marker: MarkerKind<'a>,
         is_rounded: bool,
-        text: String,
+        text: &'a str,
+        body: ListBody<'static>,
     },
-    InnerList(usize, Vec<ListMetaItem>),
+    InnerList(usize, Vec<ListMetaItem<'a>>),
 }
 
-impl ListMetaItem {
+impl<'a> ListMetaItem<'a> {
     fn get_depth(&self) -> usize {
         match self {
             ListMetaItem::InnerList(depth, _) |
-            ListMetaItem::Base{depth, marker: _, is_rounded: _, text: _} => {
+            ListMetaItem::Base{depth, ..} => {
                 *depth
             },
         }
@@ -34,65 +35,77 @@ impl ListMetaItem {
     fn is_list(&self) -> bool {
         match self {
             ListMetaItem::InnerList(_, _) => true,
-            ListMetaItem::Base{depth: _, marker: _, is_rounded: _, text: _} => false,
+            ListMetaItem::Base{..} => false,
         }
     }
 
-    fn insert(&mut self, depth: usize, marker: MarkerKind, is_rounded: bool, text: String) {
+    // `item` is always a `Base`
+    fn insert(&mut self, item: Self) {
+        let depth = item.get_depth();
         if let ListMetaItem::InnerList(own_depth, items) = self {
             if *own_depth == depth {
-                items.push(Self::Base{depth, marker, is_rounded, text});
+                items.push(item);
             }
             else if *own_depth < depth { 
                 if let Some(elem) = items.last_mut() && elem.is_list() && depth >= elem.get_depth() {
-                    elem.insert(depth, marker, is_rounded, text);
+                    elem.insert(item);
                 } else {
-                    items.push(Self::InnerList(depth, vec![ Self::Base{depth, marker, is_rounded, text} ]));
+                    items.push(Self::InnerList(depth, vec![ item ]));
                 }
             } else {
                 assert!(*own_depth > depth);
-                let old_self = self.clone();
-                let new_self = Self::InnerList(depth, vec![
-                    old_self, Self::Base{depth, marker, is_rounded, text}
-                ]);
-                *self = new_self;
+                let old_self = std::mem::replace(self, Self::InnerList(depth, vec![]));
+                *self = Self::InnerList(depth, vec![ old_self, item ]);
             }
         }
     }
 
-    fn convert(&self) -> ListItem {
+    fn convert(self) -> ListItem<'a> {
         match self {
             ListMetaItem::InnerList(_, list) => {
-                let mut output = vec![];
-                for item in list {
-                    output.push(item.convert());
-                }
-                ListItem::InnerList(output)
+                ListItem::InnerList(list.into_iter().map(Self::convert).collect())
             },
-            ListMetaItem::Base{depth: _, marker, is_rounded, text} => 
+            ListMetaItem::Base{depth: _, marker, is_rounded, text, body} => 
                 match marker {
-                    MarkerKind::Unordered => ListItem::Unordered(text.clone()),
-                    MarkerKind::Number(marker) => if *is_rounded {
+                    MarkerKind::Unordered => match checkbox(text) {
+                        Some((checked, text)) => ListItem::Task { checked, text: text.into(), body },
+                        None                  => ListItem::Unordered(text.into(), body),
+                    },
+                    MarkerKind::Number(marker) => if is_rounded {
                         let marker_num = marker.split(')').nth(0).unwrap_or("");
                         let num = marker_num.parse::<usize>().unwrap_or(0);
-                        ListItem::NumberedRounded(num, text.clone())
+                        ListItem::NumberedRounded(num, text.into(), body)
                     } else { 
                         let marker_num = marker.split('.').nth(0).unwrap_or("");
                         let num = marker_num.parse::<usize>().unwrap_or(0);
-                        ListItem::Numbered(num, text.clone())
+                        ListItem::Numbered(num, text.into(), body)
                     }
-                    MarkerKind::Alphabetic(marker) => if *is_rounded {
+                    MarkerKind::Alphabetic(marker) => if is_rounded {
                         let marker_text = marker.split(')').nth(0).unwrap_or("");
-                        ListItem::AlphabeticalRounded(marker_text.to_string(), text.clone())
+                        ListItem::AlphabeticalRounded(marker_text.into(), text.into(), body)
                     } else { 
                         let marker_text = marker.split('.').nth(0).unwrap_or("");
-                        ListItem::Alphabetical(marker_text.to_string(), text.clone())
+                        ListItem::Alphabetical(marker_text.into(), text.into(), body)
                     },
                 },
         }
     }
 }
 
+// "[ ] text" or "[x] text" after a `-`, "[x](...)" is still a link
+fn checkbox(text: &str) -> Option<(bool, &str)> {
+    let checked = match text.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let rest = &text[3..];
+    if !(rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+        return None;
+    }
+    Some((checked, rest.trim_start()))
+}
+
 fn get_pattern(text: &str) -> Option<ListPattern> {
     if text.starts_with(ListPattern::Unordered) {
         Some(ListPattern::Unordered)
@@ -109,10 +122,10 @@ fn get_pattern(text: &str) -> Option<ListPattern> {
     }
 }
 
-struct Wrap((MarkerKind, bool));
+struct Wrap<'a>((MarkerKind<'a>, bool));
 
-impl std::convert::From<(ListPattern, String)> for Wrap {
-    fn from(item: (ListPattern, String)) -> Self {
+impl<'a> std::convert::From<(ListPattern, &'a str)> for Wrap<'a> {
+    fn from(item: (ListPattern, &'a str)) -> Self {
         let (pattern, text) = item;
         match pattern {
             ListPattern::Unordered => Wrap((MarkerKind::Unordered, false)),
@@ -124,48 +137,89 @@ impl std::convert::From<(ListPattern, String)> for Wrap {
     }
 }
 
-pub fn parse_list(object: &mut ParseObject) -> Option<TopLevelSyntax> {
-    if let Some(starting_pattern) = get_pattern(object.text().trim_start()) {
-        let mut list = Vec::<ListMetaItem>::new();
-    
-        let mut line = object.current();
-        while let Some(pattern) = get_pattern(line.trim_start()) {
-            let trimmed_line = line.trim_start();
-            let marker = trimmed_line.split_inclusive(pattern.clone()).nth(0).unwrap().to_string();
-            let whitespace_count = line.len() - trimmed_line.len();
-            let mut string = &line.trim_start()[marker.len()..];
-            // let start_len = string.len();
-            string = string.trim();
-            let count = whitespace_count;
+// the lines after an item that are indented at least as far as its text belong to it, blank lines
+// only when more of them follow. leaves the object on the last line it took
+fn parse_item_body(object: &mut ParseObject<'_>, column: usize) -> ListBody<'static> {
+    let mut text = String::new();
+    let mut origins = Vec::<(usize, usize)>::new();
+    let mut blank_lines = Vec::<usize>::new();
+    let mut taken = 0;
+    let mut in_fence = false;
 
-            let Wrap((marker, is_rounded)) = (pattern, marker).into();
-            if count > 1 {
-                let pow2 = (count - 1).next_multiple_of(2);
-                if let Some(elem) = list.last_mut() && elem.is_list() {
-                    // if pow2 is 4 and depth is 2 then we insert it as an inner list
-                    elem.insert(pow2, marker, is_rounded, string.to_string());
-                } else {
-                    list.push(ListMetaItem::InnerList(pow2, vec![ ListMetaItem::Base{depth: pow2, marker, is_rounded, text: string.to_string()} ]));
-                }
-            } else {
-                list.push(ListMetaItem::Base{depth: 0, marker, is_rounded, text: string.to_string()});
-            }
+    let mut lines = object.text().split_inclusive('\n');
+    let mut line_start = object.offset() + lines.next().map_or(0, str::len);
+    for (n, raw_line) in lines.enumerate() {
+        let line = raw_line.trim_end_matches('\n').trim_end_matches('\r');
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if trimmed.is_empty() {
+            blank_lines.push(line_start);
+            line_start += raw_line.len();
+            continue;
+        }
+        // a list marker starts the next item, unless it's inside of a code block
+        if indent < column || (!in_fence && get_pattern(trimmed).is_some()) {
+            break;
+        }
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+        }
 
-    
-            object.next();
-            if object.current().is_empty() { break }
-            line = object.current();
+        for blank_line in blank_lines.drain(..) {
+            origins.push((text.len(), blank_line));
+            text.push('\n');
         }
-        
-        let mut output = vec![];
-        for item in list {
-            output.push(item.convert());
+        origins.push((text.len(), line_start + column));
+        text += line.get(column..).unwrap_or(trimmed);
+        text.push('\n');
+        line_start += raw_line.len();
+        taken = n + 1;
+    }
+
+    for _ in 0..taken {
+        object.next();
+    }
+    if text.is_empty() {
+        return vec![];
+    }
+    parse_dedented(&text, &origins).unwrap_or_default()
+}
+
+pub fn parse_list<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
+    get_pattern(object.current().trim_start())?;
+    let mut list = Vec::<ListMetaItem>::new();
+
+    while let Some(pattern) = get_pattern(object.current().trim_start()) {
+        let line = object.current();
+        let trimmed_line = line.trim_start();
+        let marker = trimmed_line.split_inclusive(pattern.clone()).nth(0).unwrap_or("");
+        let whitespace_count = line.len() - trimmed_line.len();
+        let string = trimmed_line[marker.len()..].trim();
+        // where the text of the item starts, anything indented this far belongs to the item
+        let column = line.len() - trimmed_line[marker.len()..].trim_start().len();
+        let count = whitespace_count;
+
+        let Wrap((marker, is_rounded)) = (pattern, marker).into();
+        let body = parse_item_body(object, column);
+        if count > 1 {
+            let pow2 = (count - 1).next_multiple_of(2);
+            let item = ListMetaItem::Base{depth: pow2, marker, is_rounded, text: string, body};
+            if let Some(elem) = list.last_mut() && elem.is_list() {
+                // if pow2 is 4 and depth is 2 then we insert it as an inner list
+                elem.insert(item);
+            } else {
+                list.push(ListMetaItem::InnerList(pow2, vec![ item ]));
+            }
+        } else {
+            list.push(ListMetaItem::Base{depth: 0, marker, is_rounded, text: string, body});
         }
 
-        Some(TopLevelSyntax::List(output))
-    } else {
-        None
+        // the caller moves past the line we stop on, so we only move on when the list continues
+        if get_pattern(object.peek().trim_start()).is_none() { break }
+        object.next();
     }
+
+    Some(TopLevelSyntax::List(list.into_iter().map(ListMetaItem::convert).collect()))
 }
 
 mod tests {
@@ -180,7 +234,7 @@ mod tests {
             let result = parse_list(&mut obj);
             assert!(result.is_some());
             if let Some(list) = result {
-                assert_eq!(list, TopLevelSyntax::List(vec![ ListItem::Unordered("a".into()) ]))
+                assert_eq!(list, TopLevelSyntax::List(vec![ ListItem::Unordered("a".into(), vec![]) ]))
             }
         }
         
@@ -191,9 +245,9 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Unordered("a".into()),
-                        ListItem::Unordered("b".into()),
-                        ListItem::Unordered("c".into()),
+                        ListItem::Unordered("a".into(), vec![]),
+                        ListItem::Unordered("b".into(), vec![]),
+                        ListItem::Unordered("c".into(), vec![]),
                 ]))
             }
         }
@@ -205,9 +259,9 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Unordered("a".into()),
-                        ListItem::Unordered("b".into()),
-                        ListItem::Unordered("c".into()),
+                        ListItem::Unordered("a".into(), vec![]),
+                        ListItem::Unordered("b".into(), vec![]),
+                        ListItem::Unordered("c".into(), vec![]),
                 ]))
             }
         }
@@ -219,10 +273,10 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Unordered("a".into()),
+                        ListItem::Unordered("a".into(), vec![]),
                         ListItem::InnerList(vec![
-                            ListItem::Unordered("b".into()),
-                            ListItem::Unordered("c".into()),
+                            ListItem::Unordered("b".into(), vec![]),
+                            ListItem::Unordered("c".into(), vec![]),
                         ]),
                 ]))
             }
@@ -235,15 +289,15 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Unordered("zero level".into()),
+                        ListItem::Unordered("zero level".into(), vec![]),
                         ListItem::InnerList(vec![
-                            ListItem::Unordered("first indent".into()),
+                            ListItem::Unordered("first indent".into(), vec![]),
                             ListItem::InnerList(vec![
-                                ListItem::Unordered("second indent".into()),
+                                ListItem::Unordered("second indent".into(), vec![]),
                             ]),
-                            ListItem::Unordered("first indent".into()),
+                            ListItem::Unordered("first indent".into(), vec![]),
                         ]),
-                        ListItem::Unordered("zero level".into()),
+                        ListItem::Unordered("zero level".into(), vec![]),
                 ]))
             }
         }
@@ -255,12 +309,12 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Unordered("zero level".into()),
+                        ListItem::Unordered("zero level".into(), vec![]),
                         ListItem::InnerList(vec![
                             ListItem::InnerList(vec![
-                                ListItem::Unordered("second indent".into()),
+                                ListItem::Unordered("second indent".into(), vec![]),
                             ]),
-                            ListItem::Unordered("first indent".into()),
+                            ListItem::Unordered("first indent".into(), vec![]),
                         ]),
                 ]))
             }
@@ -276,7 +330,7 @@ mod tests {
             let result = parse_list(&mut obj);
             assert!(result.is_some());
             if let Some(list) = result {
-                assert_eq!(list, TopLevelSyntax::List(vec![ ListItem::Numbered(1, "a".into()) ]))
+                assert_eq!(list, TopLevelSyntax::List(vec![ ListItem::Numbered(1, "a".into(), vec![]) ]))
             }
         }
         
@@ -287,9 +341,9 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Numbered(1, "a".into()),
-                        ListItem::Numbered(5, "b".into()),
-                        ListItem::Numbered(3, "c".into()),
+                        ListItem::Numbered(1, "a".into(), vec![]),
+                        ListItem::Numbered(5, "b".into(), vec![]),
+                        ListItem::Numbered(3, "c".into(), vec![]),
                 ]))
             }
         }
@@ -301,9 +355,9 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Numbered(1, "a".into()),
-                        ListItem::Numbered(2, "b".into()),
-                        ListItem::Numbered(3, "c".into()),
+                        ListItem::Numbered(1, "a".into(), vec![]),
+                        ListItem::Numbered(2, "b".into(), vec![]),
+                        ListItem::Numbered(3, "c".into(), vec![]),
                 ]))
             }
         }
@@ -315,10 +369,10 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Numbered(1, "a".into()),
+                        ListItem::Numbered(1, "a".into(), vec![]),
                         ListItem::InnerList(vec![
-                            ListItem::Numbered(1, "b".into()),
-                            ListItem::Numbered(2, "c".into()),
+                            ListItem::Numbered(1, "b".into(), vec![]),
+                            ListItem::Numbered(2, "c".into(), vec![]),
                         ]),
                 ]))
             }
@@ -331,15 +385,15 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Numbered(1, "zero level".into()),
+                        ListItem::Numbered(1, "zero level".into(), vec![]),
                         ListItem::InnerList(vec![
-                            ListItem::Numbered(1, "first indent".into()),
+                            ListItem::Numbered(1, "first indent".into(), vec![]),
                             ListItem::InnerList(vec![
-                                ListItem::Numbered(1, "second indent".into()),
+                                ListItem::Numbered(1, "second indent".into(), vec![]),
                             ]),
-                            ListItem::Numbered(2, "first indent".into()),
+                            ListItem::Numbered(2, "first indent".into(), vec![]),
                         ]),
-                        ListItem::Numbered(2, "zero level".into()),
+                        ListItem::Numbered(2, "zero level".into(), vec![]),
                 ]))
             }
         }
@@ -351,12 +405,12 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Numbered(1, "zero level".into()),
+                        ListItem::Numbered(1, "zero level".into(), vec![]),
                         ListItem::InnerList(vec![
                             ListItem::InnerList(vec![
-                                ListItem::Numbered(1, "second indent".into()),
+                                ListItem::Numbered(1, "second indent".into(), vec![]),
                             ]),
-                            ListItem::Numbered(1, "first indent".into()),
+                            ListItem::Numbered(1, "first indent".into(), vec![]),
                         ]),
                 ]))
             }
@@ -372,7 +426,7 @@ mod tests {
             let result = parse_list(&mut obj);
             assert!(result.is_some());
             if let Some(list) = result {
-                assert_eq!(list, TopLevelSyntax::List(vec![ ListItem::Alphabetical("a".into(), "a".into()) ]))
+                assert_eq!(list, TopLevelSyntax::List(vec![ ListItem::Alphabetical("a".into(), "a".into(), vec![]) ]))
             }
         }
         
@@ -383,9 +437,9 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Alphabetical("a".into(), "a".into()),
-                        ListItem::Alphabetical("b".into(), "b".into()),
-                        ListItem::Alphabetical("c".into(), "c".into()),
+                        ListItem::Alphabetical("a".into(), "a".into(), vec![]),
+                        ListItem::Alphabetical("b".into(), "b".into(), vec![]),
+                        ListItem::Alphabetical("c".into(), "c".into(), vec![]),
                 ]))
             }
         }
@@ -397,9 +451,9 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Alphabetical("a".into(), "a".into()),
-                        ListItem::Alphabetical("b".into(), "b".into()),
-                        ListItem::Alphabetical("c".into(), "c".into()),
+                        ListItem::Alphabetical("a".into(), "a".into(), vec![]),
+                        ListItem::Alphabetical("b".into(), "b".into(), vec![]),
+                        ListItem::Alphabetical("c".into(), "c".into(), vec![]),
                 ]))
             }
         }
@@ -411,10 +465,10 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Alphabetical("a".into(), "a".into()),
+                        ListItem::Alphabetical("a".into(), "a".into(), vec![]),
                         ListItem::InnerList(vec![
-                            ListItem::Alphabetical("b".into(), "b".into()),
-                            ListItem::Alphabetical("c".into(), "c".into()),
+                            ListItem::Alphabetical("b".into(), "b".into(), vec![]),
+                            ListItem::Alphabetical("c".into(), "c".into(), vec![]),
                         ]),
                 ]))
             }
@@ -427,15 +481,15 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Alphabetical("a".into(), "zero level".into()),
+                        ListItem::Alphabetical("a".into(), "zero level".into(), vec![]),
                         ListItem::InnerList(vec![
-                            ListItem::Alphabetical("a".into(), "first indent".into()),
+                            ListItem::Alphabetical("a".into(), "first indent".into(), vec![]),
                             ListItem::InnerList(vec![
-                                ListItem::Alphabetical("a".into(), "second indent".into()),
+                                ListItem::Alphabetical("a".into(), "second indent".into(), vec![]),
                             ]),
-                            ListItem::Alphabetical("b".into(), "first indent".into()),
+                            ListItem::Alphabetical("b".into(), "first indent".into(), vec![]),
                         ]),
-                        ListItem::Alphabetical("c".into(), "zero level".into()),
+                        ListItem::Alphabetical("c".into(), "zero level".into(), vec![]),
                 ]))
             }
         }
@@ -447,12 +501,12 @@ mod tests {
             assert!(result.is_some());
             if let Some(list) = result {
                 assert_eq!(list, TopLevelSyntax::List(vec![ 
-                        ListItem::Alphabetical("a".into(), "zero level".into()),
+                        ListItem::Alphabetical("a".into(), "zero level".into(), vec![]),
                         ListItem::InnerList(vec![
                             ListItem::InnerList(vec![
-                                ListItem::Alphabetical("a".into(), "second indent".into()),
+                                ListItem::Alphabetical("a".into(), "second indent".into(), vec![]),
                             ]),
-                            ListItem::Alphabetical("a".into(), "first indent".into()),
+                            ListItem::Alphabetical("a".into(), "first indent".into(), vec![]),
                         ]),
                 ]))
             }
@@ -466,12 +520,12 @@ mod tests {
         assert!(result.is_some());
         if let Some(list) = result {
             assert_eq!(list, TopLevelSyntax::List(vec![ 
-                    ListItem::Unordered("zero level".into()),
+                    ListItem::Unordered("zero level".into(), vec![]),
                     ListItem::InnerList(vec![
                         ListItem::InnerList(vec![
-                            ListItem::Numbered(1, "second indent".into()),
+                            ListItem::Numbered(1, "second indent".into(), vec![]),
                         ]),
-                        ListItem::Unordered( "first indent".into()),
+                        ListItem::Unordered( "first indent".into(), vec![]),
                     ]),
             ]))
         }
@@ -484,12 +538,49 @@ mod tests {
         assert!(result.is_some());
         if let Some(list) = result {
             assert_eq!(list, TopLevelSyntax::List(vec![ 
-                    ListItem::Unordered("zero level".into()),
-                    ListItem::Numbered(1, "zero level".into()),
-                    ListItem::Alphabetical("b".into(), "zero level".into()),
-                    ListItem::NumberedRounded(3, "zero level".into()),
-                    ListItem::AlphabeticalRounded("d".into(), "zero level".into()),
+                    ListItem::Unordered("zero level".into(), vec![]),
+                    ListItem::Numbered(1, "zero level".into(), vec![]),
+                    ListItem::Alphabetical("b".into(), "zero level".into(), vec![]),
+                    ListItem::NumberedRounded(3, "zero level".into(), vec![]),
+                    ListItem::AlphabeticalRounded("d".into(), "zero level".into(), vec![]),
             ]))
         }
     }
+
+    #[test]
+    fn item_with_body() {
+        let mut obj = ParseObject::new("1. step\n   more about it\n\n   ```\n   - not an item\n   ```\n2. next\nafter\n");
+        let Some(TopLevelSyntax::List(list)) = parse_list(&mut obj) else { panic!("expected a list") };
+        let ListItem::Numbered(1, text, body) = &list[0] else { panic!("expected a numbered item") };
+        assert_eq!(text, "step");
+        assert_eq!(body.len(), 2);
+        assert_eq!(body[0].node, TopLevelSyntax::Paragraph("more about it\n".into()));
+        assert_eq!(body[1].node, TopLevelSyntax::CodeBlock { info: "".into(), code: "- not an item\n".into() });
+        assert_eq!(list[1], ListItem::Numbered(2, "next".into(), vec![]));
+        // the line after the list is left for whatever comes next
+        assert_eq!(obj.current(), "2. next");
+    }
+
+    #[test]
+    fn body_needs_indentation() {
+        let mut obj = ParseObject::new("- a\n b\n\n  c\n");
+        let Some(TopLevelSyntax::List(list)) = parse_list(&mut obj) else { panic!("expected a list") };
+        assert_eq!(list, vec![ ListItem::Unordered("a".into(), vec![]) ]);
+        assert_eq!(obj.current(), "- a");
+    }
+
+    #[test]
+    fn task_items() {
+        let mut obj = ParseObject::new("- [ ] todo\n- [x] done\n  - [X]\n- [x](link)\n1. [ ] numbered\n");
+        let Some(TopLevelSyntax::List(list)) = parse_list(&mut obj) else { panic!("expected a list") };
+        assert_eq!(list, vec![
+            ListItem::Task { checked: false, text: "todo".into(), body: vec![] },
+            ListItem::Task { checked: true, text: "done".into(), body: vec![] },
+            ListItem::InnerList(vec![
+                ListItem::Task { checked: true, text: "".into(), body: vec![] },
+            ]),
+            ListItem::Unordered("[x](link)".into(), vec![]),
+            ListItem::Numbered(1, "[ ] numbered".into(), vec![]),
+        ]);
+    }
 }
@@ -68,12 +68,8 @@ impl<'a> ListPatternSearcher<'a> {
             let mut ch = ch;
             self.position += ch.len_utf8();
             if ch.is_digit(10) {
-                while peeker.peek().is_some_and(|ch| ch.is_digit(10)) {
-                    if let Some(ch) = peeker.next() {
-                        self.position += ch.len_utf8()
-                    } else {
-                        panic!("shouldn't be possible");
-                    }
+                while let Some(ch) = peeker.next_if(|ch| ch.is_digit(10)) {
+                    self.position += ch.len_utf8()
                 }
                 if let Some(nxt) = peeker.next() {
                     self.position += nxt.len_utf8();
@@ -119,12 +115,8 @@ impl<'a> ListPatternSearcher<'a> {
             let mut ch = ch;
             self.position += ch.len_utf8();
             if ch.is_alphabetic() {
-                while peeker.peek().is_some_and(|ch| ch.is_digit(10)) {
-                    if let Some(ch) = peeker.next() {
-                        self.position += ch.len_utf8()
-                    } else {
-                        panic!("shouldn't be possible");
-                    }
+                while let Some(ch) = peeker.next_if(|ch| ch.is_digit(10)) {
+                    self.position += ch.len_utf8()
                 }
                 if let Some(nxt) = peeker.next() {
                     self.position += nxt.len_utf8();
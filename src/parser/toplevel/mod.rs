@@ -3,11 +3,13 @@ mod parser_object;
 mod list_pattern;
 mod list_parser;
 mod reference;
+mod transclusion;
 pub mod structs;
 pub mod parser;
 
 pub use reference::ReferenceDefinition;
 pub use reference::PmdDate;
+pub use reference::{to_citation, to_bibliography, bibliograph_name, any_non_empty};
 pub use structs::*;
 pub use parser::*;
 
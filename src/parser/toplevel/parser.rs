@@ -1,11 +1,16 @@
 use anyhow::Result;
+use std::borrow::Cow;
 use super::list_parser::*;
 use super::parser_object::*;
 use super::structs::*;
 use super::reference::parse_reference;
+use super::transclusion::{is_transclusion, transclude};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use super::super::{config::DEFAULT_FACTBOX_TITLE, frontmatter::parse_frontmatter};
+use super::super::source::{map_offset, LineIndex, Spanned};
+use super::super::wiki_links::is_wiki_link;
 
 fn skip_comment(object: &mut ParseObject) -> bool {
     if object.current().trimmed_starts_with("%%") {
@@ -22,28 +27,72 @@ fn skip_comment(object: &mut ParseObject) -> bool {
     return false;
 }
 
-pub fn parse_quote(object: &mut ParseObject) -> Option<TopLevelSyntax> {
-    if object.starts_with('>') {
-        let mut list = Vec::<String>::new();
-    
-        let mut line = object.current();
-        while line.starts_with('>') {
-            let mut string = &line[1..];
-            string = string.trim();
-            list.push(string.into());
-    
-            object.next();
-            if object.current().is_empty() { break }
-            line = object.current();
+// "— Author" or "-- Author"
+fn attribution(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix('—').or_else(|| line.strip_prefix("--"))?;
+    if !rest.starts_with(char::is_whitespace) || rest.trim().is_empty() {
+        return None;
+    }
+    Some(rest.trim())
+}
+
+// "[!kind] title" or "[!kind]- title" at the start of a quote
+fn callout_header(line: &str) -> Option<(&str, bool, &str)> {
+    let rest = line.trim_start().strip_prefix("[!")?;
+    let end = rest.find(']')?;
+    let kind = &rest[..end];
+    if kind.is_empty() || !kind.chars().all(|c| c.is_alphanumeric() || c == '-') {
+        return None;
+    }
+    let rest = &rest[end + 1..];
+    let (collapsed, title) = match rest.strip_prefix('-') {
+        Some(title) => (true, title),
+        None => (false, rest.strip_prefix('+').unwrap_or(rest)),
+    };
+    Some((kind, collapsed, title.trim()))
+}
+
+// the text of a quote line without its first '>'
+fn quote_content(line: &str) -> &str {
+    let content = &line[1..];
+    content.strip_prefix(' ').unwrap_or(content)
+}
+
+pub fn parse_quote<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
+    if !object.starts_with('>') {
+        return None;
+    }
+
+    // every line loses its first '>' and the body is parsed again, so ">>" is a quote in a quote
+    let header = callout_header(quote_content(object.current()));
+    let mut text = String::new();
+    let mut origins = Vec::<(usize, usize)>::new();
+    let mut author = None;
+    for n in 0.. {
+        let line = object.current();
+        let content = quote_content(line);
+        let is_last = !object.peek().starts_with('>');
+        if n == 0 && header.is_some() {
+            // the header of a callout has its title, it isn't part of the body
+        } else if is_last && header.is_none() && let Some(name) = attribution(content.trim()) {
+            author = Some(name.into());
+        } else {
+            origins.push((text.len(), object.offset() + line.len() - content.len()));
+            text += content;
+            text.push('\n');
         }
-    
-        Some(TopLevelSyntax::Quote(list))
-    } else {
-        None
+        if is_last { break }
+        object.next();
     }
+
+    let body = if text.trim().is_empty() { vec![] } else { parse_dedented(&text, &origins)? };
+    Some(match header {
+        Some((kind, collapsed, title)) => TopLevelSyntax::Callout { kind: kind.into(), title: title.into(), collapsed, body },
+        None => TopLevelSyntax::Quote { body, attribution: author },
+    })
 }
 
-pub fn parse_pagebreak(object: &mut ParseObject) -> Option<TopLevelSyntax> {
+pub fn parse_pagebreak<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
     let current = object.current();
     if current.starts_with("---") && current.ends_with("---") {
         let mut is_line_break = true;
@@ -64,19 +113,139 @@ pub fn parse_pagebreak(object: &mut ParseObject) -> Option<TopLevelSyntax> {
     }
 }
 
-pub fn parse_codeblock(object: &mut ParseObject) -> Option<TopLevelSyntax> {
+pub fn parse_codeblock<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
     if object.current().starts_with("```") {
         let Some(last) = object.find("```", 3) else { return None };
-        let block = object.yoink(0, last + 3);
-        let text = block[..block.len()][3..].to_string();
+        let block = object.yoink(last + 3);
+        let block = &block[3..block.len() - 3];
+        let (info, code) = block.split_once('\n').unwrap_or((block, ""));
+        Some(TopLevelSyntax::CodeBlock { info: info.trim().into(), code: code.into() })
+    } else {
+        None
+    }
+}
 
-        Some(TopLevelSyntax::CodeBlock(text))
+pub fn parse_math_block<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
+    if object.current().starts_with("$$") {
+        let Some(last) = object.find("$$", 2) else { return None };
+        let block = object.yoink(last + 2);
+        Some(TopLevelSyntax::MathBlock(block[2..block.len() - 2].trim().into()))
     } else {
         None
     }
 }
 
 
+// splits a table row on `|`, ignoring escaped ones and the pipes at either end of the row
+fn split_table_row(line: &str) -> Vec<&str> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(stripped) if !stripped.ends_with('\\') => stripped,
+        _ => line,
+    };
+
+    let mut cells = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (index, character) in line.char_indices() {
+        if character == '|' && !escaped {
+            cells.push(line[start..index].trim());
+            start = index + 1;
+        }
+        escaped = character == '\\' && !escaped;
+    }
+    cells.push(line[start..].trim());
+    cells
+}
+
+// the `| --- | :-: | --: |` row under the header, None if it isn't one
+fn parse_table_alignments(line: &str) -> Option<Vec<Alignment>> {
+    if !line.contains('|') { return None }
+    split_table_row(line).into_iter().map(|cell| {
+        let left  = cell.starts_with(':');
+        let right = cell.ends_with(':') && cell.len() > 1;
+        let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+        if dashes.is_empty() || !dashes.chars().all(|c| c == '-') { return None }
+        Some(match (left, right) {
+            (true,  true)  => Alignment::Center,
+            (true,  false) => Alignment::Left,
+            (false, true)  => Alignment::Right,
+            (false, false) => Alignment::None,
+        })
+    }).collect()
+}
+
+fn is_table_row(line: &str) -> bool {
+    !line.trim().is_empty() && line.contains('|')
+}
+
+pub fn parse_table<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
+    let header = object.current();
+    if !header.contains('|') { return None }
+
+    let header: Vec<Cow<'a, str>> = split_table_row(header).into_iter().map(Cow::from).collect();
+    let alignments = parse_table_alignments(object.peek())?;
+    if alignments.len() != header.len() { return None }
+    object.next();
+
+    // rows are cut or padded to the width of the header, like everyone else does it
+    let mut rows = vec![];
+    while is_table_row(object.peek()) {
+        object.next();
+        let mut row: Vec<Cow<'a, str>> = split_table_row(object.current()).into_iter().map(Cow::from).collect();
+        row.resize(header.len(), Cow::Borrowed(""));
+        rows.push(row);
+    }
+
+    Some(TopLevelSyntax::Table { header, alignments, rows })
+}
+
+// the text of a ": definition" line
+fn definition(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix(':')?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim())
+}
+
+fn starts_definition(term: &str, next: &str) -> bool {
+    !term.trim().is_empty() && definition(term).is_none() && definition(next).is_some()
+}
+
+pub fn parse_definition_list<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
+    if !starts_definition(object.current(), object.peek()) { return None }
+
+    let mut entries = vec![];
+    loop {
+        let term = object.current().trim();
+        let mut definitions: Vec<Cow<'a, str>> = vec![];
+        loop {
+            let line = object.peek();
+            if let Some(text) = definition(line) {
+                definitions.push(text.into());
+            } else if let Some(last) = definitions.last_mut() && line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+                // indented lines carry on the definition above them
+                *last = format!("{last}\n{}", line.trim()).into();
+            } else {
+                break;
+            }
+            object.next();
+        }
+        entries.push((Cow::from(term), definitions));
+
+        // blank lines between two terms don't end the list
+        let mut ahead = object.text().lines().skip(1).enumerate().skip_while(|(_, line)| line.trim().is_empty());
+        let Some((skipped, term)) = ahead.next() else { break };
+        let next = ahead.next().map_or("", |(_, line)| line);
+        if !starts_definition(term, next) { break }
+        for _ in 0..=skipped {
+            object.next();
+        }
+    }
+
+    Some(TopLevelSyntax::DefinitionList(entries))
+}
+
+
 fn string_has_delimeter(text: &str) -> Option<char> {
     for c in text.chars() {
         match c {
@@ -152,27 +321,27 @@ fn is_meta(text: &str, check: &str) -> Option<usize> {
     }
 }
 
-pub fn parse_table_of_contents(object: &mut ParseObject) -> Option<TopLevelSyntax> {
+pub fn parse_table_of_contents<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
     let current = object.current();
     if let Some(n) = is_meta(current, "toc") {
-        let text = current[n..].trim_start().to_string();
+        let text = current[n..].trim_start().into();
         return Some(TopLevelSyntax::TOC(text));
     }
     
     if let Some(n) = is_meta(current, "table-of-content") {
-        let text = current[n..].trim_start().to_string();
+        let text = current[n..].trim_start().into();
         return Some(TopLevelSyntax::TOC(text));
     }
     
     if let Some(n) = is_meta(current, "table-of-contents") {
-        let text = current[n..].trim_start().to_string();
+        let text = current[n..].trim_start().into();
         return Some(TopLevelSyntax::TOC(text));
     }
 
     None 
 }
 
-pub fn parse_heading(object: &mut ParseObject) -> Option<TopLevelSyntax> {
+pub fn parse_heading<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
     if object.starts_with('#') {
         let current = object.current();
         let mut counter = 0;
@@ -187,57 +356,37 @@ pub fn parse_heading(object: &mut ParseObject) -> Option<TopLevelSyntax> {
     }
 }
 
-pub fn parse_note(object: &mut ParseObject) -> Option<TopLevelSyntax> {
+pub fn parse_note<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
     let current = object.current();
     if !current.starts_with("[") { return None }
     if !current[1..].trim_start().starts_with("^") { return None }
 
     // this is most likely a note definition. as in [^n]: ...
     let remaining = &current[1..].trim_start()[1..];
-    let mut note_id = String::new();
-    let mut peekable = remaining.chars().peekable();
-    while let Some(character) = peekable.peek() && character != &']' {
-        note_id.push(*character);
-        peekable.next();
-    }
+    let Some(end) = remaining.find(']') else { return None };
+    let Some(text) = remaining[end + 1..].strip_prefix(':') else { return None };
 
-    let Some(']') = peekable.next() else { return None };
-    let Some(':') = peekable.next() else { return None };
-
-    note_id = note_id.trim().to_string();
+    let note_id = remaining[..end].trim();
     if note_id.len() == 0 { return None }
-    
-    let text : String = peekable.collect();
 
-    Some(TopLevelSyntax::NoteDefinition{id: note_id, text: text.trim().to_string()})
+    Some(TopLevelSyntax::NoteDefinition{id: note_id.into(), text: text.trim().into()})
 }
 
+// the byte index of the delimiter closing the one right before `text`
 fn find_end_balanced(text: &str, delimiters: (char, char)) -> Option<usize> {
-    let mut found_one = false;
-    let mut character_count = 0;
-    let mut delim_count: usize = 0;
-    for character in text.chars() {
+    let mut depth: usize = 0;
+    for (index, character) in text.char_indices() {
         if character == delimiters.0 {
-            delim_count = delim_count + 1;
+            depth += 1;
+        } else if character == delimiters.1 {
+            if depth == 0 { return Some(index) }
+            depth -= 1;
         }
-        else if character == delimiters.1 {
-            if delim_count == 0 {
-                found_one = true;
-                break; 
-            }
-            delim_count = delim_count - 1;
-        }
-        character_count = character_count + 1;
-    }
-
-    if found_one {
-        Some(character_count)
-    } else {
-        None
     }
+    None
 }
 
-pub fn parse_factbox(object: &mut ParseObject) -> Option<TopLevelSyntax> {
+pub fn parse_factbox<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
     if !(object.current().starts_with("[[fact]") || object.current().starts_with("[[factbox]")) {
         return None;
     }
@@ -246,27 +395,48 @@ pub fn parse_factbox(object: &mut ParseObject) -> Option<TopLevelSyntax> {
     let last = find_end_balanced(&object.text()[len..], ('[', ']'))?;
     
     let mut text_to_parse = String::new();
-    let text = &&object.text()[len..last + len];
+    let text = &object.text()[len..last + len];
     let title = text.lines().take(1).nth(0);
-    for line in text.lines().skip(1) {
-        let trimmed = line.trim_start();
-        text_to_parse += trimmed;
-        text_to_parse.push('\n');
+
+    // the body is parsed on its own with the indentation removed, so we remember where every line
+    // came from to map the spans back onto the original text
+    let mut origins = Vec::<(usize, usize)>::new();
+    let mut line_start = object.offset() + len;
+    for (n, line) in text.split_inclusive('\n').enumerate() {
+        let line = line.trim_end_matches('\n');
+        if n != 0 {
+            let trimmed = line.trim_start();
+            origins.push((text_to_parse.len(), line_start + line.len() - trimmed.len()));
+            text_to_parse += trimmed;
+            text_to_parse.push('\n');
+        }
+        line_start += line.len() + 1;
     }
     
-    let Ok(body) = toplevel_parse(&text_to_parse) else { return None };
+    let body = parse_dedented(&text_to_parse, &origins)?;
     
     let title = if let Some(text) = title && !text.trim().is_empty() {
         text
     } else {  
         DEFAULT_FACTBOX_TITLE
-    }.to_string();
+    }.into();
 
     object.skip(last + len);
     Some(TopLevelSyntax::FactBox{title, body})
 }
 
-pub fn parse_embedding(object: &mut ParseObject) -> Option<TopLevelSyntax> {
+// `origins` has where every line of the dedented `text` starts and where that line came from, the
+// text only lives as long as the caller so the syntax can't borrow from it
+pub(super) fn parse_dedented(text: &str, origins: &[(usize, usize)]) -> Option<Vec<Spanned<TopLevelSyntax<'static>>>> {
+    let Ok(syntax) = parse_syntax(text) else { return None };
+    let mut syntax: Vec<_> = syntax.into_iter()
+        .map(|Spanned { node, span }| Spanned { node: node.into_owned(), span })
+        .collect();
+    shift(&mut syntax, &|offset| map_offset(origins, offset));
+    Some(syntax)
+}
+
+pub fn parse_embedding<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
     let current = object.current();
     if !(current.starts_with("[[") || current.starts_with("![[")) {
         return None;
@@ -276,19 +446,27 @@ pub fn parse_embedding(object: &mut ParseObject) -> Option<TopLevelSyntax> {
     let count = if first_character == '!' { 3 } else { 2 };
     let text :&str = &current[count..];
     let Some(img_end) = text.find(']') else { return None };
-    let img = &text[0..img_end];
     let mut remaining_on_line = &text[img_end + 1..];
+    // "[[post-name]]" starting a line is a wiki link in a paragraph, not an image
+    if count == 2 && remaining_on_line.starts_with(']') && is_wiki_link(&text[..img_end]) {
+        return None;
+    }
+    if count == 3 && remaining_on_line.trim_start().starts_with(']') && is_transclusion(&text[..img_end]) {
+        let (path, section) = text[..img_end].split_once('#').unwrap_or((&text[..img_end], ""));
+        let section = Some(section.trim()).filter(|x| !x.is_empty()).map(Cow::from);
+        return Some(TopLevelSyntax::Transclusion { path: path.trim().into(), section, error: None });
+    }
+    let (img, size) = split_image_size(&text[0..img_end]);
     
     let mut alt_text = String::new();
     if remaining_on_line.len() != 0 {
         remaining_on_line = remaining_on_line.trim_start();
         if remaining_on_line.starts_with(']') {
-            return Some(TopLevelSyntax::Image(Image::new(img.into())));
+            return Some(TopLevelSyntax::Image(Image::new_with_size(img.into(), size)));
         }
     
         if let Some(index) = remaining_on_line.find(']') {
-            alt_text = remaining_on_line[0..index].into();
-            return Some(TopLevelSyntax::Image(Image::new_with_alt(img.into(), alt_text)));
+            return Some(TopLevelSyntax::Image(Image::new_with_alt_and_size(img.into(), remaining_on_line[0..index].into(), size)));
         }
     
         if remaining_on_line.len() > 0 {
@@ -297,38 +475,48 @@ pub fn parse_embedding(object: &mut ParseObject) -> Option<TopLevelSyntax> {
         }
     }
     
-    let image_text = String::from(img);
-    let Some(end_index) = object.find(']', 0) else { return None };
+    // the alt text continues on the next lines, so look for the closing bracket past this one
+    let start = object.offset();
+    let Some(end_index) = object.find(']', current.len() + 1) else { return None };
     
     
     object.next();
-    alt_text += &object.text()[..end_index];
-    
-    if !image_text.contains('|') {
-        return Some(TopLevelSyntax::Image(Image::new_with_alt(image_text, alt_text)))
-    }
+    alt_text += &object.text()[..end_index - (object.offset() - start)];
 
-    let elems = image_text.split('|').collect::<Vec<_>>();
-    if elems.len() < 2 {
-        return Some(TopLevelSyntax::Image(Image::new_with_alt(image_text, alt_text)))
-    }
-
-    let img = elems[0];
-    let size = elems.last().expect("should work");
-    let width  = if size.contains('x') {
-        size.split('x').collect::<Vec<_>>().iter().nth(0).map_or_else(|| None, |text| Some(text.to_string()))
-    } else { if size.parse::<usize>().is_ok() { Some(size.to_string()) } else { None } };
-    
-    let height = if size.contains('x') {
-        size.split('x').collect::<Vec<_>>().iter().nth(1).map_or_else(|| None, |text| Some(text.to_string()))
-    } else { None };
+    Some(TopLevelSyntax::Image(Image::new_with_alt_and_size(img.into(), alt_text.into(), size)))
+}
 
-    let size = ImageSize::from_width_and_height(width, height);
+// "path/to/image.png|600x400", anything after the last '|' is the size.
+// either side of the 'x' can be left out or be "auto", so "600x", "x400" and "autox400" work too
+fn split_image_size(text: &str) -> (&str, ImageSize) {
+    let Some((img, size)) = text.split_once('|') else { return (text, ImageSize::None) };
+    let size = size.rsplit('|').next().unwrap_or(size).trim();
 
-    Some(TopLevelSyntax::Image(Image::new_with_alt_and_size(img.to_string(), alt_text, size)))
+    let dimension = |text: &str| -> Option<Option<String>> {
+        let text = text.trim();
+        if text.is_empty() || text == "auto" { return Some(None) }
+        text.starts_with(|c: char| c.is_ascii_digit()).then(|| Some(text.to_string()))
+    };
+    // units can have an 'x' in them, "600pxx400px" splits on the 'x' that's followed by the height
+    let split = size.match_indices('x').map(|(index, _)| (&size[..index], &size[index + 1..])).find(|(width, height)| {
+        let height = height.trim();
+        if height.is_empty() {
+            width.trim_end().ends_with(|c: char| c.is_ascii_digit() || c == '%') || width.trim() == "auto"
+        } else {
+            height == "auto" || height.starts_with(|c: char| c.is_ascii_digit())
+        }
+    });
+    let size = match split {
+        Some((width, height)) => dimension(width).zip(dimension(height)),
+        None => dimension(size).map(|width| (width, None)),
+    };
+    match size {
+        Some((width, height)) => (img, ImageSize::from_width_and_height(width, height)),
+        None => (img, ImageSize::None),
+    }
 }
 
-pub fn parse_citation(object: &mut ParseObject) -> Option<TopLevelSyntax> {
+pub fn parse_citation<'a>(object: &mut ParseObject<'a>) -> Option<TopLevelSyntax<'a>> {
     let current = object.current();
     let Some('£') = current.chars().nth(0) else { return None };
     if !current.chars().nth(1).is_some_and(|c| c.is_alphabetic() || c == '-') { return None }
@@ -339,16 +527,53 @@ pub fn parse_citation(object: &mut ParseObject) -> Option<TopLevelSyntax> {
     let Ok(citation) = parse_reference(object.text()[..end + 1].to_string()) else {
         return None;
     };
+    object.skip(end);
     Some(TopLevelSyntax::ReferenceDefinition(citation))
 }
 
-pub fn toplevel_parse(file_content: &String) -> Result<Vec<TopLevelSyntax>> {
-    let (frontmatter, content) = parse_frontmatter(&file_content);
-    let mut object = ParseObject::new(content);
-    
-    if let Some(frontmatter) = frontmatter {
-        object.push(TopLevelSyntax::FrontMatter(frontmatter));
+/// `file_path` is where `file_content` was read from, documents it includes are looked up next to it
+pub fn toplevel_parse<'a>(file_content: &'a str, file_path: Option<&Path>) -> Result<Vec<Spanned<TopLevelSyntax<'a>>>> {
+    let mut syntax = parse_syntax(file_content)?;
+    // the document can't include itself either
+    let mut included: Vec<PathBuf> = file_path.and_then(|path| path.canonicalize().ok()).into_iter().collect();
+    let directory = file_path.and_then(Path::parent).unwrap_or(Path::new(""));
+    transclude(&mut syntax, directory, &mut included);
+    let index = LineIndex::new(file_content);
+    locate(&mut syntax, &index);
+    Ok(syntax)
+}
+
+fn locate(syntax: &mut Vec<Spanned<TopLevelSyntax<'_>>>, index: &LineIndex) {
+    for elem in syntax {
+        index.locate(&mut elem.span);
+        for_each_body(&mut elem.node, &mut |body| locate(body, index));
     }
+}
+
+// the syntax nested inside of fact boxes, quotes, callouts and list items
+pub(super) fn for_each_body<'a>(syntax: &mut TopLevelSyntax<'a>, f: &mut dyn FnMut(&mut Vec<Spanned<TopLevelSyntax<'a>>>)) {
+    fn list<'a>(items: &mut [ListItem<'a>], f: &mut dyn FnMut(&mut Vec<Spanned<TopLevelSyntax<'a>>>)) {
+        for item in items {
+            match item {
+                ListItem::InnerList(items) => list(items, f),
+                item => if let Some(body) = item.body_mut() { f(body) },
+            }
+        }
+    }
+    match syntax {
+        TopLevelSyntax::FactBox { body, .. } |
+        TopLevelSyntax::Quote { body, .. }   |
+        TopLevelSyntax::Callout { body, .. } => f(body),
+        TopLevelSyntax::List(items)          => list(items, f),
+        _ => {},
+    }
+}
+
+// spans coming out of here only have their byte offsets set
+pub(super) fn parse_syntax(file_content: &str) -> Result<Vec<Spanned<TopLevelSyntax<'_>>>> {
+    let (frontmatter, content) = parse_frontmatter(file_content);
+    let frontmatter_length = file_content.len() - content.len();
+    let mut object = ParseObject::new(content);
 
     while object.has_text() {
         if object.current().is_empty() {
@@ -360,71 +585,109 @@ pub fn toplevel_parse(file_content: &String) -> Result<Vec<TopLevelSyntax>> {
         if skip_comment(&mut object) {
             continue;
         }
+
+        let start = object.offset();
         
         if let Some(elem) = parse_quote(&mut object) {
             object.next();
-            object.push(elem);
+            object.push(elem, start);
             continue;
         }
         
         if let Some(elem) = parse_pagebreak(&mut object) {
             object.next();
-            object.push(elem);
+            object.push(elem, start);
             continue;
         }
 
         if let Some(elem) = parse_list(&mut object) {
             object.next();
-            object.push(elem);
+            object.push(elem, start);
             continue;
         }
 
         if let Some(elem) = parse_codeblock(&mut object) {
             object.next();
-            object.push(elem);
+            object.push(elem, start);
+            continue;
+        }
+
+        if let Some(elem) = parse_math_block(&mut object) {
+            object.next();
+            object.push(elem, start);
+            continue;
+        }
+
+        if let Some(elem) = parse_table(&mut object) {
+            object.next();
+            object.push(elem, start);
             continue;
         }
 
         if let Some(elem) = parse_table_of_contents(&mut object) {
             object.next();
-            object.push(elem);
+            object.push(elem, start);
             continue;
         }
         
         if let Some(elem) = parse_heading(&mut object) {
             object.next();
-            object.push(elem);
+            object.push(elem, start);
             continue;
         }
         
         if let Some(elem) = parse_note(&mut object) {
             object.next();
-            object.push(elem);
+            object.push(elem, start);
             continue;
         }
 
         if let Some(elem) = parse_factbox(&mut object) {
             object.next();
-            object.push(elem);
+            object.push(elem, start);
             continue;
         }
 
         if let Some(elem) = parse_embedding(&mut object) {
             object.next();
-            object.push(elem);
+            object.push(elem, start);
             continue;
         }
 
         if let Some(elem) = parse_citation(&mut object) {
             object.next();
-            object.push(elem);
+            object.push(elem, start);
+            continue;
+        }
+
+        // last, anything that isn't some other block can be a term
+        if let Some(elem) = parse_definition_list(&mut object) {
+            object.next();
+            object.push(elem, start);
             continue;
         }
 
         object.eat_line();
     }
+    // a paragraph running up to the end of the text is still being eaten
+    object.consume();
 
-    Ok(object.syntax)
+    let mut syntax = object.syntax;
+    if frontmatter_length != 0 {
+        shift(&mut syntax, &|offset| offset + frontmatter_length);
+    }
+    if let Some(frontmatter) = frontmatter {
+        syntax.insert(0, Spanned::new(TopLevelSyntax::FrontMatter(frontmatter), 0..frontmatter_length));
+    }
+    Ok(syntax)
+}
+
+fn shift(syntax: &mut Vec<Spanned<TopLevelSyntax<'_>>>, map: &dyn Fn(usize) -> usize) {
+    for elem in syntax {
+        elem.span.start.offset = map(elem.span.start.offset);
+        elem.span.end.offset   = map(elem.span.end.offset);
+        for_each_body(&mut elem.node, &mut |body| shift(body, map));
+    }
 }
 
 
@@ -483,7 +746,9 @@ mod tests {
 
         #[test]
         fn test_find_end_balanced() {
-            assert!(false);
+            assert_eq!(find_end_balanced("a [b] c] d", ('[', ']')), Some(7));
+            assert_eq!(find_end_balanced("café [日本]]", ('[', ']')), Some(14));
+            assert_eq!(find_end_balanced("[never closed]", ('[', ']')), None);
         }
 
     }
@@ -491,10 +756,201 @@ mod tests {
     mod element_parsers {
         use super::*;
 
+        #[test]
+        fn codeblock() {
+            let mut object = ParseObject::new("```rust title=\"main.rs\"\nfn main() {}\n```\n");
+            let Some(TopLevelSyntax::CodeBlock { info, code }) = parse_codeblock(&mut object) else { panic!("expected a codeblock") };
+            assert_eq!(info, "rust title=\"main.rs\"");
+            assert_eq!(code, "fn main() {}\n");
+        }
+
+        #[test]
+        fn math_block() {
+            let mut object = ParseObject::new("$$\n\\frac{1}{2}\n$$\nafter");
+            let Some(TopLevelSyntax::MathBlock(tex)) = parse_math_block(&mut object) else { panic!("expected a math block") };
+            assert_eq!(tex, "\\frac{1}{2}");
+
+            let mut object = ParseObject::new("$$x^2$$");
+            let Some(TopLevelSyntax::MathBlock(tex)) = parse_math_block(&mut object) else { panic!("expected a math block") };
+            assert_eq!(tex, "x^2");
+
+            let mut object = ParseObject::new("$$ never closed");
+            assert_eq!(parse_math_block(&mut object), None);
+        }
+
+        #[test]
+        fn quote() {
+            let mut object = ParseObject::new("> first\n>\n>> inner\n> — Someone, [£cite]\nafter");
+            let Some(TopLevelSyntax::Quote { body, attribution }) = parse_quote(&mut object) else { panic!("expected a quote") };
+            assert_eq!(body[0].node, TopLevelSyntax::Paragraph("first\n".into()));
+            let TopLevelSyntax::Quote { body: inner, attribution: None } = &body[1].node else { panic!("expected a nested quote") };
+            assert_eq!(inner[0].node, TopLevelSyntax::Paragraph("inner\n".into()));
+            assert_eq!(attribution, Some("Someone, [£cite]".into()));
+            assert_eq!(object.current(), "> — Someone, [£cite]");
+
+            // a dash that isn't on the last line is just text
+            let mut object = ParseObject::new("> -- not yet\n> the end\n");
+            let Some(TopLevelSyntax::Quote { body, attribution: None }) = parse_quote(&mut object) else { panic!("expected a quote") };
+            assert_eq!(body[0].node, TopLevelSyntax::Paragraph("-- not yet\nthe end\n".into()));
+        }
+
+        #[test]
+        fn callout() {
+            let mut object = ParseObject::new("> [!note]- Read this\n> the body\n> — not an attribution\nafter");
+            let Some(TopLevelSyntax::Callout { kind, title, collapsed, body }) = parse_quote(&mut object) else { panic!("expected a callout") };
+            assert_eq!((kind.as_ref(), title.as_ref(), collapsed), ("note", "Read this", true));
+            assert_eq!(body[0].node, TopLevelSyntax::Paragraph("the body\n— not an attribution\n".into()));
+            assert_eq!(object.current(), "> — not an attribution");
+
+            // only the first line can start a callout
+            let mut object = ParseObject::new("> text\n> [!note]\n");
+            assert!(matches!(parse_quote(&mut object), Some(TopLevelSyntax::Quote { .. })));
+            assert_eq!(callout_header("[!tip]+"), Some(("tip", false, "")));
+            assert_eq!(callout_header("[!not a kind]"), None);
+        }
+
+        #[test]
+        fn definition_list() {
+            let mut object = ParseObject::new("Term\n: first\n  and more\n: second\n\nOther\n: third\n\nafter\nthe list\n");
+            let Some(TopLevelSyntax::DefinitionList(entries)) = parse_definition_list(&mut object) else { panic!("expected a definition list") };
+            assert_eq!(entries, vec![
+                ("Term".into(), vec!["first\nand more".into(), "second".into()]),
+                ("Other".into(), vec!["third".into()]),
+            ]);
+            assert_eq!(object.current(), ": third");
+
+            let mut object = ParseObject::new("just text\n:not a definition\n");
+            assert_eq!(parse_definition_list(&mut object), None);
+        }
+
+        #[test]
+        fn table() {
+            let mut object = ParseObject::new("| a | b \\| c | d |\n|:--|:-:|--:|\n| 1 | **2** |\n\nafter");
+            let Some(TopLevelSyntax::Table { header, alignments, rows }) = parse_table(&mut object) else { panic!("expected a table") };
+            assert_eq!(header, vec!["a", "b \\| c", "d"]);
+            assert_eq!(alignments, vec![Alignment::Left, Alignment::Center, Alignment::Right]);
+            assert_eq!(rows, vec![vec!["1", "**2**", ""]]);
+            assert_eq!(object.current(), "| 1 | **2** |");
+        }
+
+        #[test]
+        fn table_needs_alignment_row() {
+            let mut object = ParseObject::new("| a | b |\n| 1 | 2 |\n");
+            assert_eq!(parse_table(&mut object), None);
+            let mut object = ParseObject::new("| a | b |\n|---|\n");
+            assert_eq!(parse_table(&mut object), None);
+        }
+
     }
 
     mod full_parser {
         use super::*;
         
+        #[test]
+        fn spans() {
+            let text = "---\ntitle: spans\n---\n# heading\n\nfirst line\nsecond line\n\n---\n".to_string();
+            let syntax = toplevel_parse(&text, None).unwrap();
+            let spans: Vec<_> = syntax.iter().map(|x| &text[x.span.range()]).collect();
+            assert_eq!(spans[1], "# heading");
+            assert_eq!(spans[2], "first line\nsecond line");
+            assert_eq!(spans[3], "---");
+            assert_eq!((syntax[2].span.start.line, syntax[2].span.start.column), (6, 1));
+            assert_eq!((syntax[2].span.end.line, syntax[2].span.end.column), (7, 12));
+        }
+
+        #[test]
+        fn factbox_spans() {
+            let text = "[[fact] title\n    indented paragraph\n\n]\n".to_string();
+            let syntax = toplevel_parse(&text, None).unwrap();
+            let TopLevelSyntax::FactBox { body, .. } = &syntax[0].node else { panic!("expected a factbox") };
+            assert_eq!(&text[body[0].span.range()], "indented paragraph");
+            assert_eq!((body[0].span.start.line, body[0].span.start.column), (2, 5));
+        }
+
+        #[test]
+        fn list_body_spans() {
+            let text = "---\ntitle: lists\n---\n- item\n\n  second paragraph\n  of the item\n".to_string();
+            let syntax = toplevel_parse(&text, None).unwrap();
+            let TopLevelSyntax::List(list) = &syntax[1].node else { panic!("expected a list") };
+            let ListItem::Unordered(_, body) = &list[0] else { panic!("expected an item") };
+            assert_eq!(&text[body[0].span.range()], "second paragraph\n  of the item");
+            assert_eq!((body[0].span.start.line, body[0].span.start.column), (6, 3));
+        }
+
+        #[test]
+        fn quote_spans() {
+            let text = "> > nested\n".to_string();
+            let syntax = toplevel_parse(&text, None).unwrap();
+            let TopLevelSyntax::Quote { body, .. } = &syntax[0].node else { panic!("expected a quote") };
+            let TopLevelSyntax::Quote { body, .. } = &body[0].node else { panic!("expected a nested quote") };
+            assert_eq!(&text[body[0].span.range()], "nested");
+            assert_eq!((body[0].span.start.line, body[0].span.start.column), (1, 5));
+        }
+
+        #[test]
+        fn text_after_list_and_reference() {
+            let text = "- item\nright after\n\n£doe {\n    title: A Book,\n}\nlast paragraph".to_string();
+            let syntax = toplevel_parse(&text, None).unwrap();
+            assert_eq!(syntax.len(), 4);
+            assert_eq!(syntax[1].node, TopLevelSyntax::Paragraph("right after\n".into()));
+            assert!(matches!(syntax[2].node, TopLevelSyntax::ReferenceDefinition(_)));
+            assert_eq!(syntax[3].node, TopLevelSyntax::Paragraph("last paragraph\n".into()));
+        }
+    }
+}
+
+// `cargo +nightly bench`, each step is ten times the size of the last so the times should be too
+#[cfg(test)]
+mod bench {
+    extern crate test;
+    use super::*;
+    use test::Bencher;
+
+    const SECTION: &str = "# heading
+a paragraph with **bold** text and a note[^1]
+that carries on for a second line
+
+- one
+- two
+  - inner
+1. first
+
+```rust
+fn main() {}
+```
+
+> a quote
+> over two lines
+
+%% a comment %%
+![[image.png] the alt text]
+
+[[fact] a fact box
+    with a paragraph
+
+]
+
+[^1]: the note text
+
+";
+
+    fn parse_sections(bencher: &mut Bencher, sections: usize) {
+        let text = SECTION.repeat(sections);
+        bencher.iter(|| toplevel_parse(&text, None).map(|x| x.len()));
+    }
+
+    #[bench]
+    fn sections_10(bencher: &mut Bencher) {
+        parse_sections(bencher, 10);
+    }
+
+    #[bench]
+    fn sections_100(bencher: &mut Bencher) {
+        parse_sections(bencher, 100);
+    }
+
+    #[bench]
+    fn sections_1000(bencher: &mut Bencher) {
+        parse_sections(bencher, 1000);
     }
 }
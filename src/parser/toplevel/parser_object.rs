@@ -1,40 +1,82 @@
 
 use super::structs::*;
+use super::super::source::Spanned;
+use std::borrow::Cow;
+use std::ops::Range;
 use std::str::pattern::Pattern;
 
 #[derive(Debug, PartialEq)]
-pub(in super::super) struct ParseObject {
+pub(in super::super) struct ParseObject<'a> {
     is_eating: bool,
-    text: String,
+    // the whole input, nothing is copied out of it unless it has to be
+    source: &'a str,
+    // only used when a paragraph can't be borrowed from `source` in one piece
     paragraph_buffer: String,
-    pub(in super::super) syntax: Vec<TopLevelSyntax>
+    // byte offset into `source` of where we currently are
+    offset: usize,
+    // where the last non-empty line we moved past ended, used as the end of spans
+    line_end: usize,
+    paragraph_span: Range<usize>,
+    pub(in super::super) syntax: Vec<Spanned<TopLevelSyntax<'a>>>
     //TODO(Paw): add some diagnostics structure in here
 }
 
-impl ParseObject {
-    pub(in super::super) fn new(text: &str) -> Self {
+impl<'a> ParseObject<'a> {
+    pub(in super::super) fn new(text: &'a str) -> Self {
         Self {
-            text: text.to_string(),
+            source: text,
             is_eating: false,
             paragraph_buffer: String::new(),
+            offset: 0,
+            line_end: 0,
+            paragraph_span: 0..0,
             syntax: vec![]
         }
     }
 
+    pub(in super::super) fn offset(&self) -> usize {
+        self.offset
+    }
+
     pub(in super::super) fn has_text(&self) -> bool {
-        !self.text.is_empty()
+        self.offset < self.source.len()
     }
     
-    pub(in super::super) fn text(&self) -> &str {
-        self.text.as_str()
+    pub(in super::super) fn text(&self) -> &'a str {
+        &self.source[self.offset..]
     }
 
-    pub(in super::super) fn current(&self) -> &str {
-        self.text.lines().nth(0).unwrap_or("")
+    pub(in super::super) fn current(&self) -> &'a str {
+        let text = self.text();
+        let line = match text.find('\n') {
+            Some(end) => &text[..end],
+            None => text,
+        };
+        line.strip_suffix('\r').unwrap_or(line)
     }
     
+    // the line after the current one
+    pub(in super::super) fn peek(&self) -> &'a str {
+        let text = &self.text()[self.line_length()..];
+        let line = match text.find('\n') {
+            Some(end) => &text[..end],
+            None => text,
+        };
+        line.strip_suffix('\r').unwrap_or(line)
+    }
+
+    // length of the current line including the line ending
+    fn line_length(&self) -> usize {
+        let text = self.text();
+        text.find('\n').map_or(text.len(), |end| end + 1)
+    }
+
     pub(in super::super) fn next(&mut self) {
-        self.skip(self.current().len() + 1);
+        let length = self.current().len();
+        if length != 0 {
+            self.line_end = self.offset + length;
+        }
+        self.skip(self.line_length());
     }
 
     pub(in super::super) fn trimmed_line_find<T: Pattern>(&self, pat: T, offset: usize) -> Option<usize> {
@@ -52,7 +94,7 @@ impl ParseObject {
     }
     
     pub(in super::super) fn trimmed_find<T: Pattern>(&self, pat: T, offset: usize) -> Option<usize> {
-        let full_line = &self.text;
+        let full_line = self.text();
         let trimmed_line = full_line.trim_start(); 
         if offset >= trimmed_line.len() { return None }
 
@@ -66,7 +108,7 @@ impl ParseObject {
     }
     
     pub(in super::super) fn find<T: Pattern>(&self, pat: T, offset: usize) -> Option<usize> {
-        let text = &self.text;
+        let text = self.text();
         if offset >= text.len() { return None }
 
         let line = &text[offset..];
@@ -82,16 +124,14 @@ impl ParseObject {
     }
 
     pub(in super::super) fn skip(&mut self, offset: usize) {
-        if offset >= self.text.len() {
-            self.text = String::new();
-        } else {
-            self.text = self.text[offset..].to_string();
-        }
+        self.offset = (self.offset + offset).min(self.source.len());
     }
     
-    pub(in super::super) fn yoink(&mut self, offset: usize, len: usize) -> String {
-        let output = self.text[offset..len].to_string();
-        self.text.replace_range(offset..len, "");
+    // takes the next `len` bytes, what's left starts right after them
+    pub(in super::super) fn yoink(&mut self, len: usize) -> &'a str {
+        let output = &self.text()[..len];
+        self.offset += len;
+        self.line_end = self.offset;
         output
     }
 
@@ -102,35 +142,52 @@ impl ParseObject {
     }
 
     pub(in super::super) fn eat_line(&mut self) {
-        // this is stupid this should just be a one line function,
-        // but because of the brilliant borrow checker it has to 
-        // be inlined manually, the code I wanted to write was
-        // self.eat(self.current())
-        // which is obviously fine, but the borrow checker can't
-        // prove that it is
-        let line = self.text.lines().nth(0).unwrap_or("");
-        self.is_eating = true;
-        self.paragraph_buffer += line;
-        self.paragraph_buffer.push('\n');
-        if !self.text.is_empty() {
-            self.text = self.text[line.len() + 1..].to_string();
+        let line = self.current();
+        if !self.is_eating {
+            self.paragraph_span = self.offset..self.offset;
+        } else if self.paragraph_buffer.is_empty() && &self.source[self.paragraph_span.end..self.offset] != "\n" {
+            // something was skipped between the lines (a comment or a \r) so they can't be borrowed as one
+            self.paragraph_buffer += &self.source[self.paragraph_span.clone()];
+            self.paragraph_buffer.push('\n');
+        }
+        if !self.paragraph_buffer.is_empty() {
+            self.paragraph_buffer += line;
+            self.paragraph_buffer.push('\n');
         }
+        self.paragraph_span.end = self.offset + line.len();
+        self.is_eating = true;
+        self.skip(self.line_length());
     }
 
     pub(in super::super) fn consume(&mut self) {
         if self.is_eating {
-            let text = self.paragraph_buffer.trim();
-            if !text.is_empty() {
-                self.syntax.push(TopLevelSyntax::Paragraph(format!("{text}\n")));
+            let text = if self.paragraph_buffer.is_empty() {
+                let paragraph = &self.source[self.paragraph_span.clone()];
+                let start = self.paragraph_span.start + (paragraph.len() - paragraph.trim_start().len());
+                let end = start + paragraph.trim().len();
+                // the paragraph keeps the newline after it, which we can only borrow if it's there
+                if self.source[end..].starts_with('\n') {
+                    Cow::Borrowed(&self.source[start..end + 1])
+                } else {
+                    Cow::Owned(format!("{}\n", &self.source[start..end]))
+                }
+            } else {
+                Cow::Owned(format!("{}\n", self.paragraph_buffer.trim()))
+            };
+            if !text.trim().is_empty() {
+                let syntax = TopLevelSyntax::Paragraph(text);
+                self.syntax.push(Spanned::new(syntax, self.paragraph_span.clone()));
             }
             self.is_eating = false;
             self.paragraph_buffer.clear();
         }
     }
 
-    pub(in super::super) fn push(&mut self, syntax: TopLevelSyntax) {
+    // `start` is the offset the syntax began at, it ends at the last line we moved past
+    pub(in super::super) fn push(&mut self, syntax: TopLevelSyntax<'a>, start: usize) {
         self.consume();
-        self.syntax.push(syntax);
+        let end = if self.line_end > start { self.line_end } else { self.offset };
+        self.syntax.push(Spanned::new(syntax, start..end));
     }
 }
 
@@ -160,7 +217,7 @@ mod tests {
     #[test]
     fn creation() {
         let obj = ParseObject::new("");
-        assert_eq!(obj, ParseObject{ is_eating: false, text: "".to_string(), paragraph_buffer: String::new(), syntax: vec![] })
+        assert_eq!(obj, ParseObject{ is_eating: false, source: "", paragraph_buffer: String::new(), offset: 0, line_end: 0, paragraph_span: 0..0, syntax: vec![] })
     }
     
     #[test]
@@ -259,9 +316,10 @@ mod tests {
         assert_eq!(obj, 
             ParseObject{ 
                 is_eating: true, 
-                text: "".to_string(), 
+                source: "", 
                 paragraph_buffer: "text\n".to_string(), 
-                syntax: vec![] 
+                syntax: vec![],
+                ..ParseObject::new("")
             }
         );
     }
@@ -273,9 +331,10 @@ mod tests {
         assert_eq!(obj, 
             ParseObject{ 
                 is_eating: false, 
-                text: "".to_string(), 
+                source: "", 
                 paragraph_buffer: String::new(), 
-                syntax: vec![] 
+                syntax: vec![],
+                ..ParseObject::new("")
             }
         );
     }
@@ -288,9 +347,10 @@ mod tests {
         assert_eq!(obj, 
             ParseObject{ 
                 is_eating: false, 
-                text: "".to_string(), 
+                source: "", 
                 paragraph_buffer: String::new(), 
-                syntax: vec![TopLevelSyntax::Paragraph("text\n".to_string())] 
+                syntax: vec![Spanned::new(TopLevelSyntax::Paragraph("text\n".into()), 0..0)],
+                ..ParseObject::new("")
             }
         );
     }
@@ -298,13 +358,14 @@ mod tests {
     #[test]
     fn consume_without_eating() {
         let mut obj = ParseObject::new("");
-        obj.push(TopLevelSyntax::PageBreak);
+        obj.push(TopLevelSyntax::PageBreak, 0);
         assert_eq!(obj, 
             ParseObject{ 
                 is_eating: false, 
-                text: "".to_string(), 
+                source: "", 
                 paragraph_buffer: String::new(), 
-                syntax: vec![TopLevelSyntax::PageBreak] 
+                syntax: vec![Spanned::new(TopLevelSyntax::PageBreak, 0..0)],
+                ..ParseObject::new("")
             }
         );
     }
@@ -313,16 +374,17 @@ mod tests {
     fn consume_after_eating() {
         let mut obj = ParseObject::new("");
         obj.eat("text");
-        obj.push(TopLevelSyntax::PageBreak);
+        obj.push(TopLevelSyntax::PageBreak, 0);
         assert_eq!(obj, 
             ParseObject{ 
                 is_eating: false, 
-                text: "".to_string(), 
+                source: "", 
                 paragraph_buffer: String::new(), 
                 syntax: vec![
-                    TopLevelSyntax::Paragraph("text\n".to_string()),
-                    TopLevelSyntax::PageBreak
-                ] 
+                    Spanned::new(TopLevelSyntax::Paragraph("text\n".into()), 0..0),
+                    Spanned::new(TopLevelSyntax::PageBreak, 0..0)
+                ],
+                ..ParseObject::new("")
             }
         );
     }
@@ -5,3 +5,4 @@ pub use references::ReferenceDefinition as ReferenceDefinition;
 pub use references::parse_reference as parse_reference;
 pub use references::Month;
 pub use references::PmdDate;
+pub use references::{to_citation, to_bibliography, bibliograph_name, any_non_empty};
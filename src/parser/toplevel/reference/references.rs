@@ -1,12 +1,12 @@
 use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 
 use anyhow::{anyhow, Context, Result};
-#[cfg(not(feature = "wasm"))]
-use color_print::cprintln;
 
 use super::structured_base_parser::{peek_next_token, eat_token, parse_value};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Month {
     January,
     February,
@@ -57,7 +57,8 @@ impl Month {
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum PmdDate {
     #[default] None,
     String(String),
@@ -185,9 +186,6 @@ impl PmdDate {
         let month = maybe_month.unwrap_or(Month::January);
         let day   = maybe_day.unwrap_or(1);
 
-        #[cfg(not(feature = "wasm"))]
-        cprintln!("<cyan>debug:</> got date {} {:?} {}", year, month, day);
-
         let month = month.to_chrono_month().number_from_month();
 
         chrono::Utc.with_ymd_and_hms(year as i32, month, day, 0, 0, 0).earliest()
@@ -195,7 +193,7 @@ impl PmdDate {
 }
 
 
-#[derive(Debug, Default, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ReferenceDefinition {
     pub id: String,
     pub authors: Vec<String>,
@@ -278,19 +276,13 @@ pub fn parse_reference(content: String) -> Result<ReferenceDefinition> {
             buf = eat_token(&buf, "{");
 
             loop {
-                let opt_token = peek_next_token(&buf);
-                if opt_token.is_none() { break }
-
-                let token = opt_token.expect("token was None somehow");
+                let Some(token) = peek_next_token(&buf) else { break };
                 if token == "}" { break }
 
                 let ident = token.clone();
 
                 buf = eat_token(&buf, &token);
-                let opt_token = peek_next_token(&buf);
-                if opt_token.is_none() { break }
-
-                let token = opt_token.expect("token was None somehow");
+                let Some(token) = peek_next_token(&buf) else { break };
                 if token == "}" { break }
                 if token != ":" { break }
 
@@ -408,7 +400,7 @@ pub fn parse_reference(content: String) -> Result<ReferenceDefinition> {
                 }
             }
 
-            peek_next_token(&buf).expect("expected '}'");
+            peek_next_token(&buf).context("expected '}'")?;
             // content = eat_token(&buf, "}");
             Ok(reference)
         } else { return Err(anyhow!("expected '{{'")) }
@@ -422,25 +414,21 @@ pub fn to_citation(value: &ReferenceDefinition) -> String {
     match value.authors.len() {
         1 => {
             let author = value.authors[0].clone();
-            let split_name : Vec<_> = author.split_whitespace().collect();
-            result += split_name.last().unwrap();
+            result += author.split_whitespace().last().unwrap_or_default();
             result += ", "; 
         },
         2 => {
             let first_author  = value.authors[0].clone();
             let second_author = value.authors[1].clone();
-            let first_author_split_name : Vec<_>  = first_author.split_whitespace().collect();
-            let second_author_split_name : Vec<_> = second_author.split_whitespace().collect();
-            result += first_author_split_name.last().unwrap();
+            result += first_author.split_whitespace().last().unwrap_or_default();
             result += " & "; 
-            result += second_author_split_name.last().unwrap();
+            result += second_author.split_whitespace().last().unwrap_or_default();
             result += ", "; 
 
         },
         _ => {
             let author = value.authors[0].clone();
-            let split_name : Vec<_> = author.split_whitespace().collect();
-            result += split_name.last().unwrap();
+            result += author.split_whitespace().last().unwrap_or_default();
             result += " et al., "; 
         },
     }
@@ -456,14 +444,15 @@ pub fn to_citation(value: &ReferenceDefinition) -> String {
 
 pub fn bibliograph_name(name: &String) -> String {
     let split_name : Vec<_> = name.split_whitespace().collect();
-    let last_name = *split_name.last().unwrap();
+    let Some(last_name) = split_name.last() else { return name.clone() };
     let rest_of_name = &split_name[0..split_name.len() - 1];
 
     let mut result = last_name.to_string();
     result += ", ";
 
     for (n, name) in rest_of_name.iter().enumerate() {
-        result.push(name.chars().nth(0).unwrap());
+        let Some(initial) = name.chars().nth(0) else { continue };
+        result.push(initial);
         result.push('.');
         if n != rest_of_name.len() - 1 {
             result.push(' ');
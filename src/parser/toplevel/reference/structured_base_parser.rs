@@ -60,8 +60,10 @@ pub fn eat_token(content: &str, token: &str) -> String {
 pub fn parse_value(text: &str) -> (String, Vec<String>) {
     let opt_first_token = peek_next_token(text);
 
-    if opt_first_token.is_none() || opt_first_token.ne(&Some("[".to_string())) {
-        let token = opt_first_token.unwrap();
+    let Some(first_token) = opt_first_token else { return (text.to_string(), vec![]) };
+
+    if first_token != "[" {
+        let token = first_token;
         if token.starts_with('"') && token.ends_with('"') {
             let buf = eat_token(text, token.as_str());
             let text = &token[1..];
@@ -1,14 +1,58 @@
 
+use std::borrow::Cow;
+use serde::{Deserialize, Serialize};
 use super::reference::ReferenceDefinition;
+use super::super::source::Spanned;
+
+// text in here borrows from the file being parsed wherever it can, it's only owned when
+// it had to be pieced together (like the dedented body of a fact box)
+
+// the blocks indented under a list item, after the line with its marker
+pub type ListBody<'a> = Vec<Spanned<TopLevelSyntax<'a>>>;
 
 #[derive(Debug, PartialEq)]
-pub enum ListItem {
-    InnerList(Vec<ListItem>),
-    Unordered(String),                   // -
-    Numbered(usize, String),             // 1.
-    Alphabetical(String, String),        // a.
-    NumberedRounded(usize, String),      // 1)
-    AlphabeticalRounded(String, String), // a)
+pub enum ListItem<'a> {
+    InnerList(Vec<ListItem<'a>>),
+    Unordered(Cow<'a, str>, ListBody<'a>),                         // -
+    Numbered(usize, Cow<'a, str>, ListBody<'a>),                   // 1.
+    Alphabetical(Cow<'a, str>, Cow<'a, str>, ListBody<'a>),        // a.
+    NumberedRounded(usize, Cow<'a, str>, ListBody<'a>),            // 1)
+    AlphabeticalRounded(Cow<'a, str>, Cow<'a, str>, ListBody<'a>), // a)
+    Task{checked: bool, text: Cow<'a, str>, body: ListBody<'a>},   // - [ ] and - [x]
+}
+
+impl<'a> ListItem<'a> {
+    pub fn into_owned(self) -> ListItem<'static> {
+        match self {
+            ListItem::InnerList(list)                       => ListItem::InnerList(list.into_iter().map(ListItem::into_owned).collect()),
+            ListItem::Unordered(text, body)                 => ListItem::Unordered(owned(text), owned_body(body)),
+            ListItem::Numbered(number, text, body)          => ListItem::Numbered(number, owned(text), owned_body(body)),
+            ListItem::Alphabetical(id, text, body)          => ListItem::Alphabetical(owned(id), owned(text), owned_body(body)),
+            ListItem::NumberedRounded(number, text, body)   => ListItem::NumberedRounded(number, owned(text), owned_body(body)),
+            ListItem::AlphabeticalRounded(id, text, body)   => ListItem::AlphabeticalRounded(owned(id), owned(text), owned_body(body)),
+            ListItem::Task { checked, text, body }          => ListItem::Task { checked, text: owned(text), body: owned_body(body) },
+        }
+    }
+
+    pub fn body_mut(&mut self) -> Option<&mut ListBody<'a>> {
+        match self {
+            ListItem::InnerList(_) => None,
+            ListItem::Unordered(_, body)              |
+            ListItem::Numbered(_, _, body)            |
+            ListItem::Alphabetical(_, _, body)        |
+            ListItem::NumberedRounded(_, _, body)     |
+            ListItem::AlphabeticalRounded(_, _, body) |
+            ListItem::Task { body, .. }               => Some(body),
+        }
+    }
+}
+
+fn owned_body(body: ListBody<'_>) -> ListBody<'static> {
+    body.into_iter().map(|x| Spanned { node: x.node.into_owned(), span: x.span }).collect()
+}
+
+fn owned(text: Cow<'_, str>) -> Cow<'static, str> {
+    Cow::Owned(text.into_owned())
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -22,7 +66,7 @@ impl ImageSize {
     pub fn from_width_and_height(width: Option<String>, height: Option<String>) -> Self {
         match (width, height) {
             (Some(x), Some(y)) => Self::Double(x, y),
-            (None, Some(y)) => Self::Double("0".to_string(), y),
+            (None, Some(y)) => Self::Double("auto".to_string(), y),
             (Some(x), None) => Self::Single(x),
             (None, None) => Self::None,
         }
@@ -30,44 +74,121 @@ impl ImageSize {
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct Image {
-    pub image: String,
-    pub alt: String,
+pub struct Image<'a> {
+    pub image: Cow<'a, str>,
+    pub alt: Cow<'a, str>,
     pub size: ImageSize
 }
 
-impl Image {
-    pub fn new(image: String) -> Self {
+impl<'a> Image<'a> {
+    pub fn new(image: Cow<'a, str>) -> Self {
         Self { image, alt: "".into(), size: ImageSize::None }
     }
     
-    pub fn new_with_alt(image: String, alt: String) -> Self {
+    pub fn new_with_alt(image: Cow<'a, str>, alt: Cow<'a, str>) -> Self {
         Self { image, alt, size: ImageSize::None }
     }
     
-    pub fn new_with_size(image: String, size: ImageSize) -> Self {
+    pub fn new_with_size(image: Cow<'a, str>, size: ImageSize) -> Self {
         Self { image, alt: "".into(), size }
     }
     
-    pub fn new_with_alt_and_size(image: String, alt: String, size: ImageSize) -> Self {
+    pub fn new_with_alt_and_size(image: Cow<'a, str>, alt: Cow<'a, str>, size: ImageSize) -> Self {
         Self { image, alt, size }
     }
+
+    pub fn into_owned(self) -> Image<'static> {
+        Image { image: owned(self.image), alt: owned(self.alt), size: self.size }
+    }
 }
 
 
+// how the cells of a table column are aligned, from the `:` in its `---` row
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
 #[derive(Debug, PartialEq)]
-pub enum TopLevelSyntax {
+pub enum TopLevelSyntax<'a> {
     FrontMatter(super::super::frontmatter::Frontmatter),
-    CodeBlock(String),
-    Heading(String, usize),
-    Image(Image),
-    List(Vec<ListItem>),
-    Paragraph(String),
-    Quote(Vec<String>),
+    // `info` is whatever follows the opening fence, like "rust title=\"main.rs\" {3-5}"
+    CodeBlock{info: Cow<'a, str>, code: Cow<'a, str>},
+    MathBlock(Cow<'a, str>),
+    Heading(Cow<'a, str>, usize),
+    Image(Image<'a>),
+    List(Vec<ListItem<'a>>),
+    Paragraph(Cow<'a, str>),
+    // `attribution` is the text of a last line like "> — Author"
+    Quote{body: Vec<Spanned<TopLevelSyntax<'a>>>, attribution: Option<Cow<'a, str>>},
+    // "> [!kind]- title", the `-` makes it start collapsed
+    Callout{kind: Cow<'a, str>, title: Cow<'a, str>, collapsed: bool, body: Vec<Spanned<TopLevelSyntax<'a>>>},
     ReferenceDefinition(ReferenceDefinition),
-    NoteDefinition{id: String, text: String},
-    TOC(String),
+    NoteDefinition{id: Cow<'a, str>, text: Cow<'a, str>},
+    TOC(Cow<'a, str>),
     PageBreak,
-    FactBox{title: String, body: Vec<TopLevelSyntax>},
+    FactBox{title: Cow<'a, str>, body: Vec<Spanned<TopLevelSyntax<'a>>>},
+    Table{header: Vec<Cow<'a, str>>, alignments: Vec<Alignment>, rows: Vec<Vec<Cow<'a, str>>>},
+    // every term with the ": definition" lines under it
+    DefinitionList(Vec<(Cow<'a, str>, Vec<Cow<'a, str>>)>),
+    // "![[other-post.md#Some Heading]]", these are replaced by what they include so the only ones
+    // left after `toplevel_parse` are the ones with an `error`
+    Transclusion{path: Cow<'a, str>, section: Option<Cow<'a, str>>, error: Option<TransclusionError>},
 //  EmbeddedLink(String, String)
 }
+
+// why a transclusion couldn't be included
+#[derive(Debug, PartialEq, Clone)]
+pub enum TransclusionError {
+    Unreadable(String),
+    MissingSection,
+    // every file in the cycle, starting and ending with the one that includes itself
+    Cycle(Vec<String>),
+}
+
+impl TopLevelSyntax<'_> {
+    /// detaches the syntax from the text it was parsed from
+    pub fn into_owned(self) -> TopLevelSyntax<'static> {
+        match self {
+            TopLevelSyntax::FrontMatter(frontmatter)   => TopLevelSyntax::FrontMatter(frontmatter),
+            TopLevelSyntax::CodeBlock { info, code }   => TopLevelSyntax::CodeBlock { info: owned(info), code: owned(code) },
+            TopLevelSyntax::MathBlock(tex)             => TopLevelSyntax::MathBlock(owned(tex)),
+            TopLevelSyntax::Heading(text, level)       => TopLevelSyntax::Heading(owned(text), level),
+            TopLevelSyntax::Image(image)               => TopLevelSyntax::Image(image.into_owned()),
+            TopLevelSyntax::List(list)                 => TopLevelSyntax::List(list.into_iter().map(ListItem::into_owned).collect()),
+            TopLevelSyntax::Paragraph(text)            => TopLevelSyntax::Paragraph(owned(text)),
+            TopLevelSyntax::Quote { body, attribution } => TopLevelSyntax::Quote { body: owned_body(body), attribution: attribution.map(owned) },
+            TopLevelSyntax::Callout { kind, title, collapsed, body } => TopLevelSyntax::Callout {
+                kind: owned(kind),
+                title: owned(title),
+                collapsed,
+                body: owned_body(body),
+            },
+            TopLevelSyntax::ReferenceDefinition(def)   => TopLevelSyntax::ReferenceDefinition(def),
+            TopLevelSyntax::NoteDefinition { id, text } => TopLevelSyntax::NoteDefinition { id: owned(id), text: owned(text) },
+            TopLevelSyntax::TOC(title)                 => TopLevelSyntax::TOC(owned(title)),
+            TopLevelSyntax::PageBreak                  => TopLevelSyntax::PageBreak,
+            TopLevelSyntax::Table { header, alignments, rows } => TopLevelSyntax::Table {
+                header: header.into_iter().map(owned).collect(),
+                alignments,
+                rows: rows.into_iter().map(|row| row.into_iter().map(owned).collect()).collect(),
+            },
+            TopLevelSyntax::DefinitionList(entries)    => TopLevelSyntax::DefinitionList(
+                entries.into_iter().map(|(term, definitions)| (owned(term), definitions.into_iter().map(owned).collect())).collect()
+            ),
+            TopLevelSyntax::Transclusion { path, section, error } => TopLevelSyntax::Transclusion {
+                path: owned(path),
+                section: section.map(owned),
+                error,
+            },
+            TopLevelSyntax::FactBox { title, body }    => TopLevelSyntax::FactBox {
+                title: owned(title),
+                body: owned_body(body),
+            },
+        }
+    }
+}
@@ -0,0 +1,96 @@
+// "![[other-post.md#Some Heading]]" pulls another document, or one section of it, into the one
+// being parsed. it's done on the syntax so whatever gets included goes through the rest of the
+// parser as if it had been written where it was embedded
+
+use std::path::{Path, PathBuf};
+use super::parser::{for_each_body, parse_syntax};
+use super::structs::*;
+use super::super::inline::parse_inline;
+use super::super::parser_util::generate_id;
+use super::super::source::{SourceSpan, Spanned};
+
+const TRANSCLUSION_EXTENSIONS: [&str; 2] = ["md", "pmd"];
+
+/// whether the path in `![[...]]` is a document rather than an image, "post.md#Heading" counts
+pub fn is_transclusion(path: &str) -> bool {
+    let path = path.split('#').next().unwrap_or(path).trim();
+    let Some((_, extension)) = path.rsplit_once('.') else { return false };
+    TRANSCLUSION_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+}
+
+/// replaces every transclusion in `syntax` with what it includes, relative paths start in
+/// `directory`. `included` is every file we're currently inside of, so a file including itself
+/// somewhere down the line is left as a `TransclusionError::Cycle`
+pub(super) fn transclude<'a>(syntax: &mut Vec<Spanned<TopLevelSyntax<'a>>>, directory: &Path, included: &mut Vec<PathBuf>) {
+    let mut index = 0;
+    while index < syntax.len() {
+        let Spanned { node, span } = &mut syntax[index];
+        let TopLevelSyntax::Transclusion { path, section, error: error @ None } = node else {
+            for_each_body(node, &mut |body| transclude(body, directory, included));
+            index += 1;
+            continue;
+        };
+        match include(path, section.as_deref(), directory, included) {
+            Ok(mut body) => {
+                // the included text isn't in this file, so anything in it points at the embed
+                pin(&mut body, *span);
+                let length = body.len();
+                syntax.splice(index..index + 1, body);
+                index += length;
+            },
+            Err(reason) => {
+                *error = Some(reason);
+                index += 1;
+            },
+        }
+    }
+}
+
+fn include(path: &str, section: Option<&str>, directory: &Path, included: &mut Vec<PathBuf>) -> Result<Vec<Spanned<TopLevelSyntax<'static>>>, TransclusionError> {
+    let file = directory.join(path).canonicalize().map_err(|error| TransclusionError::Unreadable(error.to_string()))?;
+    if let Some(start) = included.iter().position(|x| *x == file) {
+        let cycle = included[start..].iter().chain(std::iter::once(&file)).map(|x| x.display().to_string()).collect();
+        return Err(TransclusionError::Cycle(cycle));
+    }
+    let text = std::fs::read_to_string(&file).map_err(|error| TransclusionError::Unreadable(error.to_string()))?;
+    let syntax = parse_syntax(&text).map_err(|error| TransclusionError::Unreadable(format!("{error:#}")))?;
+    let mut syntax: Vec<_> = syntax.into_iter()
+        .map(|Spanned { node, span }| Spanned { node: node.into_owned(), span })
+        .collect();
+
+    included.push(file.clone());
+    transclude(&mut syntax, file.parent().unwrap_or(directory), included);
+    included.pop();
+
+    let range = match section {
+        Some(section) => find_section(&syntax, section).ok_or(TransclusionError::MissingSection)?,
+        None => 0..syntax.len(),
+    };
+    // notes and references are kept wherever they are, the section could be using any of them
+    Ok(syntax.into_iter().enumerate().filter(|(index, x)| match &x.node {
+        TopLevelSyntax::FrontMatter(_) | TopLevelSyntax::TOC(_) => false,
+        TopLevelSyntax::NoteDefinition { .. } | TopLevelSyntax::ReferenceDefinition(_) => true,
+        _ => range.contains(index),
+    }).map(|(_, x)| x).collect())
+}
+
+// a section runs from its heading up to the next heading that isn't under it, `section` can be
+// the text of the heading or its id
+fn find_section(syntax: &[Spanned<TopLevelSyntax>], section: &str) -> Option<std::ops::Range<usize>> {
+    let wanted = generate_id(section)?;
+    let (start, level) = syntax.iter().enumerate().find_map(|(index, x)| match &x.node {
+        TopLevelSyntax::Heading(text, level) => parse_inline(text).ok().filter(|(_, id)| *id == wanted).map(|_| (index, *level)),
+        _ => None,
+    })?;
+    let end = syntax[start + 1..].iter()
+        .position(|x| matches!(x.node, TopLevelSyntax::Heading(_, other) if other <= level))
+        .map_or(syntax.len(), |length| start + 1 + length);
+    Some(start..end)
+}
+
+fn pin(syntax: &mut Vec<Spanned<TopLevelSyntax<'_>>>, span: SourceSpan) {
+    for elem in syntax {
+        elem.span = span;
+        for_each_body(&mut elem.node, &mut |body| pin(body, span));
+    }
+}
@@ -1,4 +1 @@
 pub mod ordered_map;
-#[macro_use]
-pub mod log;
-
@@ -2,6 +2,10 @@ use std::borrow::Borrow;
 use std::collections::hash_map::Keys;
 use std::collections::HashMap;
 use std::hash::Hash;
+use std::marker::PhantomData;
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 
 #[derive(Debug, PartialEq, Clone)]
@@ -17,8 +21,11 @@ impl<K: Eq + Clone + Hash, V> OrderedMap<K, V>
         Self { map: HashMap::new(), order: vec![] }
     }
 
+    /// a key that's already in the map keeps its place and gets the new value
     pub fn insert(&mut self, key: K, value: V) {
-        self.order.push(key.clone());
+        if !self.map.contains_key(&key) {
+            self.order.push(key.clone());
+        }
         self.map.insert(key, value);
     }
 
@@ -57,6 +64,10 @@ impl<K: Eq + Clone + Hash, V> OrderedMap<K, V>
     pub fn keys(&self) -> Keys<'_, K, V> {
         self.map.keys()
     } 
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.into_iter()
+    }
 }
 
 pub struct Iter<'a, K: Eq + Clone + Hash, V> {
@@ -125,3 +136,62 @@ impl<'a, K: Eq + Clone + Hash, V> IntoIterator for &'a mut OrderedMap<K, V> {
         Self::IntoIter { map: self, index: 0 }
     }
 }
+
+// serialized as a plain map, written and read back in insertion order
+impl<K: Eq + Clone + Hash + Serialize, V: Serialize> Serialize for OrderedMap<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.order.len()))?;
+        for (key, value) in self {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+struct OrderedMapVisitor<K, V>(PhantomData<(K, V)>);
+
+impl<'de, K, V> Visitor<'de> for OrderedMapVisitor<K, V>
+    where
+        K: Eq + Clone + Hash + Deserialize<'de>,
+        V: Deserialize<'de>,
+{
+    type Value = OrderedMap<K, V>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut map = OrderedMap::new();
+        while let Some((key, value)) = access.next_entry()? {
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for OrderedMap<K, V>
+    where
+        K: Eq + Clone + Hash + Deserialize<'de>,
+        V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(OrderedMapVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_twice() {
+        let mut map = OrderedMap::new();
+        map.insert("first", 1);
+        map.insert("second", 2);
+        map.insert("first", 3);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&"first", &3), (&"second", &2)]);
+        assert_eq!(serde_json::to_string(&map).unwrap(), r#"{"first":3,"second":2}"#);
+    }
+}
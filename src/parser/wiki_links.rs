@@ -0,0 +1,168 @@
+// `[[post-name]]` links to another post on the blog by its file name. a single post can't know
+// where those go, so they're resolved once every post is parsed, which is also when we find out
+// who links to who
+
+use super::diagnostic::Diagnostic;
+use super::source::SourceSpan;
+use super::parser_util::generate_id;
+use super::structs::*;
+use super::util::ordered_map::OrderedMap;
+
+const POST_EXTENSIONS: [&str; 2] = ["md", "pmd"];
+
+fn extension(path: &str) -> Option<&str> {
+    let (_, extension) = path.rsplit_once('.')?;
+    (!extension.is_empty() && extension.chars().all(|c| c.is_ascii_alphanumeric())).then_some(extension)
+}
+
+/// whether the text between `[[` and `]]` is a post rather than an image or a url, a path is
+/// only a post when it has no extension or ends in .md or .pmd
+pub fn is_wiki_link(text: &str) -> bool {
+    let target = text.split('|').next().unwrap_or(text).trim();
+    let post = target.split('#').next().unwrap_or(target);
+    if target.is_empty() || target.contains("://") || target.starts_with(['^', '£', '@']) {
+        return false;
+    }
+    extension(post).is_none_or(|extension| POST_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// where the html of a post ends up, the same url its `og:url` points at
+pub fn post_url(meta: &MetaData, name: &str) -> String {
+    format!("{}/{}/{name}.html", meta.url, meta.blog_dir)
+}
+
+// a post is found by its name first and then by its title, "[[My Post]]" finds "my-post.pmd"
+// as long as that's what it's called
+fn find_post(documents: &[(String, AssDownDocument)], post: &str) -> Option<usize> {
+    let post = post.trim();
+    let post = match extension(post) {
+        Some(extension) if POST_EXTENSIONS.contains(&extension.to_lowercase().as_str()) => &post[..post.len() - extension.len() - 1],
+        _ => post,
+    };
+    let id = generate_id(post).map(|id| id.to_lowercase());
+    documents.iter().position(|(name, _)| name == post)
+        .or_else(|| documents.iter().position(|(name, _)| name.eq_ignore_ascii_case(post)))
+        .or_else(|| documents.iter().position(|(_, document)| id.is_some() && generate_id(&document.meta.title).map(|x| x.to_lowercase()) == id))
+}
+
+// every wiki link in a post and where it is, or the block it's in when the link couldn't be found
+// in the source. notes aren't blocks so a link in one of those has nothing to fall back on
+fn wiki_links(document: &AssDownDocument) -> Vec<(String, Option<SourceSpan>)> {
+    let mut targets = vec![];
+    let mut collect = |element: &Element, span: Option<SourceSpan>| element.walk_spanned(span.unwrap_or_default(), &mut |element, block| {
+        if let Element::WikiLink(link) = element {
+            let span = if link.span.is_located() { Some(link.span) } else { span.map(|_| block) };
+            targets.push((link.target.clone(), span));
+        }
+    });
+    for (element, _, span) in &document.body {
+        collect(element, Some(*span));
+        if let Element::FactBox(factbox) = element {
+            factbox.notes.iter().for_each(|(_, (note, _))| collect(note, Some(*span)));
+        }
+    }
+    document.notes.iter().for_each(|(_, note)| collect(note, None));
+    targets
+}
+
+/// resolves the wiki links of every post against the others and fills in their backlinks.
+/// `documents` pairs each post with its name, the file name without an extension, and what
+/// comes back are the broken links of every post in the same order
+pub fn link_documents(documents: &mut [(String, AssDownDocument)]) -> Vec<Vec<Diagnostic>> {
+    let urls: Vec<String> = documents.iter().map(|(name, document)| post_url(&document.meta, name)).collect();
+    let mut diagnostics = vec![vec![]; documents.len()];
+    let mut resolved = vec![OrderedMap::new(); documents.len()];
+    let mut linked_from: Vec<Vec<usize>> = vec![vec![]; documents.len()];
+
+    for (index, (_, document)) in documents.iter().enumerate() {
+        for (target, span) in wiki_links(document) {
+            let (post, section) = target.split_once('#').unwrap_or((&target, ""));
+            // "[[#Some Heading]]" is a section of the post it's in
+            let other = if post.trim().is_empty() { Some(index) } else { find_post(documents, post) };
+            let Some(other) = other else {
+                let diagnostic = Diagnostic::warning("broken-wiki-link", format!("[[{target}]] doesn't point at any post"))
+                    .with_help("wiki links use the file name of a post without its extension, like [[other-post]]");
+                diagnostics[index].push(match span {
+                    Some(span) => diagnostic.with_span(span),
+                    None => diagnostic,
+                });
+                continue;
+            };
+            // a post linking the same place twice only needs it resolved once
+            if resolved[index].contains_key(&target) { continue }
+            let url = match generate_id(section) {
+                Some(id) => format!("{}#{id}", urls[other]),
+                None => urls[other].clone(),
+            };
+            resolved[index].insert(target, url);
+            if other != index && !linked_from[other].contains(&index) {
+                linked_from[other].push(index);
+            }
+        }
+    }
+
+    let backlinks: Vec<Vec<Backlink>> = linked_from.iter().map(|posts| posts.iter().map(|&other| Backlink {
+        title: documents[other].1.meta.title.clone(),
+        url: urls[other].clone(),
+    }).collect()).collect();
+    for (((_, document), wiki_links), backlinks) in documents.iter_mut().zip(resolved).zip(backlinks) {
+        document.wiki_links = wiki_links;
+        document.backlinks = backlinks;
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse;
+
+    #[test]
+    fn wiki_links() {
+        assert!(is_wiki_link("post-name"));
+        assert!(is_wiki_link("post-name|some label"));
+        assert!(is_wiki_link("other-post.pmd#Some Heading"));
+        assert!(is_wiki_link("My Post"));
+        assert!(!is_wiki_link("path/to/image.png"));
+        assert!(!is_wiki_link("image.png|600x400"));
+        assert!(!is_wiki_link("https://example.link"));
+        assert!(!is_wiki_link("^note"));
+    }
+
+    #[test]
+    fn linking() {
+        let post = |title: &str, body: &str| {
+            let text = format!("---\ntitle: {title}\ndate: 2024-01-01\nurl: https://example.com\n---\n{body}\n");
+            parse(&text, None).unwrap().0
+        };
+        let mut documents = vec![
+            ("first".to_string(), post("First", "see [[second]] and [[Second Post#Some Heading|the heading]], [[second]] again")),
+            ("second".to_string(), post("Second Post", "# Some Heading\n\nback to [[first.pmd]], [[#Some Heading]] and [[nowhere]]")),
+            ("third".to_string(), post("Third", "[[second]] is good too")),
+            ("fourth".to_string(), post("Fourth", "only a note[^1]\n\n[^1]: from [[third]] and [[gone]]")),
+        ];
+        let diagnostics = link_documents(&mut documents);
+
+        let first = &documents[0].1;
+        assert_eq!(first.wiki_links.get("second").unwrap(), "https://example.com/blog/second.html");
+        assert_eq!(first.wiki_links.get("Second Post#Some Heading").unwrap(), "https://example.com/blog/second.html#Some-Heading");
+        assert_eq!(first.wiki_links.iter().count(), 2);
+        assert_eq!(first.backlinks, [Backlink { title: "Second Post".into(), url: "https://example.com/blog/second.html".into() }]);
+
+        let second = &documents[1].1;
+        assert_eq!(second.wiki_links.get("#Some Heading").unwrap(), "https://example.com/blog/second.html#Some-Heading");
+        let titles: Vec<&str> = second.backlinks.iter().map(|x| x.title.as_str()).collect();
+        assert_eq!(titles, ["First", "Third"]);
+
+        assert!(diagnostics[0].is_empty() && diagnostics[2].is_empty());
+        assert_eq!(diagnostics[1].len(), 1);
+        assert_eq!(diagnostics[1][0].code, "broken-wiki-link");
+
+        // links in footnotes count too
+        let third = &documents[2].1;
+        assert_eq!(third.backlinks, [Backlink { title: "Fourth".into(), url: "https://example.com/blog/fourth.html".into() }]);
+        assert_eq!(documents[3].1.wiki_links.get("third").unwrap(), "https://example.com/blog/third.html");
+        assert_eq!(diagnostics[3].len(), 1);
+        assert!(diagnostics[3][0].message.contains("[[gone]]"));
+    }
+}
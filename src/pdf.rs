@@ -1,25 +1,489 @@
+use std::io::Write;
 
-use crate::*;
-#[cfg(feature = "mock_pdf")]
-use anyhow::anyhow;
-#[cfg(all(feature = "pdf", not(feature = "mock_pdf")))]
-use headless_chrome::Browser;
+// a small pdf writer, it only uses the standard 14 fonts so we never have to embed anything
+// which keeps the output small and means we don't need a browser to print a pdf for us
 
-#[cfg(all(feature = "pdf", not(feature = "mock_pdf")))]
-pub fn build_pdf(path: &str) -> Result<String> {
-    let browser = Browser::default()?;
-    let tab = browser.new_tab()?;
-    
-    tab.navigate_to(format!("file:{path}").as_str())?;
-    
-    tab.wait_until_navigated()?;
-    
-    Ok(unsafe { String::from_utf8_unchecked(tab.print_to_pdf(None)?) })
+pub const PAGE_WIDTH:  f32 = 595.28; // A4
+pub const PAGE_HEIGHT: f32 = 841.89;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FontFamily {
+    Helvetica,
+    Times,
+    Courier,
+    /// greek letters and mathematical symbols, it only comes in one style
+    Symbol,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Font {
+    pub family: FontFamily,
+    pub style:  FontStyle,
+}
+
+const FAMILIES: [FontFamily; 3] = [FontFamily::Helvetica, FontFamily::Times, FontFamily::Courier];
+const STYLES: [FontStyle; 4] = [FontStyle::Regular, FontStyle::Bold, FontStyle::Italic, FontStyle::BoldItalic];
+const FIRST_FONT_OBJECT: usize = 4;
+
+impl FontFamily {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.contains("courier") || name.contains("mono") {
+            Some(Self::Courier)
+        } else if name.contains("times") || (name.contains("serif") && !name.contains("sans")) {
+            Some(Self::Times)
+        } else if name.contains("helvetica") || name.contains("arial") || name.contains("sans") {
+            Some(Self::Helvetica)
+        } else {
+            None
+        }
+    }
+}
+
+impl FontStyle {
+    pub fn new(bold: bool, italic: bool) -> Self {
+        match (bold, italic) {
+            (false, false) => Self::Regular,
+            (true,  false) => Self::Bold,
+            (false, true)  => Self::Italic,
+            (true,  true)  => Self::BoldItalic,
+        }
+    }
+
+    pub fn is_bold(&self) -> bool {
+        matches!(self, Self::Bold | Self::BoldItalic)
+    }
+
+    pub fn is_italic(&self) -> bool {
+        matches!(self, Self::Italic | Self::BoldItalic)
+    }
+}
+
+impl Font {
+    pub fn new(family: FontFamily, style: FontStyle) -> Self {
+        Self { family, style }
+    }
+
+    fn base_name(&self) -> &'static str {
+        match (self.family, self.style) {
+            (FontFamily::Helvetica, FontStyle::Regular)    => "Helvetica",
+            (FontFamily::Helvetica, FontStyle::Bold)       => "Helvetica-Bold",
+            (FontFamily::Helvetica, FontStyle::Italic)     => "Helvetica-Oblique",
+            (FontFamily::Helvetica, FontStyle::BoldItalic) => "Helvetica-BoldOblique",
+            (FontFamily::Times,     FontStyle::Regular)    => "Times-Roman",
+            (FontFamily::Times,     FontStyle::Bold)       => "Times-Bold",
+            (FontFamily::Times,     FontStyle::Italic)     => "Times-Italic",
+            (FontFamily::Times,     FontStyle::BoldItalic) => "Times-BoldItalic",
+            (FontFamily::Courier,   FontStyle::Regular)    => "Courier",
+            (FontFamily::Courier,   FontStyle::Bold)       => "Courier-Bold",
+            (FontFamily::Courier,   FontStyle::Italic)     => "Courier-Oblique",
+            (FontFamily::Courier,   FontStyle::BoldItalic) => "Courier-BoldOblique",
+            (FontFamily::Symbol,    _)                     => "Symbol",
+        }
+    }
+
+    fn resource_index(&self) -> usize {
+        if self.family == FontFamily::Symbol {
+            return FAMILIES.len() * STYLES.len();
+        }
+        let family = FAMILIES.iter().position(|x| *x == self.family).unwrap_or(0);
+        let style  = STYLES.iter().position(|x| *x == self.style).unwrap_or(0);
+        family * STYLES.len() + style
+    }
+
+    // widths are in 1/1000 of the font size, taken from the adobe font metrics
+    // the oblique/italic cuts are close enough to the upright ones that we reuse their widths
+    pub fn char_width(&self, c: char) -> f32 {
+        let table = match (self.family, self.style.is_bold()) {
+            (FontFamily::Courier, _)       => return 600.0,
+            (FontFamily::Symbol, _)        => return symbol_char(c).map(|(_, width)| width as f32).unwrap_or(500.0),
+            (FontFamily::Helvetica, false) => &HELVETICA_WIDTHS,
+            (FontFamily::Helvetica, true)  => &HELVETICA_BOLD_WIDTHS,
+            (FontFamily::Times, false)     => &TIMES_WIDTHS,
+            (FontFamily::Times, true)      => &TIMES_BOLD_WIDTHS,
+        };
+        let code = c as u32;
+        if (32..127).contains(&code) {
+            return table[(code - 32) as usize] as f32;
+        }
+        match c {
+            '\u{2018}' | '\u{2019}' => table[(b'\'' - 32) as usize] as f32,
+            '\u{201C}' | '\u{201D}' => table[(b'"' - 32) as usize] as f32,
+            '\u{2013}' => table[(b'-' - 32) as usize] as f32 * 1.5,
+            '\u{2014}' => 1000.0,
+            '\u{2026}' => 1000.0,
+            '\u{2022}' => 350.0,
+            _ => table[(b'n' - 32) as usize] as f32,
+        }
+    }
+
+    pub fn text_width(&self, text: &str, size: f32) -> f32 {
+        text.chars().map(|c| self.char_width(c)).sum::<f32>() * size / 1000.0
+    }
 }
 
-#[cfg(feature = "mock_pdf")]
-pub fn build_pdf(_: &str) -> Result<String> {
-    Err(anyhow!("this pdf was mocked, this is a message to the user to check the Cargo.toml, if this is intended as debug check the tmp.html file for the actual 'pdf'"))
+#[derive(Debug, PartialEq, Clone)]
+pub struct LinkArea {
+    pub rect: [f32; 4],
+    pub uri:  String,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct Page {
+    pub content: Vec<u8>,
+    pub links:   Vec<LinkArea>,
+}
+
+impl Page {
+    pub fn new() -> Self {
+        Self { content: Vec::new(), links: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    pub fn text(&mut self, x: f32, y: f32, font: Font, size: f32, text: &str) {
+        self.text_with_rise(x, y, font, size, 0.0, text);
+    }
+
+    pub fn text_with_rise(&mut self, x: f32, y: f32, font: Font, size: f32, rise: f32, text: &str) {
+        let index = font.resource_index();
+        let _ = write!(self.content, "BT /F{index} {size:.2} Tf {rise:.2} Ts {x:.2} {y:.2} Td (");
+        let encoded = match font.family {
+            FontFamily::Symbol => encode_symbol(text),
+            _ => encode_text(text),
+        };
+        self.content.extend(escape_string(&encoded));
+        self.content.extend(b") Tj ET\n");
+    }
+
+    pub fn set_color(&mut self, r: f32, g: f32, b: f32) {
+        let _ = writeln!(self.content, "{r:.3} {g:.3} {b:.3} rg");
+    }
+
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, gray: f32) {
+        let _ = writeln!(self.content, "q {gray:.3} g {x:.2} {y:.2} {width:.2} {height:.2} re f Q");
+    }
+
+    pub fn fill_rect_rgb(&mut self, x: f32, y: f32, width: f32, height: f32, (r, g, b): (f32, f32, f32)) {
+        let _ = writeln!(self.content, "q {r:.3} {g:.3} {b:.3} rg {x:.2} {y:.2} {width:.2} {height:.2} re f Q");
+    }
+
+    pub fn stroke_rect(&mut self, x: f32, y: f32, width: f32, height: f32, line_width: f32, gray: f32) {
+        let _ = writeln!(self.content, "q {line_width:.2} w {gray:.3} G {x:.2} {y:.2} {width:.2} {height:.2} re S Q");
+    }
 
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32), line_width: f32, gray: f32) {
+        let _ = writeln!(self.content, "q {line_width:.2} w {gray:.3} G {:.2} {:.2} m {:.2} {:.2} l S Q", from.0, from.1, to.0, to.1);
+    }
+
+    pub fn link(&mut self, x: f32, y: f32, width: f32, height: f32, uri: &str) {
+        self.links.push(LinkArea { rect: [x, y, x + width, y + height], uri: uri.to_string() });
+    }
+}
+
+// pdf strings use WinAnsiEncoding with the standard fonts, anything we can't map turns into '?'
+pub fn encode_text(text: &str) -> Vec<u8> {
+    text.chars().map(|c| match c {
+        '\t' => b' ',
+        ' '..='~' => c as u8,
+        '\u{00A0}'..='\u{00FF}' => c as u32 as u8,
+        '\u{20AC}' => 0x80,
+        '\u{2026}' => 0x85,
+        '\u{2018}' => 0x91,
+        '\u{2019}' => 0x92,
+        '\u{201C}' => 0x93,
+        '\u{201D}' => 0x94,
+        '\u{2022}' => 0x95,
+        '\u{2013}' => 0x96,
+        '\u{2014}' => 0x97,
+        _ => b'?',
+    }).collect()
+}
+
+// the symbol font has an encoding of its own, these are the codes and widths from its font metrics
+pub fn symbol_char(c: char) -> Option<(u8, u16)> {
+    const GREEK: &str = "ΑΒΧΔΕΦΓΗΙϑΚΛΜΝΟΠΘΡΣΤΥςΩΞΨΖ";
+    const GREEK_WIDTHS: [u16; 26] = [722, 667, 722, 612, 611, 763, 603, 722, 333, 631, 722, 686, 889, 722, 722, 768, 741, 556, 592, 611, 690, 439, 768, 645, 795, 611];
+    const GREEK_LOWER: &str = "αβχδεφγηιϕκλμνοπθρστυϖωξψζ";
+    const GREEK_LOWER_WIDTHS: [u16; 26] = [631, 549, 549, 494, 439, 521, 411, 603, 329, 603, 549, 549, 576, 521, 549, 549, 521, 549, 603, 439, 576, 713, 686, 493, 686, 494];
+
+    if let Some(index) = GREEK.chars().position(|x| x == c) {
+        return Some((b'A' + index as u8, GREEK_WIDTHS[index]));
+    }
+    if let Some(index) = GREEK_LOWER.chars().position(|x| x == c) {
+        return Some((b'a' + index as u8, GREEK_LOWER_WIDTHS[index]));
+    }
+    Some(match c {
+        ' ' => (b' ', 250),
+        '0'..='9' => (c as u8, 500),
+        '(' | ')' | '[' | ']' => (c as u8, 333),
+        '{' | '}' => (c as u8, 480),
+        '+' | '=' | '<' | '>' => (c as u8, 549),
+        '|' => (b'|', 200),
+        '/' => (b'/', 278),
+        ',' | '.' => (c as u8, 250),
+        ':' | ';' => (c as u8, 278),
+        'ϵ' => (b'e', 439),
+        '−' => (b'-', 549),
+        '∗' => (b'*', 500),
+        '∀' => (0x22, 713),
+        '∃' => (0x24, 549),
+        '∼' => (0x7E, 549),
+        '⊥' => (0x5E, 658),
+        '′' => (0xA2, 247),
+        '≤' => (0xA3, 549),
+        '∞' => (0xA5, 713),
+        '↔' => (0xAB, 1042),
+        '←' => (0xAC, 987),
+        '→' => (0xAE, 987),
+        '°' | '∘' => (0xB0, 400),
+        '±' => (0xB1, 549),
+        '≥' => (0xB3, 549),
+        '×' => (0xB4, 549),
+        '∝' => (0xB5, 713),
+        '∂' => (0xB6, 494),
+        '÷' => (0xB8, 549),
+        '≠' => (0xB9, 549),
+        '≡' => (0xBA, 549),
+        '≈' => (0xBB, 549),
+        '…' | '⋯' => (0xBC, 1000),
+        'ℵ' => (0xC0, 823),
+        '∅' => (0xC6, 823),
+        '∩' | '⋂' => (0xC7, 768),
+        '∪' | '⋃' => (0xC8, 768),
+        '⊃' => (0xC9, 713),
+        '⊂' => (0xCC, 713),
+        '⊆' => (0xCD, 713),
+        '∈' => (0xCE, 713),
+        '∉' => (0xCF, 713),
+        '∠' => (0xD0, 768),
+        '∇' => (0xD1, 713),
+        '∏' => (0xD5, 823),
+        '√' => (0xD6, 549),
+        '·' => (0xD7, 250),
+        '¬' => (0xD8, 713),
+        '∧' => (0xD9, 603),
+        '∨' => (0xDA, 603),
+        '⇔' => (0xDB, 1042),
+        '⇐' => (0xDC, 987),
+        '⇒' => (0xDE, 987),
+        '⟨' => (0xE1, 329),
+        '∑' => (0xE5, 713),
+        '⟩' => (0xF1, 329),
+        '∫' | '∮' => (0xF2, 274),
+        _ => return None,
+    })
+}
+
+pub fn encode_symbol(text: &str) -> Vec<u8> {
+    text.chars().map(|c| symbol_char(c).map(|(code, _)| code).unwrap_or(b'?')).collect()
+}
+
+fn escape_string(bytes: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(bytes.len());
+    for byte in bytes {
+        if matches!(byte, b'(' | b')' | b'\\') {
+            result.push(b'\\');
+        }
+        result.push(*byte);
+    }
+    result
+}
+
+fn literal(text: &str) -> Vec<u8> {
+    let mut result = vec![b'('];
+    result.extend(escape_string(&encode_text(text)));
+    result.push(b')');
+    result
+}
+
+struct ObjectWriter {
+    output:  Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl ObjectWriter {
+    fn begin(&mut self, id: usize) {
+        if self.offsets.len() < id {
+            self.offsets.resize(id, 0);
+        }
+        self.offsets[id - 1] = self.output.len();
+        let _ = write!(self.output, "{id} 0 obj\n");
+    }
+
+    fn end(&mut self) {
+        self.output.extend(b"\nendobj\n");
+    }
+
+    fn object(&mut self, id: usize, body: &[u8]) {
+        self.begin(id);
+        self.output.extend(body);
+        self.end();
+    }
+}
+
+pub fn build_pdf(pages: &[Page], title: &str) -> Vec<u8> {
+    let mut writer = ObjectWriter { output: Vec::new(), offsets: Vec::new() };
+    writer.output.extend(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n");
+
+    let font_count = FAMILIES.len() * STYLES.len() + 1;
+    let mut next_id = FIRST_FONT_OBJECT + font_count;
+    let mut page_ids = Vec::new();
+    for page in pages {
+        page_ids.push(next_id);
+        next_id += 2 + page.links.len();
+    }
+
+    writer.object(1, b"<< /Type /Catalog /Pages 2 0 R >>");
+
+    let kids = page_ids.iter().map(|id| format!("{id} 0 R")).collect::<Vec<_>>().join(" ");
+    writer.object(2, format!("<< /Type /Pages /Kids [{kids}] /Count {} >>", pages.len()).as_bytes());
+
+    let mut info = b"<< /Producer (pmd-parser) /Title ".to_vec();
+    info.extend(literal(title));
+    info.extend(b" >>");
+    writer.object(3, &info);
+
+    let mut fonts = String::new();
+    for family in FAMILIES {
+        for style in STYLES {
+            let font = Font::new(family, style);
+            let id = FIRST_FONT_OBJECT + font.resource_index();
+            let name = font.base_name();
+            writer.object(id, format!("<< /Type /Font /Subtype /Type1 /BaseFont /{name} /Encoding /WinAnsiEncoding >>").as_bytes());
+            fonts += format!("/F{} {id} 0 R ", font.resource_index()).as_str();
+        }
+    }
+    let symbol = Font::new(FontFamily::Symbol, FontStyle::Regular);
+    let id = FIRST_FONT_OBJECT + symbol.resource_index();
+    writer.object(id, b"<< /Type /Font /Subtype /Type1 /BaseFont /Symbol >>");
+    fonts += format!("/F{} {id} 0 R ", symbol.resource_index()).as_str();
+
+    for (page, page_id) in pages.iter().zip(page_ids) {
+        let content_id = page_id + 1;
+        let annotations = (0..page.links.len()).map(|i| format!("{} 0 R", content_id + 1 + i)).collect::<Vec<_>>().join(" ");
+
+        writer.object(page_id, format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH:.2} {PAGE_HEIGHT:.2}] /Resources << /Font << {fonts}>> >> /Contents {content_id} 0 R /Annots [{annotations}] >>"
+        ).as_bytes());
+
+        writer.begin(content_id);
+        let _ = write!(writer.output, "<< /Length {} >>\nstream\n", page.content.len());
+        writer.output.extend(&page.content);
+        writer.output.extend(b"\nendstream");
+        writer.end();
+
+        for (i, link) in page.links.iter().enumerate() {
+            let [x1, y1, x2, y2] = link.rect;
+            let mut body = format!("<< /Type /Annot /Subtype /Link /Rect [{x1:.2} {y1:.2} {x2:.2} {y2:.2}] /Border [0 0 0] /A << /S /URI /URI ").into_bytes();
+            body.extend(literal(&link.uri));
+            body.extend(b" >> >>");
+            writer.object(content_id + 1 + i, &body);
+        }
+    }
+
+    let xref = writer.output.len();
+    let _ = write!(writer.output, "xref\n0 {}\n0000000000 65535 f \n", writer.offsets.len() + 1);
+    for offset in writer.offsets.clone() {
+        let _ = write!(writer.output, "{offset:010} 00000 n \n");
+    }
+    let _ = write!(writer.output, "trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R >>\nstartxref\n{xref}\n%%EOF\n", writer.offsets.len() + 1);
+
+    writer.output
+}
+
+#[rustfmt::skip]
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+#[rustfmt::skip]
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+#[rustfmt::skip]
+const TIMES_WIDTHS: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444,
+    921, 722, 667, 667, 722, 611, 556, 722, 722, 333, 389, 722, 611, 889, 722, 722,
+    556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611, 333, 278, 333, 469, 500,
+    333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778, 500, 500,
+    500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+
+#[rustfmt::skip]
+const TIMES_BOLD_WIDTHS: [u16; 95] = [
+    250, 333, 555, 500, 500, 1000, 833, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    930, 722, 667, 722, 722, 667, 611, 778, 778, 389, 500, 778, 667, 944, 722, 778,
+    611, 778, 722, 556, 667, 722, 722, 1000, 722, 722, 667, 333, 278, 333, 581, 500,
+    333, 500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556, 278, 833, 556, 500,
+    556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444, 394, 220, 394, 520,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_width() {
+        let font = Font::new(FontFamily::Helvetica, FontStyle::Regular);
+        // H=722 e=556 l=222 l=222 o=556
+        assert!((font.text_width("Hello", 10.0) - 22.78).abs() < 0.001);
+        let mono = Font::new(FontFamily::Courier, FontStyle::Bold);
+        assert_eq!(mono.text_width("abc", 10.0), 18.0);
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(encode_text("a(b)"), b"a(b)".to_vec());
+        assert_eq!(escape_string(&encode_text("a(b)\\")), b"a\\(b\\)\\\\".to_vec());
+        assert_eq!(encode_text("\u{201C}æ\u{201D}✓"), vec![0x93, 0xE6, 0x94, b'?']);
+    }
+
+    #[test]
+    fn cross_reference_table() {
+        let mut page = Page::new();
+        page.text(10.0, 10.0, Font::new(FontFamily::Times, FontStyle::Italic), 12.0, "hello");
+        page.link(10.0, 10.0, 20.0, 12.0, "https://example.com");
+        let pdf = build_pdf(&[page, Page::new()], "title");
+        let text = String::from_utf8_lossy(&pdf).to_string();
+
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("/URI (https://example.com)"));
+
+        let startxref = text.rfind("startxref\n").unwrap() + "startxref\n".len();
+        let offset: usize = text[startxref..].lines().next().unwrap().parse().unwrap();
+        assert!(pdf[offset..].starts_with(b"xref"));
+
+        // every object in the table has to point at its own header
+        let entries = text[offset..].lines().skip(3);
+        for (i, entry) in entries.take_while(|x| x.ends_with(" n ")).enumerate() {
+            let position: usize = entry[..10].parse().unwrap();
+            assert!(pdf[position..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+    }
+}
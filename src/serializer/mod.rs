@@ -0,0 +1,31 @@
+mod pmd_serializer;
+#[cfg(any(feature = "wasm", feature = "html", feature = "rss", feature = "pdf"))]
+pub mod pmd_html_shared;
+#[cfg(any(feature = "html", feature = "wasm"))]
+pub mod pmd_html;
+#[cfg(feature = "rss")]
+pub mod pmd_rss;
+#[cfg(feature = "text")]
+pub mod pmd_pure_text;
+#[cfg(any(feature = "pdf", feature = "wasm"))]
+pub mod pmd_pdf;
+#[cfg(any(feature = "pdf", feature = "wasm"))]
+mod pmd_pdf_math;
+#[cfg(any(feature = "json", feature = "wasm"))]
+pub mod pmd_json;
+#[cfg(feature = "wasm")]
+pub mod pmd_wasm;
+
+pub use pmd_serializer::*;
+#[cfg(any(feature = "html", feature = "wasm"))]
+pub use pmd_html::*;
+#[cfg(feature = "rss")]
+pub use pmd_rss::*;
+#[cfg(feature = "text")]
+pub use pmd_pure_text::*;
+#[cfg(any(feature = "pdf", feature = "wasm"))]
+pub use pmd_pdf::*;
+#[cfg(any(feature = "json", feature = "wasm"))]
+pub use pmd_json::*;
+#[cfg(feature = "wasm")]
+pub use pmd_wasm::*;
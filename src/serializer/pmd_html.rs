@@ -1,62 +1,123 @@
-use ordered_map::OrderedMap;
-use pmd_html_shared::{ObjectKind, PMDSharedHTMLSerializer, Reference, PMDHTML};
-use crate::*;
-
-pub struct PMDHTMLSerializer { 
-    pub common: PMDHTML<PMDHTMLSerializer>,
-    header: BlogHeader,
-    references: OrderedMap<String, Reference<ReferenceDefinition>>,
-    notes: OrderedMap<String, Reference<BlogBody>>,
-    notes_id: String,
-    bibliography_id: String,
-/*
-    quote_id:   usize,
-    list_id :   usize,
-    code_id:    usize,
-    factbox_id: usize,
-    image_id:   usize,
-    missing_id: usize,
-*/
+use anyhow::{anyhow, Result};
+use crate::parser::*;
+use super::pmd_html_shared::{
+    alphabetical_value,
+    code_theme,
+    convert_custom_citation,
+    glossary_entry_id,
+    highlighted_html,
+    image_size_attributes,
+    sanitize_id,
+    sanitize_text,
+    to_html_bibliography,
+    to_mathml,
+    to_plain_text,
+    ObjectKind,
+};
+use super::{Context, Serializer};
+use crate::highlight::{highlight, split_lines, Theme};
+
+pub struct PMDHTMLSerializer {
+    pub filename: String,
+    num_tabs: usize,
+    times_used: OrderedMap<String, usize>,
+    theme: Option<&'static Theme>,
 }
 
-impl PMDSharedHTMLSerializer for PMDHTMLSerializer {
-    fn get_header(&self) -> &BlogHeader {
-        &self.header
+impl PMDHTMLSerializer {
+    pub fn new(filename: &str) -> Self {
+        Self {
+            filename: filename.into(),
+            num_tabs: 0,
+            times_used: OrderedMap::new(),
+            theme: None,
+        }
     }
 
-    fn notes_id(&mut self) -> String {
-        self.notes_id.clone()
+    fn push_tab(&mut self) { self.num_tabs += 1; }
+    fn pop_tab(&mut self) { if self.num_tabs > 0 { self.num_tabs -= 1; } }
+    fn tab(&mut self) -> String {
+        if self.num_tabs == 0 { "".to_string() }
+        else {
+            let mut result = String::new();
+            for _ in 0..(self.num_tabs) {
+                result += "    ";
+            }
+            result
+        }
     }
 
-    fn bibliography_id(&mut self) -> String {
-        self.bibliography_id.clone()
+    fn use_reference(&mut self, id: &String) -> usize {
+        if let Some(times_used) = self.times_used.get_mut(id) {
+            *times_used += 1;
+            *times_used - 1
+        } else {
+            self.times_used.insert(id.clone(), 1);
+            0
+        }
+    }
+
+    fn element_link(&mut self, id: &String, opt_text: Option<&str>, opt_class: Option<&str>) -> String {
+        let text  = opt_text.unwrap_or("¶");
+        let class = opt_class.unwrap_or("paragraph");
+
+        format!("<a class='{class}' href='#{id}' aria-hidden='true'>{text}</a>")
     }
 
-    fn get_description(&mut self, md: &PawsMarkdown) -> Result<String> {
-        let paragraph = md.body.iter().find(|(x, _)| match x { BlogBody::Paragraph(_) => true, _ => false});
-        Ok(if let Some((BlogBody::Paragraph(content), id)) = paragraph {
-            let text = PMDPureTextSerializer::new().convert_paragraph(content, id)?;
+    fn generate_link(&mut self, id: &String, kind: ObjectKind) -> String {
+        match kind {
+            ObjectKind::Header(depth) => self.element_link(&id, Some(format!("<h{depth}>§</h{depth}>").as_str()), Some("header")),
+            ObjectKind::CodeBlock | ObjectKind::Quote | ObjectKind::Callout | ObjectKind::Image | ObjectKind::Media | ObjectKind::FactBox |
+            ObjectKind::List  | ObjectKind::Table | ObjectKind::DefinitionList | ObjectKind::Math | ObjectKind::Paragraph => self.element_link(id, None, None),
+        }
+    }
+
+    // videos, sounds and iframes all sit in a section like images do, `player` is the element itself
+    fn convert_media(&mut self, id: &String, class: &str, player: String) -> String {
+        let id = sanitize_id(id);
+        let link = self.generate_link(&id, ObjectKind::Media);
+
+        let mut result = self.tab();
+        result += format!("<section class='{class}' id='{id}'>\n").as_str();
+        self.push_tab();
+            result += self.tab().as_str();
+            result += format!("{link}\n").as_str();
+
+            result += self.tab().as_str();
+            result += format!("{player}\n").as_str();
+        self.pop_tab();
+        result += self.tab().as_str();
+        result += "</section>\n";
+        result
+    }
+
+    fn get_description(&mut self, md: &AssDownDocument) -> String {
+        let paragraph = md.body.iter().find(|(x, _, _)| match x { Element::Paragraph(_) => true, _ => false});
+        if let Some((Element::Paragraph(content), _, _)) = paragraph {
+            let text = to_plain_text(content);
             text.trim_end().to_string()
         } else {
             String::new()
-        })
+        }
     }
 
-    fn prepare_html_header(&mut self, description: &String) -> String {
+    fn prepare_html_header(&mut self, meta: &MetaData, description: &String) -> String {
         let mut output = String::new();
-        let title = &self.header.title;
-        let banner = &self.header.banner;
-        let url = &self.header.url;
-        let data_dir = &self.header.data_dir;
-        let blog_dir = &self.header.blog_dir;
+        let title = &meta.title;
+        let banner = &meta.banner;
+        let url = &meta.url;
+        let data_dir = &meta.data_dir;
+        let blog_dir = &meta.blog_dir;
 
         output += "<meta http-equiv=\"content-type\" content=\"text/html; charset=utf-8\">\n";
         output += "\n";
-        output += "<!-- highlight.js -->\n";
-        output += "<script src=\"https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.7.0/highlight.min.js\"></script>\n";
-        output += "<link rel=\"stylesheet\" href=\"https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.7.0/styles/default.min.css\">\n";
-        output += "<script>hljs.highlightAll();</script>\n";
-        output += "\n";
+        if let Some(theme) = self.theme {
+            output += format!("<!-- code highlighting, theme '{}' -->\n", theme.name).as_str();
+            output += "<style>\n";
+            output += theme.css().as_str();
+            output += "</style>\n";
+            output += "\n";
+        }
         output += "<!-- tag needed for media query -->\n";
         output += "<meta name=\"viewport\"    content=\"width=device-width, initial-scale=1, minimum-scale=1\" />\n";
         output += "\n";
@@ -67,7 +128,7 @@ impl PMDSharedHTMLSerializer for PMDHTMLSerializer {
         output += "\n";
         output += "<!-- Open Graph / Facebook -->\n";
         output += "<meta property=\"og:type\" content=\"website\">\n";
-        output += format!("<meta property=\"og:url\" content=\"{url}/{blog_dir}/{}.html\">\n", self.common.filename).as_str();
+        output += format!("<meta property=\"og:url\" content=\"{url}/{blog_dir}/{}.html\">\n", self.filename).as_str();
         output += format!("<meta property=\"og:title\" content=\"{}\">\n", title).as_str();
         output += format!("<meta property=\"og:description\" content=\"{}\">\n", description).as_str();
         if banner.len() > 0 {
@@ -82,7 +143,7 @@ impl PMDSharedHTMLSerializer for PMDHTMLSerializer {
         } else {
             output += "<meta property=\"twitter:card\" content=\"summary\">\n";
         }
-        output += format!("<meta property=\"twitter:url\" content=\"{url}/{blog_dir}/{}.html\">\n", self.common.filename).as_str();
+        output += format!("<meta property=\"twitter:url\" content=\"{url}/{blog_dir}/{}.html\">\n", self.filename).as_str();
         output += format!("<meta property=\"twitter:title\" content=\"{}\">\n", title).as_str();
         output += format!("<meta property=\"twitter:description\" content=\"{}\">\n", description).as_str();
         if banner.len() > 0 {
@@ -104,558 +165,918 @@ impl PMDSharedHTMLSerializer for PMDHTMLSerializer {
         output += "<script type='text/javascript' src='../js/popup.js'></script>\n";
         output += "<script type='text/javascript' src='../js/dropdown.js'></script>\n";
         output += "<script type='text/javascript' src='../js/backref.js'></script>\n";
-        
+
         output
     }
 
-    fn convert_body(&mut self, md: &PawsMarkdown) -> Result<String> {
+    fn prepare_navbar(&mut self) -> String {
         let mut output = String::new();
-        for (element, id) in &md.body {
-            let result = self.convert_element((element, id))?;
-            match element {
-                BlogBody::CodeBlock(_) => {
-                    output += result.as_str();
-                    output += "\n";
-                },
-                _ => {
-                    output += result.as_str();
-                }
-            }
-        }
-        Ok(output)
-    }
 
-    fn generate_link(&mut self, id: &String, kind: ObjectKind) -> String {
-        match kind {
-            ObjectKind::Header(depth) => self.element_link(&id, Some(format!("<h{depth}>§</h{depth}>").as_str()), Some("header")),
-            ObjectKind::CodeBlock | ObjectKind::Quote | ObjectKind::Image | ObjectKind::FactBox |
-            ObjectKind::List  | ObjectKind::Paragraph => self.element_link(id, None, None),
-        }
-    }
-    
-    
-    fn references(&mut self) -> &OrderedMap<String, Reference<ReferenceDefinition>> {
-        &self.references
-    }
-    
-    fn mut_references(&mut self) -> &mut OrderedMap<String, Reference<ReferenceDefinition>> {
-        &mut self.references
-    }
-
-    fn get_reference<T: AsRef<str>>(&mut self, key: T) -> Option<&Reference<ReferenceDefinition>> {
-        self.references.get(key.as_ref())
-    }
-    
-    fn get_mut_reference<T: AsRef<str>>(&mut self, key: T) -> Option<&mut Reference<ReferenceDefinition>> {
-        self.references.get_mut(key.as_ref())
-    }
-}
-
-impl PMDHTMLSerializer {
-    pub fn new(filename: &str) -> Box<Self> {
-        let mut value = Box::new(Self {
-            common: PMDHTML::uninit(),
-            header: BlogHeader::default(),
-            references: OrderedMap::new(),
-            notes: OrderedMap::new(),
-            notes_id: String::new(),
-            bibliography_id: String::new(),
-        });
-        value.common = PMDHTML::new(filename, &mut *value);
-        value
-    }
-
-    fn element_link(&mut self, id: &String, opt_text: Option<&str>, opt_class: Option<&str>) -> String {
-        let text  = opt_text.unwrap_or("¶");
-        let class = opt_class.unwrap_or("paragraph");
-
-        format!("<a class='{class}' href='#{id}' aria-hidden='true'>{text}</a>")
-    }
-
-    fn prepare_navbar(&mut self) -> Result<String> {
-        let mut output = String::new();
-        
-        output += self.common.tab().as_str();
+        output += self.tab().as_str();
         output += "<nav class=\"nav-bar\">\n";
-        self.common.push_tab();
-            output += self.common.tab().as_str();
+        self.push_tab();
+            output += self.tab().as_str();
             output += "<a class=\"paw-holder\" href=\"..\">\n";
-            self.common.push_tab();
-            output += self.common.tab().as_str();
+            self.push_tab();
+            output += self.tab().as_str();
             output += "<div class=\"paw-beans\">\n";
-            self.common.push_tab();
-                output += self.common.tab().as_str();
+            self.push_tab();
+                output += self.tab().as_str();
                 output += "<div class=\"bean bean-nth-0\"></div>\n";
-                output += self.common.tab().as_str();
+                output += self.tab().as_str();
                 output += "<div class=\"bean bean-nth-1\"></div>\n";
-                output += self.common.tab().as_str();
+                output += self.tab().as_str();
                 output += "<div class=\"bean bean-nth-2\"></div>\n";
-                output += self.common.tab().as_str();
+                output += self.tab().as_str();
                 output += "<div class=\"bean bean-nth-3\"></div>\n";
-            self.common.pop_tab();
-            output += self.common.tab().as_str();
+            self.pop_tab();
+            output += self.tab().as_str();
             output += "</div>\n";
 
-            output += self.common.tab().as_str();
+            output += self.tab().as_str();
             output += "<div class=\"paw-pad\"></div>\n";
 
-            self.common.pop_tab();
-            output += self.common.tab().as_str();
+            self.pop_tab();
+            output += self.tab().as_str();
             output += "</a>\n";
-            
-            output += self.common.tab().as_str();
+
+            output += self.tab().as_str();
             output += "<section>\n";
-            self.common.push_tab();
-                output += self.common.tab().as_str();
+            self.push_tab();
+                output += self.tab().as_str();
                 output += "<button class='nav-phone-dropdown-button' onclick='toggle_dropdown()'>☰ </button>\n";
-                output += self.common.tab().as_str();
+                output += self.tab().as_str();
                 output += "<ul class='nav-list'>\n";
-                self.common.push_tab();
-                    output += self.common.tab().as_str();
+                self.push_tab();
+                    output += self.tab().as_str();
                     output += "<li><a href='../about.html'>⭐ About</a></li>\n";
-                    output += self.common.tab().as_str();
+                    output += self.tab().as_str();
                     output += "<li><a href='../art.html'>🎨 Art</a></li>\n";
-                    output += self.common.tab().as_str();
+                    output += self.tab().as_str();
                     output += "<li><a href='../code.html'>🦄 Code</a></li>\n";
-                    output += self.common.tab().as_str();
-                    output += format!("<li><a href='md/{}.md'>📋 Raw</a></li>\n", self.common.filename).as_str();
-                self.common.pop_tab();
-                output += self.common.tab().as_str();
+                    output += self.tab().as_str();
+                    output += format!("<li><a href='md/{}.md'>📋 Raw</a></li>\n", self.filename).as_str();
+                self.pop_tab();
+                output += self.tab().as_str();
                 output += "</ul>\n";
-            self.common.pop_tab();
-            output += self.common.tab().as_str();
+            self.pop_tab();
+            output += self.tab().as_str();
             output += "</section>\n";
 
-        self.common.pop_tab();
-        output += self.common.tab().as_str();
+        self.pop_tab();
+        output += self.tab().as_str();
         output += "</nav>\n";
 
 
-        output += self.common.tab().as_str();
+        output += self.tab().as_str();
         output += "<ul id='phone-dropdown' class='nav-phone-dropdown off'>\n";
-        self.common.push_tab();
-            output += self.common.tab().as_str();
+        self.push_tab();
+            output += self.tab().as_str();
             output += "<li><a href='../about.html'>⭐ About</a></li>\n";
-            output += self.common.tab().as_str();
+            output += self.tab().as_str();
             output += "<li><a href='../art.html'>🎨 Art</a></li>\n";
-            output += self.common.tab().as_str();
+            output += self.tab().as_str();
             output += "<li><a href='../code.html'>🦄 Code</a></li>\n";
-            output += self.common.tab().as_str();
-            output += format!("<li><a href='md/{}.md'>📋 Raw</a></li>\n", self.common.filename).as_str();
-        self.common.pop_tab();
-        output += self.common.tab().as_str();
+            output += self.tab().as_str();
+            output += format!("<li><a href='md/{}.md'>📋 Raw</a></li>\n", self.filename).as_str();
+        self.pop_tab();
+        output += self.tab().as_str();
         output += "</ul>\n";
-        
+
+        output
+    }
+
+    fn page_break(&mut self) -> String {
+        let mut result = self.tab();
+
+        result += format!("<section class='page-break'>\n").as_str();
+        self.push_tab();
+            result += self.tab().as_str();
+            result += "<hr>\n";
+        self.pop_tab();
+        result += self.tab().as_str();
+        result += format!("</section>\n").as_str();
+
+        result
+    }
+
+    fn notes_header(&mut self, id: &String, title: &String) -> String {
+        let link = self.generate_link(id, ObjectKind::Header(1));
+        let mut output = self.page_break();
+
+        output += self.tab().as_str();
+        output += format!("<section class='notes' id='{id}'>\n").as_str();
+        self.push_tab();
+            output += self.tab().as_str();
+            output += link.as_str();
+            output.push('\n');
+
+            output += self.tab().as_str();
+            output += format!("<h1>{title}</h1>\n").as_str();
+        self.pop_tab();
+        output += self.tab().as_str();
+        output += format!("</section>\n").as_str();
+
+        output
+    }
+
+    fn note(&mut self, ctx: &Context, key: &String, note_id: &String, note: &Element) -> Result<String> {
+        let result = self.visit_inline(ctx, note)?;
+        let link = format!("<a href='#^{note_id}'>^{key}:</a>");
+
+        let mut output = self.tab();
+        output += format!("<section class='note' id=\"^{note_id}\">").as_str();
+        output.push('\n');
+        self.push_tab();
+
+            output += self.tab().as_str();
+            output += "<sup>";
+            self.push_tab();
+                output += link.as_str();
+                output.push('\n');
+
+                output += self.tab().as_str();
+                output += result.as_str();
+                output.push('\n');
+
+                output += self.tab().as_str();
+                output += format!("<a href=\"#{note_id}-backref\">").as_str();
+                output += "↩";
+                output += "</a>";
+                output.push('\n');
+            self.pop_tab();
+            output += self.tab().as_str();
+            output += "</sup>";
+            output.push('\n');
+
+        self.pop_tab();
+        output += self.tab().as_str();
+        output += "</section>\n";
+
         Ok(output)
-    } 
-}
+    }
+
+    fn bibliography(&mut self, md: &AssDownDocument) -> String {
+        let id = md.bibliography_id.clone();
+        let title = &md.meta.bibliography_title;
+        let link = self.generate_link(&id, ObjectKind::Header(1));
+
+        let mut output = self.page_break();
+
+        output += self.tab().as_str();
+        output += format!("<section id='{id}'>\n").as_str();
+        self.push_tab();
+            output += self.tab().as_str();
+            output += link.as_str();
+            output.push('\n');
+
+            output += self.tab().as_str();
+            output += format!("<h1>{title}</h1>\n").as_str();
+        self.pop_tab();
+        output += self.tab().as_str();
+        output += format!("</section>\n").as_str();
+
+        for (key, val) in &md.references {
+            // the parser already warned about it as `unused-reference`
+            if !self.times_used.contains_key(key) {
+                continue;
+            }
+
+            output += self.tab().as_str();
+            output += format!("<section class='citation' id='{key}'>\n").as_str();
+            self.push_tab();
+                output += self.tab().as_str();
+                output += format!("<p>\n").as_str();
+                self.push_tab();
+
+                    output += to_html_bibliography(val).as_str();
+                    output.push('\n');
+
+                    output += self.tab().as_str();
+                    output += format!("<a href=''>").as_str();
+                    output += "↩";
+                    output += "</a>";
+                    output.push('\n');
+                self.pop_tab();
+                output += self.tab().as_str();
+                output += format!("</p>\n").as_str();
+
+            self.pop_tab();
+            output += self.tab().as_str();
+            output += "</section>\n";
+        }
 
-impl PMDSerializer for PMDHTMLSerializer {
-    fn current_factbox(&mut self) -> Option<(FactBox, Option<String>)> {
-        self.common.current_factbox()
+        output
     }
 
-    fn convert(&mut self, md: &PawsMarkdown) -> Result<String> {
-        let navbar = self.prepare_navbar()?;
-        self.header = md.header.clone();
-        self.notes_id = md.notes_id.clone();
-        self.bibliography_id = md.bibliography_id.clone();
-        
-        for (id, reference) in &md.references {
-            self.references.insert(id.clone(), Reference::new(reference.clone()));
+    fn popup(&mut self) -> String {
+        let mut output = self.tab();
+        output +="<div id='popup' class='popup-hidden' aria-hidden='true'>\n";
+        self.push_tab();
+
+            output += self.tab().as_str();
+            output +="<div class='popup-clickable-region' onclick='close_popup(this)' ></div>\n";
+            output += self.tab().as_str();
+            output +="<div class='popup-container'>\n";
+            self.push_tab();
+
+                output += self.tab().as_str();
+                output +="<img id='popup-image'>\n";
+                output += self.tab().as_str();
+                output +="<p id='popup-caption'></p>\n";
+
+            self.pop_tab();
+            output += self.tab().as_str();
+            output +="</div>\n";
+            self.pop_tab();
+
+        output += self.tab().as_str();
+        output +="</div>\n";
+
+        output
+    }
+
+    fn convert_table_row(&mut self, ctx: &Context, cells: &[Element], alignments: &[Alignment], tag: &str) -> Result<String> {
+        self.push_tab();
+        let mut result = self.tab();
+        result += "<tr>";
+        for (cell, alignment) in cells.iter().zip(alignments) {
+            let text = self.visit_inline(ctx, cell)?;
+            let style = match alignment {
+                Alignment::None   => "",
+                Alignment::Left   => " style='text-align: left'",
+                Alignment::Center => " style='text-align: center'",
+                Alignment::Right  => " style='text-align: right'",
+            };
+            result += format!("<{tag}{style}>{text}</{tag}>").as_str();
+        }
+        result += "</tr>\n";
+        self.pop_tab();
+        Ok(result)
+    }
+
+    // `ids` gives the terms an id of their own, the glossary uses that so they can be linked to
+    fn convert_definitions<'a>(&mut self, ctx: &Context, list: impl Iterator<Item = (&'a Definition, Option<String>)>) -> Result<String> {
+        let mut result = self.tab();
+        result += "<dl>\n";
+        self.push_tab();
+        for (definition, id) in list {
+            let term = self.visit_inline(ctx, &definition.term)?;
+            let id = id.map(|id| format!(" id='{id}'")).unwrap_or_default();
+            result += self.tab().as_str();
+            result += format!("<dt{id}>{term}</dt>\n").as_str();
+            for text in &definition.definitions {
+                let text = self.visit_inline(ctx, text)?;
+                result += self.tab().as_str();
+                result += format!("<dd>{text}</dd>\n").as_str();
+            }
         }
-        
-        for (id, note) in &md.notes {
-            self.notes.insert(id.clone(), Reference::new(note.clone()));
+        self.pop_tab();
+        result += self.tab().as_str();
+        result += "</dl>\n";
+        Ok(result)
+    }
+
+    fn glossary(&mut self, ctx: &Context, md: &AssDownDocument) -> Result<String> {
+        let id = md.glossary_id.clone();
+        let title = &md.meta.glossary_title;
+        let link = self.generate_link(&id, ObjectKind::Header(1));
+
+        let mut output = self.page_break();
+
+        output += self.tab().as_str();
+        output += format!("<section class='glossary' id='{id}'>\n").as_str();
+        self.push_tab();
+            output += self.tab().as_str();
+            output += link.as_str();
+            output.push('\n');
+
+            output += self.tab().as_str();
+            output += format!("<h1>{title}</h1>\n").as_str();
+
+            let entries = md.glossary.iter().map(|(key, definition)| (definition, Some(glossary_entry_id(&id, key))));
+            output += self.convert_definitions(ctx, entries)?.as_str();
+        self.pop_tab();
+        output += self.tab().as_str();
+        output += "</section>\n";
+
+        Ok(output)
+    }
+
+    // the other posts with a wiki link to this one, filled in by `wiki_links::link_documents`
+    fn backlinks(&mut self, md: &AssDownDocument) -> String {
+        let id = "backlinks".to_string();
+        let title = &md.meta.backlinks_title;
+        let link = self.generate_link(&id, ObjectKind::Header(1));
+
+        let mut output = self.page_break();
+
+        output += self.tab().as_str();
+        output += format!("<section class='backlinks' id='{id}'>\n").as_str();
+        self.push_tab();
+            output += self.tab().as_str();
+            output += link.as_str();
+            output.push('\n');
+
+            output += self.tab().as_str();
+            output += format!("<h1>{title}</h1>\n").as_str();
+
+            output += self.tab().as_str();
+            output += "<ul>\n";
+            self.push_tab();
+            for backlink in &md.backlinks {
+                output += self.tab().as_str();
+                output += format!("<li><a class='wiki-link' href='{}'>{}</a></li>\n", backlink.url, backlink.title).as_str();
+            }
+            self.pop_tab();
+            output += self.tab().as_str();
+            output += "</ul>\n";
+        self.pop_tab();
+        output += self.tab().as_str();
+        output += "</section>\n";
+
+        output
+    }
+
+    // the blocks in the body of an item are numbered after the item so they get their own links
+    fn convert_list_items(&mut self, ctx: &Context, list: &[ListItem], id: &str) -> Result<String> {
+        let (open, close) = match list.first() {
+            Some(ListItem::Numbered(..)) |
+            Some(ListItem::NumberedRounded(..))     => ("<ol>", "</ol>"),
+            Some(ListItem::Alphabetical(..)) |
+            Some(ListItem::AlphabeticalRounded(..)) => ("<ol type='a'>", "</ol>"),
+            _                                       => ("<ul>", "</ul>"),
+        };
+
+        let mut result = self.tab();
+        result += open;
+        result.push('\n');
+        self.push_tab();
+        for (index, item) in list.iter().enumerate() {
+            let value = match item {
+                ListItem::List(inner) => {
+                    result += self.convert_list_items(ctx, inner, format!("{id}-{index}").as_str())?.as_str();
+                    continue;
+                },
+                ListItem::Unordered(..)                       |
+                ListItem::Task { .. }                         => None,
+                ListItem::Numbered(number, ..)                |
+                ListItem::NumberedRounded(number, ..)         => Some(*number),
+                ListItem::Alphabetical(marker, ..)            |
+                ListItem::AlphabeticalRounded(marker, ..)     => alphabetical_value(marker),
+            };
+            let Some((text, body)) = item.content() else { continue };
+            let text = self.visit_inline(ctx, text)?;
+            let open = match (item, value) {
+                (ListItem::Task { checked, .. }, _) => {
+                    let checked = if *checked { " checked" } else { "" };
+                    format!("<li class='task'><input type='checkbox' disabled{checked}> ")
+                },
+                (_, Some(value)) => format!("<li value='{value}'>"),
+                (_, None)        => "<li>".to_string(),
+            };
+            result += self.tab().as_str();
+            if body.is_empty() {
+                result += format!("{open}{text}</li>\n").as_str();
+                continue;
+            }
+
+            result += format!("{open}{text}\n").as_str();
+            self.push_tab();
+            for (n, Spanned { node: element, .. }) in body.iter().enumerate() {
+                result += self.visit_element(ctx, element, &format!("{id}-{index}-{n}"))?.as_str();
+            }
+            self.pop_tab();
+            result += self.tab().as_str();
+            result += "</li>\n";
         }
+        self.pop_tab();
+        result += self.tab().as_str();
+        result += close;
+        result.push('\n');
 
-        self.common.html(md, Some(&navbar), &md.references, &md.notes)
+        Ok(result)
     }
-    
-    fn convert_factbox(&mut self, factbox: &FactBox, id: &String) -> Result<String> {
-        self.common.convert_factbox(factbox, id)
+}
+
+impl Serializer for PMDHTMLSerializer {
+    type Output = String;
+
+    fn text(&mut self, _: &Context, text: &str) -> Result<String> {
+        Ok(sanitize_text(&text.to_string()))
+    }
+
+    fn join(&mut self, parts: Vec<String>) -> String {
+        parts.concat()
+    }
+
+    fn visit_body(&mut self, ctx: &Context, body: &[(Element, String, SourceSpan)]) -> Result<String> {
+        let mut output = String::new();
+        for (element, id, _) in body {
+            output += self.visit_element(ctx, element, id)?.as_str();
+            if let Element::CodeBlock(_) = element {
+                output += "\n";
+            }
+        }
+        Ok(output)
     }
 
-    /*
-    fn convert_factbox(&mut self, factbox: &FactBox) -> Result<String> {
-        let id = self.generate_missing_factbox_id();
-        let link = self.element_link(&id, None, None);
+    fn visit_factbox(&mut self, ctx: &Context, factbox: &FactBox, id: &String) -> Result<String> {
+        let id = sanitize_id(id);
+        let link = self.generate_link(&id, ObjectKind::FactBox);
         let title = &factbox.title;
+        let meta = ctx.meta();
 
-        let mut output = self.common.tab();
+        let mut output = self.tab();
         output += format!("<section id='{id}'>\n").as_str();
-        self.common.push_tab();
-            output += self.common.tab().as_str();
+        self.push_tab();
+            output += self.tab().as_str();
             output += format!("{link}\n").as_str();
-            
-            output += self.common.tab().as_str();
+
+            output += self.tab().as_str();
             output += "<article class='factbox'>\n";
-            self.common.push_tab();
+            self.push_tab();
 
-                output += self.common.tab().as_str();
+                output += self.tab().as_str();
                 output += "<header class='factbox-header'>\n";
-                self.common.push_tab();
+                self.push_tab();
 
-                    output += self.common.tab().as_str();
+                    output += self.tab().as_str();
                     output += format!("<h2>{title}</h2>\n").as_str();
 
-                self.common.pop_tab();
-                output += self.common.tab().as_str();
+                self.pop_tab();
+                output += self.tab().as_str();
                 output += "</header>\n";
-                
-                output += self.common.tab().as_str();
+
+                output += self.tab().as_str();
                 output += "<section class='factbox-content'>\n";
-                self.common.push_tab();
-                for element in self.convert_factbox_elements(factbox, Some(&id))? {
-                    output += element.as_str();
+                self.push_tab();
+
+                let inner = ctx.in_factbox(factbox, &id);
+                for (element, element_id, _) in &factbox.body {
+                    output += self.visit_element(&inner, element, element_id)?.as_str();
                 }
-                    
-
-                if !(factbox.notes.is_empty() || self.header.hide_notes) {
-                    let id = PMDHTML::generate_id(&self.header.notes_title, ||"missing".into());
-                    let title = self.header.notes_title.clone();
-                    let link  = self.element_link(&id, Some(format!("<h1>§</h1>").as_str()), Some("header"));
-                
-                    
-                    output += self.common.tab().as_str();
-                    output += format!("<section class='page-break'>\n").as_str();
-                    self.common.push_tab();
-                        output += self.common.tab().as_str();
-                        output += "<hr>\n";
-                    self.common.pop_tab();
-                    output += self.common.tab().as_str();
-                    output += format!("</section>\n").as_str();
-                        
-                    output += self.common.tab().as_str();
-                    output += format!("<section class='notes' id='{id}'>\n").as_str();
-                    self.common.push_tab();
-                        output += self.common.tab().as_str();
-                        output += link.as_str();
-                        output.push('\n');
-                        
-                        output += self.common.tab().as_str();
-                        output += format!("<h1>{title}</h1>\n").as_str();
-                    self.common.pop_tab();
-                    output += self.common.tab().as_str();
-                    output += format!("</section>\n").as_str();
-                        
-                    for (key, val) in &factbox.notes {
-                        let result = self.convert_element(val)?;
-                        let link = format!("<a href='#^{id}-{key}'>^{key}:</a>");
-                    
-                        output += self.common.tab().as_str();
-                        output += format!("<section class='note' id=\"^{key}\">").as_str();
-                        output.push('\n');
-                        self.common.push_tab();
-                    
-                            output += self.common.tab().as_str();
-                            output += "<sup>";
-                            self.common.push_tab();
-                                output += link.as_str();
-                                output.push('\n');
-                                
-                                output += self.common.tab().as_str();
-                                output += result.as_str();
-                                output.push('\n');
-                                
-                                output += self.common.tab().as_str();
-                                output += format!("<a href=\"#{key}-backref\">").as_str();
-                                output += "↩";
-                                output += "</a>";
-                                output.push('\n');
-                            self.common.pop_tab();
-                            output += self.common.tab().as_str();
-                            output += "</sup>";
-                            output.push('\n');
-                    
-                        self.common.pop_tab();
-                        output += self.common.tab().as_str();
-                        output += "</section>\n";
+
+                if !(factbox.notes.is_empty() || meta.hide_notes) {
+                    let notes_id = format!("{id}-{}", ctx.document.notes_id);
+                    output += self.notes_header(&notes_id, &meta.notes_title).as_str();
+
+                    for (key, (val, note_id)) in &factbox.notes {
+                        output += self.note(&inner, key, note_id, val)?.as_str();
                     }
                 }
 
-                self.common.pop_tab();
-                output += self.common.tab().as_str();
+                self.pop_tab();
+                output += self.tab().as_str();
                 output += "</section>\n";
-            
-            self.common.pop_tab();
-            output += self.common.tab().as_str();
+
+            self.pop_tab();
+            output += self.tab().as_str();
             output += "</article>\n";
-            
-        self.common.pop_tab();
-        output += self.common.tab().as_str();
+
+        self.pop_tab();
+        output += self.tab().as_str();
         output += "</section>\n";
 
         Ok(output)
     }
-    */
 
-    fn convert_hoverable(&mut self, hoverable: &Alternative) -> Result<String> {
-        // let base = self.convert_element(&hoverable.base)?;
-        // let alt  = self.convert_element(&hoverable.alt)?;
-        // Ok(format!("<span class='hoverable'><span class='hover-base'>{base}</span><span class='hover-alt'>{alt}</span></span>"))
-        self.common.convert_hoverable(hoverable)
+    fn visit_hoverable(&mut self, ctx: &Context, hoverable: &Alternative) -> Result<String> {
+        let base = self.visit_inline(ctx, &hoverable.base)?;
+        let alt  = self.visit_inline(ctx, &hoverable.alt)?;
+        Ok(format!("<span class='hoverable'><span class='hover-base'>{base}</span><span class='hover-alt'>{alt}</span></span>"))
     }
 
-    fn convert_styled(&mut self, styled: &Alternative) -> Result<String> {
-        // let text  = self.convert_element(&styled.alt)?;
-        // let style = self.convert_element(&styled.base)?;
-        // Ok(format!("<span class='embedded-style' style='{style}'>{text}</span>"))
-        self.common.convert_styled(styled)
+    fn visit_styled(&mut self, ctx: &Context, styled: &Alternative) -> Result<String> {
+        let text  = self.visit_inline(ctx, &styled.alt)?;
+        let style = self.visit_inline(ctx, &styled.base)?;
+        Ok(format!("<span class='embedded-style' style='{style}'>{text}</span>"))
     }
-    
-    // fn convert_embedded_link(&mut self, src: &String, alt: &String) -> Result<String> {
-    // }
 
-    fn convert_link(&mut self, link: &Alternative) -> Result<String> {
-        self.common.convert_link(link)
-        /*
-        let href = self.convert_element(&link.alt)?;
-        let text = self.convert_element(&link.base)?;
+    fn visit_link(&mut self, ctx: &Context, link: &Alternative) -> Result<String> {
+        let meta = ctx.meta();
         match &link.alt {
-            box BlogBody::Citation(citation) => {
-                if let Some(reference) = convert_custom_citation(self.references.get_mut(citation.as_str()) , &citation, &text, self.header.hide_references) {
+            box Element::Citation(citation) => {
+                let text = self.visit_inline(ctx, &link.base)?;
+                let times_used = if ctx.reference(citation).is_some() {
+                    Some(self.use_reference(citation))
+                } else { None };
+                if let Some(reference) = convert_custom_citation(times_used, citation, &text, meta.hide_references) {
                     Ok(reference)
                 } else {
-                    cprintln!("<y>warning:</> {} has no source", citation);
-                    Ok(format!("<cite style='color=red; background-color: yellow'>{text}</cite>"))
+                    Ok(format!("<cite style='color: red; background-color: yellow'>{text}</cite>"))
                 }
-            }, 
-            box BlogBody::Note(note) => {
-                if self.header.hide_notes {
+            },
+            box Element::Note(note) => {
+                let text = self.visit_inline(ctx, &link.base)?;
+                if meta.hide_notes {
                     Ok("".into())
                 } else {
                     Ok(format!("<a class='inline-link' href='#^{note}'>{text}</a>"))
                 }
             },
             _ => {
+                let href = self.visit_inline(ctx, &link.alt)?;
+                let text = self.visit_inline(ctx, &link.base)?;
                 Ok(format!("<a class='inline-link' href='{href}'>{text}</a>"))
             }
         }
-        */
     }
 
-    fn convert_header(&mut self, text: &Box<BlogBody>, depth: usize, id: &String) -> Result<String> {
-        self.common.convert_header(text, depth, id)
-        /*
-        let text  = self.convert_element(text)?;
-        let id    = Self::generate_id(&text, ||"missing".into());
-        let link  = self.element_link(&id, Some(format!("<h{depth}>§</h{depth}>").as_str()), Some("header"));
-        
-        let mut result = self.common.tab();
+    fn visit_header(&mut self, ctx: &Context, text: &Element, depth: usize, id: &String) -> Result<String> {
+        let id = sanitize_id(id);
+        let text  = self.visit_inline(ctx, text)?;
+        let link = self.generate_link(&id, ObjectKind::Header(depth));
+
+        let mut result = self.tab();
         result += format!("<section id='{id}'>\n").as_str();
-        self.common.push_tab();
-            result += self.common.tab().as_str();
+        self.push_tab();
+            result += self.tab().as_str();
             result += link.as_str();
             result.push('\n');
-            
-            result += self.common.tab().as_str();
+
+            result += self.tab().as_str();
             result += format!("<h{depth}>{text}</h{depth}>\n").as_str();
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
+        self.pop_tab();
+        result += self.tab().as_str();
         result += "</section>\n";
 
         Ok(result)
-            */
     }
 
-    fn convert_italics(&mut self, text: &Box<BlogBody>) -> Result<String> {
-        self.common.convert_italics(text)
-        /*
-        let inner_text = self.convert_element(&text)?;
+    fn visit_italics(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
         Ok(format!("<i>{inner_text}</i>"))
-        */
     }
 
-    fn convert_bold(&mut self, text: &Box<BlogBody>) -> Result<String> {
-        self.common.convert_bold(text)
-        /*
-        let inner_text = self.convert_element(&text)?;
+    fn visit_bold(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
         Ok(format!("<b>{inner_text}</b>"))
-        */
-    }
-
-    fn convert_inlinecode(&mut self, text: &String) -> Result<String> {
-        self.common.convert_inlinecode(text)
-        // Ok(format!("<code>{text}</code>"))
-    }
-
-    fn convert_codeblock(&mut self, text: &String, id: &String) -> Result<String> {
-        self.common.convert_codeblock(text, id)
-        /*
-        let first_line = text.lines().nth(0).context("expected at least one line in codeblock")?;
-        let mut words = first_line.split(|x: char| x.is_whitespace());
-        let lang = words.nth(0).unwrap_or("plaintext");
-        let mut body = text[text.find('\n').context("expected at least one line in codeblock")? + 1..].to_string();
-        let id = Self::generate_id(&"".into(), ||self.generate_missing_code_id());
-        let link = self.element_link(&id, None, None);
-
-        body = body.replace("&", "&amp;")
-                   .replace("<", "&lt;")
-                   .replace(">", "&gt;");
-        body = body.trim_end().replace("\r\n", "\n");
-        
-        let mut result = self.common.tab();
-        result += format!("<section class='code-block' id='{id}'>\n").as_str();
-        self.common.push_tab();
-            result += self.common.tab().as_str();
+    }
+
+    fn visit_strikethrough(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<s>{inner_text}</s>"))
+    }
+
+    fn visit_underline(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<u>{inner_text}</u>"))
+    }
+
+    fn visit_highlight(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<mark>{inner_text}</mark>"))
+    }
+
+    fn visit_superscript(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<sup>{inner_text}</sup>"))
+    }
+
+    fn visit_subscript(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<sub>{inner_text}</sub>"))
+    }
+
+    fn visit_inline_code(&mut self, _: &Context, text: &String) -> Result<String> {
+        let text = sanitize_text(text);
+        Ok(format!("<code>{text}</code>"))
+    }
+
+    fn visit_inline_math(&mut self, _: &Context, tex: &String) -> Result<String> {
+        Ok(to_mathml(tex, false))
+    }
+
+    fn visit_math_block(&mut self, _: &Context, tex: &String, id: &String) -> Result<String> {
+        let id = sanitize_id(id);
+        let link = self.generate_link(&id, ObjectKind::Math);
+
+        let mut result = self.tab();
+        result += format!("<section class='math' id='{id}'>\n").as_str();
+        self.push_tab();
+            result += self.tab().as_str();
             result += format!("{link}\n").as_str();
-            
-            result += self.common.tab().as_str();
-            result += format!("<pre><code class='language-{lang}'>{body}</code></pre>\n").as_str();
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
+            result += self.tab().as_str();
+            result += format!("{}\n", to_mathml(tex, true)).as_str();
+        self.pop_tab();
+        result += self.tab().as_str();
         result += "</section>\n";
 
         Ok(result)
-        */
     }
 
-    fn convert_image(&mut self, src: &String, alt: &String, id: &String) -> Result<String> {
-        self.common.convert_image(src, alt, id)
-        /*
-        let id = Self::generate_id(alt, || self.generate_missing_image_id());
-        let link = self.element_link(&id, None, None);
+    fn visit_codeblock(&mut self, ctx: &Context, block: &CodeBlock, id: &String) -> Result<String> {
+        let title = ctx.listing_title(block, id);
+        let id = sanitize_id(id);
+        let lang = if block.lang.is_empty() { "plaintext".to_string() } else { sanitize_id(&block.lang) };
+        let link = self.generate_link(&id, ObjectKind::CodeBlock);
+
+        let tokens = match self.theme {
+            Some(_) => highlight(&block.lang, &block.code),
+            None => highlight("", &block.code),
+        };
+        let body = if block.has_line_markup() {
+            let mut lines: Vec<String> = vec![];
+            for (index, (line, tokens)) in block.code.split('\n').zip(split_lines(&tokens)).enumerate() {
+                let mut class = String::from("line");
+                if block.is_highlighted(index + 1) { class += " highlighted" }
+                if block.diff && line.starts_with('+') { class += " inserted" }
+                if block.diff && line.starts_with('-') { class += " deleted" }
+                let number = match block.start_line {
+                    Some(start) => format!(" data-line='{}'", start + index),
+                    None => String::new(),
+                };
+                lines.push(format!("<span class='{class}'{number}>{}</span>", highlighted_html(&tokens, None)));
+            }
+            lines.join("\n")
+        } else {
+            highlighted_html(&tokens, None)
+        };
+        let numbered = if block.start_line.is_some() { " class='line-numbers'" } else { "" };
+
+        let mut result = self.tab();
+        result += format!("<section class='code-block' id='{id}'>\n").as_str();
+        self.push_tab();
+            result += self.tab().as_str();
+            result += format!("{link}\n").as_str();
+
+            if let Some(title) = &title {
+                result += self.tab().as_str();
+                result += format!("<div class='code-title'>{}</div>\n", sanitize_text(title)).as_str();
+            }
+
+            result += self.tab().as_str();
+            result += format!("<pre{numbered}><code class='language-{lang}'>{body}</code></pre>\n").as_str();
+        self.pop_tab();
+        result += self.tab().as_str();
+        result += "</section>\n";
 
-        let mut result = self.common.tab();
+        Ok(result)
+    }
+
+    fn visit_image(&mut self, ctx: &Context, image: &Image, id: &String) -> Result<String> {
+        let caption = match (&image.caption, ctx.figure(id)) {
+            (Some(caption), Some(figure)) => Some(format!("<span class='figure-number'>{}:</span> {}", figure.label(), self.visit_inline(ctx, caption)?)),
+            (Some(caption), None) => Some(self.visit_inline(ctx, caption)?),
+            (None, _) => None,
+        };
+        let id = sanitize_id(id);
+        let link = self.generate_link(&id, ObjectKind::Image);
+        let src = sanitize_id(&image.src);
+        let alt = sanitize_id(&to_plain_text(&image.alt));
+        let size = image_size_attributes(&image.size);
+
+        let mut result = self.tab();
         result += format!("<section class='image' id='{id}'>\n").as_str();
-        self.common.push_tab();
-            result += self.common.tab().as_str();
+        self.push_tab();
+            result += self.tab().as_str();
             result += format!("{link}\n").as_str();
-            
-            result += self.common.tab().as_str();
-            result += format!("<img onclick='makePopup(this)' src='{src}' alt='{alt}'></img>\n").as_str();
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
+
+            let img = format!("<img onclick='makePopup(this)' src='{src}' alt='{alt}'{size}></img>");
+            if let Some(caption) = caption {
+                result += self.tab().as_str();
+                result += "<figure>\n";
+                self.push_tab();
+                    result += self.tab().as_str();
+                    result += format!("{img}\n").as_str();
+                    result += self.tab().as_str();
+                    result += format!("<figcaption>{caption}</figcaption>\n").as_str();
+                self.pop_tab();
+                result += self.tab().as_str();
+                result += "</figure>\n";
+            } else {
+                result += self.tab().as_str();
+                result += format!("{img}\n").as_str();
+            }
+        self.pop_tab();
+        result += self.tab().as_str();
         result += "</section>\n";
 
         Ok(result)
-        */
-    }
-
-    fn convert_quote(&mut self, lines: &Vec<BlogBody>, id: &String) -> Result<String> {
-        self.common.convert_quote(lines, id)
-        /*
-        let id = Self::generate_id(&"".to_string(), || self.generate_missing_quote_id());
-        let link = self.element_link(&id, None, None);
-        
-        let mut quote_elements : Vec<String> = vec![];
-        for elem in lines {
-            let text = self.convert_element(elem)?;
-            quote_elements.push(format!("{text}<br/>"));
-        }
-        let text = quote_elements.join("\n");
+    }
+
+    fn visit_video(&mut self, _: &Context, video: &Media, id: &String) -> Result<String> {
+        let src = sanitize_id(&video.src);
+        let title = sanitize_id(&to_plain_text(&video.title));
+        let size = image_size_attributes(&video.size);
+        let fallback = if title.is_empty() { &src } else { &title };
+        let player = format!("<video controls preload='metadata' src='{src}' title='{title}'{size}><a href='{src}'>{fallback}</a></video>");
+        Ok(self.convert_media(id, "video", player))
+    }
+
+    fn visit_audio(&mut self, _: &Context, audio: &Media, id: &String) -> Result<String> {
+        let src = sanitize_id(&audio.src);
+        let title = sanitize_id(&to_plain_text(&audio.title));
+        let size = image_size_attributes(&audio.size);
+        let fallback = if title.is_empty() { &src } else { &title };
+        let player = format!("<audio controls preload='metadata' src='{src}' title='{title}'{size}><a href='{src}'>{fallback}</a></audio>");
+        Ok(self.convert_media(id, "audio", player))
+    }
+
+    fn visit_iframe(&mut self, _: &Context, iframe: &Media, id: &String) -> Result<String> {
+        let src = sanitize_id(&iframe.src);
+        let title = sanitize_id(&to_plain_text(&iframe.title));
+        // without a size it gets the 16:9 youtube uses for its own embeds
+        let size = match iframe.size {
+            ImageSize::None => " width='560' height='315'".to_string(),
+            _ => image_size_attributes(&iframe.size),
+        };
+        let player = format!("<iframe src='{src}' title='{title}'{size} loading='lazy' referrerpolicy='no-referrer' \
+            sandbox='allow-scripts allow-same-origin allow-popups allow-presentation' allow='fullscreen; picture-in-picture'></iframe>");
+        Ok(self.convert_media(id, "embed", player))
+    }
 
-        let mut result = self.common.tab();
+    fn visit_quote(&mut self, ctx: &Context, quote: &Quote, id: &String) -> Result<String> {
+        let id = sanitize_id(id);
+        let link = self.generate_link(&id, ObjectKind::Quote);
+
+        let mut result = self.tab();
         result += format!("<section class='quote' id='{id}'>\n").as_str();
-        self.common.push_tab();
-            result += self.common.tab().as_str();
+        self.push_tab();
+            result += self.tab().as_str();
             result += format!("{link}\n").as_str();
-            
-            result += self.common.tab().as_str();
-            result += format!("<div class='quote-line'></div><blockquote class='quote-text'>{text}</blockquote>\n").as_str();
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
+
+            result += self.tab().as_str();
+            result += "<div class='quote-line'></div>\n";
+            result += self.tab().as_str();
+            result += "<blockquote class='quote-text'>\n";
+            self.push_tab();
+            for (n, Spanned { node: element, .. }) in quote.body.iter().enumerate() {
+                result += self.visit_element(ctx, element, &format!("{id}-{n}"))?.as_str();
+            }
+            self.pop_tab();
+            result += self.tab().as_str();
+            result += "</blockquote>\n";
+
+            if let Some(attribution) = &quote.attribution {
+                let attribution = self.visit_inline(ctx, attribution)?;
+                result += self.tab().as_str();
+                result += format!("<p class='quote-attribution'>— {attribution}</p>\n").as_str();
+            }
+        self.pop_tab();
+        result += self.tab().as_str();
         result += "</section>\n";
 
         Ok(result)
-        */
     }
 
-    fn convert_list(&mut self, list: &Vec<BlogBody>, id: &String) -> Result<String> {
-        self.common.convert_list(list, id)
-        /*
-        let id = Self::generate_id(&"".to_string(), ||self.generate_missing_list_id());
-        let link = self.element_link(&id, None, None);
+    fn visit_callout(&mut self, ctx: &Context, callout: &Callout, id: &String) -> Result<String> {
+        let id = sanitize_id(id);
+        let link = self.generate_link(&id, ObjectKind::Callout);
+        let kind = sanitize_id(&callout.kind);
+        let open = if callout.collapsed { "" } else { " open" };
+        let title = self.visit_inline(ctx, &callout.title)?;
+
+        let mut result = self.tab();
+        result += format!("<section class='callout' id='{id}'>\n").as_str();
+        self.push_tab();
+            result += self.tab().as_str();
+            result += format!("{link}\n").as_str();
 
-        let mut result = self.common.tab();
+            result += self.tab().as_str();
+            result += format!("<details class='callout-box callout-{kind}'{open}>\n").as_str();
+            self.push_tab();
+                result += self.tab().as_str();
+                result += format!("<summary class='callout-title'>{title}</summary>\n").as_str();
+                for (n, Spanned { node: element, .. }) in callout.body.iter().enumerate() {
+                    result += self.visit_element(ctx, element, &format!("{id}-{n}"))?.as_str();
+                }
+            self.pop_tab();
+            result += self.tab().as_str();
+            result += "</details>\n";
+        self.pop_tab();
+        result += self.tab().as_str();
+        result += "</section>\n";
+
+        Ok(result)
+    }
+
+    fn visit_list(&mut self, ctx: &Context, list: &[ListItem], id: &String) -> Result<String> {
+        let id = sanitize_id(id);
+        let link = self.generate_link(&id, ObjectKind::List);
+
+        let mut result = self.tab();
         result += format!("<section class='list' id='{id}'>\n").as_str();
-        self.common.push_tab();
-            result += self.common.tab().as_str();
+        self.push_tab();
+            result += self.tab().as_str();
             result += format!("{link}\n").as_str();
-            
-            result += self.common.tab().as_str();
-            result += "<ul>";
-            self.common.push_tab();
-            for elem in list {
-                let text = self.convert_element(elem)?;
-                result += self.common.tab().as_str();
-                result += format!("<li>{text}</li>\n").as_str();
-            }
-            self.common.pop_tab();
-            result += self.common.tab().as_str();
-            result += "</ul>";
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
+            result += self.convert_list_items(ctx, list, &id)?.as_str();
+        self.pop_tab();
+        result += self.tab().as_str();
+        result += "</section>\n";
+
+        Ok(result)
+    }
+
+    fn visit_table(&mut self, ctx: &Context, table: &Table, id: &String) -> Result<String> {
+        let figure = ctx.figure(id);
+        let id = sanitize_id(id);
+        let link = self.generate_link(&id, ObjectKind::Table);
+
+        let mut result = self.tab();
+        result += format!("<section class='table' id='{id}'>\n").as_str();
+        self.push_tab();
+            result += self.tab().as_str();
+            result += format!("{link}\n").as_str();
+
+            result += self.tab().as_str();
+            result += "<table>\n";
+            self.push_tab();
+                if let Some(figure) = figure {
+                    result += self.tab().as_str();
+                    result += format!("<caption><span class='figure-number'>{}</span></caption>\n", figure.label()).as_str();
+                }
+                result += self.tab().as_str();
+                result += "<thead>\n";
+                result += self.convert_table_row(ctx, &table.header, &table.alignments, "th")?.as_str();
+                result += self.tab().as_str();
+                result += "</thead>\n";
+
+                result += self.tab().as_str();
+                result += "<tbody>\n";
+                for row in &table.rows {
+                    result += self.convert_table_row(ctx, row, &table.alignments, "td")?.as_str();
+                }
+                result += self.tab().as_str();
+                result += "</tbody>\n";
+            self.pop_tab();
+            result += self.tab().as_str();
+            result += "</table>\n";
+        self.pop_tab();
+        result += self.tab().as_str();
         result += "</section>\n";
 
         Ok(result)
-        */
     }
 
-    fn convert_paragraph(&mut self, text: &Box<BlogBody>, id: &String) -> Result<String> {
-        self.common.convert_paragraph(text, id)
-        /*
-        let paragraph = self.convert_element(text)?;
-        let id = Self::generate_id(&paragraph, || self.generate_missing_id());
-        let link = self.element_link(&id, None, None);
+    fn visit_definition_list(&mut self, ctx: &Context, list: &[Definition], id: &String) -> Result<String> {
+        let id = sanitize_id(id);
+        let link = self.generate_link(&id, ObjectKind::DefinitionList);
+
+        let mut result = self.tab();
+        result += format!("<section class='definitions' id='{id}'>\n").as_str();
+        self.push_tab();
+            result += self.tab().as_str();
+            result += format!("{link}\n").as_str();
+            result += self.convert_definitions(ctx, list.iter().map(|definition| (definition, None)))?.as_str();
+        self.pop_tab();
+        result += self.tab().as_str();
+        result += "</section>\n";
 
-        let mut result = self.common.tab();
+        Ok(result)
+    }
+
+    fn visit_paragraph(&mut self, ctx: &Context, text: &Element, id: &String) -> Result<String> {
+        let id = sanitize_id(id);
+        let paragraph = self.visit_inline(ctx, text)?;
+        let link = self.generate_link(&id, ObjectKind::Paragraph);
+
+        let mut result = self.tab();
         result += format!("<section id='{id}'>\n").as_str();
-        self.common.push_tab();
-            result += self.common.tab().as_str();
+        self.push_tab();
+            result += self.tab().as_str();
             result += format!("{link}\n").as_str();
-            
-            result += self.common.tab().as_str();
+
+            result += self.tab().as_str();
             result += "<p>\n";
-            self.common.push_tab();
-            
+            self.push_tab();
+
             for line in paragraph.trim_end().lines() {
-                result += self.common.tab().as_str();
+                result += self.tab().as_str();
                 result += line;
                 result.push('\n');
             }
 
-            self.common.pop_tab();
-            result += self.common.tab().as_str();
+            self.pop_tab();
+            result += self.tab().as_str();
             result += "</p>\n";
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
+        self.pop_tab();
+        result += self.tab().as_str();
         result += "</section>\n";
 
         Ok(result)
-        */
-    }
-
-    fn convert_text(&mut self, text: &String) -> Result<String> {
-        self.common.convert_text(text)
     }
 
-    fn convert_span(&mut self, span: &Span) -> Result<String> {
-        self.common.convert_span(span)
-        /*
+    fn visit_span(&mut self, ctx: &Context, span: &Span) -> Result<String> {
         let mut result = String::new();
         for elem in &span.elements {
-            result += if let BlogBody::Text(text) = elem {
-                text.to_string()
+            result += if let Element::Text(text) = elem {
+                sanitize_text(text)
             } else {
-                self.convert_element(elem)?
+                self.visit_inline(ctx, elem)?
             }.as_str()
         }
         Ok(result)
-        */
-    }
-    
-    fn convert_citation(&mut self, id: &String) -> Result<String> {
-        self.common.convert_citation(id)
-        /*
-        if let Some(reference) = self.references.get_mut(id) {
-            let num = reference.times_used;
-            reference.times_used += 1;
-            if self.header.hide_references {
+    }
+
+    fn visit_citation(&mut self, ctx: &Context, id: &String) -> Result<String> {
+        if let Some(reference) = ctx.reference(id) {
+            let num = self.use_reference(id);
+            if ctx.meta().hide_references {
                 Ok("".into())
             } else {
-                let text = to_citation(&reference.def);
+                let text = to_citation(reference);
                 let mut result = String::new();
                 result += "<cite>";
                 result += format!("<a id='{id}-{num}' href='#{id}' onclick='backref(\"{id}\", \"{id}-{num}\")'>").as_str();
@@ -665,88 +1086,245 @@ impl PMDSerializer for PMDHTMLSerializer {
                 Ok(result)
             }
         } else {
-            cprintln!("<y>warning:</> {} has no source", id);
             Ok(format!("(MISSING CITATION)").to_string())
         }
-        */
     }
-    
 
-    fn convert_note(&mut self, id: &String) -> Result<String> {
-        self.common.convert_note(id)
-        /*
-        if self.header.hide_notes {
+    fn visit_note(&mut self, ctx: &Context, id: &String) -> Result<String> {
+        if ctx.meta().hide_notes {
             Ok("".into())
         } else {
-            Ok(format!("<sup><a id='{id}-backref' href='#^{id}'>{id}</a></sup>"))
+            let note_id = ctx.note_id(id);
+            Ok(format!("<sup><a id='{note_id}-backref' href='#^{note_id}'>{id}</a></sup>"))
         }
-        */
     }
-    
-    fn convert_factbox_note(&mut self, factbox: &FactBox, factbox_id: Option<&String>, id: &String) -> Result<String> {
-        self.common.convert_factbox_note(factbox, factbox_id, id)
+
+    fn visit_cross_reference(&mut self, ctx: &Context, kind: FigureKind, id: &String) -> Result<String> {
+        if let Some(figure) = ctx.figure(id) {
+            Ok(format!("<a class='cross-reference' href='#{}'>{}</a>", sanitize_id(id), figure.label()))
+        } else {
+            Ok(format!("<span class='cross-reference missing' style='color: red; background-color: yellow'>(Missing {})</span>", kind.name()))
+        }
+    }
+
+    fn visit_wiki_link(&mut self, ctx: &Context, link: &WikiLink) -> Result<String> {
+        let text = match &link.label {
+            Some(label) => self.visit_inline(ctx, label)?,
+            None => self.text(ctx, &link.target)?,
+        };
+        if let Some(url) = ctx.wiki_link(&link.target) {
+            Ok(format!("<a class='wiki-link' href='{}'>{text}</a>", sanitize_id(url)))
+        } else {
+            Ok(format!("<span class='wiki-link broken'>{text}</span>"))
+        }
     }
 
-    fn convert_toc(&mut self) -> Result<String> {
-        self.common.convert_toc()
-        /*
-        if self.header.toc.is_none() { return Err(anyhow!("")); }
-        let link  = self.element_link(&String::from("table-of-contents"), Some("<h1>§</h1>"), Some("header"));
-        let toc = self.header.toc.clone().unwrap();
+    fn visit_toc(&mut self, ctx: &Context) -> Result<String> {
+        let toc = ctx.meta().toc.as_ref().ok_or(anyhow!("expected a table of content but none was found"))?;
+        let link = self.generate_link(&String::from("table-of-contents"), ObjectKind::Header(1));
         let title = &toc.title;
 
-        let mut result = self.common.tab();
-        
+        let mut result = self.tab();
+
         result += "<section id='table-of-contents'>\n";
-        self.common.push_tab();
-            result += self.common.tab().as_str();
+        self.push_tab();
+            result += self.tab().as_str();
             result += link.as_str();
             result.push('\n');
-            
-            result += self.common.tab().as_str();
+
+            result += self.tab().as_str();
             result += format!("<h1>{title}</h1>\n").as_str();
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
+        self.pop_tab();
+        result += self.tab().as_str();
         result += "</section>\n";
 
-        result += self.common.tab().as_str();
+        result += self.tab().as_str();
         result += "<section>\n";
-        self.common.push_tab();
-            result += self.common.tab().as_str();
+        self.push_tab();
+            result += self.tab().as_str();
             result += "<ul>\n";
-            self.common.push_tab();
-                for (elem, depth) in &toc.headers {
-                    let text = self.convert_element(elem)?;
-                    let id    = Self::generate_id(&text, ||"missing".into());
-                    result += self.common.tab().as_str();
+            self.push_tab();
+
+                for (elem, depth, id) in &toc.headers {
+                    let text = if let box Element::FactBox(fbox) = elem {
+                        fbox.title.clone()
+                    } else {
+                        self.visit_inline(ctx, elem)?
+                    };
+
+                    result += self.tab().as_str();
                     result += format!("<li class='toci-{depth}'><a href='#{id}'>{text}</a></li>\n").as_str();
                 }
-            self.common.pop_tab();
-            result += self.common.tab().as_str();
+            self.pop_tab();
+            result += self.tab().as_str();
             result += "</ul>\n";
 
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
+        self.pop_tab();
+        result += self.tab().as_str();
         result += "</section>\n";
 
         Ok(result)
-        */
     }
 
-    fn convert_page_break(&mut self) -> Result<String> {
-        self.common.convert_page_break()
-        /*
-        let mut result = self.common.tab();
+    fn visit_page_break(&mut self, _: &Context) -> Result<String> {
+        Ok(self.page_break())
+    }
 
-        result += format!("<section class='page-break'>\n").as_str();
-        self.common.push_tab();
-            result += self.common.tab().as_str();
-            result += "<hr>\n";
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
-        result += format!("</section>\n").as_str();
+    fn visit_error(&mut self, _: &Context, raw: &String) -> Result<String> {
+        Ok(format!("<span class=\"parse-error\">{}</span>", sanitize_text(raw)))
+    }
 
-        Ok(result)
-        */
+    fn serialize(&mut self, md: &AssDownDocument) -> Result<String> {
+        let ctx = Context::new(md);
+        let meta = &md.meta;
+        self.times_used = OrderedMap::new();
+        self.theme = code_theme(meta);
+
+        let mut output = String::new();
+        let navbar = self.prepare_navbar();
+        let description = self.get_description(md);
+        let header = self.prepare_html_header(meta, &description);
+        output +=   "<!doctype html>\n";
+        output +=   "<html>\n";
+        self.push_tab();
+
+        output += self.tab().as_str();
+        output +=   "<head>\n";
+        self.push_tab();
+        for line in header.lines() {
+            output += self.tab().as_str();
+            output += line;
+            output += "\n";
+        }
+        self.pop_tab();
+        output += self.tab().as_str();
+        output += "</head>\n";
+
+        output += self.tab().as_str();
+        output += "<body>\n";
+        self.push_tab();
+
+        for line in navbar.lines() {
+            output += self.tab().as_str();
+            output += line;
+            output += "\n";
+        }
+
+        output += self.tab().as_str();
+        output += "<main>\n";
+        self.push_tab();
+
+        {
+            let title    = &meta.title;
+            let subtitle = &meta.subtitle;
+            output += self.tab().as_str();
+            output += "<section class='title'>\n";
+            self.push_tab();
+                output += self.tab().as_str();
+                output += format!("<h1>{title}</h1>\n").as_str();
+
+                output += self.tab().as_str();
+                output += format!("<p class='subtitle'>{subtitle}</p>\n").as_str();
+            self.pop_tab();
+
+            output += self.tab().as_str();
+            output += format!("</section>\n").as_str();
+        }
+
+        output += self.visit_body(&ctx, &md.body)?.as_str();
+
+        if !(md.notes.is_empty() || meta.hide_notes) {
+            output += self.notes_header(&md.notes_id, &meta.notes_title).as_str();
+            for (key, val) in &md.notes {
+                output += self.note(&ctx, key, key, val)?.as_str();
+            }
+        }
+
+        if !(md.glossary.is_empty() || meta.hide_glossary) {
+            output += self.glossary(&ctx, md)?.as_str();
+        }
+
+        if !(md.references.is_empty() || meta.hide_references) {
+            output += self.bibliography(md).as_str();
+        }
+
+        if !md.backlinks.is_empty() {
+            output += self.backlinks(md).as_str();
+        }
+
+        self.pop_tab();
+        output += self.tab().as_str();
+        output += "</main>\n";
+
+        output += self.popup().as_str();
+
+        self.pop_tab();
+        output += self.tab().as_str();
+        output +=   "</body>\n";
+
+        self.pop_tab();
+        output += self.tab().as_str();
+        output +=   "</html>\n";
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::wiki_links::link_documents;
+
+    // the html of every block in the body on its own
+    fn blocks(text: &str) -> Vec<String> {
+        let (document, _) = parse(&text.to_string(), None).unwrap();
+        let ctx = Context::new(&document);
+        document.body.iter().map(|(element, id, _)| PMDHTMLSerializer::new("post").visit_element(&ctx, element, id).unwrap()).collect()
+    }
+
+    #[test]
+    fn paragraph() {
+        assert_eq!(blocks("some **bold** and `code` with [a link](https://example.com)\n\n"), [concat!(
+            "<section id='some-bold-and-code-with-a-link'>\n",
+            "    <a class='paragraph' href='#some-bold-and-code-with-a-link' aria-hidden='true'>¶</a>\n",
+            "    <p>\n",
+            "        some <b>bold</b> and <code>code</code> with <a class='inline-link' href='https://example.com'>a link</a>\n",
+            "    </p>\n",
+            "</section>\n",
+        )]);
+    }
+
+    #[test]
+    fn escaping() {
+        let blocks = blocks("# It's <here> & there\n\na <b> & 'q' **bold** `x<y`\n\n");
+        assert_eq!(blocks[0], concat!(
+            "<section id='It&#39;s-&lt;here&gt;-&amp;-there'>\n",
+            "    <a class='header' href='#It&#39;s-&lt;here&gt;-&amp;-there' aria-hidden='true'><h1>§</h1></a>\n",
+            "    <h1>It's &lt;here&gt; &amp; there</h1>\n",
+            "</section>\n",
+        ));
+        // text next to other inline elements is escaped just like text on its own
+        assert_eq!(blocks[1], concat!(
+            "<section id='a-&lt;b&gt;-&amp;-&#39;q&#39;-bold-x&lt;y'>\n",
+            "    <a class='paragraph' href='#a-&lt;b&gt;-&amp;-&#39;q&#39;-bold-x&lt;y' aria-hidden='true'>¶</a>\n",
+            "    <p>\n",
+            "        a &lt;b&gt; &amp; 'q' <b>bold</b> <code>x&lt;y</code>\n",
+            "    </p>\n",
+            "</section>\n",
+        ));
+    }
+
+    #[test]
+    fn wiki_link_escaping() {
+        let post = |body: &str| parse(&format!("---\ntitle: post\nurl: https://example.com\n---\n{body}\n"), None).unwrap().0;
+        let mut documents = vec![
+            ("first".to_string(), post("[[a<img src=x onerror=alert(1)>]] and [[second#x' onclick='alert(1)]]")),
+            ("second".to_string(), post("the other post")),
+        ];
+        link_documents(&mut documents);
+        let document = &documents[0].1;
+        let Element::Paragraph(text) = &document.body[0].0 else { panic!("expected a paragraph") };
+        let output = PMDHTMLSerializer::new("first").visit_inline(&Context::new(document), text).unwrap();
+        assert_eq!(output.trim_end(), "<span class='wiki-link broken'>a&lt;img src=x onerror=alert(1)&gt;</span> and <a class='wiki-link' href='https://example.com/blog/second.html#x&#39;-onclick=&#39;alert(1)'>second#x' onclick='alert(1)</a>");
     }
 }
@@ -1,80 +1,15 @@
-#[cfg(not(feature = "wasm"))]
-use color_print::cprintln;
-use anyhow::{Context, Result, anyhow};
-use std::ops::{Deref, DerefMut};
-
-use crate::{
+use crate::parser::{
     any_non_empty, 
     bibliograph_name, 
-    ordered_map::OrderedMap, 
-    paws_markdown::BlogBody, 
-    to_citation, 
-    Alternative, 
-    BlogHeader, 
-    FactBox, 
-    PMDSerializer, 
-    PawsMarkdown, 
+    Element, 
+    FrontmatterHelper,
+    ImageSize,
+    MetaData,
     ReferenceDefinition, 
-    Span
+    Unit,
 };
-
-pub struct Reference<T> {
-    pub def: T,
-    pub times_used: usize
-}
-
-impl<T> Reference<T> {
-    pub fn new(def: T) -> Self {
-        Self { def, times_used: 0 }
-    }
-}
-
-pub struct Weak<T> {
-    data: *mut T,
-}
-
-impl<T> Weak<T> {
-    pub fn new(value: &mut T) -> Self {
-        Self { data: value as *mut T }
-    }
-    #[allow(dead_code)]
-    pub fn from_ref(value: &T) -> Self {
-        Self { data: (value as *const T) as *mut T}
-    }
-    pub fn uninit() -> Self {
-        Self { data: std::ptr::null::<T>() as *mut T }
-    }
-    pub fn launder(ptr: &Self) -> Self {
-        Self { data: ptr.data }
-    }
-
-    #[allow(dead_code)]
-    pub fn is_null(&self) -> bool {
-        self.data == std::ptr::null_mut::<T>()
-    }
-
-    pub fn as_ref(&self) -> &T {
-        unsafe { self.data.as_ref_unchecked() }
-    }
-
-    pub fn as_mut(&self) -> &mut T {
-        unsafe { self.data.as_mut_unchecked() }
-    } 
-}
-
-impl<T> Deref for Weak<T> {
-    type Target = T;
-
-    fn deref(&self) -> &Self::Target {
-        self.as_ref()
-    }
-}
-
-impl<T> DerefMut for Weak<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        self.as_mut()
-    }
-}
+use crate::parser::math::{parse_tex, MathNode, MathVariant};
+use crate::highlight::{theme, Theme, Token, TokenKind, DEFAULT_THEME};
 
 #[derive(Clone)]
 pub enum ObjectKind {
@@ -82,838 +17,16 @@ pub enum ObjectKind {
     Paragraph,
     CodeBlock,
     Quote,
+    Callout,
     Image,
+    Media,
     List,
+    Table,
+    DefinitionList,
+    Math,
     FactBox,
 }
 
-pub trait PMDSharedHTMLSerializer: PMDSerializer {
-    const LINK_ELEMENTS: bool = true;
-    const POPUPS: bool = true;
-    const SHOW_BACKREFS: bool = true;
-
-    // rust is dumb as shit so we have to copy the header
-    fn get_header(&self) -> &BlogHeader;
-    fn get_description(&mut self, md: &PawsMarkdown) -> Result<String>;
-    fn prepare_html_header(&mut self, description: &String) -> String;
-    fn convert_body(&mut self, md: &PawsMarkdown) -> Result<String>;
-    fn generate_link(&mut self, id: &String, kind: ObjectKind) -> String;
-
-    fn notes_id(&mut self) -> String;
-    fn bibliography_id(&mut self) -> String;
-
-    fn references(&mut self) -> &OrderedMap<String, Reference<ReferenceDefinition>>;
-    
-    fn mut_references(&mut self) -> &mut OrderedMap<String, Reference<ReferenceDefinition>>;
-    
-    fn get_mut_reference<T: AsRef<str>>(&mut self, key: T) -> Option<&mut Reference<ReferenceDefinition>>;
-
-    fn get_reference<T: AsRef<str>>(&mut self, key: T) -> Option<&Reference<ReferenceDefinition>>;
-}
-
-pub struct PMDHTML<T: PMDSharedHTMLSerializer> {
-    pub filename: String,
-    pub parent: Weak<T>,
-    num_tabs:   usize,
-    current_factbox: Option<(FactBox, Option<String>)>
-}
-
-impl<T: PMDSharedHTMLSerializer> PMDHTML<T> {
-    pub fn new(filename: &str, parent: &mut T) -> Self {
-        Self { 
-            filename: filename.into(),
-            parent: Weak::new(parent),
-            num_tabs:   0,
-            current_factbox: None,
-        }
-    }
-
-    pub fn uninit() -> Self {
-        Self { 
-            filename: String::new(),
-            parent: Weak::uninit(),
-            num_tabs:   0,
-            current_factbox: None,
-        }
-    }
-    
-    /*
-    pub fn generate_id<F: FnMut()->String>(text: &String, kind: ObjectKind, mut default_generator: F) -> String {
-        let result = if text.len() == 0 { default_generator() } else {
-            let mut len = 0;
-            for c in text.chars() {
-                if c.is_ascii_punctuation() { 
-                    break
-                } else {
-                    len += c.len_utf8();
-                }
-            }
-            if len == 0 || text[0..len].trim_start().trim_end().len() == 0 { default_generator() } else {
-                let mut result = String::new();
-                for c in text[0..len].trim_start().trim_end().chars() {
-                    result.push(if c.is_whitespace() { '-' } else { c })
-                }
-                result.remove_matches(|x: char| x != '-' && x.is_ascii_punctuation());
-                sanitize_text(&result.to_lowercase())
-            }
-        };
-
-        match kind {
-            ObjectKind::Header(depth) => format!("{result}-h{depth}"),
-            ObjectKind::Paragraph => format!("{result}-p"),
-            ObjectKind::CodeBlock => format!("{result}-cb"),
-            ObjectKind::Quote => format!("{result}-q"),
-            ObjectKind::Image => format!("{result}-img"),
-            ObjectKind::List => format!("{result}-list"),
-            ObjectKind::FactBox => format!("{result}-factbox"),
-        }
-    }
-    */
-    
-    pub fn push_tab(&mut self) { self.num_tabs += 1; }
-    pub fn pop_tab(&mut self) { if self.num_tabs > 0 { self.num_tabs -= 1; } }
-    pub fn tab(&mut self) -> String {
-        if self.num_tabs == 0 { "".to_string() }
-        else {
-            let mut result = String::new();
-            for _ in 0..(self.num_tabs) {
-                result += "    ";
-            }
-            result
-        }
-    }
-
-    pub fn current_factbox(&mut self) -> Option<(FactBox, Option<String>)> {
-        self.current_factbox.clone()
-    }
-
-    pub fn html(
-        &mut self, md: &PawsMarkdown, navbar: Option<&String>, references: &OrderedMap<String, ReferenceDefinition>, notes: &OrderedMap<String, BlogBody>) 
-        -> Result<String> 
-    {
-        let mut output = String::new();
-        let laundered_parent = Weak::launder(&self.parent);
-        let description = self.parent.get_description(md)?;
-        let header = self.parent.prepare_html_header(&description);
-        let blog_header = laundered_parent.get_header();
-        output +=   "<!doctype html>\n";
-        output +=   "<html>\n";
-        self.push_tab();
-
-        output += self.tab().as_str();
-        output +=   "<head>\n";
-        self.push_tab();
-        for line in header.lines() {
-            output += self.tab().as_str();
-            output += line;
-            output += "\n";
-        }
-        self.pop_tab();
-        output += self.tab().as_str();
-        output += "</head>\n";
-
-        output += self.tab().as_str();
-        output += "<body>\n";
-        self.push_tab();
-
-        if let Some(navbar) = navbar {
-            for line in navbar.lines() {
-                output += self.tab().as_str();
-                output += line;
-                output += "\n";
-            }
-        }
-
-        output += self.tab().as_str();
-        output += "<main>\n";
-        self.push_tab();
-
-        {
-            let title    = &blog_header.title;
-            let subtitle = &blog_header.subtitle;
-            output += self.tab().as_str();
-            output += "<section class='title'>\n";
-            self.push_tab();
-                output += self.tab().as_str();
-                output += format!("<h1>{title}</h1>\n").as_str();
-                
-                output += self.tab().as_str();
-                output += format!("<p class='subtitle'>{subtitle}</p>\n").as_str();
-            self.pop_tab();
-
-            output += self.tab().as_str();
-            output += format!("</section>\n").as_str();
-        }
-
-        output += self.parent.convert_body(md)?.as_str();
-
-        if !(notes.is_empty() || blog_header.hide_notes) {
-            let id = self.parent.notes_id();
-            let title = &blog_header.notes_title;
-            let link = if T::LINK_ELEMENTS {
-                self.parent.generate_link(&id, ObjectKind::Header(1))
-            } else {
-                "".into()
-            };
-
-            
-            output += self.tab().as_str();
-            output += format!("<section class='page-break'>\n").as_str();
-            self.push_tab();
-                output += self.tab().as_str();
-                output += "<hr>\n";
-            self.pop_tab();
-            output += self.tab().as_str();
-            output += format!("</section>\n").as_str();
-                
-            output += self.tab().as_str();
-            output += format!("<section class='notes' id='{id}'>\n").as_str();
-            self.push_tab();
-            if T::LINK_ELEMENTS {
-                output += self.tab().as_str();
-                output += link.as_str();
-                output.push('\n');
-            }
-                
-                output += self.tab().as_str();
-                output += format!("<h1>{title}</h1>\n").as_str();
-            self.pop_tab();
-            output += self.tab().as_str();
-            output += format!("</section>\n").as_str();
-                
-            for (key, val) in notes {
-                let result = self.parent.convert_element((val, &String::new()))?;
-                let link = format!("<a href='#^{key}'>^{key}:</a>");
-            
-                output += self.tab().as_str();
-                output += format!("<section class='note' id=\"^{key}\">").as_str();
-                output.push('\n');
-                self.push_tab();
-            
-                    output += self.tab().as_str();
-                    output += "<sup>";
-                    self.push_tab();
-                        output += link.as_str();
-                        output.push('\n');
-                        
-                        output += self.tab().as_str();
-                        output += result.as_str();
-                        output.push('\n');
-                        
-                    if T::SHOW_BACKREFS {
-                        output += self.tab().as_str();
-                        output += format!("<a href=\"#{key}-backref\">").as_str();
-                        output += "↩";
-                        output += "</a>";
-                        output.push('\n');
-                    }
-                    self.pop_tab();
-                    output += self.tab().as_str();
-                    output += "</sup>";
-                    output.push('\n');
-            
-                self.pop_tab();
-                output += self.tab().as_str();
-                output += "</section>\n";
-            }
-        }
-
-        if !(references.is_empty() || blog_header.hide_references) {
-            let id = self.parent.bibliography_id();
-            let title = &blog_header.bibliography_title;
-            let link = if T::LINK_ELEMENTS {
-                self.parent.generate_link(&id, ObjectKind::Header(1))
-            } else {
-                "".into()
-            };
-
-            output += self.tab().as_str();
-            output += format!("<section class='page-break'>\n").as_str();
-            self.push_tab();
-                output += self.tab().as_str();
-                output += "<hr>\n";
-            self.pop_tab();
-            output += self.tab().as_str();
-            output += format!("</section>\n").as_str();
-
-            output += self.tab().as_str();
-            output += format!("<section id='{id}'>\n").as_str();
-            self.push_tab();
-            if T::LINK_ELEMENTS {
-                output += self.tab().as_str();
-                output += link.as_str();
-                output.push('\n');
-            }
-                
-                output += self.tab().as_str();
-                output += format!("<h1>{title}</h1>\n").as_str();
-            self.pop_tab();
-            output += self.tab().as_str();
-            output += format!("</section>\n").as_str();
-
-            for (key, val) in self.parent.references() {
-                if val.times_used == 0 {
-                    #[cfg(not(feature = "wasm"))]
-                    cprintln!("<y>warning:</> reference '{}' is not used and will not be included", key);
-                }
-            }
-            
-            for (key, val) in references {
-                let reference = self.parent.get_reference(key).unwrap();
-                if reference.times_used == 0 { continue; }
-
-                output += self.tab().as_str();
-                output += format!("<section class='citation' id='{key}'>\n").as_str();
-                self.push_tab();
-                    output += self.tab().as_str();
-                    output += format!("<p>\n").as_str();
-                    self.push_tab();
-                    
-                        output += to_html_bibliography(val).as_str();
-                        output.push('\n');
-                    
-                    if T::SHOW_BACKREFS {
-                        output += self.tab().as_str();
-                        output += format!("<a href=''>").as_str();
-                        output += "↩";
-                        output += "</a>";
-                        output.push('\n');
-                    }
-                    self.pop_tab();
-                    output += self.tab().as_str();
-                    output += format!("</p>\n").as_str();
-
-                self.pop_tab();
-                output += self.tab().as_str();
-                output += "</section>\n";
-            }
-        }        
-
-        self.pop_tab();
-        output += self.tab().as_str();
-        output += "</main>\n";
-
-        if T::POPUPS {
-            output += self.tab().as_str();
-            output +="<div id='popup' class='popup-hidden' aria-hidden='true'>\n";
-            self.push_tab();
-            
-                output += self.tab().as_str();
-                output +="<div class='popup-clickable-region' onclick='close_popup(this)' ></div>\n";
-                output += self.tab().as_str();
-                output +="<div class='popup-container'>\n";
-                self.push_tab();
-            
-                    output += self.tab().as_str();
-                    output +="<img id='popup-image'>\n";
-                    output += self.tab().as_str();
-                    output +="<p id='popup-caption'></p>\n";
-            
-                self.pop_tab();
-                output += self.tab().as_str();
-                output +="</div>\n";
-                self.pop_tab();
-
-            output += self.tab().as_str();
-            output +="</div>\n";
-        }
-
-        self.pop_tab();
-        output += self.tab().as_str();
-        output +=   "</body>\n";
-
-        self.pop_tab();
-        output += self.tab().as_str();
-        output +=   "</html>\n";
-
-        Ok(output)
-    }
-    
-    pub fn convert_factbox(&mut self, factbox: &FactBox, id: &String) -> Result<String> {
-        let id = sanitize_id(id);
-        let link = if T::LINK_ELEMENTS { self.parent.generate_link(&id, ObjectKind::FactBox) } else { String::new() };
-        let title = &factbox.title;
-        let laundered_parent = Weak::launder(&self.parent);
-        let header = laundered_parent.get_header();
-
-        let mut output = self.tab();
-        output += format!("<section id='{id}'>\n").as_str();
-        self.push_tab();
-        if T::LINK_ELEMENTS {
-            output += self.tab().as_str();
-            output += format!("{link}\n").as_str();
-        }
-            
-            output += self.tab().as_str();
-            output += "<article class='factbox'>\n";
-            self.push_tab();
-
-                output += self.tab().as_str();
-                output += "<header class='factbox-header'>\n";
-                self.push_tab();
-
-                    output += self.tab().as_str();
-                    output += format!("<h2>{title}</h2>\n").as_str();
-
-                self.pop_tab();
-                output += self.tab().as_str();
-                output += "</header>\n";
-                
-                output += self.tab().as_str();
-                output += "<section class='factbox-content'>\n";
-                self.push_tab();
-
-                self.current_factbox = Some((factbox.clone(), Some(id.clone())));
-
-                for element in self.parent.convert_factbox_elements(factbox, Some(&id))? {
-                    output += element.as_str();
-                }
-                
-                self.current_factbox = None;
-
-
-                if !(factbox.notes.is_empty() || header.hide_notes) {
-                    let notes_id = self.parent.notes_id();
-                    let id = format!("{id}-{notes_id}");
-                    let title = header.notes_title.clone();
-                    let link = if T::LINK_ELEMENTS { self.parent.generate_link(&id, ObjectKind::Header(1)) } else { String::new() };
-                
-                    
-                    output += self.tab().as_str();
-                    output += format!("<section class='page-break'>\n").as_str();
-                    self.push_tab();
-                        output += self.tab().as_str();
-                        output += "<hr>\n";
-                    self.pop_tab();
-                    output += self.tab().as_str();
-                    output += format!("</section>\n").as_str();
-                        
-                    output += self.tab().as_str();
-                    output += format!("<section class='notes' id='{id}'>\n").as_str();
-                    self.push_tab();
-                    if T::LINK_ELEMENTS {
-                        output += self.tab().as_str();
-                        output += link.as_str();
-                        output.push('\n');
-                    }
-                        
-                        output += self.tab().as_str();
-                        output += format!("<h1>{title}</h1>\n").as_str();
-                    self.pop_tab();
-                    output += self.tab().as_str();
-                    output += format!("</section>\n").as_str();
-                        
-                    for (key, (val, note_id)) in &factbox.notes {
-                        let result = self.parent.convert_element(no_id!(val))?;
-                        let link = format!("<a href='#^{note_id}'>^{key}:</a>");
-                    
-                        output += self.tab().as_str();
-                        output += format!("<section class='note' id=\"^{note_id}\">").as_str();
-                        output.push('\n');
-                        self.push_tab();
-                    
-                            output += self.tab().as_str();
-                            output += "<sup>";
-                            self.push_tab();
-                                output += link.as_str();
-                                output.push('\n');
-                                
-                                output += self.tab().as_str();
-                                output += result.as_str();
-                                output.push('\n');
-                                
-                            if T::SHOW_BACKREFS {
-                                output += self.tab().as_str();
-                                output += format!("<a href=\"#{note_id}-backref\">").as_str();
-                                output += "↩";
-                                output += "</a>";
-                                output.push('\n');
-                            }
-                            self.pop_tab();
-                            output += self.tab().as_str();
-                            output += "</sup>";
-                            output.push('\n');
-                    
-                        self.pop_tab();
-                        output += self.tab().as_str();
-                        output += "</section>\n";
-                    }
-                }
-
-                self.pop_tab();
-                output += self.tab().as_str();
-                output += "</section>\n";
-            
-            self.pop_tab();
-            output += self.tab().as_str();
-            output += "</article>\n";
-            
-        self.pop_tab();
-        output += self.tab().as_str();
-        output += "</section>\n";
-
-        Ok(output)
-    }
-    
-    pub fn convert_hoverable(&mut self, hoverable: &Alternative) -> Result<String> {
-        let base = self.parent.convert_element(no_id!(&hoverable.base))?;
-        let alt  = self.parent.convert_element(no_id!(&hoverable.alt))?;
-        Ok(format!("<span class='hoverable'><span class='hover-base'>{base}</span><span class='hover-alt'>{alt}</span></span>"))
-    }
-
-    pub fn convert_styled(&mut self, styled: &Alternative) -> Result<String> {
-        let text  = self.parent.convert_element(no_id!(&styled.alt))?;
-        let style = self.parent.convert_element(no_id!(&styled.base))?;
-        Ok(format!("<span class='embedded-style' style='{style}'>{text}</span>"))
-    }
-    
-    // fn convert_embedded_link<T: PMDSharedHTMLSerializer>(&mut self, src: &String, alt: &String) -> Result<String> {
-    // }
-
-    pub fn convert_link(&mut self, link: &Alternative) -> Result<String> {
-        let laundered_parent = Weak::launder(&self.parent);
-        let href = self.parent.convert_element(no_id!(&link.alt))?;
-        let text = self.parent.convert_element(no_id!(&link.base))?;
-        let header = laundered_parent.get_header();
-        match &link.alt {
-            box BlogBody::Citation(citation) => {
-                if let Some(reference) = convert_custom_citation(
-                    self.parent.mut_references().get_mut(citation.as_str()) , 
-                    &citation, &text, header.hide_references, T::SHOW_BACKREFS)
-                {
-                    Ok(reference)
-                } else {
-                    #[cfg(not(feature = "wasm"))]
-                    cprintln!("<y>warning:</> {} has no source", citation);
-                    Ok(format!("<cite style='color=red; background-color: yellow'>{text}</cite>"))
-                }
-            }, 
-            box BlogBody::Note(note) => {
-                if header.hide_notes {
-                    Ok("".into())
-                } else {
-                    Ok(format!("<a class='inline-link' href='#^{note}'>{text}</a>"))
-                }
-            },
-            _ => {
-                Ok(format!("<a class='inline-link' href='{href}'>{text}</a>"))
-            }
-        }
-    }
-
-    pub fn convert_header(&mut self, text: &Box<BlogBody>, depth: usize, id: &String) -> Result<String> {
-        let id = sanitize_id(id);
-        let text  = self.parent.convert_element(no_id!(text))?;
-        let link = if T::LINK_ELEMENTS { self.parent.generate_link(&id, ObjectKind::Header(depth)) } else { "".into() };
-        
-        let mut result = self.tab();
-        result += format!("<section id='{id}'>\n").as_str();
-        self.push_tab();
-        if T::LINK_ELEMENTS {
-            result += self.tab().as_str();
-            result += link.as_str();
-            result.push('\n');
-        }
-            
-            result += self.tab().as_str();
-            result += format!("<h{depth}>{text}</h{depth}>\n").as_str();
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-    }
-
-    pub fn convert_italics(&mut self, text: &Box<BlogBody>) -> Result<String> {
-        let inner_text = self.parent.convert_element(no_id!(&text))?;
-        Ok(format!("<i>{inner_text}</i>"))
-    }
-
-    pub fn convert_bold(&mut self, text: &Box<BlogBody>) -> Result<String> {
-        let inner_text = self.parent.convert_element(no_id!(&text))?;
-        Ok(format!("<b>{inner_text}</b>"))
-    }
-
-    pub fn convert_inlinecode(&mut self, text: &String) -> Result<String> {
-        let text = sanitize_text(text);
-        Ok(format!("<code>{text}</code>"))
-    }
-
-    pub fn convert_codeblock(&mut self, text: &String, id: &String) -> Result<String> {
-        let id = sanitize_id(id);
-        let first_line = text.lines().nth(0).context("expected at least one line in codeblock")?;
-        let mut words = first_line.split(|x: char| x.is_whitespace());
-        let lang = words.nth(0).unwrap_or("plaintext");
-        let mut body = text[text.find('\n').context("expected at least one line in codeblock")? + 1..].to_string();
-        let link = if T::LINK_ELEMENTS { self.parent.generate_link(&id, ObjectKind::CodeBlock) } else { String::new() };
-        
-
-        body = sanitize_text(&body);
-        body = body.trim_end().replace("\r\n", "\n");
-        
-        let mut result = self.tab();
-        result += format!("<section class='code-block' id='{id}'>\n").as_str();
-        self.push_tab();
-        if T::LINK_ELEMENTS {
-            result += self.tab().as_str();
-            result += format!("{link}\n").as_str();
-        }
-            
-            result += self.tab().as_str();
-            result += format!("<pre><code class='language-{lang}'>{body}</code></pre>\n").as_str();
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-    }
-
-    pub fn convert_image(&mut self, src: &String, alt: &String, id: &String) -> Result<String> {
-        let id = sanitize_id(id);
-        let link = if T::LINK_ELEMENTS { self.parent.generate_link(&id, ObjectKind::Image) } else { String::new() };
-
-        let mut result = self.tab();
-        result += format!("<section class='image' id='{id}'>\n").as_str();
-        self.push_tab();
-        if T::LINK_ELEMENTS {
-            result += self.tab().as_str();
-            result += format!("{link}\n").as_str();
-        }
-            
-            result += self.tab().as_str();
-            result += format!("<img onclick='makePopup(this)' src='{src}' alt='{alt}'></img>\n").as_str();
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-    }
-
-    pub fn convert_quote(&mut self, lines: &Vec<BlogBody>, id: &String) -> Result<String> {
-        let id = sanitize_id(id);
-        let link = if T::LINK_ELEMENTS { self.parent.generate_link(&id, ObjectKind::Quote) } else { String::new() };
-        
-        let mut quote_elements : Vec<String> = vec![];
-        for elem in lines {
-            let text = self.parent.convert_element(no_id!(elem))?;
-            quote_elements.push(format!("{text}<br/>"));
-        }
-        let text = quote_elements.join("\n");
-
-        let mut result = self.tab();
-        result += format!("<section class='quote' id='{id}'>\n").as_str();
-        self.push_tab();
-        if T::LINK_ELEMENTS {
-            result += self.tab().as_str();
-            result += format!("{link}\n").as_str();
-        }
-            
-            result += self.tab().as_str();
-            result += format!("<div class='quote-line'></div><blockquote class='quote-text'>{text}</blockquote>\n").as_str();
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-    }
-
-    pub fn convert_list(&mut self, list: &Vec<BlogBody>, id: &String) -> Result<String> {
-        let id = sanitize_id(id);
-        let link = if T::LINK_ELEMENTS { self.parent.generate_link(&id, ObjectKind::List) } else { String::new() };
-
-        let mut result = self.tab();
-        result += format!("<section class='list' id='{id}'>\n").as_str();
-        self.push_tab();
-        if T::LINK_ELEMENTS {
-            result += self.tab().as_str();
-            result += format!("{link}\n").as_str();
-        }
-            
-            result += self.tab().as_str();
-            result += "<ul>";
-            self.push_tab();
-            for elem in list {
-                let text = self.parent.convert_element(no_id!(elem))?;
-                result += self.tab().as_str();
-                result += format!("<li>{text}</li>\n").as_str();
-            }
-            self.pop_tab();
-            result += self.tab().as_str();
-            result += "</ul>";
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-    }
-
-    pub fn convert_paragraph(&mut self, text: &Box<BlogBody>, id: &String) -> Result<String> {
-        let id = sanitize_id(id);
-        let paragraph = self.parent.convert_element(no_id!(text))?;
-        let link = if T::LINK_ELEMENTS { self.parent.generate_link(&id, ObjectKind::Paragraph) } else { String::new() };
-
-        let mut result = self.tab();
-        result += format!("<section id='{id}'>\n").as_str();
-        self.push_tab();
-        if T::LINK_ELEMENTS {
-            result += self.tab().as_str();
-            result += format!("{link}\n").as_str();
-        }
-            
-            result += self.tab().as_str();
-            result += "<p>\n";
-            self.push_tab();
-            
-            for line in paragraph.trim_end().lines() {
-                result += self.tab().as_str();
-                result += line;
-                result.push('\n');
-            }
-
-            self.pop_tab();
-            result += self.tab().as_str();
-            result += "</p>\n";
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-    }
-
-    pub fn convert_text(&mut self, text: &String) -> Result<String> {
-        Ok(sanitize_text(text))
-    }
-
-    pub fn convert_span(&mut self, span: &Span) -> Result<String> {
-        let mut result = String::new();
-        for elem in &span.elements {
-            result += if let BlogBody::Text(text) = elem {
-                text.to_string()
-            } else {
-                self.parent.as_mut().convert_element(no_id!(elem))?
-            }.as_str()
-        }
-        Ok(result)
-    }
-    
-    pub fn convert_citation(&mut self, id: &String) -> Result<String> {
-        let laundered_parent = Weak::launder(&self.parent);
-        if let Some(reference) = self.parent.get_mut_reference(id) {
-            let num = reference.times_used;
-            reference.times_used += 1;
-            if laundered_parent.get_header().hide_references {
-                Ok("".into())
-            } else {
-                let text = to_citation(&reference.def);
-                let mut result = String::new();
-                result += "<cite>";
-                if T::SHOW_BACKREFS {
-                    result += format!("<a id='{id}-{num}' href='#{id}' onclick='backref(\"{id}\", \"{id}-{num}\")'>").as_str();
-                } else {
-                    result += format!("<a id='{id}-{num}' href='#{id}'>").as_str();
-                }
-                result += text.as_str();
-                result += "</a>";
-                result += "</cite>";
-                Ok(result)
-            }
-        } else {
-            #[cfg(not(feature = "wasm"))]
-            cprintln!("<y>warning:</> {} has no source", id);
-            Ok(format!("(MISSING CITATION)").to_string())
-        }
-    }
-    
-    pub fn convert_factbox_note(&mut self, factbox: &FactBox, _: Option<&String>, id: &String) -> Result<String> {
-        // let factbox_id = factbox_id.unwrap();
-        if self.parent.get_header().hide_notes {
-            Ok("".into())
-        } else {
-            if let Some((_, actual_id)) = factbox.notes.get(id) {
-                Ok(format!("<sup><a id='{actual_id}-backref' href='#^{actual_id}'>{id}</a></sup>"))
-            } else {
-                Ok(format!("<sup><a id='{id}-backref' href='#^{id}'>{id}</a></sup>"))
-            }
-        }
-    }
-
-    pub fn convert_note(&mut self, id: &String) -> Result<String> {
-        if self.parent.as_mut().get_header().hide_notes {
-            Ok("".into())
-        } else {
-            Ok(format!("<sup><a id='{id}-backref' href='#^{id}'>{id}</a></sup>"))
-        }
-    }
-
-    pub fn convert_toc(&mut self) -> Result<String> {
-        if self.parent.get_header().toc.is_none() { 
-            return Err(anyhow!("expected a table of content but none was found")); 
-        }
-        let link = if T::LINK_ELEMENTS {
-            self.parent.generate_link(&String::from("table-of-contents"), ObjectKind::Header(1))
-        } else { String::new() };
-        let toc = self.parent.get_header().toc.clone().unwrap();
-        let title = &toc.title;
-
-        let mut result = self.tab();
-        
-        result += "<section id='table-of-contents'>\n";
-        self.push_tab();
-        if T::LINK_ELEMENTS {
-            result += self.tab().as_str();
-            result += link.as_str();
-            result.push('\n');
-        }
-            
-            result += self.tab().as_str();
-            result += format!("<h1>{title}</h1>\n").as_str();
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        result += self.tab().as_str();
-        result += "<section>\n";
-        self.push_tab();
-            result += self.tab().as_str();
-            result += "<ul>\n";
-            self.push_tab();
-
-                for (elem, depth, id) in &toc.headers {
-                    let text = if let &box BlogBody::FactBox(fbox) = &elem {
-                        fbox.title.clone()
-                    } else {
-                        self.parent.convert_element(no_id!(elem))?
-                    };
-
-                    result += self.tab().as_str();
-                    result += format!("<li class='toci-{depth}'><a href='#{id}'>{text}</a></li>\n").as_str();
-                }
-            self.pop_tab();
-            result += self.tab().as_str();
-            result += "</ul>\n";
-
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-    }
-
-    pub fn convert_page_break(&mut self) -> Result<String> {
-        let mut result = self.tab();
-
-        result += format!("<section class='page-break'>\n").as_str();
-        self.push_tab();
-            result += self.tab().as_str();
-            result += "<hr>\n";
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += format!("</section>\n").as_str();
-
-        Ok(result)
-    }
-}
-
 pub fn to_html_bibliography(value: &ReferenceDefinition) -> String {
     //TODO(Paw): sanitize this properly 
     let mut result = "".to_string();
@@ -1119,27 +232,190 @@ pub fn sanitize_id(text: &String) -> String {
     output
 }
 
-pub fn convert_custom_citation<T>(citation: Option<&mut Reference<T>>, id: &String, text: &String, hide: bool, show_backrefs: bool) -> Option<String> {
-    if let Some(reference) = citation {
-        let num = reference.times_used;
-        reference.times_used += 1;
-        if hide {
-            Some("".into())
-        } else {
-            let mut result = String::new();
-            result += "<cite>";
-        if show_backrefs {
-            result += format!("<a id='{id}-{num}' href='#{id}' onclick='backref(\"{id}\", \"{id}-{num}\")'>").as_str();
-        } else {
-            result += format!("<a id='{id}-{num}' href='#{id}'>").as_str();
-        }
-            result += text.as_str();
-            result += "</a>";
-            result += "</cite>";
-            Some(result)
-        }
+// the id of a term in the glossary, the key with its spaces turned into dashes
+pub fn glossary_entry_id(glossary_id: &str, key: &str) -> String {
+    sanitize_id(&format!("{glossary_id}-{}", key.split_whitespace().collect::<Vec<_>>().join("-")))
+}
+
+/// the theme code blocks are coloured with, picked with `code-theme` in the frontmatter,
+/// `code-theme: none` leaves them uncoloured
+pub fn code_theme(meta: &MetaData) -> Option<&'static Theme> {
+    let name = meta.frontmatter.as_ref()
+        .and_then(|frontmatter| frontmatter["code-theme"].as_string())
+        .unwrap_or(DEFAULT_THEME.to_string());
+    if name == "none" { return None }
+
+    // an unknown theme is reported by the parser as `unknown-code-theme`
+    theme(&name).or_else(|| theme(DEFAULT_THEME))
+}
+
+/// a span around every token, with `inline` the colours are written into the spans for readers
+/// that drop stylesheets, otherwise the `hl-*` classes are used
+pub fn highlighted_html(tokens: &[Token], inline: Option<&Theme>) -> String {
+    let mut result = String::new();
+    for token in tokens {
+        let text = sanitize_text(&token.text.to_string());
+        result += match (token.kind, inline) {
+            (TokenKind::Plain, _)  => text,
+            (kind, Some(theme))    => format!("<span style='{}'>{text}</span>", theme.style(kind)),
+            (kind, None)           => format!("<span class='{}'>{text}</span>", kind.class()),
+        }.as_str();
+    }
+    result
+}
+
+// letters in bold or blackboard bold live in their own unicode block, mathvariant isn't supported
+// everywhere so we pick the characters ourselves
+fn styled_letter(character: char, variant: Option<MathVariant>) -> char {
+    let offset = |base: u32| match character {
+        'A'..='Z' => char::from_u32(base + character as u32 - 'A' as u32),
+        'a'..='z' => char::from_u32(base + 26 + character as u32 - 'a' as u32),
+        _ => None,
+    };
+    let styled = match variant {
+        Some(MathVariant::Bold) => offset(0x1D400),
+        Some(MathVariant::Blackboard) => match character {
+            'C' => Some('ℂ'), 'H' => Some('ℍ'), 'N' => Some('ℕ'), 'P' => Some('ℙ'),
+            'Q' => Some('ℚ'), 'R' => Some('ℝ'), 'Z' => Some('ℤ'),
+            _ => offset(0x1D538),
+        },
+        _ => None,
+    };
+    styled.unwrap_or(character)
+}
+
+fn mathml_nodes(nodes: &[MathNode], variant: Option<MathVariant>) -> String {
+    nodes.iter().map(|node| mathml_node(node, variant)).collect()
+}
+
+fn mathml_node(node: &MathNode, variant: Option<MathVariant>) -> String {
+    let text = |text: &String| sanitize_text(text);
+    match node {
+        MathNode::Identifier(name) => match variant {
+            Some(MathVariant::Roman) => format!("<mi mathvariant='normal'>{}</mi>", text(name)),
+            _ => format!("<mi>{}</mi>", text(&name.chars().map(|c| styled_letter(c, variant)).collect())),
+        },
+        MathNode::Number(number)   => format!("<mn>{}</mn>", text(number)),
+        MathNode::Operator(op)     => format!("<mo>{}</mo>", text(op)),
+        MathNode::LargeOperator(op) => format!("<mo largeop='true'>{}</mo>", text(op)),
+        MathNode::Function(name)   => format!("<mi>{}</mi><mo>&#x2061;</mo>", text(name)),
+        MathNode::Text(value)      => format!("<mtext>{}</mtext>", text(value)),
+        MathNode::Space(width)     => format!("<mspace width='{width}em'/>"),
+        MathNode::Group(nodes)     => format!("<mrow>{}</mrow>", mathml_nodes(nodes, variant)),
+        MathNode::Scripts { base, sub, sup } => {
+            // sums and products put their limits above and below, integrals keep them on the side
+            let limits = matches!(&**base, MathNode::LargeOperator(op) if op != "∫" && op != "∮");
+            let base = mathml_node(base, variant);
+            let (sub_tag, sup_tag, both_tag) = if limits { ("munder", "mover", "munderover") } else { ("msub", "msup", "msubsup") };
+            match (sub, sup) {
+                (Some(sub), Some(sup)) => format!("<{both_tag}>{base}{}{}</{both_tag}>", mathml_node(sub, variant), mathml_node(sup, variant)),
+                (Some(sub), None)      => format!("<{sub_tag}>{base}{}</{sub_tag}>", mathml_node(sub, variant)),
+                (None, Some(sup))      => format!("<{sup_tag}>{base}{}</{sup_tag}>", mathml_node(sup, variant)),
+                (None, None)           => base,
+            }
+        },
+        MathNode::Fraction(numerator, denominator) => format!("<mfrac>{}{}</mfrac>", mathml_node(numerator, variant), mathml_node(denominator, variant)),
+        MathNode::Root { radicand, index: None } => format!("<msqrt>{}</msqrt>", mathml_node(radicand, variant)),
+        MathNode::Root { radicand, index: Some(index) } => format!("<mroot>{}{}</mroot>", mathml_node(radicand, variant), mathml_node(index, variant)),
+        MathNode::Fenced { open, close, body } => {
+            let fence = |delimiter: &String| if delimiter.is_empty() { String::new() } else {
+                format!("<mo fence='true' stretchy='true'>{}</mo>", text(delimiter))
+            };
+            format!("<mrow>{}{}{}</mrow>", fence(open), mathml_nodes(body, variant), fence(close))
+        },
+        MathNode::Accent { base, accent } => format!("<mover accent='true'>{}<mo>{}</mo></mover>", mathml_node(base, variant), text(accent)),
+        MathNode::Styled(variant, node) => mathml_node(node, Some(*variant)),
+        MathNode::Error(raw) => format!("<merror><mtext>{}</mtext></merror>", text(raw)),
+    }
+}
+
+/// the tex as a `<math>` element, the source is kept as an annotation so copying the formula
+/// gives back something that can be pasted into another post
+pub fn to_mathml(tex: &String, display: bool) -> String {
+    let (nodes, _) = parse_tex(tex);
+    let display = if display { " display='block'" } else { "" };
+    format!("<math{display}><semantics><mrow>{}</mrow><annotation encoding='application/x-tex'>{}</annotation></semantics></math>",
+        mathml_nodes(&nodes, None), sanitize_text(tex))
+}
+
+pub fn convert_custom_citation(times_used: Option<usize>, id: &String, text: &String, hide: bool) -> Option<String> {
+    let num = times_used?;
+    if hide {
+        Some("".into())
     } else {
-        None
+        let mut result = String::new();
+        result += "<cite>";
+        result += format!("<a id='{id}-{num}' href='#{id}' onclick='backref(\"{id}\", \"{id}-{num}\")'>").as_str();
+        result += text.as_str();
+        result += "</a>";
+        result += "</cite>";
+        Some(result)
+    }
+}
+
+/// converts an alphabetical list marker like `c` or `aa` into the number html expects in `value`
+pub fn alphabetical_value(marker: &String) -> Option<usize> {
+    let mut value = 0usize;
+    for character in marker.to_lowercase().chars() {
+        if !character.is_ascii_lowercase() { return None }
+        value = value * 26 + (character as usize - 'a' as usize + 1);
     }
+    if value == 0 { None } else { Some(value) }
 }
 
+/// the size of an image as attributes for `<img>`, starting with a space when there are any.
+/// pixels go in `width`/`height` so the page doesn't jump around while the image loads,
+/// every other unit only works in css
+pub fn image_size_attributes(size: &ImageSize) -> String {
+    let (width, height) = size.dimensions();
+    let mut attributes = String::new();
+    let mut style = vec![];
+    for (name, unit) in [("width", width), ("height", height)] {
+        match unit {
+            Some(Unit::Px(n)) => attributes += format!(" {name}='{n}'").as_str(),
+            Some(unit) => style.push(format!("{name}: {}", unit.to_string())),
+            None => {},
+        }
+    }
+    if !style.is_empty() {
+        attributes += format!(" style='{}'", style.join("; ")).as_str();
+    }
+    attributes
+}
+
+/// flattens an inline element into its visible text, used for attributes like `alt` and `content`
+pub fn to_plain_text(element: &Element) -> String {
+    match element {
+        Element::Hoverable(alternative) |
+        Element::Link(alternative)      => to_plain_text(&alternative.base),
+        Element::Styled(alternative)    => to_plain_text(&alternative.alt),
+        Element::WikiLink(link)         => link.label.as_deref().map_or(link.target.clone(), to_plain_text),
+        Element::Header(text, _)        |
+        Element::Italics(text)          |
+        Element::Bold(text)             |
+        Element::Strikethrough(text)    |
+        Element::Underline(text)        |
+        Element::Highlight(text)        |
+        Element::Superscript(text)      |
+        Element::Subscript(text)        |
+        Element::Paragraph(text)        => to_plain_text(text),
+        Element::InlineCode(text)       |
+        Element::InlineMath(text)       |
+        Element::Error(text)            |
+        Element::Text(text)             => text.clone(),
+        Element::Span(span)             => span.elements.iter().map(to_plain_text).collect(),
+        Element::Image(image)           => to_plain_text(&image.alt),
+        Element::Video(media)           |
+        Element::Audio(media)           |
+        Element::Iframe(media)          => to_plain_text(&media.title),
+        Element::Quote(quote)           => quote.body.iter().map(|x| to_plain_text(&x.node)).collect::<Vec<_>>().join(" "),
+        Element::Callout(callout)       => std::iter::once(&*callout.title).chain(callout.body.iter().map(|x| &x.node))
+            .map(to_plain_text).collect::<Vec<_>>().join(" "),
+        Element::Table(table)           => table.header.iter().chain(table.rows.iter().flatten())
+            .map(to_plain_text).collect::<Vec<_>>().join(" "),
+        Element::DefinitionList(list)   => list.iter()
+            .flat_map(|definition| std::iter::once(&*definition.term).chain(&definition.definitions))
+            .map(to_plain_text).collect::<Vec<_>>().join(" "),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use crate::parser::*;
+
+/// bumped whenever the shape of the json changes in a way older readers would trip over. nothing
+/// has been released with json output yet, so everything up to the first release is version 1
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct VersionedDocument<'a> {
+    version: u32,
+    document: &'a AssDownDocument,
+}
+
+#[derive(Deserialize)]
+struct OwnedVersionedDocument {
+    version: u32,
+    document: AssDownDocument,
+}
+
+/// the parsed document as json, wrapped as `{ "version": .., "document": .. }` so tools
+/// outside of rust can read posts without reimplementing the parser
+pub fn to_json(md: &AssDownDocument) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&VersionedDocument { version: JSON_SCHEMA_VERSION, document: md })?)
+}
+
+pub fn from_json(text: &str) -> Result<AssDownDocument> {
+    let versioned: OwnedVersionedDocument = serde_json::from_str(text)?;
+    if versioned.version != JSON_SCHEMA_VERSION {
+        bail!("expected json schema version {JSON_SCHEMA_VERSION} but got {}", versioned.version);
+    }
+    Ok(versioned.document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = "---\ntitle: json\ndate: 2024-01-01\n---\n# heading\n\nsome **bold** text[^1]\n\n- a\n- b\n\n![[image.png|10px] alt]\n\n```rust title=\"main.rs\" {1}\nfn main() {}\n```\n\n[^1]: a note\n\n".to_string();
+        let (document, _) = parse(&text, None).unwrap();
+        let json = to_json(&document).unwrap();
+        assert_eq!(from_json(&json).unwrap(), document);
+    }
+
+    #[test]
+    fn schema() {
+        let text = "---\ntitle: json\ndate: 2024-01-01\n---\n# heading\n\n".to_string();
+        let (document, _) = parse(&text, None).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&to_json(&document).unwrap()).unwrap();
+        assert_eq!(json["version"], JSON_SCHEMA_VERSION);
+        assert_eq!(json["document"]["meta"]["title"], "json");
+        assert_eq!(json["document"]["body"][0][0]["type"], "header");
+        assert_eq!(json["document"]["body"][0][0]["value"][1], 1);
+    }
+
+    #[test]
+    fn pinned_version() {
+        // if this changes so did the shape, bump the version along with it
+        let text = "# heading\n\nsome *text*\n\n".to_string();
+        let (document, _) = parse(&text, None).unwrap();
+        assert_eq!(JSON_SCHEMA_VERSION, 1);
+        assert_eq!(serde_json::to_string(&document).unwrap(), concat!(
+            r#"{"meta":{"title":"","subtitle":"","banner":"","url":"https://sirpaws.dev","data_dir":"data","blog_dir":"blog","#,
+            r#""date_written":{"type":"none"},"last_update":{"type":"none"},"hide_references":false,"hide_notes":false,"#,
+            r#""hide_contacts":false,"hide_glossary":false,"toc_callouts":false,"number_figures":false,"toc":null,"#,
+            r#""bibliography_title":"References","notes_title":"Notes","glossary_title":"Glossary","backlinks_title":"Linked from","#,
+            r#""frontmatter":null},"bibliography_id":"References","notes_id":"Notes","glossary_id":"Glossary","#,
+            r#""references":{},"notes":{},"glossary":{},"figures":{},"wiki_links":{},"backlinks":[],"body":["#,
+            r#"[{"type":"header","value":[{"type":"text","value":"heading"},1]},"heading","#,
+            r#"{"start":{"offset":0,"line":1,"column":1},"end":{"offset":9,"line":1,"column":10}}],"#,
+            r#"[{"type":"paragraph","value":{"type":"span","value":{"elements":[{"type":"text","value":"some "},"#,
+            r#"{"type":"italics","value":{"type":"text","value":"text"}},{"type":"text","value":"\n"}]}}},"some-text","#,
+            r#"{"start":{"offset":11,"line":3,"column":1},"end":{"offset":22,"line":3,"column":12}}]]}"#,
+        ));
+    }
+
+    #[test]
+    fn readme_version() {
+        let readme = include_str!("../../README.md");
+        let (_, rest) = readme.split_once("{ \"version\": ").expect("the README shows the json shape");
+        let version: u32 = rest.split(',').next().unwrap().parse().unwrap();
+        assert_eq!(version, JSON_SCHEMA_VERSION, "the README documents an old version of the json");
+    }
+
+    #[test]
+    fn wrong_version() {
+        let json = r#"{ "version": 0, "document": {} }"#;
+        assert!(from_json(json).is_err());
+    }
+}
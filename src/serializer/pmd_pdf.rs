@@ -1,1241 +1,1120 @@
-use anyhow::anyhow;
-use ordered_map::OrderedMap;
-use pdf::build_pdf;
-use pmd_html_shared::Reference;
-use crate::pmd_html_shared::{ObjectKind, PMDSharedHTMLSerializer, PMDHTML};
-use crate::*;
-use tempfile::Builder;
-use std::io::Write;
+use anyhow::Result;
 use serde_yaml::Value;
-
-pub struct PMDPDFSerializer { 
-    pub common: PMDHTML<PMDPDFSerializer>,
-    header: BlogHeader,
-    references: OrderedMap<String, Reference<ReferenceDefinition>>,
-    notes: OrderedMap<String, Reference<BlogBody>>,
-    notes_id: String,
-    bibliography_id: String,
+use crate::parser::*;
+use super::{Context, Serializer};
+use super::pmd_html_shared::code_theme;
+use super::pmd_pdf_math::{layout_math, MathBox};
+use crate::highlight::{highlight, split_lines, Theme, TokenKind};
+use crate::pdf::{build_pdf, Font, FontFamily, FontStyle, Page, PAGE_HEIGHT, PAGE_WIDTH};
+
+const MARGIN: f32 = 56.7; // 2cm
+const INDENT: f32 = 18.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+const HEADER_SCALE: [f32; 6] = [2.0, 1.6, 1.35, 1.2, 1.1, 1.0];
+
+#[derive(Debug, PartialEq, Clone)]
+struct PdfSettings {
+    family: FontFamily,
+    text_size: f32,
+    line_height: f32,
+    first_page: bool,
+    header: [String; 3],
+    footer: [String; 3],
 }
 
+impl PdfSettings {
+    fn new(frontmatter: &Frontmatter) -> Self {
+        let family = if let Some(name) = frontmatter["pdf-font"].as_string() {
+            // an unknown font is reported by the parser as `unknown-pdf-font`
+            FontFamily::from_name(&name).unwrap_or(FontFamily::Helvetica)
+        } else {
+            FontFamily::Helvetica
+        };
 
-impl PMDSharedHTMLSerializer for PMDPDFSerializer {
-    const LINK_ELEMENTS: bool = false;
-    const POPUPS: bool = false;
-    const SHOW_BACKREFS: bool = false;
+        let string = |key: &str| frontmatter[key].as_string().unwrap_or_default();
+        let mut header_center = string("pdf-header-center");
+        if header_center.is_empty() {
+            header_center = string("pdf-header");
+        }
+        let mut footer_center = string("pdf-footer-center");
+        if footer_center.is_empty() {
+            footer_center = string("pdf-footer");
+        }
 
-    fn get_header(&self) -> &BlogHeader {
-        &self.header
+        Self {
+            family,
+            text_size:   Self::positive(&frontmatter["pdf-text-size"]).unwrap_or(11.0),
+            line_height: Self::positive(&frontmatter["pdf-line-height"]).unwrap_or(1.5),
+            first_page:  !frontmatter.has("pdf-no-first-page"),
+            header: [string("pdf-header-left"), header_center, string("pdf-header-right")],
+            footer: [string("pdf-footer-left"), footer_center, string("pdf-footer-right")],
+        }
     }
-    
-    fn notes_id(&mut self) -> String {
-        self.notes_id.clone()
+
+    fn positive(value: &Value) -> Option<f32> {
+        value.as_f64().filter(|x| *x > 0.0).map(|x| x as f32)
     }
+}
 
-    fn bibliography_id(&mut self) -> String {
-        self.bibliography_id.clone()
+// replaces %p/%page with the current page and %np/%pages with the page count
+fn expand_page_string(text: &str, page: usize, pages: usize) -> String {
+    text.replace("%pages", &pages.to_string())
+        .replace("%page",  &page.to_string())
+        .replace("%np",    &pages.to_string())
+        .replace("%p",     &page.to_string())
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fragment {
+    text: String,
+    font: Font,
+    size: f32,
+    rise: f32,
+    link: Option<String>,
+    space_before: bool,
+    decoration: Decoration,
+    /// inline math is laid out up front and placed as a single unbreakable box
+    math: Option<MathBox>,
+}
+
+impl Fragment {
+    fn width(&self) -> f32 {
+        match &self.math {
+            Some(math) => math.width,
+            None => self.font.text_width(&self.text, self.size),
+        }
     }
+}
 
-    fn get_description(&mut self, md: &PawsMarkdown) -> Result<String> {
-        let paragraph = md.body.iter().find(|(x, _)| match x { BlogBody::Paragraph(_) => true, _ => false});
-        Ok(if let Some((BlogBody::Paragraph(content), _)) = paragraph {
-            let text = PMDPureTextSerializer::new().convert_paragraph(content, &String::new())?;
-            text.trim_end().to_string()
-        } else {
-            String::new()
-        })
+#[derive(Debug, PartialEq, Clone)]
+struct Line {
+    fragments: Vec<(f32, Fragment)>,
+    width: f32,
+}
+
+// greedy line breaking, fragments that are wider than a whole line get broken between characters
+fn break_lines(fragments: &[Fragment], max_width: f32) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut current = Line { fragments: vec![], width: 0.0 };
+
+    for fragment in fragments {
+        let mut fragment = fragment.clone();
+        loop {
+            let space = if fragment.space_before && !current.fragments.is_empty() {
+                fragment.font.text_width(" ", fragment.size)
+            } else { 0.0 };
+            let width = fragment.width();
+
+            if current.width + space + width <= max_width {
+                current.fragments.push((current.width + space, fragment.clone()));
+                current.width += space + width;
+                break;
+            }
+
+            if !current.fragments.is_empty() && (width <= max_width || fragment.math.is_some()) {
+                lines.push(current);
+                current = Line { fragments: vec![], width: 0.0 };
+                continue;
+            }
+
+            // the fragment doesn't fit on a line by itself, formulas just stick out
+            if fragment.math.is_some() {
+                current.fragments.push((space, fragment.clone()));
+                current.width = space + width;
+                break;
+            }
+            let mut head = String::new();
+            let mut head_width = current.width + space;
+            for c in fragment.text.chars() {
+                let char_width = fragment.font.char_width(c) * fragment.size / 1000.0;
+                if head_width + char_width > max_width && !head.is_empty() {
+                    break;
+                }
+                head.push(c);
+                head_width += char_width;
+            }
+            if head.is_empty() {
+                lines.push(current);
+                current = Line { fragments: vec![], width: 0.0 };
+                continue;
+            }
+            let tail = fragment.text[head.len()..].to_string();
+            current.fragments.push((current.width + space, Fragment { text: head, ..fragment.clone() }));
+            current.width = head_width;
+            lines.push(current);
+            current = Line { fragments: vec![], width: 0.0 };
+
+            if tail.is_empty() { break; }
+            fragment = Fragment { text: tail, space_before: false, ..fragment };
+        }
     }
 
-    fn prepare_html_header(&mut self, description: &String) -> String {
-        let mut output = String::new();
+    if !current.fragments.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
 
-        let mut max_depth = 0;
-        if let Some(toc) = self.header.toc.as_ref() {
-            max_depth = toc.max_depth;
+// folds the empty fragments left behind by trailing whitespace into the next word
+fn merge_spaces(fragments: Vec<Fragment>) -> Vec<Fragment> {
+    let mut merged: Vec<Fragment> = vec![];
+    let mut pending_space = false;
+    for fragment in fragments {
+        if fragment.text.is_empty() && fragment.math.is_none() {
+            pending_space |= fragment.space_before;
+            continue;
         }
+        let space_before = fragment.space_before || pending_space;
+        pending_space = false;
+        merged.push(Fragment { space_before, ..fragment });
+    }
+    merged
+}
 
-        let title = &self.header.title;
+// lines and fills drawn along with the text rather than by the font
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+struct Decoration {
+    strike: bool,
+    underline: bool,
+    highlight: bool,
+}
 
-        let frontmatter = self.header.frontmatter.clone().unwrap_or(Frontmatter::new());
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct InlineStyle {
+    bold: bool,
+    italic: bool,
+    mono: bool,
+    superscript: bool,
+    subscript: bool,
+    decoration: Decoration,
+}
 
-        let top_left   = Self::parse_pdf_string(&frontmatter["pdf-header-left"]  );
-        let mut top_center = Self::parse_pdf_string(&frontmatter["pdf-header-center"]);
-        let top_right  = Self::parse_pdf_string(&frontmatter["pdf-header-right"]  );
+impl InlineStyle {
+    fn new() -> Self {
+        Self { bold: false, italic: false, mono: false, superscript: false, subscript: false, decoration: Decoration::default() }
+    }
+}
 
-        let bottom_left   = Self::parse_pdf_string(&frontmatter["pdf-footer-left"]  );
-        let mut bottom_center = Self::parse_pdf_string(&frontmatter["pdf-footer-center"]);
-        let bottom_right  = Self::parse_pdf_string(&frontmatter["pdf-footer-right"]  );
-        let font = &frontmatter["pdf-font"].as_str();
+pub struct PMDPDFSerializer {
+    settings: PdfSettings,
+    times_used: OrderedMap<String, usize>,
+    pages: Vec<Page>,
+    y: f32,
+    style: InlineStyle,
+    link: Option<String>,
+    indent: f32,
+    theme: Option<&'static Theme>,
+}
 
-        if top_center == "" {
-            top_center = Self::parse_pdf_string(&frontmatter["pdf-header"]);
-        }
-        if bottom_center == "" {
-            bottom_center = Self::parse_pdf_string(&frontmatter["pdf-footer"]);
+impl PMDPDFSerializer {
+    pub fn new() -> Self {
+        Self {
+            settings: PdfSettings::new(&Frontmatter::new()),
+            times_used: OrderedMap::new(),
+            pages: vec![],
+            y: 0.0,
+            style: InlineStyle::new(),
+            link: None,
+            indent: 0.0,
+            theme: None,
         }
+    }
 
-        let text_size   = frontmatter["pdf-text-size"].as_i64();
-        let line_height = frontmatter["pdf-line-height"].as_i64();
-
-        output += "<meta http-equiv=\"content-type\" content=\"text/html; charset=utf-8\">\n";
-        output += "\n";
-        output += "<!-- tag needed for media query -->\n";
-        output += "<meta name=\"viewport\"    content=\"width=device-width, initial-scale=1, minimum-scale=1\" />\n";
-        output += "\n";
-        output += "<!-- Primary Meta Tags -->\n";
-        output += format!("<title>{title}</title>\n").as_str();
-        output += format!("<meta name=\"title\" content=\"{title}\">\n").as_str();
-        output += format!("<meta name=\"description\" content=\"{description}\">\n").as_str();
-        output += "\n";
-        output += "<!-- stylesheets -->\n";
-        output += "<style>\n";
-        output += "main {\n";
-        if let Some(font) = font {
-            output += format!("    font-family: '{font}';\n").as_str()
-        } else {
-            output += "    font-family: 'Atkinson Hyperlegible', sans-serif;\n";
+    fn font(&self, bold: bool, italic: bool) -> Font {
+        Font::new(self.settings.family, FontStyle::new(bold, italic))
+    }
+
+    fn line_height(&self, size: f32) -> f32 {
+        size * self.settings.line_height
+    }
+
+    fn page(&mut self) -> &mut Page {
+        if self.pages.is_empty() {
+            self.new_page();
         }
-        if let Some(text_size) = text_size {
-            output += format!("    font-size: {text_size}pt;\n").as_str();
+        self.pages.last_mut().expect("there is always a page")
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Page::new());
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn ensure_space(&mut self, height: f32) {
+        if self.pages.is_empty() || self.y - height < MARGIN {
+            self.new_page();
         }
-        if let Some(line_height) = line_height {
-            output += format!("    line-height: {line_height};\n").as_str();
+    }
+
+    fn skip(&mut self, height: f32) {
+        // spacing is dropped at the top of a page
+        if self.y < PAGE_HEIGHT - MARGIN {
+            self.y -= height;
         }
-        output += "}\n";
-        output += "\n";
-        output += "h1 {margin-top: 2em; margin-bottom: 0; font-size: 18pt; }\n";
-        output += "h2 {margin-top: 2em; margin-bottom: 0; font-size: 16pt; }\n";
-        output += "h3 {margin-top: 2em; margin-bottom: 0; font-size: 14pt; }\n";
-        output += "h4 {margin-top: 2em; margin-bottom: 0; font-size: 12pt; }\n";
-        output += "h5 {margin-top: 2em; margin-bottom: 0; font-size: 12pt; }\n";
-        output += "h6 {margin-top: 2em; margin-bottom: 0; font-size: 12pt; }\n";
-        output += ".bibliography h1 { margin-top: auto; margin-bottom: auto; font-size: 18pt; }\n";
-        output += ".notes h1 { margin-top: auto; margin-bottom: auto; font-size: 18pt; }\n";
-        output += "\n";
-        if !frontmatter.has("pdf-no-first-page") {
-            output += ".banner { \n";
-            output += "    width: 100%;\n";
-            output += "}\n";
+    }
+
+    // visits an element with a different inline style, restoring the current one afterwards
+    fn styled(&mut self, ctx: &Context, element: &Element, style: InlineStyle) -> Result<Vec<Fragment>> {
+        let previous = std::mem::replace(&mut self.style, style);
+        let result = self.visit_inline(ctx, element);
+        self.style = previous;
+        result
+    }
+
+    fn with_indent<T>(&mut self, indent: f32, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let previous = std::mem::replace(&mut self.indent, indent);
+        let result = f(self);
+        self.indent = previous;
+        result
+    }
+
+    fn push_text(&self, text: &str, style: InlineStyle, link: &Option<String>, out: &mut Vec<Fragment>) {
+        let font = if style.mono {
+            Font::new(FontFamily::Courier, FontStyle::new(style.bold, style.italic))
         } else {
-            output += ".banner { \n";
-            output += "    max-height: 16cm;\n";
-            output += "    align-self: center;\n";
-            output += "}\n";
-        }
-        output += "\n";
-        output += ".image { \n";
-        output += "    justify-content: center;\n";
-        output += "    display: flex;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".quote { \n";
-        output += "    justify-content: center;\n";
-        output += "    margin-bottom: 1em;\n";
-        output += "    display: flex;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".quote-line {\n";
-        output += "    width: 1ch;\n";
-        output += "    padding-left: 0;\n";
-        output += "    padding-right: 1ch;\n";
-        output += "    border-left: 0.3ch solid black;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".quote-text {\n";
-        output += "    margin-left: 0;\n";
-        output += "    margin-top: 0;\n";
-        output += "    margin-bottom: 0;\n";
-        output += "}\n";
-        output += "\n";
-        output += "a {\n";
-        output += "    text-decoration: none;\n";
-        output += "    color: black;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".inline-link:link {color: blue}\n";
-        output += ".inline-link:visited {color: purple}\n";
-        output += ".inline-link:active {color: red}\n";
-        output += "\n";
-        output += ".toc ul {\n";
-        output += "    padding: 0;\n";
-        output += "    list-style: none;\n";
-        output += "    font-size: larger;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".toci-header {\n";
-        output += "    position: relative;\n";
-        output += "    overflow: hidden;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".toci-header::after {\n";
-        output += "    position: absolute;\n";
-        output += "    padding-left: .25ch;\n";
-        output += "    content: \" . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . \"\n";
-        output += "    \". . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . \"\n";
-        output += "    \". . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . \";\n";
-        output += "    text-align: right;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".toci-number {\n";
-        output += "    min-width: 1ch;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".toci-number::after {\n";
-        output += "    content: target-counter(attr(href), page);\n";
-        output += "}\n";
-        output += "\n";
-        output += ".toci-1 {\n";
-        output += "    display: grid;\n";
-        output += "    grid-template-columns: auto max-content;\n";
-        output += "    grid-template-areas: \"toci-header toci-number\";\n";
-        output += "    align-items: end;\n";
-        output += "    gap: 0.25rem;\n";
-        output += "}\n";
-        output += "\n";
-
-        if max_depth > 1 {
-            for i in 2..(max_depth + 1) {
-                let margin = i - 1;
-                output += format!(".toci-{i} {{\n").as_str();
-                output += "    display: grid;\n";
-                output += "    grid-template-columns: auto max-content;\n";
-                output += "    grid-template-areas: \"toci-header toci-number\";\n";
-                output += "    align-items: end;\n";
-                output += "    gap: 0.25rem;\n";
-                output += format!("    margin-left: {margin}em;\n").as_str();
-                output += "}\n";
-                output += "\n";
-            }
-        }
-        
-        output += ".factbox {\n";
-        output += "    background-color: #f3f3f3;\n";
-        output += "    border-radius: .5vmin;\n";
-        output += "    display: flex;\n";
-        output += "    flex: auto;\n";
-        output += "    flex-direction: column;\n";
-        output += "    overflow: hidden;\n";
-        output += "    margin-bottom: 1em;\n";
-        output += "    padding-bottom: 1em;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".factbox-header {\n";
-        output += "    font-size: 14pt;\n";
-        output += "    padding: 1pt;\n";
-        output += "    padding-left: 1em;\n";
-        output += "    margin-bottom: 1em;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".factbox-header > h2 {\n";
-        output += "    font-size: 14pt;\n";
-        output += "    margin-top: 1em;\n";
-        output += "    margin-bottom: 0;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".factbox-content {\n";
-        output += "    min-width: fit-content;\n";
-        output += "    display: flex;\n";
-        output += "    flex: auto;\n";
-        output += "    flex-direction: column;\n";
-        output += "    align-items: flex-start;\n";
-        output += "    padding: 0;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".factbox-content > section {\n";
-        output += "    padding-left: 2em;\n";
-        output += "    padding-right: 1em;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".factbox-content > .page-break {\n";
-        output += "    width: calc(100%);\n";
-        output += "    padding-left: 1em;\n";
-        output += "}\n";
-
-        output += "\n";
-        output += "@media print {\n";
-        if !frontmatter.has("pdf-no-first-page") {
-            output += "    .title {\n";
-            output += "        page-break-after: always;\n";
-            output += "    }\n";
+            self.font(style.bold, style.italic)
+        };
+        let (size, rise) = if style.superscript {
+            (self.settings.text_size * 0.7, self.settings.text_size * 0.35)
+        } else if style.subscript {
+            (self.settings.text_size * 0.7, self.settings.text_size * -0.15)
         } else {
-            output += "    .title {\n";
-            output += "        display: flex;\n";
-            output += "        flex-direction: column;\n";
-            output += "    }\n";
+            (self.settings.text_size, 0.0)
+        };
+        let decoration = style.decoration;
+
+        let mut space_before = text.starts_with(char::is_whitespace);
+        for word in text.split_whitespace() {
+            out.push(Fragment { text: word.to_string(), font, size, rise, link: link.clone(), space_before, decoration, math: None });
+            space_before = true;
         }
-        output += "\n";
-        output += "    .toc {\n";
-        output += "        page-break-after: always;\n";
-        output += "    }\n";
-        output += "\n";
-        output += "    @page {\n";
-        output += format!("       @top-left   {{content: {top_left  }}}\n").as_str();
-        output += format!("       @top-center {{content: {top_center}}}\n").as_str();
-        output += format!("       @top-right  {{content: {top_right }}}\n").as_str();
-        output += "\n";
-        output += format!("       @bottom-left   {{content: {bottom_left  }}}\n").as_str();
-        output += format!("       @bottom-center {{content: {bottom_center}}}\n").as_str();
-        output += format!("       @bottom-right  {{content: {bottom_right }}}\n").as_str();
-        output += "    }\n";
-        output += "\n";
-        if !frontmatter.has("pdf-no-first-page") {
-            output += "   @page:first {\n";
-            output += "       @top-left   {content: none}\n";
-            output += "       @top-center {content: none}\n";
-            output += "       @top-right  {content: none}\n";
-            output += "\n";
-            output += "       @bottom-left   {content: none}\n";
-            output += "       @bottom-center {content: none}\n";
-            output += "       @bottom-right  {content: none}\n";
-            output += "   }\n";
+        // trailing whitespace has to carry over to whatever comes next
+        if text.ends_with(char::is_whitespace) {
+            out.push(Fragment { text: String::new(), font, size, rise, link: None, space_before: true, decoration, math: None });
         }
-        output += "}\n";
-        output += "</style>\n";
-        output += "\n";
-        output += "<!-- for Atkinson Hyperlegible font-->\n";
-        output += "<link rel=\"preconnect\" href=\"https://fonts.googleapis.com\">\n";
-        output += "<link rel=\"preconnect\" href=\"https://fonts.gstatic.com\" crossorigin>\n";
-        output += "<link href=\"https://fonts.googleapis.com/css2?family=Atkinson+Hyperlegible:ital,wght@0,400;0,700;1,400;1,700&display=swap\" rel=\"stylesheet\">\n";
-        output += "\n";
-        output += "<!-- paged.js -->\n";
-        output += "<script src='https://unpkg.com/pagedjs/dist/paged.polyfill.js'></script>\n";
-        output += "\n";
-        output += "<!-- highlight.js -->\n";
-        output += "<script src=\"https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.7.0/highlight.min.js\"></script>\n";
-        output += "<link rel=\"stylesheet\" href=\"https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.7.0/styles/default.min.css\">\n";
-        output += "<script>\n";
-        output += "    class HighlightJSHandler extends Paged.Handler {\n";
-        output += "        constructor(chunker, polisher, caller) {\n";
-        output += "            super(chunker, polisher, caller);\n";
-        output += "        }\n";
-        output += "\n";
-        output += "        afterParsed(parsed) {\n";
-        output += "            hljs.highlightAll();\n";
-        output += "            parsed.querySelectorAll('code').forEach((el) => {\n";
-        output += "                hljs.highlightElement(el);\n";
-        output += "            })\n";
-        output += "        }\n";
-        output += "    }\n";
-        output += "\n";
-        output += "    Paged.registerHandlers(HighlightJSHandler);\n";
-        output += "</script>\n";
-        output
-    }
-
-    fn convert_body(&mut self, md: &PawsMarkdown) -> Result<String> {
-        let mut output = String::new();
-        for (element, id) in &md.body {
-            let result = self.convert_element((element, id))?;
-            match element {
-                BlogBody::CodeBlock(_) => {
-                    output += result.as_str();
-                    output += "\n";
-                },
-                _ => {
-                    output += result.as_str();
-                }
+    }
+
+    fn draw_line(&mut self, line: &Line, left: f32, baseline: f32) {
+        let mut previous: Option<(f32, Decoration)> = None;
+        for (x, fragment) in &line.fragments {
+            let x = left + x;
+            let width = fragment.width();
+            let decoration = fragment.decoration;
+            // decorations run through the space between two words that share them
+            let start = match previous {
+                Some((end, previous)) if previous == decoration => end,
+                _ => x,
+            };
+            previous = Some((x + width, decoration));
+            let (size, y) = (fragment.size, baseline + fragment.rise);
+            let page = self.page();
+            if decoration.highlight {
+                page.fill_rect_rgb(start, y - size * 0.25, x + width - start, size * 1.1, (1.0, 0.93, 0.45));
+            }
+            if decoration.underline {
+                page.line((start, y - size * 0.12), (x + width, y - size * 0.12), size * 0.06, 0.0);
+            }
+            if decoration.strike {
+                page.line((start, y + size * 0.28), (x + width, y + size * 0.28), size * 0.06, 0.0);
+            }
+            if let Some(math) = &fragment.math {
+                math.draw(page, x, baseline + fragment.rise);
+            } else if let Some(uri) = &fragment.link {
+                page.set_color(0.1, 0.2, 0.6);
+                page.text_with_rise(x, baseline, fragment.font, fragment.size, fragment.rise, &fragment.text);
+                page.set_color(0.0, 0.0, 0.0);
+                page.link(x, baseline - fragment.size * 0.2, width, fragment.size, uri);
+            } else {
+                page.text_with_rise(x, baseline, fragment.font, fragment.size, fragment.rise, &fragment.text);
             }
         }
-        Ok(output)
     }
 
-    fn generate_link(&mut self, _: &String, _: ObjectKind) -> String {
-        String::new()
+    fn render_fragments(&mut self, fragments: Vec<Fragment>, indent: f32, spacing_after: f32) {
+        let merged = merge_spaces(fragments);
+        if merged.is_empty() { return; }
+
+        let size = merged.iter().map(|x| x.size).fold(0.0, f32::max);
+        for line in break_lines(&merged, CONTENT_WIDTH - indent) {
+            // tall formulas like fractions push the lines around them apart instead of overlapping them
+            let math = line.fragments.iter().filter_map(|(_, fragment)| fragment.math.as_ref());
+            let (above, below) = math.fold((0.0f32, 0.0f32), |(above, below), math| {
+                (above.max(math.ascent - size * 0.9), below.max(math.descent - size * 0.3))
+            });
+            let height = self.line_height(size) + above + below;
+            self.ensure_space(height);
+            let baseline = self.y - height + (height - size) / 2.0 + size * 0.2 + (below - above) / 2.0;
+            self.draw_line(&line, MARGIN + indent, baseline);
+            self.y -= height;
+        }
+        self.skip(spacing_after);
     }
 
-    fn references(&mut self) -> &OrderedMap<String, Reference<ReferenceDefinition>> {
-        &self.references
+    fn render_inline(&mut self, ctx: &Context, element: &Element, style: InlineStyle, indent: f32) -> Result<()> {
+        let fragments = self.styled(ctx, element, style)?;
+        self.render_fragments(fragments, indent, self.settings.text_size * 0.6);
+        Ok(())
     }
 
-    fn mut_references(&mut self) -> &mut OrderedMap<String, Reference<ReferenceDefinition>> {
-        &mut self.references
+    fn render_header(&mut self, ctx: &Context, text: &Element, depth: usize) -> Result<()> {
+        let scale = HEADER_SCALE[depth.clamp(1, HEADER_SCALE.len()) - 1];
+        let size  = self.settings.text_size * scale;
+
+        let mut fragments = self.styled(ctx, text, InlineStyle { bold: true, ..InlineStyle::new() })?;
+        for fragment in &mut fragments {
+            fragment.size *= scale;
+        }
+
+        self.skip(size * 0.8);
+        // keep the header on the same page as at least two lines of what follows it
+        self.ensure_space(self.line_height(size) + 2.0 * self.line_height(self.settings.text_size));
+        self.render_fragments(fragments, 0.0, size * 0.3);
+        Ok(())
     }
 
-    fn get_mut_reference<T: AsRef<str>>(&mut self, key: T) -> Option<&mut Reference<ReferenceDefinition>> {
-        self.references.get_mut(key.as_ref())
+    // the standard fonts don't have a ballot box so it's drawn, `x` and `baseline` are its bottom left
+    fn checkbox(&mut self, x: f32, baseline: f32, width: f32, checked: bool) {
+        let page = self.page();
+        page.stroke_rect(x, baseline, width, width, width * 0.08, 0.0);
+        if checked {
+            let point = |dx: f32, dy: f32| (x + dx * width, baseline + dy * width);
+            page.line(point(0.2, 0.5), point(0.42, 0.25), width * 0.12, 0.0);
+            page.line(point(0.42, 0.25), point(0.82, 0.8), width * 0.12, 0.0);
+        }
     }
 
-    fn get_reference<T: AsRef<str>>(&mut self, key: T) -> Option<&Reference<ReferenceDefinition>> {
-        self.references.get(key.as_ref())
+    // images aren't embedded yet, so we leave the alt text and the source in their place
+    fn render_image(&mut self, ctx: &Context, image: &Image) -> Result<()> {
+        let span = Element::Span(Span { elements: vec![
+            (*image.alt).clone(),
+            Element::Text(format!(" ({})", image.src)),
+        ]});
+        let style = InlineStyle { italic: true, ..InlineStyle::new() };
+        if image.size == ImageSize::None {
+            return self.render_inline(ctx, &span, style, self.indent);
+        }
+
+        // a sized image keeps its space on the page as a frame with the text inside of it
+        const PADDING: f32 = 6.0;
+        let indent = self.indent;
+        let size = self.settings.text_size;
+        let available = CONTENT_WIDTH - indent;
+        let (width, height) = image.size.dimensions();
+        let width = width.and_then(|x| unit_to_points(x, size, available)).unwrap_or(available).min(available).max(2.0 * PADDING);
+
+        let fragments = merge_spaces(self.styled(ctx, &span, style)?);
+        let lines = break_lines(&fragments, width - 2.0 * PADDING);
+        let text_height = lines.len() as f32 * self.line_height(size) + 2.0 * PADDING;
+        let height = height.and_then(|x| unit_to_points(x, size, PAGE_HEIGHT - 2.0 * MARGIN)).unwrap_or(text_height)
+            .clamp(text_height.min(PAGE_HEIGHT - 2.0 * MARGIN), PAGE_HEIGHT - 2.0 * MARGIN);
+
+        self.skip(size * 0.3);
+        self.ensure_space(height);
+        let top = self.y;
+        self.page().stroke_rect(MARGIN + indent, top - height, width, height, 0.75, 0.6);
+        for (n, line) in lines.iter().enumerate() {
+            let line_height = self.line_height(size);
+            let baseline = top - PADDING - (n + 1) as f32 * line_height + (line_height - size) / 2.0 + size * 0.2;
+            if baseline < top - height { break; }
+            self.draw_line(line, MARGIN + indent + PADDING, baseline);
+        }
+        self.y -= height;
+        self.skip(size * 0.6);
+        Ok(())
     }
-}
 
-impl PMDPDFSerializer {
-    pub fn new(filename: &str) -> Box<Self> {
-        let mut value = Box::new(Self {
-            common: PMDHTML::uninit(),
-            header: BlogHeader::default(),
-            references: OrderedMap::new(),
-            notes: OrderedMap::new(),
-            notes_id: String::new(),
-            bibliography_id: String::new(),
-        });
-        value.common = PMDHTML::new(filename, &mut *value);
-        value
+    // paper can't play anything, so media is a link to where it was embedded from
+    fn render_media_link(&mut self, ctx: &Context, media: &Media, kind: &str) -> Result<()> {
+        let title = if plain_text(&media.title).trim().is_empty() { Element::Text(media.link.clone()) } else { (*media.title).clone() };
+        let span = Element::Span(Span { elements: vec![
+            Element::Text(format!("{kind}: ")),
+            Element::Link(Alternative { base: Box::new(title), alt: Box::new(Element::Text(media.link.clone())) }),
+        ]});
+        self.render_inline(ctx, &span, InlineStyle { italic: true, ..InlineStyle::new() }, self.indent)
     }
 
-    fn get_header_info(frontmatter: &Frontmatter) -> (String, String, String) {
-        (
-            Self::parse_pdf_string(&frontmatter["pdf-header-left"]),
-            if let result = Self::parse_pdf_string(&frontmatter["pdf-header-center"]) 
-            && !result.trim().is_empty() {
-                result
-            } else {
-                Self::parse_pdf_string(&frontmatter["pdf-header"])
-            },
-            Self::parse_pdf_string(&frontmatter["pdf-header-right"])
-        )
-    }
-
-    fn get_footer_info(frontmatter: &Frontmatter) -> (String, String, String) {
-        let bottom_left   = Self::parse_pdf_string(&frontmatter["pdf-footer-left"]  );
-        let mut bottom_center = Self::parse_pdf_string(&frontmatter["pdf-footer-center"]);
-        let bottom_right  = Self::parse_pdf_string(&frontmatter["pdf-footer-right"]  );
-        (
-            Self::parse_pdf_string(&frontmatter["pdf-footer-left"]),
-            if let result = Self::parse_pdf_string(&frontmatter["pdf-header-center"]) 
-            && !result.trim().is_empty() {
-                result
+    fn render_list_items(&mut self, ctx: &Context, list: &[ListItem], indent: f32) -> Result<()> {
+        for item in list {
+            let marker = match item {
+                ListItem::Unordered(..)                   => "\u{2022}".to_string(),
+                ListItem::Numbered(num, ..)               => format!("{num}."),
+                ListItem::Alphabetical(marker, ..)        => format!("{marker}."),
+                ListItem::NumberedRounded(num, ..)        => format!("{num})"),
+                ListItem::AlphabeticalRounded(marker, ..) => format!("{marker})"),
+                ListItem::Task { .. }                     => String::new(),
+                ListItem::List(sublist) => {
+                    self.render_list_items(ctx, sublist, indent + INDENT)?;
+                    continue;
+                },
+            };
+            let size = self.settings.text_size;
+            self.ensure_space(self.line_height(size));
+            let font = self.font(false, false);
+            let marker_x = MARGIN + indent + INDENT - font.text_width(&marker, size) - 4.0;
+            let baseline = self.y - self.line_height(size) + (self.line_height(size) - size) / 2.0 + size * 0.2;
+            if let ListItem::Task { checked, .. } = item {
+                self.checkbox(MARGIN + indent + INDENT - size * 0.7 - 4.0, baseline, size * 0.7, *checked);
             } else {
-                Self::parse_pdf_string(&frontmatter["pdf-header"])
-            },
-            Self::parse_pdf_string(&frontmatter["pdf-header-right"])
-        )
-    }
-
-    fn parse_pdf_string(text: &Value) -> String {
-        if let Some(text) = text.as_string() {
-            let mut text = text.clone();
-            text.insert(0, '"');
-            text.push('"');
-            text = text.replace("%pages", "\"counter(pages)\"");
-            text = text.replace("%page", "\"counter(page)\"");
-            text = text.replace("%np", "\"counter(pages)\"");
-            text = text.replace("%p",  "\"counter(page)\"");
-            text
-        } else { String::new() }
-    }
-
-    /*
-    fn prepare_header(&mut self, frontmatter: &Option<Frontmatter>, max_depth: usize, title: &String, description: &String) -> String {
-        let mut output = String::new();
-
-        let frontmatter = frontmatter.clone().unwrap_or(Frontmatter::new());
-
-        let top_left   = Self::parse_pdf_string(&frontmatter["pdf-header-left"]  );
-        let mut top_center = Self::parse_pdf_string(&frontmatter["pdf-header-center"]);
-        let top_right  = Self::parse_pdf_string(&frontmatter["pdf-header-right"]  );
-
-        let bottom_left   = Self::parse_pdf_string(&frontmatter["pdf-footer-left"]  );
-        let mut bottom_center = Self::parse_pdf_string(&frontmatter["pdf-footer-center"]);
-        let bottom_right  = Self::parse_pdf_string(&frontmatter["pdf-footer-right"]  );
-        let font = &frontmatter["pdf-font"].as_str();
-
-        if top_center == "" {
-            top_center = Self::parse_pdf_string(&frontmatter["pdf-header"]);
-        }
-        if bottom_center == "" {
-            bottom_center = Self::parse_pdf_string(&frontmatter["pdf-footer"]);
+                self.page().text(marker_x, baseline, font, size, &marker);
+            }
+
+            let Some((text, body)) = item.content() else { continue };
+            let fragments = self.visit_inline(ctx, text)?;
+            self.render_fragments(fragments, indent + INDENT, 0.0);
+            self.with_indent(indent + INDENT, |this| {
+                for Spanned { node: element, .. } in body {
+                    let fragments = this.visit_element(ctx, element, &String::new())?;
+                    this.render_fragments(fragments, this.indent, this.settings.text_size * 0.3);
+                }
+                Ok(())
+            })?;
         }
+        Ok(())
+    }
 
-        let text_size   = frontmatter["pdf-text-size"].as_i64();
-        let line_height = frontmatter["pdf-line-height"].as_i64();
-
-        output += "<meta http-equiv=\"content-type\" content=\"text/html; charset=utf-8\">\n";
-        output += "\n";
-        output += "<!-- tag needed for media query -->\n";
-        output += "<meta name=\"viewport\"    content=\"width=device-width, initial-scale=1, minimum-scale=1\" />\n";
-        output += "\n";
-        output += "<!-- Primary Meta Tags -->\n";
-        output += format!("<title>{title}</title>\n").as_str();
-        output += format!("<meta name=\"title\" content=\"{title}\">\n").as_str();
-        output += format!("<meta name=\"description\" content=\"{description}\">\n").as_str();
-        output += "\n";
-        output += "<!-- stylesheets -->\n";
-        output += "<style>\n";
-        output += "main {\n";
-        if let Some(font) = font {
-            output += format!("    font-family: '{font}';\n").as_str()
-        } else {
-            output += "    font-family: 'Atkinson Hyperlegible', sans-serif;\n";
+    fn render_note(&mut self, ctx: &Context, key: &String, note: &Element, indent: f32) -> Result<()> {
+        let mut fragments = self.text(ctx, format!("{key}: ").as_str())?;
+        fragments.extend(self.visit_inline(ctx, note)?);
+        self.render_fragments(fragments, indent, self.settings.text_size * 0.6);
+        Ok(())
+    }
+
+    fn rule(&mut self, indent: f32, width: f32) {
+        self.ensure_space(width + 4.0);
+        let y = self.y - 2.0;
+        self.page().line((MARGIN + indent, y), (PAGE_WIDTH - MARGIN, y), width, 0.5);
+        self.y -= width + 4.0;
+    }
+
+    fn render_title_page(&mut self, ctx: &Context) -> Result<()> {
+        let title    = &ctx.meta().title;
+        let subtitle = &ctx.meta().subtitle;
+        if title.is_empty() && subtitle.is_empty() { return Ok(()); }
+
+        if self.settings.first_page {
+            self.new_page();
+            self.y = PAGE_HEIGHT * 0.6;
         }
-        if let Some(text_size) = text_size {
-            output += format!("    font-size: {text_size}pt;\n").as_str();
+        if !title.is_empty() {
+            self.render_header(ctx, &Element::Text(title.clone()), 1)?;
         }
-        if let Some(line_height) = line_height {
-            output += format!("    line-height: {line_height};\n").as_str();
+        if !subtitle.is_empty() {
+            self.render_inline(ctx, &Element::Text(subtitle.clone()), InlineStyle { italic: true, ..InlineStyle::new() }, 0.0)?;
         }
-        output += "}\n";
-        output += "\n";
-        output += "h1 {margin-top: 2em; margin-bottom: 0; font-size: 18pt; }\n";
-        output += "h2 {margin-top: 2em; margin-bottom: 0; font-size: 16pt; }\n";
-        output += "h3 {margin-top: 2em; margin-bottom: 0; font-size: 14pt; }\n";
-        output += "h4 {margin-top: 2em; margin-bottom: 0; font-size: 12pt; }\n";
-        output += "h5 {margin-top: 2em; margin-bottom: 0; font-size: 12pt; }\n";
-        output += "h6 {margin-top: 2em; margin-bottom: 0; font-size: 12pt; }\n";
-        output += ".bibliography h1 { margin-top: auto; margin-bottom: auto; font-size: 18pt; }\n";
-        output += ".notes h1 { margin-top: auto; margin-bottom: auto; font-size: 18pt; }\n";
-        output += "\n";
-        if !frontmatter.has("pdf-no-first-page") {
-            output += ".banner { \n";
-            output += "    width: 100%;\n";
-            output += "}\n";
+        if self.settings.first_page {
+            self.new_page();
         } else {
-            output += ".banner { \n";
-            output += "    max-height: 16cm;\n";
-            output += "    align-self: center;\n";
-            output += "}\n";
+            self.skip(self.settings.text_size);
         }
-        output += "\n";
-        output += ".image { \n";
-        output += "    justify-content: center;\n";
-        output += "    display: flex;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".quote { \n";
-        output += "    justify-content: center;\n";
-        output += "    margin-bottom: 1em;\n";
-        output += "    display: flex;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".quote-line {\n";
-        output += "    width: 1ch;\n";
-        output += "    padding-left: 0;\n";
-        output += "    padding-right: 1ch;\n";
-        output += "    border-left: 0.3ch solid black;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".quote-text {\n";
-        output += "    margin-left: 0;\n";
-        output += "    margin-top: 0;\n";
-        output += "    margin-bottom: 0;\n";
-        output += "}\n";
-        output += "\n";
-        output += "a {\n";
-        output += "    text-decoration: none;\n";
-        output += "    color: black;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".inline-link:link {color: blue}\n";
-        output += ".inline-link:visited {color: purple}\n";
-        output += ".inline-link:active {color: red}\n";
-        output += "\n";
-        output += ".toc ul {\n";
-        output += "    padding: 0;\n";
-        output += "    list-style: none;\n";
-        output += "    font-size: larger;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".toci-header {\n";
-        output += "    position: relative;\n";
-        output += "    overflow: hidden;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".toci-header::after {\n";
-        output += "    position: absolute;\n";
-        output += "    padding-left: .25ch;\n";
-        output += "    content: \" . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . \"\n";
-        output += "    \". . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . \"\n";
-        output += "    \". . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . . \";\n";
-        output += "    text-align: right;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".toci-number {\n";
-        output += "    min-width: 1ch;\n";
-        output += "}\n";
-        output += "\n";
-        output += ".toci-number::after {\n";
-        output += "    content: target-counter(attr(href), page);\n";
-        output += "}\n";
-        output += "\n";
-        output += ".toci-1 {\n";
-        output += "    display: grid;\n";
-        output += "    grid-template-columns: auto max-content;\n";
-        output += "    grid-template-areas: \"toci-header toci-number\";\n";
-        output += "    align-items: end;\n";
-        output += "    gap: 0.25rem;\n";
-        output += "}\n";
-        output += "\n";
-
-        if max_depth > 1 {
-            for i in 2..(max_depth + 1) {
-                let margin = i - 1;
-                output += format!(".toci-{i} {{\n").as_str();
-                output += "    display: grid;\n";
-                output += "    grid-template-columns: auto max-content;\n";
-                output += "    grid-template-areas: \"toci-header toci-number\";\n";
-                output += "    align-items: end;\n";
-                output += "    gap: 0.25rem;\n";
-                output += format!("    margin-left: {margin}em;\n").as_str();
-                output += "}\n";
-                output += "\n";
-            }
+        Ok(())
+    }
+
+    fn render_notes(&mut self, ctx: &Context) -> Result<()> {
+        let md = ctx.document;
+        if md.notes.is_empty() || md.meta.hide_notes { return Ok(()); }
+
+        self.render_header(ctx, &Element::Text(md.meta.notes_title.clone()), 1)?;
+        for (key, note) in &md.notes {
+            self.render_note(ctx, key, note, 0.0)?;
         }
-        
-
-        output += "\n";
-        output += "@media print {\n";
-        if !frontmatter.has("pdf-no-first-page") {
-            output += "    .title {\n";
-            output += "        page-break-after: always;\n";
-            output += "    }\n";
-        } else {
-            output += "    .title {\n";
-            output += "        display: flex;\n";
-            output += "        flex-direction: column;\n";
-            output += "    }\n";
+        Ok(())
+    }
+
+    fn render_definitions<'a>(&mut self, ctx: &Context, list: impl Iterator<Item = &'a Definition>) -> Result<()> {
+        let indent = self.indent;
+        for definition in list {
+            let fragments = self.styled(ctx, &definition.term, InlineStyle { bold: true, ..InlineStyle::new() })?;
+            self.render_fragments(fragments, indent, self.settings.text_size * 0.1);
+            for text in &definition.definitions {
+                let fragments = self.styled(ctx, text, InlineStyle::new())?;
+                self.render_fragments(fragments, indent + INDENT, self.settings.text_size * 0.1);
+            }
+            self.skip(self.settings.text_size * 0.3);
         }
-        output += "\n";
-        output += "    .toc {\n";
-        output += "        page-break-after: always;\n";
-        output += "    }\n";
-        output += "\n";
-        output += "    @page {\n";
-        output += format!("       @top-left   {{content: {top_left  }}}\n").as_str();
-        output += format!("       @top-center {{content: {top_center}}}\n").as_str();
-        output += format!("       @top-right  {{content: {top_right }}}\n").as_str();
-        output += "\n";
-        output += format!("       @bottom-left   {{content: {bottom_left  }}}\n").as_str();
-        output += format!("       @bottom-center {{content: {bottom_center}}}\n").as_str();
-        output += format!("       @bottom-right  {{content: {bottom_right }}}\n").as_str();
-        output += "    }\n";
-        output += "\n";
-        if !frontmatter.has("pdf-no-first-page") {
-            output += "   @page:first {\n";
-            output += "       @top-left   {content: none}\n";
-            output += "       @top-center {content: none}\n";
-            output += "       @top-right  {content: none}\n";
-            output += "\n";
-            output += "       @bottom-left   {content: none}\n";
-            output += "       @bottom-center {content: none}\n";
-            output += "       @bottom-right  {content: none}\n";
-            output += "   }\n";
+        Ok(())
+    }
+
+    fn render_glossary(&mut self, ctx: &Context) -> Result<()> {
+        let md = ctx.document;
+        if md.glossary.is_empty() || md.meta.hide_glossary { return Ok(()); }
+
+        self.render_header(ctx, &Element::Text(md.meta.glossary_title.clone()), 1)?;
+        self.render_definitions(ctx, md.glossary.iter().map(|(_, definition)| definition))
+    }
+
+    fn render_bibliography(&mut self, ctx: &Context) -> Result<()> {
+        let md = ctx.document;
+        if md.meta.hide_references || self.times_used.is_empty() { return Ok(()); }
+
+        self.render_header(ctx, &Element::Text(md.meta.bibliography_title.clone()), 1)?;
+        for (key, reference) in &md.references {
+            if !self.times_used.contains_key(key) { continue; }
+            self.render_inline(ctx, &Element::Text(to_bibliography(reference)), InlineStyle::new(), 0.0)?;
         }
-        output += "}\n";
-        output += "</style>\n";
-        output += "\n";
-        output += "<!-- for Atkinson Hyperlegible font-->\n";
-        output += "<link rel=\"preconnect\" href=\"https://fonts.googleapis.com\">\n";
-        output += "<link rel=\"preconnect\" href=\"https://fonts.gstatic.com\" crossorigin>\n";
-        output += "<link href=\"https://fonts.googleapis.com/css2?family=Atkinson+Hyperlegible:ital,wght@0,400;0,700;1,400;1,700&display=swap\" rel=\"stylesheet\">\n";
-        output += "\n";
-        output += "<!-- paged.js -->\n";
-        output += "<script src='https://unpkg.com/pagedjs/dist/paged.polyfill.js'></script>\n";
-        output += "\n";
-        output += "<!-- highlight.js -->\n";
-        output += "<script src=\"https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.7.0/highlight.min.js\"></script>\n";
-        output += "<link rel=\"stylesheet\" href=\"https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.7.0/styles/default.min.css\">\n";
-        output += "<script>\n";
-        output += "    class HighlightJSHandler extends Paged.Handler {\n";
-        output += "        constructor(chunker, polisher, caller) {\n";
-        output += "            super(chunker, polisher, caller);\n";
-        output += "        }\n";
-        output += "\n";
-        output += "        afterParsed(parsed) {\n";
-        output += "            hljs.highlightAll();\n";
-        output += "            parsed.querySelectorAll('code').forEach((el) => {\n";
-        output += "                hljs.highlightElement(el);\n";
-        output += "            })\n";
-        output += "        }\n";
-        output += "    }\n";
-        output += "\n";
-        output += "    Paged.registerHandlers(HighlightJSHandler);\n";
-        output += "</script>\n";
-        output
-    }
-
-    fn generate_id<F: FnMut()->String>(text: &String, mut default_generator: F) -> String {
-        if text.len() == 0 { default_generator() } else {
-
-            let mut len = 0;
-            for c in text.chars() {
-                if c.is_ascii_punctuation() { 
-                    break
-                } else { len += c.len_utf8() }
-            }
-            if len == 0 || text[0..len].trim_start().trim_end().len() == 0 { default_generator() } else {
-                let mut result = String::new();
-                for c in text[0..len].trim_start().trim_end().chars() {
-                    result.push(if c.is_whitespace() { '-' } else { c })
+        Ok(())
+    }
+
+    fn render_margins(&mut self, has_title: bool) {
+        let size  = self.settings.text_size * 0.8;
+        let font  = self.font(false, false);
+        let pages = self.pages.len();
+        let skip  = if self.settings.first_page && has_title { 1 } else { 0 };
+
+        for (index, page) in self.pages.iter_mut().enumerate().skip(skip) {
+            for (texts, y) in [(&self.settings.header, PAGE_HEIGHT - MARGIN / 2.0), (&self.settings.footer, MARGIN / 2.0 - size / 2.0)] {
+                for (column, text) in texts.iter().enumerate() {
+                    if text.is_empty() { continue; }
+                    let text  = expand_page_string(text, index + 1, pages);
+                    let width = font.text_width(&text, size);
+                    let x = match column {
+                        0 => MARGIN,
+                        1 => (PAGE_WIDTH - width) / 2.0,
+                        _ => PAGE_WIDTH - MARGIN - width,
+                    };
+                    page.text(x, y, font, size, &text);
                 }
-                result.remove_matches(|x: char| x != '-' && x.is_ascii_punctuation());
-                result.to_lowercase()
             }
         }
-    }*/
-
+    }
 
+    pub fn convert(&mut self, md: &AssDownDocument) -> Result<Vec<u8>> {
+        self.serialize(md)?;
+        Ok(build_pdf(&self.pages, &md.meta.title))
+    }
 }
 
-impl PMDSerializer for PMDPDFSerializer {
-    fn current_factbox(&mut self) -> Option<(FactBox, Option<String>)> {
-        self.common.current_factbox()
+// inline elements come back as fragments, block elements are drawn onto the pages as they're visited
+impl Serializer for PMDPDFSerializer {
+    type Output = Vec<Fragment>;
+
+    fn text(&mut self, _: &Context, text: &str) -> Result<Vec<Fragment>> {
+        let mut fragments = vec![];
+        self.push_text(text, self.style, &self.link, &mut fragments);
+        Ok(fragments)
     }
 
-    fn convert(&mut self, md: &PawsMarkdown) -> Result<String> {
-        self.header = md.header.clone();
-        self.notes_id = md.notes_id.clone();
-        self.bibliography_id = md.bibliography_id.clone();
+    fn join(&mut self, parts: Vec<Vec<Fragment>>) -> Vec<Fragment> {
+        parts.concat()
+    }
 
-        for (id, reference) in &md.references {
-            self.references.insert(id.clone(), Reference::new(reference.clone()));
-        }
-        
-        for (id, reference) in &md.notes {
-            self.notes.insert(id.clone(), Reference::new(reference.clone()));
+    fn visit_body(&mut self, ctx: &Context, body: &[(Element, String, SourceSpan)]) -> Result<Vec<Fragment>> {
+        for (element, id, _) in body {
+            let fragments = self.visit_element(ctx, element, id)?;
+            self.render_fragments(fragments, self.indent, self.settings.text_size * 0.6);
         }
+        Ok(vec![])
+    }
 
-        let output = self.common.html(md, None, &md.references, &md.notes)?;
-/*
-        let paragraph = md.body.iter().find(|&x| match x { BlogBody::Paragraph(_) => true, _ => false});
-        if let Some(BlogBody::Paragraph(content)) = paragraph {
-            let text = PMDPureTextSerializer::new().convert_paragraph(content)?;
-            description = text.trim_end().to_string();
-        } else {
-            description = "".into();
-        }
+    fn visit_factbox(&mut self, ctx: &Context, factbox: &FactBox, id: &String) -> Result<Vec<Fragment>> {
+        let size = self.settings.text_size;
+        let indent = self.indent;
+        self.skip(size * 0.5);
+        self.ensure_space(3.0 * self.line_height(size));
+        self.rule(indent, 1.0);
+
+        let title = Element::Text(factbox.title.trim().to_string());
+        self.render_inline(ctx, &title, InlineStyle { bold: true, ..InlineStyle::new() }, indent + 6.0)?;
+
+        let inner = ctx.in_factbox(factbox, id);
+        self.with_indent(indent + 6.0, |this| this.visit_body(&inner, &factbox.body))?;
 
-        let mut max_depth = 0;
-        if let Some(toc) = md.header.toc.as_ref() {
-            max_depth = toc.max_depth;
+        if !factbox.notes.is_empty() && !ctx.meta().hide_notes {
+            for (key, (note, _)) in &factbox.notes {
+                self.render_note(&inner, key, note, indent + 6.0)?;
+            }
         }
+        self.rule(indent, 1.0);
+        self.skip(size * 0.5);
+        Ok(vec![])
+    }
 
-        let header = self.prepare_header(&md.header.frontmatter, max_depth, &md.header.title, &description);
+    fn visit_hoverable(&mut self, ctx: &Context, hoverable: &Alternative) -> Result<Vec<Fragment>> {
+        let mut fragments = self.visit_inline(ctx, &hoverable.base)?;
+        fragments.extend(self.text(ctx, " (")?);
+        fragments.extend(self.visit_inline(ctx, &hoverable.alt)?);
+        fragments.extend(self.text(ctx, ")")?);
+        Ok(fragments)
+    }
 
-        output +=   "<!doctype html>\n";
-        output +=   "<html>\n";
-        self.push_tab();
+    fn visit_link(&mut self, ctx: &Context, link: &Alternative) -> Result<Vec<Fragment>> {
+        let previous = std::mem::replace(&mut self.link, Some(plain_text(&link.alt)));
+        let result = self.visit_inline(ctx, &link.base);
+        self.link = previous;
+        result
+    }
 
-        output += self.tab().as_str();
-        output +=   "<head>\n";
-        self.push_tab();
-        for line in header.lines() {
-            output += self.tab().as_str();
-            output += line;
-            output += "\n";
-        }
-        self.pop_tab();
-        output += self.tab().as_str();
-        output += "</head>\n";
-
-        output += self.tab().as_str();
-        output += "<body>\n";
-        self.push_tab();
-
-        output += self.tab().as_str();
-        output += "<main>\n";
-        self.push_tab();
-
-        {
-            let title    = &md.header.title;
-            let subtitle = &md.header.subtitle;
-            let banner   = &md.header.banner;
-            output += self.tab().as_str();
-            output += "<section class='title'>\n";
-            self.push_tab();
-                output += self.tab().as_str();
-                output += format!("<h1>{title}</h1>\n").as_str();
-                
-                output += self.tab().as_str();
-                output += format!("<p class='subtitle'>{subtitle}</p>\n").as_str();
-                
-                if banner.trim() != "" {
-                    output += self.tab().as_str();
-                    output += format!("<img src='{banner}'class='banner'></img>\n").as_str();
-                }
-            self.pop_tab();
+    fn visit_wiki_link(&mut self, ctx: &Context, link: &WikiLink) -> Result<Vec<Fragment>> {
+        // a broken link is only text
+        let url = ctx.wiki_link(&link.target).cloned().or_else(|| self.link.clone());
+        let previous = std::mem::replace(&mut self.link, url);
+        let result = match &link.label {
+            Some(label) => self.visit_inline(ctx, label),
+            None => self.text(ctx, &link.target),
+        };
+        self.link = previous;
+        result
+    }
 
-            output += self.tab().as_str();
-            output += format!("</section>\n").as_str();
-        }
+    fn visit_header(&mut self, ctx: &Context, text: &Element, depth: usize, _: &String) -> Result<Vec<Fragment>> {
+        self.render_header(ctx, text, depth)?;
+        Ok(vec![])
+    }
 
-        for element in &md.body {
-            let result = self.convert_element(element)?;
-            match element {
-                BlogBody::CodeBlock(_) => {
-                    output += result.as_str();
-                    output += "\n";
-                },
-                _ => {
-                    output += result.as_str();
-                }
-            }
-        }
-        if md.notes.len() != 0 {
-            let id = PMDPDFSerializer::generate_id(&md.header.notes_title, ||"missing".into());
-            let title = &md.header.notes_title;
-            
-            output += self.tab().as_str();
-            output += format!("<section class='page-break'>\n").as_str();
-            self.push_tab();
-                output += self.tab().as_str();
-                output += "<hr>\n";
-            self.pop_tab();
-            output += self.tab().as_str();
-            output += format!("</section>\n").as_str();
-                
-            output += self.tab().as_str();
-            output += format!("<section class='notes' id='{id}'>\n").as_str();
-            self.push_tab();
-                output += self.tab().as_str();
-                output += format!("<h1>{title}</h1>\n").as_str();
-            self.pop_tab();
-            output += self.tab().as_str();
-            output += format!("</section>\n").as_str();
-
-            for (key, val) in &md.notes {
-                let result = self.convert_element(val)?;
-                let link = format!("<a href='#^{key}'>^{key}:</a>");
-
-                output += self.tab().as_str();
-                output += format!("<section class='note' id=\"^{key}\">").as_str();
-                output.push('\n');
-                self.push_tab();
-
-                    output += self.tab().as_str();
-                    output += "<p>\n";
-
-                    self.push_tab();
-                        output += self.tab().as_str();
-                        output += "<sup>\n";
-
-                        self.push_tab();
-                            output += self.tab().as_str();
-                            output += link.as_str();
-                            output.push('\n');
-                            
-                            output += self.tab().as_str();
-                            output += result.as_str();
-                            output.push('\n');
-                            
-                            output += self.tab().as_str();
-                            output += format!("<a href=\"#{key}-backref\">").as_str();
-                            output += "↩";
-                            output += "</a>";
-                            output.push('\n');
-                        self.pop_tab();
-
-                        output += self.tab().as_str();
-                        output += "</sup>\n";
-                    self.pop_tab();
-
-                    output += self.tab().as_str();
-                    output += "</p>";
-                    output.push('\n');
-
-                self.pop_tab();
-                output += self.tab().as_str();
-                output += "</section>\n";
-            }
+    fn visit_italics(&mut self, ctx: &Context, text: &Element) -> Result<Vec<Fragment>> {
+        self.styled(ctx, text, InlineStyle { italic: true, ..self.style })
+    }
+
+    fn visit_bold(&mut self, ctx: &Context, text: &Element) -> Result<Vec<Fragment>> {
+        self.styled(ctx, text, InlineStyle { bold: true, ..self.style })
+    }
+
+    fn visit_strikethrough(&mut self, ctx: &Context, text: &Element) -> Result<Vec<Fragment>> {
+        let decoration = Decoration { strike: true, ..self.style.decoration };
+        self.styled(ctx, text, InlineStyle { decoration, ..self.style })
+    }
+
+    fn visit_underline(&mut self, ctx: &Context, text: &Element) -> Result<Vec<Fragment>> {
+        let decoration = Decoration { underline: true, ..self.style.decoration };
+        self.styled(ctx, text, InlineStyle { decoration, ..self.style })
+    }
+
+    fn visit_highlight(&mut self, ctx: &Context, text: &Element) -> Result<Vec<Fragment>> {
+        let decoration = Decoration { highlight: true, ..self.style.decoration };
+        self.styled(ctx, text, InlineStyle { decoration, ..self.style })
+    }
+
+    fn visit_superscript(&mut self, ctx: &Context, text: &Element) -> Result<Vec<Fragment>> {
+        self.styled(ctx, text, InlineStyle { superscript: true, subscript: false, ..self.style })
+    }
+
+    fn visit_subscript(&mut self, ctx: &Context, text: &Element) -> Result<Vec<Fragment>> {
+        self.styled(ctx, text, InlineStyle { subscript: true, superscript: false, ..self.style })
+    }
+
+    fn visit_inline_code(&mut self, _: &Context, code: &String) -> Result<Vec<Fragment>> {
+        let mut fragments = vec![];
+        self.push_text(code, InlineStyle { mono: true, ..self.style }, &self.link, &mut fragments);
+        Ok(fragments)
+    }
+
+    fn visit_inline_math(&mut self, _: &Context, tex: &String) -> Result<Vec<Fragment>> {
+        let size = self.settings.text_size;
+        let math = layout_math(tex, size, false);
+        let font = self.font(false, false);
+        Ok(vec![Fragment { text: tex.clone(), font, size, rise: 0.0, link: None, space_before: false, decoration: self.style.decoration, math: Some(math) }])
+    }
+
+    fn visit_math_block(&mut self, _: &Context, tex: &String, _: &String) -> Result<Vec<Fragment>> {
+        let indent = self.indent;
+        let size = self.settings.text_size;
+        let math = layout_math(tex, size, true);
+        let height = math.ascent + math.descent;
+
+        self.skip(size * 0.5);
+        self.ensure_space(height);
+        let x = MARGIN + indent + ((CONTENT_WIDTH - indent - math.width) / 2.0).max(0.0);
+        let baseline = self.y - math.ascent;
+        math.draw(self.page(), x, baseline);
+        self.y -= height;
+        self.skip(size);
+        Ok(vec![])
+    }
+
+    fn visit_codeblock(&mut self, ctx: &Context, block: &CodeBlock, id: &String) -> Result<Vec<Fragment>> {
+        let indent = self.indent;
+        let theme = self.theme;
+        let size = self.settings.text_size * 0.9;
+        let font = Font::new(FontFamily::Courier, FontStyle::Regular);
+        let height = size * 1.3;
+        let char_width = font.text_width(" ", size);
+        let code = block.code.replace('\t', "    ");
+        let tokens = highlight(if theme.is_some() { &block.lang } else { "" }, &code);
+        let lines = split_lines(&tokens);
+        // line numbers get a gutter as wide as the largest of them
+        let gutter = match block.start_line {
+            Some(start) => (start + lines.len().saturating_sub(1)).to_string().len() as f32 * char_width + 6.0,
+            None => 0.0,
+        };
+        let max_chars = ((CONTENT_WIDTH - indent - 12.0 - gutter) / char_width).max(1.0) as usize;
+
+        // without a theme everything is black on grey, `shade` goes from the normal background to the title bar
+        let fill = |page: &mut Page, y: f32, shade: f32| match theme {
+            Some(theme) => page.fill_rect_rgb(MARGIN + indent, y, CONTENT_WIDTH - indent, height, theme.background.mix(theme.foreground, shade).rgb()),
+            None => page.fill_rect(MARGIN + indent, y, CONTENT_WIDTH - indent, height, 0.94 - shade * 0.4),
+        };
+        let color = |kind: TokenKind| theme.map_or((0.0, 0.0, 0.0), |theme| theme.color(kind).rgb());
+
+        self.skip(size * 0.3);
+        if let Some(title) = &ctx.listing_title(block, id) {
+            let title_font = Font::new(FontFamily::Courier, FontStyle::Bold);
+            self.ensure_space(height * 2.0);
+            let y = self.y - height;
+            let page = self.page();
+            fill(page, y, 0.2);
+            let (r, g, b) = color(TokenKind::Plain);
+            page.set_color(r, g, b);
+            page.text(MARGIN + indent + 6.0, y + size * 0.35, title_font, size, title);
+            page.set_color(0.0, 0.0, 0.0);
+            self.y -= height;
         }
-        
-
-        if md.references.len() != 0 {
-            let id = PMDPDFSerializer::generate_id(&md.header.bibliography_title, ||"missing".into());
-            let title = &md.header.bibliography_title;
-
-            output += self.tab().as_str();
-            output += format!("<section class='page-break'>\n").as_str();
-            self.push_tab();
-                output += self.tab().as_str();
-                output += "<hr>\n";
-            self.pop_tab();
-            output += self.tab().as_str();
-            output += format!("</section>\n").as_str();
-
-            output += self.tab().as_str();
-            output += format!("<section class='bibliography' id='{id}'>\n").as_str();
-            self.push_tab();
-                output += self.tab().as_str();
-                output += format!("<h1>{title}</h1>\n").as_str();
-            self.pop_tab();
-            output += self.tab().as_str();
-            output += format!("</section>\n").as_str();
-
-            for (key, val) in &self.references {
-                if val.times_used == 0 {
-                    cprintln!("<y>warning:</> reference '{}' is not used and will not be included", key);
+        for (index, line) in lines.iter().enumerate() {
+            let chars: Vec<(TokenKind, char)> = line.iter().flat_map(|token| token.text.chars().map(|c| (token.kind, c))).collect();
+            let chunks: Vec<&[(TokenKind, char)]> = if chars.is_empty() { vec![&[]] } else { chars.chunks(max_chars).collect() };
+            let shade = if block.is_highlighted(index + 1) { 0.12 } else { 0.0 };
+            for (n, chunk) in chunks.iter().enumerate() {
+                self.ensure_space(height);
+                let y = self.y - height;
+                let page = self.page();
+                fill(page, y, shade);
+                if let Some(start) = block.start_line && n == 0 {
+                    let (r, g, b) = theme.map_or((0.5, 0.5, 0.5), |theme| theme.comment.rgb());
+                    page.set_color(r, g, b);
+                    page.text(MARGIN + indent + 6.0, y + size * 0.35, font, size, &(start + index).to_string());
                 }
-            }
-            
-            for (key, val) in &md.references {
-                let reference = self.references.get(key).unwrap();
-                if reference.times_used == 0 { continue; }
-
-                output += self.tab().as_str();
-                output += format!("<section id='{key}'>\n").as_str();
-                self.push_tab();
-                    output += self.tab().as_str();
-                    output += format!("<p>\n").as_str();
-                    self.push_tab();
-                    
-                        output += to_html_bibliography(val).as_str();
-                        output.push('\n');
-                    
-                    self.pop_tab();
-                    output += self.tab().as_str();
-                    output += "</p>\n";
-
-                self.pop_tab();
-                output += self.tab().as_str();
-                output += "</section>\n";
+                // courier is monospaced so every run of one colour starts at its column
+                let mut column = 0;
+                for run in chunk.chunk_by(|a, b| a.0 == b.0) {
+                    let (r, g, b) = color(run[0].0);
+                    let text: String = run.iter().map(|x| x.1).collect();
+                    page.set_color(r, g, b);
+                    page.text(MARGIN + indent + 6.0 + gutter + column as f32 * char_width, y + size * 0.35, font, size, &text);
+                    column += run.len();
+                }
+                page.set_color(0.0, 0.0, 0.0);
+                self.y -= height;
             }
         }
+        self.skip(self.settings.text_size * 0.6);
+        Ok(vec![])
+    }
 
-        self.pop_tab();
-        output += self.tab().as_str();
-        output +=   "</main>\n";
+    fn visit_image(&mut self, ctx: &Context, image: &Image, id: &String) -> Result<Vec<Fragment>> {
+        self.render_image(ctx, image)?;
+        if let Some(caption) = &image.caption {
+            let caption = match ctx.figure(id) {
+                Some(figure) => Element::Span(Span { elements: vec![
+                    Element::Bold(Box::new(Element::Text(format!("{}: ", figure.label())))),
+                    (**caption).clone(),
+                ]}),
+                None => (**caption).clone(),
+            };
+            self.render_inline(ctx, &caption, InlineStyle::new(), self.indent)?;
+        }
+        Ok(vec![])
+    }
 
-        self.pop_tab();
-        output += self.tab().as_str();
-        output +=   "</body>\n";
+    fn visit_video(&mut self, ctx: &Context, video: &Media, _: &String) -> Result<Vec<Fragment>> {
+        self.render_media_link(ctx, video, "Video")?;
+        Ok(vec![])
+    }
 
-        self.pop_tab();
-        output += self.tab().as_str();
-        output +=   "</html>\n";
+    fn visit_audio(&mut self, ctx: &Context, audio: &Media, _: &String) -> Result<Vec<Fragment>> {
+        self.render_media_link(ctx, audio, "Audio")?;
+        Ok(vec![])
+    }
+
+    fn visit_iframe(&mut self, ctx: &Context, iframe: &Media, _: &String) -> Result<Vec<Fragment>> {
+        self.render_media_link(ctx, iframe, "Embedded")?;
+        Ok(vec![])
+    }
 
-        */
-        let mut html = Builder::new().suffix(".html").tempfile_in(".")?;
+    fn visit_quote(&mut self, ctx: &Context, quote: &Quote, _: &String) -> Result<Vec<Fragment>> {
+        let indent = self.indent;
+        let top = self.y;
+        let pages = self.pages.len();
+        let italic = InlineStyle { italic: true, ..self.style };
+        let previous = std::mem::replace(&mut self.style, italic);
+        let result = self.with_indent(indent + INDENT, |this| {
+            for Spanned { node: element, .. } in &quote.body {
+                let fragments = this.visit_element(ctx, element, &String::new())?;
+                this.render_fragments(fragments, this.indent, this.settings.text_size * 0.3);
+            }
+            Ok(())
+        });
+        self.style = previous;
+        result?;
+        // only draw the bar if we didn't move to another page while rendering the quote
+        if pages == self.pages.len() {
+            let x = MARGIN + indent + INDENT / 2.0;
+            let bottom = self.y;
+            self.page().line((x, top), (x, bottom), 2.0, 0.6);
+        }
+        if let Some(attribution) = &quote.attribution {
+            let text = Element::Span(Span { elements: vec![Element::Text("— ".into()), (**attribution).clone()] });
+            self.render_inline(ctx, &text, InlineStyle::new(), indent + INDENT)?;
+        }
+        Ok(vec![])
+    }
 
-        writeln!(html, "{}", output)?;
-        let path = html.path().to_str().expect("could not convert parth to &str");
-        std::fs::write("tmp.html", output)?;
+    fn visit_callout(&mut self, ctx: &Context, callout: &Callout, _: &String) -> Result<Vec<Fragment>> {
+        let indent = self.indent;
+        let top = self.y;
+        let pages = self.pages.len();
+        self.render_inline(ctx, &callout.title, InlineStyle { bold: true, ..InlineStyle::new() }, indent + INDENT)?;
+        self.with_indent(indent + INDENT, |this| {
+            for Spanned { node: element, .. } in &callout.body {
+                let fragments = this.visit_element(ctx, element, &String::new())?;
+                this.render_fragments(fragments, this.indent, this.settings.text_size * 0.3);
+            }
+            Ok(())
+        })?;
+        // same as quotes, a bar that would span two pages isn't drawn
+        if pages == self.pages.len() {
+            let x = MARGIN + indent + INDENT / 2.0 - 2.0;
+            let bottom = self.y;
+            self.page().fill_rect_rgb(x, bottom, 4.0, top - bottom, callout_color(&callout.kind));
+        }
+        self.skip(self.settings.text_size * 0.3);
+        Ok(vec![])
+    }
 
-        build_pdf(path)
+    fn visit_list(&mut self, ctx: &Context, list: &[ListItem], _: &String) -> Result<Vec<Fragment>> {
+        self.render_list_items(ctx, list, self.indent)?;
+        self.skip(self.settings.text_size * 0.3);
+        Ok(vec![])
     }
 
-    fn convert_factbox(&mut self, factbox: &FactBox, id: &String) -> Result<String> {
-        // cprintln!("<r>error:</> fact boxes aren't implemented for pdf output");
-        // Ok("".into())
-        self.common.convert_factbox(factbox, id)
-    }
-
-    fn convert_hoverable(&mut self, hoverable: &Alternative) -> Result<String> {
-        Ok(self.convert_element(no_id!(&hoverable.base))?)
-    }
+    fn visit_definition_list(&mut self, ctx: &Context, list: &[Definition], _: &String) -> Result<Vec<Fragment>> {
+        self.render_definitions(ctx, list.iter())?;
+        self.skip(self.settings.text_size * 0.3);
+        Ok(vec![])
+    }
 
-    fn convert_styled(&mut self, styled: &Alternative) -> Result<String> {
-        /*
-        let text  = self.convert_element(&styled.alt)?;
-        let style = self.convert_element(&styled.base)?;
-        Ok(format!("<span class='embedded-style' style='{style}'>{text}</span>"))
-        */
-        self.common.convert_styled(styled)
-    }
-
-    fn convert_link(&mut self, link: &Alternative) -> Result<String> {
-        /*
-        let href = self.convert_element(&link.alt)?;
-        let text = self.convert_element(&link.base)?;
-        Ok(format!("<a class='inline-link' href='{href}'>{text}</a>"))
-        */
-        self.common.convert_link(link)
-    }
-
-    fn convert_header(&mut self, text: &Box<BlogBody>, depth: usize, id: &String) -> Result<String> {
-        self.common.convert_header(text, depth, id)
-        /*
-        let text  = self.convert_element(text)?;
-        let id    = Self::generate_id(&text, ||"missing".into());
-
-        let mut result = self.tab();
-        result += format!("<section id={id}>\n").as_str();
-        self.push_tab();
-            result += self.tab().as_str();
-            result += format!("<h{depth}>{text}</h{depth}>\n").as_str();
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-        */
-    }
-
-    fn convert_italics(&mut self, text: &Box<BlogBody>) -> Result<String> {
-        self.common.convert_italics(text)
-        /*
-        let inner_text = self.convert_element(&text)?;
-        Ok(format!("<i>{inner_text}</i>"))
-        */
-    }
-
-    fn convert_bold(&mut self, text: &Box<BlogBody>) -> Result<String> {
-        self.common.convert_bold(text)
-        /*
-        let inner_text = self.convert_element(&text)?;
-        Ok(format!("<b>{inner_text}</b>"))
-        */
-    }
-
-    fn convert_inlinecode(&mut self, text: &String) -> Result<String> {
-        // Ok(format!("<code>{text}</code>"))
-        self.common.convert_inlinecode(text)
-    }
-
-    fn convert_codeblock(&mut self, text: &String, id: &String) -> Result<String> {
-        self.common.convert_codeblock(text, id)
-        /*
-        let first_line = text.lines().nth(0).context("expected at least one line in codeblock")?;
-        let mut words = first_line.split(|x: char| x.is_whitespace());
-        let lang = words.nth(0).unwrap_or("plaintext");
-        let mut body = text[text.find('\n').context("expected at least one line in codeblock")? + 1..].to_string();
-
-        body = body.replace("&", "&amp;")
-                   .replace("<", "&lt;")
-                   .replace(">", "&gt;");
-        body = body.trim_end().replace("\r\n", "\n");
-        
-        let mut result = self.tab();
-        result += format!("<section class='code-block'>\n").as_str();
-        self.push_tab();
-            result += self.tab().as_str();
-            result += format!("<pre><code class='language-{lang}'>{body}</code></pre>\n").as_str();
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-        */
-    }
-
-    fn convert_image(&mut self, src: &String, alt: &String, id: &String) -> Result<String> {
-        self.common.convert_image(src, alt, id)
-        /*
-        let mut result = self.tab();
-        result += format!("<section class='image'>\n").as_str();
-        self.push_tab();
-            result += self.tab().as_str();
-            result += format!("<img src='{src}' alt='{alt}'></img>\n").as_str();
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-        */
-    }
-
-    fn convert_quote(&mut self, lines: &Vec<BlogBody>, id: &String) -> Result<String> {
-        self.common.convert_quote(lines, id)
-        /*
-        let mut quote_elements : Vec<String> = vec![];
-        for elem in lines {
-            let text = self.convert_element(elem)?;
-            quote_elements.push(format!("{text}<br/>"));
+    fn visit_table(&mut self, ctx: &Context, table: &Table, id: &String) -> Result<Vec<Fragment>> {
+        const PADDING: f32 = 4.0;
+        let indent = self.indent;
+        let size = self.settings.text_size;
+        let height = self.line_height(size);
+        // every column gets the same width, the cells wrap inside of it
+        let column_width = (CONTENT_WIDTH - indent) / table.header.len().max(1) as f32;
+
+        self.skip(size * 0.3);
+        if let Some(figure) = ctx.figure(id) {
+            self.render_inline(ctx, &Element::Text(figure.label()), InlineStyle { bold: true, ..InlineStyle::new() }, indent)?;
         }
-        let text = quote_elements.join("\n");
-
-        let mut result = self.tab();
-        result += format!("<section class='quote'>\n").as_str();
-        self.push_tab();
-            result += self.tab().as_str();
-            result += format!("<div class='quote-line'></div><blockquote class='quote-text'>{text}</blockquote>\n").as_str();
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-        */
-    }
-
-    fn convert_list(&mut self, list: &Vec<BlogBody>, id: &String) -> Result<String> {
-        self.common.convert_list(list, id)
-        /*
-        let mut result = self.tab();
-        result += format!("<section class='list'>\n").as_str();
-        self.push_tab();
-            result += self.tab().as_str();
-            result += "<ul>";
-            self.push_tab();
-            for elem in list {
-                let text = self.convert_element(elem)?;
-                result += self.tab().as_str();
-                result += format!("<li>{text}</li>\n").as_str();
+        for (index, row) in std::iter::once(&table.header).chain(&table.rows).enumerate() {
+            let style = InlineStyle { bold: index == 0, ..InlineStyle::new() };
+            let mut cells = vec![];
+            for cell in row {
+                let fragments = merge_spaces(self.styled(ctx, cell, style)?);
+                cells.push(break_lines(&fragments, column_width - 2.0 * PADDING));
             }
-            self.pop_tab();
-            result += self.tab().as_str();
-            result += "</ul>";
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
-
-        Ok(result)
-        */
-    }
-
-    fn convert_paragraph(&mut self, text: &Box<BlogBody>, id: &String) -> Result<String> {
-        self.common.convert_paragraph(text, id)
-        /*
-        let paragraph = self.convert_element(text)?;
-
-        let mut result = self.tab();
-        result += format!("<section>\n").as_str();
-        self.push_tab();
-            result += self.tab().as_str();
-            result += "<p>\n";
-            self.push_tab();
-            
-            for line in paragraph.trim_end().lines() {
-                result += self.tab().as_str();
-                result += line;
-                result.push('\n');
+            let row_height = cells.iter().map(Vec::len).max().unwrap_or(0).max(1) as f32 * height;
+
+            self.ensure_space(row_height);
+            if index == 0 {
+                let y = self.y - row_height;
+                self.page().fill_rect(MARGIN + indent, y, CONTENT_WIDTH - indent, row_height, 0.94);
             }
+            for (column, (lines, alignment)) in cells.iter().zip(&table.alignments).enumerate() {
+                let left = MARGIN + indent + column as f32 * column_width + PADDING;
+                for (n, line) in lines.iter().enumerate() {
+                    let free = column_width - 2.0 * PADDING - line.width;
+                    let x = match alignment {
+                        Alignment::Right  => left + free,
+                        Alignment::Center => left + free / 2.0,
+                        _                 => left,
+                    };
+                    let baseline = self.y - (n + 1) as f32 * height + (height - size) / 2.0 + size * 0.2;
+                    self.draw_line(line, x, baseline);
+                }
+            }
+            self.y -= row_height;
 
-            self.pop_tab();
-            result += self.tab().as_str();
-            result += "</p>\n";
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += "</section>\n";
+            let y = self.y;
+            let width = if index == 0 { 1.0 } else { 0.5 };
+            self.page().line((MARGIN + indent, y), (PAGE_WIDTH - MARGIN, y), width, 0.6);
+        }
+        self.skip(size * 0.6);
+        Ok(vec![])
+    }
 
-        Ok(result)
-        */
+    fn visit_citation(&mut self, ctx: &Context, id: &String) -> Result<Vec<Fragment>> {
+        if let Some(reference) = ctx.reference(id) {
+            if let Some(times_used) = self.times_used.get_mut(id) {
+                *times_used += 1;
+            } else {
+                self.times_used.insert(id.clone(), 1);
+            }
+            self.text(ctx, to_citation(reference).trim_end())
+        } else {
+            self.text(ctx, "(Missing Source)")
+        }
     }
 
-    fn convert_text(&mut self, text: &String) -> Result<String> {
-        // Ok(text.clone())
-        self.common.convert_text(text)
+    fn visit_note(&mut self, ctx: &Context, id: &String) -> Result<Vec<Fragment>> {
+        if ctx.meta().hide_notes { return Ok(vec![]); }
+
+        let mut fragments = vec![];
+        self.push_text(id, InlineStyle { superscript: true, ..self.style }, &self.link, &mut fragments);
+        if let Some(first) = fragments.first_mut() {
+            first.space_before = false;
+        }
+        Ok(fragments)
     }
 
-    fn convert_span(&mut self, span: &Span) -> Result<String> {
-        self.common.convert_span(span)
-        /*
-        let mut result = String::new();
-        for elem in &span.elements {
-            result += if let BlogBody::Text(text) = elem {
-                text.to_string()
+    fn visit_toc(&mut self, ctx: &Context) -> Result<Vec<Fragment>> {
+        let Some(toc) = &ctx.meta().toc else { return Ok(vec![]) };
+        self.render_header(ctx, &Element::Text(toc.title.clone()), 1)?;
+        for (text, depth, _) in &toc.headers {
+            if *depth > toc.max_depth && toc.max_depth != 0 { continue; }
+            let indent = INDENT * depth.saturating_sub(1) as f32;
+            let fragments = if let box Element::FactBox(factbox) = text {
+                self.styled(ctx, &Element::Text(factbox.title.trim().to_string()), InlineStyle::new())?
             } else {
-                self.convert_element(elem)?
-            }.as_str()
+                self.styled(ctx, text, InlineStyle::new())?
+            };
+            self.render_fragments(fragments, indent, 0.0);
         }
-        Ok(result)
-        */
-    }
-    
-    fn convert_citation(&mut self, id: &String) -> Result<String> {
-        self.common.convert_citation(id)
-        /*
-        if let Some(reference) = self.references.get_mut(id) {
-            reference.times_used += 1;
-            let text = to_citation(&reference.def);
-            let mut result = self.tab();
-            result += "<cite>";
-            result += format!("<a href='#{id}'>").as_str();
-            result += text.as_str();
-            result += "</a>";
-            result += "</cite>";
-            Ok(result)
-        } else {
-            cprintln!("<y>warning:</> {} has no source", id);
-            Ok(format!("(MISSING CITATION)").to_string())
+        self.skip(self.settings.text_size * 0.6);
+        Ok(vec![])
+    }
+
+    fn visit_page_break(&mut self, _: &Context) -> Result<Vec<Fragment>> {
+        if let Some(page) = self.pages.last() && !page.is_empty() {
+            self.new_page();
         }
-        */
-    }
-
-    fn convert_factbox_note(&mut self, factbox: &FactBox, factbox_id: Option<&String>, id: &String) -> Result<String> {
-        self.common.convert_factbox_note(factbox, factbox_id, id)
-    }
-
-    fn convert_note(&mut self, id: &String) -> Result<String> {
-        // Ok(format!("<sup><a id='{id}-backref' href='#^{id}'>{id}</a></sup>"))
-        self.common.convert_note(id)
-    }
-
-    fn convert_toc(&mut self) -> Result<String> {
-        // self.common.convert_toc()
-       
-        if self.header.toc.is_none() { return Err(anyhow!("")); }
-        let toc = self.header.toc.clone().unwrap();
-        let title = &toc.title;
-
-        let mut result = self.common.tab();
-        
-        result += "<section id='table-of-contents'>\n";
-        self.common.push_tab();
-            result += self.common.tab().as_str();
-            result += format!("<h1>{title}</h1>\n").as_str();
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
-        result += "</section>\n";
-
-        result += self.common.tab().as_str();
-        result += "<section class='toc'>\n";
-        self.common.push_tab();
-            result += self.common.tab().as_str();
-            result += "<ul>\n";
-            self.common.push_tab();
-                for (elem, depth, id) in &toc.headers {
-                    // let text = self.convert_element(elem)?;
-                    // let id    = Self::generate_id(&text, ||"missing".into());
-                    let text = if let &box BlogBody::FactBox(fbox) = &elem {
-                        fbox.title.clone()
-                    } else {
-                        self.convert_element(no_id!(elem))?
-                    };
-                    result += self.common.tab().as_str();
-                    result += format!("<li class='toci-{depth}'>\n").as_str();
-                    self.common.push_tab();
-                        result += self.common.tab().as_str();
-                        result += format!("<a class='toci-header' href='#{id}'>{text}</a>\n").as_str();
-                        result += self.common.tab().as_str();
-                        result += format!("<a class='toci-number' href='#{id}'></a>\n").as_str();
-                    self.common.pop_tab();
-                    result += self.common.tab().as_str();
-                    result += "</li>\n";
-                }
-                /*
-                if !self.notes.is_empty() { 
-                    let title = self.header.notes_title.clone();
-                    let id = &self.notes_id;
-                    result += self.common.tab().as_str();
-                    result += format!("<li class='toci-1'>\n").as_str();
-                    self.common.push_tab();
-                        result += self.common.tab().as_str();
-                        result += format!("<a class='toci-header' href='#{id}'>{title}</a>\n").as_str();
-                        result += self.common.tab().as_str();
-                        result += format!("<a class='toci-number' href='#{id}'></a>\n").as_str();
-                    self.common.pop_tab();
-                    result += self.common.tab().as_str();
-                    result += "</li>\n";
-                }
-                if !self.references.is_empty() { 
-                    let title = self.header.bibliography_title.clone();
-                    let id = &self.bibliography_id;
-                    result += self.common.tab().as_str();
-                    result += format!("<li class='toci-1'>\n").as_str();
-                    self.common.push_tab();
-                        result += self.common.tab().as_str();
-                        result += format!("<a class='toci-header' href='#{id}'>{title}</a>\n").as_str();
-                        result += self.common.tab().as_str();
-                        result += format!("<a class='toci-number' href='#{id}'></a>\n").as_str();
-                    self.common.pop_tab();
-                    result += self.common.tab().as_str();
-                    result += "</li>\n";
-                }
-                */
-            self.common.pop_tab();
-            result += self.common.tab().as_str();
-            result += "</ul>\n";
+        Ok(vec![])
+    }
+
+    fn serialize(&mut self, md: &AssDownDocument) -> Result<Vec<Fragment>> {
+        let ctx = Context::new(md);
+        self.settings = PdfSettings::new(&md.meta.frontmatter.clone().unwrap_or(Frontmatter::new()));
+        self.theme = code_theme(&md.meta);
+        self.times_used = OrderedMap::new();
+        self.pages = vec![];
+        self.style = InlineStyle::new();
+        self.link = None;
+        self.indent = 0.0;
+
+        self.render_title_page(&ctx)?;
+        self.visit_body(&ctx, &md.body)?;
+        self.render_notes(&ctx)?;
+        self.render_glossary(&ctx)?;
+        self.render_bibliography(&ctx)?;
+
+        if self.pages.is_empty() {
+            self.new_page();
+        }
+        self.render_margins(!md.meta.title.is_empty());
+
+        Ok(vec![])
+    }
+}
+
+// the bar next to a callout, kinds nobody thought of are purple
+fn callout_color(kind: &str) -> (f32, f32, f32) {
+    match kind {
+        "note" | "info" | "todo" | "abstract" | "summary"   => (0.25, 0.45, 0.85),
+        "tip" | "hint" | "success" | "check" | "done"       => (0.2, 0.65, 0.35),
+        "warning" | "caution" | "attention" | "question"    => (0.95, 0.6, 0.1),
+        "danger" | "error" | "bug" | "failure" | "fail"     => (0.85, 0.2, 0.2),
+        _ => (0.55, 0.35, 0.8),
+    }
+}
 
-        self.common.pop_tab();
-        result += self.common.tab().as_str();
-        result += "</section>\n";
+// a css unit in points, `relative_to` is what a percentage is a percentage of.
+// the font relative units all use the text size, and the viewport ones use the page
+fn unit_to_points(unit: &Unit, text_size: f32, relative_to: f32) -> Option<f32> {
+    let (value, scale) = match *unit {
+        Unit::Px(n)  => (n, 0.75),
+        Unit::Pt(n)  => (n, 1.0),
+        Unit::Pc(n)  => (n, 12.0),
+        Unit::In(n)  => (n, 72.0),
+        Unit::Cm(n)  => (n, 72.0 / 2.54),
+        Unit::Mm(n)  => (n, 72.0 / 25.4),
+        Unit::Q(n)   => (n, 72.0 / 101.6),
+        Unit::Em(n) | Unit::Rem(n) | Unit::Lh(n) | Unit::Rlh(n) | Unit::Ic(n) | Unit::Ric(n) => (n, text_size),
+        Unit::Ch(n) | Unit::Rch(n) | Unit::Ex(n) | Unit::Cap(n) | Unit::Rcap(n) => (n, text_size * 0.5),
+        Unit::Percentage(n) | Unit::Cqw(n) | Unit::Cqi(n) | Unit::Cqh(n) | Unit::Cqb(n) |
+        Unit::Cqmin(n) | Unit::Cqmax(n) => (n, relative_to / 100.0),
+        Unit::Vw(n) | Unit::Vi(n) => (n, PAGE_WIDTH / 100.0),
+        Unit::Vh(n) | Unit::Vb(n) => (n, PAGE_HEIGHT / 100.0),
+        Unit::Vmax(n) => (n, PAGE_WIDTH.max(PAGE_HEIGHT) / 100.0),
+        Unit::Auto => return None,
+    };
+    Some(value as f32 * scale).filter(|x| *x > 0.0)
+}
 
-        Ok(result)
+fn plain_text(element: &Element) -> String {
+    match element {
+        Element::Text(text) | Element::InlineCode(text) | Element::InlineMath(text) => text.clone(),
+        Element::Span(span) => span.elements.iter().map(plain_text).collect(),
+        Element::Bold(inner) | Element::Italics(inner) | Element::Paragraph(inner) |
+        Element::Strikethrough(inner) | Element::Underline(inner) | Element::Highlight(inner) |
+        Element::Superscript(inner) | Element::Subscript(inner) => plain_text(inner),
+        _ => String::new(),
     }
+}
 
-    fn convert_page_break(&mut self) -> Result<String> {
-        self.common.convert_page_break()
-        /*
-        let mut result = self.tab();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(text: &str) -> Fragment {
+        Fragment {
+            text: text.into(),
+            font: Font::new(FontFamily::Courier, FontStyle::Regular),
+            size: 10.0,
+            rise: 0.0,
+            link: None,
+            space_before: true,
+            decoration: Decoration::default(),
+            math: None,
+        }
+    }
 
-        result += format!("<section class='page-break'>\n").as_str();
-        self.push_tab();
-            result += self.tab().as_str();
-            result += "<hr>\n";
-        self.pop_tab();
-        result += self.tab().as_str();
-        result += format!("</section>\n").as_str();
+    #[test]
+    fn page_strings() {
+        assert_eq!(expand_page_string("%p/%np", 2, 7), "2/7");
+        assert_eq!(expand_page_string("page %page of %pages", 3, 4), "page 3 of 4");
+    }
+
+    #[test]
+    fn line_breaking() {
+        // every character in courier at 10pt is 6pt wide
+        let words = [fragment("aaaa"), fragment("bbbb"), fragment("cccc")];
+        let lines = break_lines(&words, 60.0);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].fragments.len(), 2);
+        assert_eq!(lines[0].fragments[1].0, 30.0);
+        assert_eq!(lines[1].fragments[0].0, 0.0);
+
+        let long = [fragment("abcdefghijklmnopqrstuvwxyz")];
+        let lines = break_lines(&long, 60.0);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].fragments[0].1.text, "abcdefghij");
+        assert_eq!(lines[2].fragments[0].1.text, "uvwxyz");
+    }
+
+    #[test]
+    fn page_breaks() {
+        let paragraph = |text: &str| (Element::Paragraph(Box::new(Element::Text(text.into()))), String::new(), SourceSpan::default());
+        let md = AssDownDocument {
+            meta: MetaData::default(),
+            bibliography_id: String::new(),
+            notes_id: String::new(),
+            glossary_id: String::new(),
+            references: OrderedMap::new(),
+            notes: OrderedMap::new(),
+            glossary: OrderedMap::new(),
+            figures: OrderedMap::new(),
+            wiki_links: OrderedMap::new(),
+            backlinks: vec![],
+            body: vec![paragraph("first"), (Element::PageBreak, String::new(), SourceSpan::default()), paragraph("second")],
+        };
+        let mut serializer = PMDPDFSerializer::new();
+        let pdf = serializer.convert(&md).unwrap();
+        assert_eq!(serializer.pages.len(), 2);
+        assert!(pdf.starts_with(b"%PDF-"));
+    }
 
-        Ok(result)
-        */
+    #[test]
+    fn image_units() {
+        assert_eq!(unit_to_points(&Unit::Px(400), 11.0, 100.0), Some(300.0));
+        assert_eq!(unit_to_points(&Unit::In(2), 11.0, 100.0), Some(144.0));
+        assert_eq!(unit_to_points(&Unit::Em(2), 11.0, 100.0), Some(22.0));
+        assert_eq!(unit_to_points(&Unit::Percentage(50), 11.0, 300.0), Some(150.0));
+        assert_eq!(unit_to_points(&Unit::Auto, 11.0, 100.0), None);
     }
 }
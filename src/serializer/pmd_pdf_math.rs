@@ -0,0 +1,414 @@
+use crate::parser::math::{parse_tex, MathNode, MathVariant};
+use crate::pdf::{symbol_char, Font, FontFamily, FontStyle, Page};
+
+// a tiny version of what tex does with formulas, everything is a box with a width, a height above
+// the baseline and a depth below it. boxes are placed next to, above or below each other and the
+// things that can't be drawn with a font, like fraction bars and radicals, are drawn as lines
+
+#[derive(Debug, PartialEq, Clone)]
+enum MathItem {
+    Glyphs { x: f32, y: f32, font: Font, size: f32, text: String },
+    Line { from: (f32, f32), to: (f32, f32), width: f32 },
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MathBox {
+    pub width: f32,
+    pub ascent: f32,
+    pub descent: f32,
+    items: Vec<MathItem>,
+}
+
+impl MathBox {
+    fn space(width: f32) -> Self {
+        Self { width, ..Self::default() }
+    }
+
+    // places `other` with its origin at (x, y) of this box without touching the size
+    fn place(&mut self, other: MathBox, x: f32, y: f32) {
+        self.ascent  = self.ascent.max(other.ascent + y);
+        self.descent = self.descent.max(other.descent - y);
+        for item in other.items {
+            self.items.push(match item {
+                MathItem::Glyphs { x: gx, y: gy, font, size, text } => MathItem::Glyphs { x: gx + x, y: gy + y, font, size, text },
+                MathItem::Line { from, to, width } => MathItem::Line { from: (from.0 + x, from.1 + y), to: (to.0 + x, to.1 + y), width },
+            });
+        }
+    }
+
+    fn append(&mut self, other: MathBox) {
+        let width = other.width;
+        self.place(other, self.width, 0.0);
+        self.width += width;
+    }
+
+    fn line(&mut self, from: (f32, f32), to: (f32, f32), width: f32) {
+        self.ascent  = self.ascent.max(from.1.max(to.1) + width / 2.0);
+        self.descent = self.descent.max(-from.1.min(to.1) + width / 2.0);
+        self.items.push(MathItem::Line { from, to, width });
+    }
+
+    pub fn draw(&self, page: &mut Page, x: f32, baseline: f32) {
+        for item in &self.items {
+            match item {
+                MathItem::Glyphs { x: gx, y: gy, font, size, text } => page.text(x + gx, baseline + gy, *font, *size, text),
+                MathItem::Line { from, to, width } => page.line((x + from.0, baseline + from.1), (x + to.0, baseline + to.1), *width, 0.0),
+            }
+        }
+    }
+}
+
+// where fraction bars sit and operators are centered, roughly the height of a minus sign
+const AXIS: f32 = 0.25;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Spacing {
+    None,
+    Binary,
+    Relation,
+    Punctuation,
+}
+
+fn spacing(op: &str) -> Spacing {
+    match op {
+        "+" | "−" | "±" | "∓" | "×" | "÷" | "·" | "∗" | "∘" | "∪" | "∩" | "∧" | "∨" | "∖" => Spacing::Binary,
+        "=" | "<" | ">" | "≤" | "≥" | "≠" | "≈" | "≡" | "∼" | "∝" | "≪" | "≫" | "∈" | "∉" |
+        "⊂" | "⊆" | "⊃" | "→" | "←" | "↔" | "⇒" | "⇐" | "⇔" | "↦" | ":" | "⊥" => Spacing::Relation,
+        "," | ";" => Spacing::Punctuation,
+        _ => Spacing::None,
+    }
+}
+
+fn times(style: FontStyle) -> Font {
+    Font::new(FontFamily::Times, style)
+}
+
+// a run of text, split between times and the symbol font depending on which one has the glyph
+fn glyphs(text: &str, font: Font, size: f32) -> MathBox {
+    let mut result = MathBox { ascent: size * 0.7, descent: size * 0.2, ..MathBox::default() };
+    let mut run = String::new();
+    let mut run_font = font;
+    // the symbol font has no double bar
+    let text = text.replace('‖', "||");
+    let flush = |result: &mut MathBox, run: &mut String, run_font: Font| {
+        if run.is_empty() { return }
+        let width = run_font.text_width(run, size);
+        result.items.push(MathItem::Glyphs { x: result.width, y: 0.0, font: run_font, size, text: std::mem::take(run) });
+        result.width += width;
+    };
+    for c in text.chars() {
+        // the minus sign isn't in the text fonts, and they only have latin-1 apart from punctuation
+        let in_text_font = c != '−' && (c as u32) < 0x100;
+        let char_font = if !in_text_font && symbol_char(c).is_some() {
+            Font::new(FontFamily::Symbol, FontStyle::Regular)
+        } else {
+            font
+        };
+        if char_font != run_font {
+            flush(&mut result, &mut run, run_font);
+            run_font = char_font;
+        }
+        run.push(c);
+    }
+    flush(&mut result, &mut run, run_font);
+    result
+}
+
+struct Layout {
+    size: f32,
+    /// display style, big operators and fractions stay large
+    display: bool,
+    variant: Option<MathVariant>,
+}
+
+impl Layout {
+    fn smaller(&self, scale: f32) -> Layout {
+        Layout { size: (self.size * scale).max(5.0), display: false, variant: self.variant }
+    }
+
+    fn sequence(&self, nodes: &[MathNode]) -> MathBox {
+        let mut result = MathBox::default();
+        let mut previous: Option<&MathNode> = None;
+        for node in nodes {
+            if let MathNode::Operator(op) = node {
+                // a minus at the start or after another operator is a sign, not a subtraction
+                let unary = matches!(previous, None | Some(MathNode::Operator(_)));
+                let space = match spacing(op) {
+                    Spacing::Binary if unary => 0.0,
+                    Spacing::Binary => 0.22,
+                    Spacing::Relation => 0.28,
+                    _ => 0.0,
+                };
+                let space = if self.display || self.size >= 8.0 { space * self.size } else { 0.0 };
+                result.append(MathBox::space(space));
+                result.append(self.node(node));
+                result.append(MathBox::space(if spacing(op) == Spacing::Punctuation { 0.17 * self.size } else { space }));
+            } else {
+                result.append(self.node(node));
+            }
+            previous = Some(node);
+        }
+        result
+    }
+
+    fn node(&self, node: &MathNode) -> MathBox {
+        let size = self.size;
+        match node {
+            MathNode::Identifier(name) => {
+                let style = match self.variant {
+                    Some(MathVariant::Roman) => FontStyle::Regular,
+                    Some(MathVariant::Bold | MathVariant::Blackboard) => FontStyle::Bold,
+                    None => FontStyle::Italic,
+                };
+                glyphs(name, times(style), size)
+            },
+            MathNode::Number(text) => glyphs(text, times(if self.variant == Some(MathVariant::Bold) { FontStyle::Bold } else { FontStyle::Regular }), size),
+            MathNode::Operator(op) => glyphs(op, times(FontStyle::Regular), size),
+            MathNode::Text(text)   => glyphs(text, times(FontStyle::Regular), size),
+            MathNode::Error(raw)   => glyphs(raw, Font::new(FontFamily::Courier, FontStyle::Regular), size),
+            MathNode::Function(name) => {
+                let mut result = glyphs(name, times(FontStyle::Regular), size);
+                result.append(MathBox::space(0.17 * size));
+                result
+            },
+            MathNode::Space(width) => MathBox::space(width * size),
+            MathNode::Group(nodes) => self.sequence(nodes),
+            MathNode::Styled(variant, node) => Layout { variant: Some(*variant), ..*self }.node(node),
+            MathNode::LargeOperator(op) => {
+                let integral = op == "∫" || op == "∮";
+                let scale = match (self.display, integral) {
+                    (true, true) => 2.2,
+                    (true, false) => 1.6,
+                    (false, _) => 1.2,
+                };
+                let glyph_size = size * scale;
+                // center the glyph on the axis, the symbol font draws them from about -0.11 to 0.75 (0.92 for integrals)
+                let center = if integral { 0.4 } else { 0.32 };
+                let mut glyph = glyphs(op, times(FontStyle::Regular), glyph_size);
+                let shift = AXIS * size - center * glyph_size;
+                let mut result = MathBox::default();
+                glyph.ascent = (if integral { 0.92 } else { 0.75 }) * glyph_size;
+                glyph.descent = 0.11 * glyph_size;
+                result.place(glyph.clone(), 0.0, shift);
+                result.width = glyph.width + 0.1 * size;
+                result
+            },
+            MathNode::Scripts { base, sub, sup } => self.scripts(base, sub.as_deref(), sup.as_deref()),
+            MathNode::Fraction(numerator, denominator) => {
+                let inner = if self.display { Layout { display: false, ..*self } } else { self.smaller(0.75) };
+                let numerator = inner.node(numerator);
+                let denominator = inner.node(denominator);
+                let thickness = (size * 0.05).max(0.4);
+                let gap = size * 0.12;
+                let width = numerator.width.max(denominator.width) + size * 0.2;
+                let axis = AXIS * size;
+
+                let mut result = MathBox::default();
+                let numerator_y = axis + thickness / 2.0 + gap + numerator.descent;
+                let denominator_y = axis - thickness / 2.0 - gap - denominator.ascent;
+                result.place(numerator.clone(), (width - numerator.width) / 2.0, numerator_y);
+                result.place(denominator.clone(), (width - denominator.width) / 2.0, denominator_y);
+                result.line((size * 0.05, axis), (width - size * 0.05, axis), thickness);
+                result.width = width;
+                result
+            },
+            MathNode::Root { radicand, index } => {
+                let radicand = self.node(radicand);
+                let thickness = (size * 0.05).max(0.4);
+                let top = radicand.ascent.max(size * 0.7) + size * 0.12;
+                let bottom = -radicand.descent.max(size * 0.2) - size * 0.05;
+                let height = top - bottom;
+
+                // the index sits over the short stroke of the radical, pushing it to the right if it's wide
+                let mut result = MathBox::default();
+                let mut left = 0.0;
+                if let Some(index) = index {
+                    let index = self.smaller(0.6).node(index);
+                    left = (index.width - size * 0.3).max(0.0);
+                    let index_width = index.width;
+                    result.place(index, left + size * 0.3 - index_width, bottom + height * 0.55);
+                }
+                let tick  = (left, bottom + height * 0.45);
+                let knee  = (left + size * 0.12, bottom + height * 0.5);
+                let foot  = (left + size * 0.3, bottom);
+                let peak  = (left + size * 0.55, top);
+                let end   = (left + size * 0.65 + radicand.width + size * 0.05, top);
+                result.line(tick, knee, thickness);
+                result.line(knee, foot, thickness * 2.0);
+                result.line(foot, peak, thickness);
+                result.line(peak, end, thickness);
+                result.place(radicand, left + size * 0.65, 0.0);
+                result.width = end.0 + size * 0.05;
+                result
+            },
+            MathNode::Fenced { open, close, body } => {
+                let body = self.sequence(body);
+                let axis = AXIS * size;
+                // delimiters are as tall as the content, measured symmetrically around the axis
+                let half = (body.ascent - axis).max(body.descent + axis).max(size * 0.5) + size * 0.1;
+                let mut result = MathBox::default();
+                result.append(self.delimiter(open, half));
+                result.append(body);
+                result.append(self.delimiter(close, half));
+                result
+            },
+            MathNode::Accent { base, accent } => {
+                let base = self.node(base);
+                let above = base.ascent + size * 0.08;
+                let thickness = (size * 0.05).max(0.4);
+                let width = base.width;
+                let mut result = MathBox::default();
+                result.append(base);
+                match accent.as_str() {
+                    "¯" => result.line((size * 0.05, above), (width - size * 0.02, above), thickness),
+                    "→" => {
+                        let y = above + size * 0.06;
+                        result.line((0.0, y), (width, y), thickness);
+                        result.line((width - size * 0.15, y + size * 0.08), (width, y), thickness);
+                        result.line((width - size * 0.15, y - size * 0.08), (width, y), thickness);
+                    },
+                    accent => {
+                        // lift the glyph so its bottom sits just above the base
+                        let (text, bottom) = match accent {
+                            "^" => ("^", 0.3),
+                            "~" => ("~", 0.18),
+                            "¨" => ("\u{A8}", 0.58),
+                            _   => (".", -0.01),
+                        };
+                        let glyph = glyphs(text, times(FontStyle::Regular), size);
+                        let glyph_width = glyph.width;
+                        let y = above - bottom * size;
+                        result.place(glyph, (width - glyph_width) / 2.0 + size * 0.05, y);
+                        result.ascent = result.ascent.max(y + size * 0.7 - bottom * size * 0.5);
+                    },
+                }
+                result
+            },
+        }
+    }
+
+    fn delimiter(&self, delimiter: &str, half: f32) -> MathBox {
+        let size = self.size;
+        let axis = AXIS * size;
+        let thickness = (size * 0.05).max(0.4);
+        let (top, bottom) = (axis + half, axis - half);
+        let mut result = MathBox::default();
+        match delimiter {
+            "" => return MathBox::space(size * 0.1),
+            "|" | "‖" => {
+                let bars = if delimiter == "|" { 1 } else { 2 };
+                for bar in 0..bars {
+                    let x = size * (0.12 + 0.12 * bar as f32);
+                    result.line((x, bottom), (x, top), thickness);
+                }
+                result.width = size * (0.12 + 0.12 * bars as f32);
+            },
+            "[" | "]" | "⌊" | "⌋" | "⌈" | "⌉" => {
+                let (x, tip) = if matches!(delimiter, "[" | "⌊" | "⌈") { (size * 0.12, size * 0.3) } else { (size * 0.23, size * 0.05) };
+                result.line((x, bottom), (x, top), thickness);
+                if delimiter != "⌈" && delimiter != "⌉" { result.line((x, bottom), (tip, bottom), thickness) }
+                if delimiter != "⌊" && delimiter != "⌋" { result.line((x, top), (tip, top), thickness) }
+                result.width = size * 0.35;
+            },
+            _ => {
+                // parentheses, braces and angles come from the fonts and are scaled up to the height,
+                // their outlines go from about -0.18 to 0.69 of the font size
+                let glyph_size = (2.0 * half / 0.87).max(size);
+                let glyph = glyphs(delimiter, times(FontStyle::Regular), glyph_size);
+                result.width = glyph.width;
+                result.place(glyph, 0.0, axis - 0.255 * glyph_size);
+            },
+        }
+        result
+    }
+
+    fn scripts(&self, base: &MathNode, sub: Option<&MathNode>, sup: Option<&MathNode>) -> MathBox {
+        let size = self.size;
+        let limits = self.display && matches!(base, MathNode::LargeOperator(op) if op != "∫" && op != "∮");
+        let base_box = self.node(base);
+        let script = self.smaller(0.7);
+        let sub = sub.map(|node| script.node(node));
+        let sup = sup.map(|node| script.node(node));
+
+        let mut result = MathBox::default();
+        if limits {
+            let width = [Some(base_box.width), sub.as_ref().map(|x| x.width), sup.as_ref().map(|x| x.width)]
+                .into_iter().flatten().fold(0.0, f32::max);
+            let gap = size * 0.15;
+            if let Some(sup) = sup {
+                let y = base_box.ascent + gap + sup.descent;
+                let x = (width - sup.width) / 2.0;
+                result.place(sup, x, y);
+            }
+            if let Some(sub) = sub {
+                let y = -(base_box.descent + gap + sub.ascent);
+                let x = (width - sub.width) / 2.0;
+                result.place(sub, x, y);
+            }
+            let x = (width - base_box.width) / 2.0;
+            result.place(base_box, x, 0.0);
+            result.width = width;
+            return result;
+        }
+
+        let italic = matches!(base, MathNode::Identifier(_)) && self.variant.is_none();
+        let mut width = base_box.width;
+        let (base_ascent, base_descent) = (base_box.ascent, base_box.descent);
+        result.append(base_box);
+        let mut scripts_width: f32 = 0.0;
+        if let Some(sup) = sup {
+            let y = (base_ascent - size * 0.3).max(size * 0.38);
+            // italic letters lean right, the superscript follows the slant
+            let x = width + if italic { size * 0.05 } else { size * 0.02 };
+            scripts_width = scripts_width.max(sup.width + x - width);
+            result.place(sup, x, y);
+        }
+        if let Some(sub) = sub {
+            let y = -(base_descent).max(size * 0.22);
+            let x = width + size * 0.02;
+            scripts_width = scripts_width.max(sub.width + size * 0.02);
+            result.place(sub, x, y);
+        }
+        width += scripts_width + size * 0.05;
+        result.width = width;
+        result
+    }
+}
+
+/// lays out `tex` at `size` points, display math is bigger and puts limits above and below sums
+pub fn layout_math(tex: &str, size: f32, display: bool) -> MathBox {
+    let (nodes, _) = parse_tex(tex);
+    Layout { size, display, variant: None }.sequence(&nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fraction_is_taller_than_text() {
+        let text = layout_math("x", 10.0, true);
+        let fraction = layout_math("\\frac{a}{b}", 10.0, true);
+        assert!(fraction.ascent > text.ascent);
+        assert!(fraction.descent > text.descent);
+        assert!(fraction.items.iter().any(|item| matches!(item, MathItem::Line { .. })));
+    }
+
+    #[test]
+    fn symbols_use_the_symbol_font() {
+        let layout = layout_math("\\alpha + x", 10.0, false);
+        let fonts: Vec<FontFamily> = layout.items.iter().filter_map(|item| match item {
+            MathItem::Glyphs { font, .. } => Some(font.family),
+            _ => None,
+        }).collect();
+        assert_eq!(fonts, vec![FontFamily::Symbol, FontFamily::Times, FontFamily::Times]);
+    }
+
+    #[test]
+    fn limits_only_in_display() {
+        let display = layout_math("\\sum_{i=0}^{n} i", 10.0, true);
+        let inline  = layout_math("\\sum_{i=0}^{n} i", 10.0, false);
+        assert!(display.ascent > inline.ascent);
+        assert!(display.width < inline.width + 10.0);
+    }
+}
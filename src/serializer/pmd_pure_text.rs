@@ -1,43 +1,36 @@
-use crate::*;
-use anyhow::*;
-use ordered_map::OrderedMap;
-
-pub struct PMDPureTextSerializer {
-    pub notes_title: String,
-    pub hide_references: bool,
-    pub hide_notes: bool,
-    pub hide_contacts: bool,
-    pub toc: Option<TableOfContent>,
-    pub references: OrderedMap<String, ReferenceDefinition>,
-}
+use anyhow::Result;
+use crate::parser::*;
+use super::{Context, Serializer};
+
+pub struct PMDPureTextSerializer {}
 
 impl PMDPureTextSerializer {
-    pub fn new() -> Self { 
-        Self {
-            notes_title: String::new(),
-            hide_references: false, 
-            hide_notes: false, 
-            hide_contacts: false, 
-            toc: None, 
-            references: OrderedMap::new()
-        } 
+    pub fn new() -> Self {
+        Self {}
     }
 }
 
-impl PMDSerializer for PMDPureTextSerializer {
-    fn current_factbox(&mut self) -> Option<(FactBox, Option<String>)> {
-        None
+impl Serializer for PMDPureTextSerializer {
+    type Output = String;
+
+    fn text(&mut self, _: &Context, text: &str) -> Result<String> {
+        Ok(text.to_string())
     }
 
-    fn convert_factbox(&mut self, factbox: &FactBox, _: &String) -> Result<String> {
+    fn join(&mut self, parts: Vec<String>) -> String {
+        parts.concat()
+    }
+
+    fn visit_factbox(&mut self, ctx: &Context, factbox: &FactBox, id: &String) -> Result<String> {
         let title = &factbox.title;
         let mut result = String::new();
         result += "--------------------------------------------------------------------------------\n";
         result += format!("| {title}   \n").as_str();
         result += "--------------------------------------------------------------------------------\n";
-        
-        let elements = self.convert_factbox_elements(factbox, None)?;
-        for element in elements {
+
+        let inner = ctx.in_factbox(factbox, id);
+        for (element, id, _) in &factbox.body {
+            let element = self.visit_element(&inner, element, id)?;
             for line in element.lines() {
                 result += format!("| {line}\n").as_str();
             }
@@ -46,9 +39,9 @@ impl PMDSerializer for PMDPureTextSerializer {
         result += "--------------------------------------------------------------------------------\n";
 
         if !factbox.notes.is_empty() {
-            result += format!("| {}: \n", self.notes_title).as_str();
+            result += format!("| {}: \n", ctx.meta().notes_title).as_str();
             for (key, (val, _)) in &factbox.notes {
-                let element = self.convert_element(no_id!(val))?;
+                let element = self.visit_inline(&inner, val)?;
                 result += format!("|     ^{key}: {element}\n").as_str();
             }
             result += "| \n";
@@ -58,75 +51,109 @@ impl PMDSerializer for PMDPureTextSerializer {
         Ok(result)
     }
 
-    fn convert_hoverable(&mut self, hoverable: &Alternative) -> Result<String> {
-        let alt    = self.convert_element(no_id!(&hoverable.alt))?;
-        let actual = self.convert_element(no_id!(&hoverable.base))?;
+    fn visit_hoverable(&mut self, ctx: &Context, hoverable: &Alternative) -> Result<String> {
+        let alt    = self.visit_inline(ctx, &hoverable.alt)?;
+        let actual = self.visit_inline(ctx, &hoverable.base)?;
         Ok(format!("{alt}({actual})"))
     }
-    
 
-    fn convert_styled(&mut self, styled: &Alternative) -> Result<String> {
-        self.convert_element((&styled.alt, &String::new()))
-    }
-
-    // fn convert_embedded_link(&mut self, src: &String, alt: &String) -> Result<String> {
-    //     self.convert_link(&Alternative{base: Box::new(BlogBody::Text(src.to_string())), alt: Box::new(BlogBody::Text(alt.to_string())) })   
-    // }
-
-    fn convert_link(&mut self, link: &Alternative) -> Result<String> {
-        let text = self.convert_element(no_id!(&link.alt))?;
+    fn visit_link(&mut self, ctx: &Context, link: &Alternative) -> Result<String> {
+        let text = self.visit_inline(ctx, &link.alt)?;
         match &link.base {
-            box BlogBody::Citation(_) | box BlogBody::Note(_) => {
+            box Element::Citation(_) | box Element::Note(_) => {
                 Ok(format!("{text}"))
             },
             _ => {
-                let link = self.convert_element(no_id!(&link.base))?;
+                let link = self.visit_inline(ctx, &link.base)?;
                 Ok(format!("{link}({text})"))
             }
         }
     }
 
-    fn convert_header(&mut self, text: &Box<BlogBody>, _: usize, _: &String) -> Result<String> {
-        self.convert_element(no_id!(text))
+    fn visit_wiki_link(&mut self, ctx: &Context, link: &WikiLink) -> Result<String> {
+        let text = match &link.label {
+            Some(label) => self.visit_inline(ctx, label)?,
+            None => link.target.clone(),
+        };
+        match ctx.wiki_link(&link.target) {
+            Some(url) => Ok(format!("{text}({url})")),
+            None => Ok(text),
+        }
+    }
+
+    fn visit_codeblock(&mut self, ctx: &Context, block: &CodeBlock, id: &String) -> Result<String> {
+        let mut result :String = "-----\n".to_string();
+        if let Some(title) = ctx.listing_title(block, id).or(Some(block.lang.clone()).filter(|x| !x.is_empty())) {
+            result += format!("{title}\n").as_str();
+        }
+        for (index, line) in block.code.lines().enumerate() {
+            if let Some(start) = block.start_line {
+                result += format!("{:>4} ", start + index).as_str();
+            }
+            result += format!("{line}\n").as_str();
+        }
+        result += "-----\n";
+        Ok(result)
     }
 
-    fn convert_italics(&mut self, text: &Box<BlogBody>) -> Result<String> {
-        self.convert_element(no_id!(text))
+    fn visit_math_block(&mut self, _: &Context, tex: &String, _: &String) -> Result<String> {
+        Ok(format!("{tex}\n"))
     }
 
-    fn convert_bold(&mut self, text: &Box<BlogBody>) -> Result<String> {
-        self.convert_element(no_id!(text))
+    fn visit_image(&mut self, ctx: &Context, image: &Image, id: &String) -> Result<String> {
+        let alt = self.visit_inline(ctx, &image.alt)?;
+        let src = &image.src;
+        match ctx.figure(id) {
+            Some(figure) => Ok(format!("{}: {alt}({src})", figure.label())),
+            None => Ok(format!("{alt}({src})")),
+        }
     }
 
-    fn convert_inlinecode(&mut self, text: &String) -> Result<String> {
-        Ok(text.clone())
+    fn visit_video(&mut self, ctx: &Context, video: &Media, _: &String) -> Result<String> {
+        let title = self.visit_inline(ctx, &video.title)?;
+        Ok(format!("{title}({})", video.link))
     }
 
-    fn convert_codeblock(&mut self, text: &String, _: &String) -> Result<String> {
-        let mut result :String = "-----\n".to_string();
-        result += text.as_str();
-        result += "-----\n";
-        Ok(result)
+    fn visit_audio(&mut self, ctx: &Context, audio: &Media, _: &String) -> Result<String> {
+        let title = self.visit_inline(ctx, &audio.title)?;
+        Ok(format!("{title}({})", audio.link))
     }
 
-    fn convert_image(&mut self, src: &String, alt: &String, _: &String) -> Result<String> {
-        Ok(format!("{alt}({src})"))
+    fn visit_iframe(&mut self, ctx: &Context, iframe: &Media, _: &String) -> Result<String> {
+        let title = self.visit_inline(ctx, &iframe.title)?;
+        Ok(format!("{title}({})", iframe.link))
     }
 
-    fn convert_quote(&mut self, lines: &Vec<BlogBody>, _: &String) -> Result<String> {
+    fn visit_quote(&mut self, ctx: &Context, quote: &Quote, _: &String) -> Result<String> {
         let mut quote_elements : Vec<String> = vec![];
-        for elem in lines {
-            let text = self.convert_element(no_id!(elem))?;
-            quote_elements.push(format!("{text}\n"));
+        for Spanned { node: elem, .. } in &quote.body {
+            let text = self.visit_element(ctx, elem, &String::new())?;
+            quote_elements.push(format!("{}\n", text.trim_end()));
         }
         let text = quote_elements.join("\n");
-        Ok(format!("\"{text}\""))
+        let Some(attribution) = &quote.attribution else {
+            return Ok(format!("\"{text}\""));
+        };
+        let attribution = self.visit_inline(ctx, attribution)?;
+        Ok(format!("\"{text}\"\n    — {attribution}\n"))
     }
-    
-    fn convert_citation(&mut self, citation: &String) -> Result<String> {
-        let maybe_source = self.references.get(citation);
-        if let Some(source) = maybe_source {
-            if self.hide_references {
+
+    fn visit_callout(&mut self, ctx: &Context, callout: &Callout, _: &String) -> Result<String> {
+        let title = self.visit_inline(ctx, &callout.title)?;
+        let mut result = format!("[{}] {}\n", callout.kind, title.trim());
+        for Spanned { node: elem, .. } in &callout.body {
+            let text = self.visit_element(ctx, elem, &String::new())?;
+            for line in text.trim_end().lines() {
+                result += format!("| {line}\n").trim_end();
+                result.push('\n');
+            }
+        }
+        Ok(result)
+    }
+
+    fn visit_citation(&mut self, ctx: &Context, citation: &String) -> Result<String> {
+        if let Some(source) = ctx.reference(citation) {
+            if ctx.meta().hide_references {
                 Ok("".into())
             } else {
                 Ok(to_citation(source))
@@ -135,55 +162,91 @@ impl PMDSerializer for PMDPureTextSerializer {
             Ok("(Missing Source)".to_string())
         }
     }
-    
-    fn convert_note(&mut self, id: &String) -> Result<String> {
-        if !self.hide_notes {
+
+    fn visit_note(&mut self, ctx: &Context, id: &String) -> Result<String> {
+        if ctx.factbox.is_some() || !ctx.meta().hide_notes {
             Ok(format!("^{id}").to_string())
         } else {
             Ok("".into())
         }
     }
-    
-    fn convert_factbox_note(&mut self, _: &FactBox, _: Option<&String>, id: &String) -> Result<String> {
-        Ok(format!("^{id}").to_string())
-    }
 
-    fn convert_list(&mut self, list: &Vec<BlogBody>, _: &String) -> Result<String> {
+    fn visit_list(&mut self, ctx: &Context, list: &[ListItem], _: &String) -> Result<String> {
         let mut list_elements: Vec<String> = vec![];
-        for elem in list {
-            let text = self.convert_element(no_id!(elem))?;
-            list_elements.push(format!("- {text}"));
+        for item in list {
+            let marker = match item {
+                ListItem::Unordered(..)                   => "-".to_string(),
+                ListItem::Numbered(num, ..)               => format!("{num}."),
+                ListItem::Alphabetical(marker, ..)        => format!("{marker}."),
+                ListItem::NumberedRounded(num, ..)        => format!("{num})"),
+                ListItem::AlphabeticalRounded(marker, ..) => format!("{marker})"),
+                ListItem::Task { checked: false, .. }     => "\u{2610}".to_string(),
+                ListItem::Task { checked: true, .. }      => "\u{2611}".to_string(),
+                ListItem::List(_) => {
+                    let text = self.visit_list_item(ctx, item)?;
+                    for line in text.lines() {
+                        list_elements.push(format!("    {line}"));
+                    }
+                    continue;
+                },
+            };
+            let Some((text, body)) = item.content() else { continue };
+            let text = self.visit_inline(ctx, text)?;
+            list_elements.push(format!("{marker} {text}"));
+            // the body lines up with the text after the marker
+            let indent = " ".repeat(marker.chars().count() + 1);
+            for Spanned { node: element, .. } in body {
+                let text = self.visit_element(ctx, element, &String::new())?;
+                for line in text.trim_end().lines() {
+                    list_elements.push(format!("{indent}{line}").trim_end().to_string());
+                }
+            }
         }
         let text = list_elements.join("\n");
         Ok(text)
     }
 
-    fn convert_paragraph(&mut self, text: &Box<BlogBody>, _: &String) -> Result<String> {
-        self.convert_element(no_id!(text))
+    fn visit_definition_list(&mut self, ctx: &Context, list: &[Definition], _: &String) -> Result<String> {
+        let mut lines: Vec<String> = vec![];
+        for definition in list {
+            lines.push(self.visit_inline(ctx, &definition.term)?.trim().to_string());
+            for text in &definition.definitions {
+                let text = self.visit_inline(ctx, text)?;
+                lines.push(format!("    {}", text.trim().replace('\n', "\n    ")));
+            }
+        }
+        Ok(lines.join("\n"))
     }
 
-    fn convert_text(&mut self, text: &String) -> Result<String> {
-        Ok(text.clone())
-    }
+    fn visit_table(&mut self, ctx: &Context, table: &Table, id: &String) -> Result<String> {
+        let mut rows: Vec<Vec<String>> = vec![];
+        for row in std::iter::once(&table.header).chain(&table.rows) {
+            rows.push(row.iter().map(|cell| self.visit_inline(ctx, cell)).collect::<Result<_>>()?);
+        }
 
-    fn convert_span(&mut self, span: &Span) -> Result<String> {
-        let mut result = String::new();
-        for elem in &span.elements {
-            result += if let BlogBody::Text(text) = elem {
-                text.to_string()
-            } else {
-                let mut inner = String::new();
-                inner += self.convert_element(no_id!(elem))?.as_str();
-                inner
-            }.as_str()
+        // pad every column out to its widest cell so the pipes line up
+        let widths: Vec<usize> = (0..table.header.len())
+            .map(|column| rows.iter().map(|row| row.get(column).map_or(0, |cell| cell.chars().count())).max().unwrap_or(0))
+            .collect();
+        let mut lines: Vec<String> = ctx.figure(id).map(|figure| figure.label()).into_iter().collect();
+        for (index, row) in rows.iter().enumerate() {
+            let cells: Vec<String> = row.iter().zip(&widths).zip(&table.alignments).map(|((cell, &width), alignment)| match alignment {
+                Alignment::Right  => format!("{cell:>width$}"),
+                Alignment::Center => format!("{cell:^width$}"),
+                _                 => format!("{cell:<width$}"),
+            }).collect();
+            lines.push(format!("| {} |", cells.join(" | ")));
+            if index == 0 {
+                let rule: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+                lines.push(format!("|-{}-|", rule.join("-|-")));
+            }
         }
-        Ok(result)
+        Ok(lines.join("\n"))
     }
-    
-    fn convert_toc(&mut self) -> Result<String> {
-        if self.toc.is_none() { return Err(anyhow!("")); }
+
+    fn visit_toc(&mut self, ctx: &Context) -> Result<String> {
+        let Some(toc) = &ctx.meta().toc else { return Ok(String::new()) };
         let mut result = String::new();
-        let toc = self.toc.clone().unwrap();
         let title = &toc.title;
 
         result += format!("{title}:\n").as_str();
@@ -191,32 +254,26 @@ impl PMDSerializer for PMDPureTextSerializer {
             for _ in 0..depth.clone() {
                 result += "    ";
             }
-            result += self.convert_element(no_id!(&text))?.as_str();
+            result += self.visit_inline(ctx, text)?.as_str();
             result += "\n"
         }
 
         Ok(result)
     }
 
-
-    fn convert_page_break(&mut self) -> Result<String> {
+    fn visit_page_break(&mut self, _: &Context) -> Result<String> {
         Ok("---\n".into())
     }
 
-    fn convert(&mut self, md: &PawsMarkdown) -> Result<String> {
+    fn serialize(&mut self, md: &AssDownDocument) -> Result<String> {
+        let ctx = Context::new(md);
         let mut output = String::new();
-        self.references = md.references.clone();
-        self.toc = md.header.toc.clone();
-        self.hide_references = md.header.hide_references;
-        self.hide_notes      = md.header.hide_notes;
-        self.hide_contacts   = md.header.hide_contacts;
-        self.notes_title     = md.header.notes_title.clone();
-
-        for (element, _) in &md.body {
-            let result = self.convert_element(no_id!(element))?;
+
+        for (element, id, _) in &md.body {
+            let result = self.visit_element(&ctx, element, id)?;
             output += result.as_str();
             match element {
-                BlogBody::Header(_, _) => {
+                Element::Header(_, _) => {
                     output += ":\n";
                 },
                 _ => {
@@ -226,20 +283,28 @@ impl PMDSerializer for PMDPureTextSerializer {
             }
         }
 
-        if !(md.notes.is_empty() || self.hide_notes) {
+        if !(md.notes.is_empty() || md.meta.hide_notes) {
             output += "--------------------------------------------------------------------------------\n";
-            output += format!("{}: \n", md.header.notes_title).as_str();
+            output += format!("{}: \n", md.meta.notes_title).as_str();
             for (key, val) in &md.notes {
-                let result = self.convert_element(no_id!(val))?;
+                let result = self.visit_inline(&ctx, val)?;
                 output += format!("    ^{key}: {result}\n").as_str();
             }
             output.push('\n');
         }
 
-        if !(self.references.is_empty() || self.hide_references) {
+        if !(md.glossary.is_empty() || md.meta.hide_glossary) {
+            output += "--------------------------------------------------------------------------------\n";
+            output += format!("{}: \n", md.meta.glossary_title).as_str();
+            let list: Vec<Definition> = md.glossary.iter().map(|(_, definition)| definition.clone()).collect();
+            output += self.visit_definition_list(&ctx, &list, &String::new())?.as_str();
+            output += "\n\n";
+        }
+
+        if !(md.references.is_empty() || md.meta.hide_references) {
             output += "--------------------------------------------------------------------------------\n";
-            output += format!("{}: \n", md.header.bibliography_title).as_str();
-            for (_, val) in &self.references {
+            output += format!("{}: \n", md.meta.bibliography_title).as_str();
+            for (_, val) in &md.references {
                 output += to_bibliography(val).as_str();
                 output += "\n";
             }
@@ -248,3 +313,36 @@ impl PMDSerializer for PMDPureTextSerializer {
         Ok(output)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::to_string;
+
+    #[test]
+    fn output() {
+        let text = "# Cats\n\nthey are **great** at [naps](https://example.com)[^1]\n\n- one\n- two\n\nPurr\n: a happy noise\n\n[^1]: mostly\n\n".to_string();
+        let (document, _) = parse(&text, None).unwrap();
+        assert_eq!(to_string(&document, PMDPureTextSerializer::new()).unwrap(), concat!(
+            "Cats:\n",
+            "they are great at naps(https://example.com)^1\n",
+            "\n",
+            "\n",
+            "- one\n",
+            "- two\n",
+            "\n",
+            "Purr\n",
+            "    a happy noise\n",
+            "\n",
+            "--------------------------------------------------------------------------------\n",
+            "Notes: \n",
+            "    ^1: mostly\n",
+            "\n",
+            "--------------------------------------------------------------------------------\n",
+            "Glossary: \n",
+            "Purr\n",
+            "    a happy noise\n",
+            "\n",
+        ));
+    }
+}
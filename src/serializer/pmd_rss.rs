@@ -1,33 +1,25 @@
-use color_print::cprintln;
-use pmd_html_shared::sanitize_text;
-use crate::*;
-
-#[derive(Clone)]
-struct Reference {
-    def: ReferenceDefinition,
-    times_used: usize,
-}
-
-impl Reference {
-    fn new(def: ReferenceDefinition) -> Self {
-        Self { def, times_used: 0 }
-    }
-} 
+use anyhow::Result;
+use crate::parser::*;
+use super::pmd_html_shared::{code_theme, highlighted_html, image_size_attributes, sanitize_id, sanitize_text, to_mathml, to_plain_text};
+use super::{Context, Serializer};
+use crate::highlight::{highlight, Theme};
 
 pub struct PMDRSSSerializer {
     filename: String,
     num_tabs:   usize,
-    references: HashMap<String, Reference>,
+    times_used: OrderedMap<String, usize>,
     output: String,
+    theme: Option<&'static Theme>,
 }
 
 impl PMDRSSSerializer {
     pub fn new(filename: &str) -> Self {
-        Self { 
+        Self {
             filename: filename.into(),
             num_tabs: 0,
-            references: HashMap::new(),
-            output: String::new()
+            times_used: OrderedMap::new(),
+            output: String::new(),
+            theme: None,
         }
     }
 
@@ -37,7 +29,15 @@ impl PMDRSSSerializer {
         self.output += text.as_ref();
         self.output.push('\n');
     }
-    
+
+    // feed readers strip out players and iframes, so media turns into a link to whatever was embedded
+    fn media_link(&mut self, ctx: &Context, media: &Media) -> Result<String> {
+        let link = sanitize_text(&media.link);
+        let title = self.visit_inline(ctx, &media.title)?;
+        let title = if title.trim().is_empty() { link.clone() } else { title };
+        Ok(format!("<p><a href='{link}'>{title}</a></p>"))
+    }
+
     fn push_tab(&mut self) { self.num_tabs += 1; }
     fn pop_tab(&mut self) { if self.num_tabs > 0 { self.num_tabs -= 1; } }
     fn tab(&mut self) -> String {
@@ -49,140 +49,277 @@ impl PMDRSSSerializer {
             }
             result
         }
-    } 
+    }
 }
 
-impl PMDSerializer for PMDRSSSerializer {
-    fn current_factbox(&mut self) -> Option<(FactBox, Option<String>)> {
-        None
+impl Serializer for PMDRSSSerializer {
+    type Output = String;
+
+    fn text(&mut self, _: &Context, text: &str) -> Result<String> {
+        Ok(sanitize_text(&text.to_string()))
     }
 
-    fn convert_factbox(&mut self, _: &FactBox, _: &String) -> Result<String> {
-        cprintln!("<r>error:</> fact boxes aren't implemented for html output");
-        Ok("".into())
+    fn join(&mut self, parts: Vec<String>) -> String {
+        parts.concat()
     }
 
-    fn convert_hoverable(&mut self, hoverable: &Alternative) -> Result<String> {
-        let base = self.convert_element(no_id!(&hoverable.base))?;
-        let alt  = self.convert_element(no_id!(&hoverable.alt))?;
+    fn visit_factbox(&mut self, ctx: &Context, factbox: &FactBox, id: &String) -> Result<String> {
+        let title = sanitize_text(&factbox.title.trim().to_string());
+        let inner = ctx.in_factbox(factbox, id);
+        let mut body = vec![];
+        for (element, id, _) in &factbox.body {
+            body.push(self.visit_element(&inner, element, id)?.trim_end().to_string());
+        }
+        let body = body.join("\n");
+        Ok(format!("<aside><h2>{title}</h2>\n{body}</aside>"))
+    }
+
+    fn visit_hoverable(&mut self, ctx: &Context, hoverable: &Alternative) -> Result<String> {
+        let base = self.visit_inline(ctx, &hoverable.base)?;
+        let alt  = self.visit_inline(ctx, &hoverable.alt)?;
         Ok(format!("<span><span>{base}</span><span>({alt})</span></span>"))
     }
 
-    fn convert_styled(&mut self, styled: &Alternative) -> Result<String> {
-        let text  = self.convert_element(no_id!(&styled.alt))?;
-        // let style = self.convert_element(no_id!(&styled.base))?;
+    fn visit_styled(&mut self, ctx: &Context, styled: &Alternative) -> Result<String> {
+        let text  = self.visit_inline(ctx, &styled.alt)?;
         Ok(format!("<span>{text}</span>"))
     }
 
-    // fn convert_embedded_link(&mut self, src: &String, alt: &String) -> Result<String> {
-    //     self.convert_link(&Alternative{base: Box::new(BlogBody::Text(src.to_string())), alt: Box::new(BlogBody::Text(alt.to_string())) })
-    // }
-
-    fn convert_link(&mut self, link: &Alternative) -> Result<String> {
-        let href = self.convert_element(no_id!(&link.alt))?;
-        let text = self.convert_element(no_id!(&link.base))?;
+    fn visit_link(&mut self, ctx: &Context, link: &Alternative) -> Result<String> {
+        let href = self.visit_inline(ctx, &link.alt)?;
+        let text = self.visit_inline(ctx, &link.base)?;
         Ok(format!("<a class='inline-link' href='{href}'>{text}</a>"))
     }
 
-    fn convert_header(&mut self, text: &Box<BlogBody>, depth: usize, _: &String) -> Result<String> {
-        let text  = self.convert_element(no_id!(text))?;
+    fn visit_wiki_link(&mut self, ctx: &Context, link: &WikiLink) -> Result<String> {
+        let text = match &link.label {
+            Some(label) => self.visit_inline(ctx, label)?,
+            None => self.text(ctx, &link.target)?,
+        };
+        match ctx.wiki_link(&link.target) {
+            Some(url) => Ok(format!("<a href='{}'>{text}</a>", sanitize_id(url))),
+            None => Ok(text),
+        }
+    }
+
+    fn visit_header(&mut self, ctx: &Context, text: &Element, depth: usize, _: &String) -> Result<String> {
+        let text  = self.visit_inline(ctx, text)?;
         Ok(format!("<h{depth}>{text}</h{depth}>"))
     }
 
-    fn convert_italics(&mut self, text: &Box<BlogBody>) -> Result<String> {
-        let inner_text = self.convert_element(no_id!(&text))?;
-        Ok(format!("<i>{inner_text}</i>)"))
+    fn visit_italics(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<i>{inner_text}</i>"))
     }
 
-    fn convert_bold(&mut self, text: &Box<BlogBody>) -> Result<String> {
-        let inner_text = self.convert_element(no_id!(&text))?;
+    fn visit_bold(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
         Ok(format!("<b>{inner_text}</b>"))
     }
 
-    fn convert_inlinecode(&mut self, text: &String) -> Result<String> {
+    fn visit_strikethrough(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<s>{inner_text}</s>"))
+    }
+
+    fn visit_underline(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<u>{inner_text}</u>"))
+    }
+
+    fn visit_highlight(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<mark>{inner_text}</mark>"))
+    }
+
+    fn visit_superscript(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<sup>{inner_text}</sup>"))
+    }
+
+    fn visit_subscript(&mut self, ctx: &Context, text: &Element) -> Result<String> {
+        let inner_text = self.visit_inline(ctx, text)?;
+        Ok(format!("<sub>{inner_text}</sub>"))
+    }
+
+    fn visit_inline_code(&mut self, _: &Context, text: &String) -> Result<String> {
         let text = sanitize_text(text);
         Ok(format!("<code>{text}</code>"))
     }
 
-    fn convert_codeblock(&mut self, text: &String, _: &String) -> Result<String> {
-        let first_line = text.lines().nth(0).context("expected at least one line in codeblock")?;
-        let mut words = first_line.split(|x: char| x.is_whitespace());
-        let _lang = words.nth(0).unwrap_or("plaintext");
-        let mut body = text[text.find('\n').context("expected at least one line in codeblock")? + 1..].to_string();
+    fn visit_inline_math(&mut self, _: &Context, tex: &String) -> Result<String> {
+        Ok(to_mathml(tex, false))
+    }
+
+    fn visit_math_block(&mut self, _: &Context, tex: &String, _: &String) -> Result<String> {
+        Ok(to_mathml(tex, true))
+    }
 
-        body = sanitize_text(&body);
-        body = body.trim_end().replace("\r\n", "\n");
+    fn visit_codeblock(&mut self, ctx: &Context, block: &CodeBlock, id: &String) -> Result<String> {
+        // feed readers drop stylesheets so the colours have to go on the elements themselves
+        let (body, style) = match self.theme {
+            Some(theme) => (
+                highlighted_html(&highlight(&block.lang, &block.code), Some(theme)),
+                format!(" style='background: {}; color: {};'", theme.background.hex(), theme.foreground.hex()),
+            ),
+            None => (sanitize_text(&block.code), String::new()),
+        };
+        match ctx.listing_title(block, id) {
+            Some(title) => Ok(format!("<p><code>{}</code></p><pre{style}><code>{body}</code></pre>", sanitize_text(&title))),
+            None => Ok(format!("<pre{style}><code>{body}</code></pre>")),
+        }
+    }
+
+    fn visit_image(&mut self, ctx: &Context, image: &Image, id: &String) -> Result<String> {
+        let src = sanitize_text(&image.src);
+        let alt = sanitize_text(&to_plain_text(&image.alt));
+        let size = image_size_attributes(&image.size);
+        let Some(caption) = &image.caption else {
+            return Ok(format!("<img src='{src}' alt='{alt}'{size}></img>"));
+        };
+        let caption = match ctx.figure(id) {
+            Some(figure) => format!("<b>{}:</b> {}", figure.label(), self.visit_inline(ctx, caption)?),
+            None => self.visit_inline(ctx, caption)?,
+        };
+        Ok(format!("<figure id='{}'><img src='{src}' alt='{alt}'{size}></img><figcaption>{caption}</figcaption></figure>", sanitize_id(id)))
+    }
 
-        Ok(format!("<pre><code>{body}</code></pre>"))
+    fn visit_video(&mut self, ctx: &Context, video: &Media, _: &String) -> Result<String> {
+        self.media_link(ctx, video)
     }
 
-    fn convert_image(&mut self, src: &String, alt: &String, _: &String) -> Result<String> {
-        Ok(format!("<img src='{src}' alt='{alt}'></img>"))
+    fn visit_audio(&mut self, ctx: &Context, audio: &Media, _: &String) -> Result<String> {
+        self.media_link(ctx, audio)
     }
 
-    fn convert_quote(&mut self, lines: &Vec<BlogBody>, _: &String) -> Result<String> {
+    fn visit_iframe(&mut self, ctx: &Context, iframe: &Media, _: &String) -> Result<String> {
+        self.media_link(ctx, iframe)
+    }
+
+    fn visit_quote(&mut self, ctx: &Context, quote: &Quote, _: &String) -> Result<String> {
         let mut quote_elements : Vec<String> = vec![];
-        for elem in lines {
-            let text = self.convert_element(no_id!(elem))?;
-            quote_elements.push(format!("{text}<br/>"));
+        for Spanned { node: elem, .. } in &quote.body {
+            quote_elements.push(self.visit_element(ctx, elem, &String::new())?);
         }
         let text = quote_elements.join("\n");
-        Ok(format!("<blockquote class='quote-text'>{text}</blockquote>"))
+        let Some(attribution) = &quote.attribution else {
+            return Ok(format!("<blockquote class='quote-text'>{text}</blockquote>"));
+        };
+        let attribution = self.visit_inline(ctx, attribution)?;
+        Ok(format!("<figure><blockquote class='quote-text'>{text}</blockquote><figcaption>— {attribution}</figcaption></figure>"))
     }
 
-    fn convert_list(&mut self, list: &Vec<BlogBody>, _: &String) -> Result<String> {
+    fn visit_list(&mut self, ctx: &Context, list: &[ListItem], _: &String) -> Result<String> {
+        let tag = match list.first() {
+            Some(ListItem::Numbered(..) | ListItem::NumberedRounded(..) |
+                 ListItem::Alphabetical(..) | ListItem::AlphabeticalRounded(..)) => "ol",
+            _ => "ul",
+        };
+
         let mut list_elements: Vec<String> = vec![];
-        for elem in list {
-            let text = self.convert_element(no_id!(elem))?;
+        for item in list {
+            let text = self.visit_list_item(ctx, item)?;
             list_elements.push(format!("<li>{text}</li>"));
         }
         let text = list_elements.join("\n");
-        Ok(format!("<ul>{text}</ul>"))
+        Ok(format!("<{tag}>{text}</{tag}>"))
     }
 
-    fn convert_paragraph(&mut self, text: &Box<BlogBody>, _: &String) -> Result<String> {
-        let paragraph = self.convert_element(no_id!(text))?;
-        Ok(format!("<p>{paragraph}</p>"))
+    fn visit_table(&mut self, ctx: &Context, table: &Table, id: &String) -> Result<String> {
+        let mut header = String::new();
+        for cell in &table.header {
+            header += format!("<th>{}</th>", self.visit_inline(ctx, cell)?).as_str();
+        }
+
+        let mut rows: Vec<String> = vec![];
+        for row in &table.rows {
+            let mut text = String::new();
+            for cell in row {
+                text += format!("<td>{}</td>", self.visit_inline(ctx, cell)?).as_str();
+            }
+            rows.push(format!("<tr>{text}</tr>"));
+        }
+        let rows = rows.join("\n");
+        let caption = match ctx.figure(id) {
+            Some(figure) => format!("<caption>{}</caption>", figure.label()),
+            None => String::new(),
+        };
+        Ok(format!("<table>{caption}<thead><tr>{header}</tr></thead><tbody>{rows}</tbody></table>"))
+    }
+
+    fn visit_definition_list(&mut self, ctx: &Context, list: &[Definition], _: &String) -> Result<String> {
+        let mut entries: Vec<String> = vec![];
+        for definition in list {
+            entries.push(format!("<dt>{}</dt>", self.visit_inline(ctx, &definition.term)?));
+            for text in &definition.definitions {
+                entries.push(format!("<dd>{}</dd>", self.visit_inline(ctx, text)?));
+            }
+        }
+        Ok(format!("<dl>{}</dl>", entries.join("\n")))
+    }
+
+    fn visit_paragraph(&mut self, ctx: &Context, text: &Element, _: &String) -> Result<String> {
+        let paragraph = self.visit_inline(ctx, text)?;
+        Ok(format!("<p>{}</p>", paragraph.trim_end()))
     }
 
-    fn convert_text(&mut self, text: &String) -> Result<String> {
+    fn visit_text(&mut self, _: &Context, text: &String) -> Result<String> {
         Ok(sanitize_text(&text).trim_end().to_string())
     }
 
-    fn convert_span(&mut self, span: &Span) -> Result<String> {
+    fn visit_span(&mut self, ctx: &Context, span: &Span) -> Result<String> {
         let mut result = String::new();
         for elem in &span.elements {
-            result += if let BlogBody::Text(text) = elem {
+            result += if let Element::Text(text) = elem {
                 sanitize_text(text)
             } else {
-                self.convert_element(no_id!(elem))?
+                self.visit_inline(ctx, elem)?
             }.as_str()
         }
         Ok(result)
     }
-    
-    fn convert_citation(&mut self, id: &String) -> Result<String> {
-        if let Some(citation) = &self.references.get(id) {
-            Ok(to_citation(&citation.def).trim_end().to_string())
+
+    fn visit_callout(&mut self, ctx: &Context, callout: &Callout, _: &String) -> Result<String> {
+        let title = self.visit_inline(ctx, &callout.title)?;
+        let mut elements = vec![format!("<p><strong>{title}</strong></p>")];
+        for Spanned { node: elem, .. } in &callout.body {
+            elements.push(self.visit_element(ctx, elem, &String::new())?);
+        }
+        let kind = sanitize_id(&callout.kind);
+        Ok(format!("<blockquote class='callout callout-{kind}'>{}</blockquote>", elements.join("\n")))
+    }
+
+    fn visit_citation(&mut self, ctx: &Context, id: &String) -> Result<String> {
+        if let Some(citation) = ctx.reference(id) {
+            if let Some(times_used) = self.times_used.get_mut(id) {
+                *times_used += 1;
+            } else {
+                self.times_used.insert(id.clone(), 1);
+            }
+            Ok(to_citation(citation).trim_end().to_string())
         } else {
             Ok("(MISSING CITATION)".into())
         }
     }
 
-    fn convert_note(&mut self, id: &String) -> Result<String> {
-        Ok(format!("<sup><a id='{id}-backref' href='#^{id}'>{id}</sup></a>"))
+    fn visit_note(&mut self, ctx: &Context, id: &String) -> Result<String> {
+        let note_id = ctx.note_id(id);
+        Ok(format!("<sup><a id='{note_id}-backref' href='#^{note_id}'>{id}</a></sup>"))
     }
-    
-    fn convert_factbox_note(&mut self, _: &FactBox, _: Option<&String>, _: &String) -> Result<String> {
-        cprintln!("<r>error:</> fact boxes aren't implemented for rss output");
-        Ok("".into())
+
+    fn visit_cross_reference(&mut self, ctx: &Context, kind: FigureKind, id: &String) -> Result<String> {
+        match ctx.figure(id) {
+            Some(figure) => Ok(format!("<a href='#{}'>{}</a>", sanitize_id(id), figure.label())),
+            None => Ok(format!("(MISSING {})", kind.name().to_uppercase())),
+        }
     }
 
-    fn convert_toc(&mut self) -> Result<String> {
-        todo!()
+    fn visit_toc(&mut self, _: &Context) -> Result<String> {
+        // feed readers have no use for a table of contents
+        Ok("".into())
     }
-    
-    fn convert_page_break(&mut self) -> Result<String> {
+
+    fn visit_page_break(&mut self, _: &Context) -> Result<String> {
         let mut result = self.tab();
 
         result += format!("<section class='page-break'>\n").as_str();
@@ -196,22 +333,20 @@ impl PMDSerializer for PMDRSSSerializer {
         Ok(result)
     }
 
-    fn convert(&mut self, md: &PawsMarkdown) -> Result<String> {
-        let title = &md.header.title;
-
-        for (key, val) in &md.references {
-            self.references.insert(key.clone(), Reference::new(val.clone()));
-        }
+    fn serialize(&mut self, md: &AssDownDocument) -> Result<String> {
+        let ctx = Context::new(md);
+        let title = sanitize_text(&md.meta.title);
+        self.theme = code_theme(&md.meta);
 
         let filename = self.filename.clone();
-        let date = if md.header.last_update.is_not_none() {
-                md.header.last_update.to_date().unwrap_or(chrono::Utc::now())
+        let date = if md.meta.last_update.is_not_none() {
+                md.meta.last_update.to_date().unwrap_or(chrono::Utc::now())
             } else {
-                md.header.date_written.to_date().unwrap_or(chrono::Utc::now())
+                md.meta.date_written.to_date().unwrap_or(chrono::Utc::now())
             }.to_rfc3339();
-        let url = &md.header.url;
-        // let data_dir = &md.header.data_dir;
-        let blog_dir = &md.header.blog_dir;
+        let url = &md.meta.url;
+        // let data_dir = &md.meta.data_dir;
+        let blog_dir = &md.meta.blog_dir;
 
         self.push_line("<entry>\n");
         self.push_tab();
@@ -222,15 +357,15 @@ impl PMDSerializer for PMDRSSSerializer {
         self.push_line("<content type=\"xhtml\">");
 
         self.push_tab();
-        for (element, id) in &md.body {
-            let result = self.convert_element((element, id))?;
+        for (element, id, _) in &md.body {
+            let result = self.visit_element(&ctx, element, id)?;
             self.push_line(result.trim_end());
         }
 
         if md.notes.len() != 0 {
             self.push_line("<hr>");
             for (key, val) in &md.notes {
-                let result = self.convert_element(no_id!(val))?;
+                let result = self.visit_inline(&ctx, val)?;
                 self.push_line("<p>");
                 self.push_tab();
                         self.push_line(format!("<sup>^{key}:</sup>{result}"));
@@ -239,14 +374,20 @@ impl PMDSerializer for PMDRSSSerializer {
             }
         }
 
+        if !(md.glossary.is_empty() || md.meta.hide_glossary) {
+            self.push_line("<hr>");
+            let list: Vec<Definition> = md.glossary.iter().map(|(_, definition)| definition.clone()).collect();
+            let result = self.visit_definition_list(&ctx, &list, &String::new())?;
+            self.push_line(result);
+        }
 
-        if self.references.len() != 0 {
+        if self.times_used.len() != 0 {
             self.push_line("<hr>");
-            for (_, val) in self.references.clone() {
-                if val.times_used == 0 { continue; }
+            for (key, val) in &md.references {
+                if !self.times_used.contains_key(key) { continue; }
                 self.push_line("<p>");
                 self.push_tab();
-                    self.push_line(to_bibliography(&val.def));
+                    self.push_line(to_bibliography(val));
                 self.pop_tab();
                 self.push_line("</p>");
             }
@@ -256,7 +397,57 @@ impl PMDSerializer for PMDRSSSerializer {
         self.push_line("</content>");
         self.pop_tab();
         self.push_line("</entry>");
-        
+
         Ok(self.output.clone())
-    }    
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::to_string;
+    use crate::parser::wiki_links::link_documents;
+
+    fn entry(frontmatter: &str, body: &str) -> String {
+        let (document, _) = parse(&format!("---\n{frontmatter}---\n{body}\n"), None).unwrap();
+        to_string(&document, PMDRSSSerializer::new("post")).unwrap()
+    }
+
+    #[test]
+    fn entry_fields() {
+        let rss = entry("title: Cats & <Dogs>\ndate: March 5, 2024\nurl: https://example.com\n", "fish < chips & **'peas'**\n");
+        assert_eq!(rss, concat!(
+            "<entry>\n",
+            "\n",
+            "    <title>Cats &amp; &lt;Dogs&gt;</title>\n",
+            "    <link href=\"https://example.com/blog/post\"/>\n",
+            "    <updated>2024-03-05T00:00:00+00:00</updated>\n",
+            "    <id>https://example.com/blog/post</id>\n",
+            "    <content type=\"xhtml\">\n",
+            "        <p>fish &lt; chips &amp; <b>'peas'</b></p>\n",
+            "    </content>\n",
+            "</entry>\n",
+        ));
+    }
+
+    #[test]
+    fn updated_date() {
+        // the last update wins over when it was written
+        let rss = entry("title: Cats\ndate: March 5, 2024\nlast-update: April 1, 2024\n", "hi\n");
+        assert_eq!(rss.lines().nth(4), Some("    <updated>2024-04-01T00:00:00+00:00</updated>"));
+    }
+
+    #[test]
+    fn wiki_link_escaping() {
+        let post = |body: &str| parse(&format!("---\ntitle: post\nurl: https://example.com\n---\n{body}\n"), None).unwrap().0;
+        let mut documents = vec![
+            ("first".to_string(), post("[[a<img src=x onerror=alert(1)>]] and [[second#x' onclick='alert(1)]]")),
+            ("second".to_string(), post("the other post")),
+        ];
+        link_documents(&mut documents);
+        let document = &documents[0].1;
+        let Element::Paragraph(text) = &document.body[0].0 else { panic!("expected a paragraph") };
+        let output = PMDRSSSerializer::new("first").visit_inline(&Context::new(document), text).unwrap();
+        assert_eq!(output.trim_end(), "a&lt;img src=x onerror=alert(1)&gt; and <a href='https://example.com/blog/second.html#x&#39;-onclick=&#39;alert(1)'>second#x' onclick='alert(1)</a>");
+    }
 }
@@ -1,107 +1,383 @@
+use anyhow::Result;
+use crate::parser::*;
 
-use crate::*;
-
-
-pub trait PMDSerializer {
-    // because rust is such a lovely language where I can't actually describe the link between
-    // the lifetime of factbox and id here, i'll have to copy them each time, hwo fucking lovely
-    fn current_factbox(&mut self) -> Option<(FactBox, Option<String>)>;
-
-    fn convert_hoverable(&mut self, hoverable: &Alternative) -> Result<String>;
-    fn convert_styled(&mut self, styled: &Alternative) -> Result<String>;
-    fn convert_link(&mut self, link: &Alternative) -> Result<String>;
-    fn convert_header(&mut self, text: &Box<BlogBody>, depth: usize, id: &String) -> Result<String>;
-    fn convert_italics(&mut self, text: &Box<BlogBody>) -> Result<String>;
-    fn convert_bold(&mut self, text: &Box<BlogBody>) -> Result<String>;
-    fn convert_inlinecode(&mut self, text: &String) -> Result<String>;
-    fn convert_codeblock(&mut self, text: &String, id: &String) -> Result<String>;
-    fn convert_image(&mut self, src: &String, alt: &String, id: &String) -> Result<String>;
-    fn convert_quote(&mut self, lines: &Vec<BlogBody>, id: &String) -> Result<String>;
-    fn convert_list(&mut self, list: &Vec<BlogBody>, id: &String) -> Result<String>;
-    fn convert_paragraph(&mut self, text: &Box<BlogBody>, id: &String) -> Result<String>;
-    fn convert_text(&mut self, text: &String) -> Result<String>;
-    fn convert_span(&mut self, span: &Span) -> Result<String>;
-    fn convert_citation(&mut self, citation: &String) -> Result<String>;
-    fn convert_note(&mut self, id: &String) -> Result<String>;
-    fn convert_toc(&mut self) -> Result<String>;
-    fn convert_page_break(&mut self) -> Result<String>;
-    // fn convert_embedded_link(&mut self, src: &String, alt: &String) -> Result<String>;
-
-    fn convert_factbox(&mut self, factbox: &FactBox, id: &String) -> Result<String>;
-    fn convert_factbox_note(&mut self, factbox: &FactBox, factbox_id: Option<&String>, id: &String) -> Result<String>;
-
-    fn convert_factbox_elements(&mut self, factbox: &FactBox, factbox_id: Option<&String>) -> Result<Vec<String>> {
-        let mut result = Vec::new();
-        for element in &factbox.body {
-            match element {
-                (BlogBody::FactBox(factbox), id)                    => result.push(self.convert_factbox(factbox, id)?),
-                (BlogBody::Hoverable(hoverable), _)            => result.push(self.convert_hoverable(hoverable)?),
-                (BlogBody::Styled(styled), _)                  => result.push(self.convert_styled(styled)?),
-                (BlogBody::Link(link), _)                      => result.push(self.convert_link(link)?),
-                (BlogBody::Header(text, depth), id)   => result.push(self.convert_header(text, *depth, id)?),
-                (BlogBody::Italics(text), _)                 => result.push(self.convert_italics(text)?),
-                (BlogBody::Bold(text), _)                    => result.push(self.convert_bold(text)?)   ,
-                (BlogBody::InlineCode(text), _)                     => result.push(self.convert_inlinecode(text)?) ,
-                (BlogBody::CodeBlock(text), id)                      => result.push(self.convert_codeblock(text, id)?)  ,
-                (BlogBody::Image(text, alt), id)            => result.push(self.convert_image(text, alt, id)?),
-                // BlogBody::EmbeddedLink(text, alt) => self.convert_embedresult.push(ded_link(text, alt),
-                (BlogBody::Quote(lines), id)                  => result.push(self.convert_quote(lines, id)?),
-                (BlogBody::List(list), id)                    => result.push(self.convert_list(list, id)?),
-                (BlogBody::Paragraph(text), id)               => result.push(self.convert_paragraph(text, id)?),
-                (BlogBody::Text(text), _)                           => result.push(self.convert_text(text)?),
-                (BlogBody::Span(span), _)                             => result.push(self.convert_span(span)?),
-                (BlogBody::Citation(text), _)                       => result.push(self.convert_citation(text)?),
-                (BlogBody::Note(text), _)                           => result.push(self.convert_factbox_note(factbox, factbox_id, text)?),
-                (BlogBody::TOCLocationMarker, _)                             => result.push(self.convert_toc()?),
-                (BlogBody::PageBreak, _)                                     => result.push(self.convert_page_break()?),
-            }
-        }
-        Ok(result)
-    }
-
-    fn convert_element(&mut self, element: (&BlogBody, &String)) -> Result<String> {
+// everything a serializer might need to look up while walking the tree, borrowed from the document
+// so backends don't have to copy references, notes or the factbox they're currently inside of
+pub struct Context<'a> {
+    pub document: &'a AssDownDocument,
+    pub factbox:  Option<(&'a FactBox, &'a String)>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(document: &'a AssDownDocument) -> Self {
+        Self { document, factbox: None }
+    }
+
+    pub fn meta(&self) -> &'a MetaData {
+        &self.document.meta
+    }
+
+    pub fn in_factbox<'b>(&self, factbox: &'b FactBox, id: &'b String) -> Context<'b>
+        where 'a: 'b
+    {
+        Context { document: self.document, factbox: Some((factbox, id)) }
+    }
+
+    pub fn reference(&self, id: &str) -> Option<&'a ReferenceDefinition> {
+        self.document.references.get(id)
+    }
+
+    pub fn figure(&self, id: &str) -> Option<&'a FigureNumber> {
+        self.document.figures.get(id)
+    }
+
+    /// where a wiki link goes, `None` until the posts are linked or when it's broken
+    pub fn wiki_link(&self, target: &str) -> Option<&'a String> {
+        self.document.wiki_links.get(target)
+    }
+
+    /// the title of a numbered code block, "Listing 2: main.rs" or just "Listing 2"
+    pub fn listing_title(&self, block: &CodeBlock, id: &str) -> Option<String> {
+        match (self.figure(id), &block.title) {
+            (Some(figure), Some(title)) => Some(format!("{}: {title}", figure.label())),
+            (Some(figure), None)        => Some(figure.label()),
+            (None, title)               => title.clone(),
+        }
+    }
+
+    // notes inside a factbox shadow the ones in the document
+    pub fn note(&self, id: &str) -> Option<&'a Element> {
+        if let Some((factbox, _)) = self.factbox && let Some((note, _)) = factbox.notes.get(id) {
+            return Some(note);
+        }
+        self.document.notes.get(id)
+    }
+
+    // the id a note is rendered with, factbox notes get their own so they don't collide with the document
+    pub fn note_id(&self, id: &String) -> String {
+        if let Some((factbox, _)) = self.factbox && let Some((_, note_id)) = factbox.notes.get(id) {
+            return note_id.clone();
+        }
+        id.clone()
+    }
+}
+
+pub trait Serializer {
+    type Output;
+
+    // the only things a backend has to provide, everything else defaults to walking the children
+    fn text(&mut self, ctx: &Context, text: &str) -> Result<Self::Output>;
+    fn join(&mut self, parts: Vec<Self::Output>) -> Self::Output;
+
+    fn empty(&mut self) -> Self::Output {
+        self.join(vec![])
+    }
+
+    fn visit_element(&mut self, ctx: &Context, element: &Element, id: &String) -> Result<Self::Output> {
         match element {
-            (BlogBody::FactBox(factbox), id)                    => self.convert_factbox(factbox, id),
-            (BlogBody::Hoverable(hoverable), _)    => self.convert_hoverable(hoverable),
-            (BlogBody::Styled(styled), _)          => self.convert_styled(styled),
-            (BlogBody::Link(link), _)              => self.convert_link(link),
-            (BlogBody::Header(text, depth), id)     => self.convert_header(text, *depth, id),
-            (BlogBody::Italics(text), _)           => self.convert_italics(text),
-            (BlogBody::Bold(text), _)              => self.convert_bold(text)   ,
-            (BlogBody::InlineCode(text), _)        => self.convert_inlinecode(text) ,
-            (BlogBody::CodeBlock(text), id)         => self.convert_codeblock(text, id)  ,
-            (BlogBody::Image(text, alt), id)        => self.convert_image(text, alt, id),
-            // BlogBody::EmbeddedLink(text, alt) => self.convert_embedded_link(text, alt),
-            (BlogBody::Quote(lines), id)            => self.convert_quote(lines, id),
-            (BlogBody::List(list), id)              => self.convert_list(list, id),
-            (BlogBody::Paragraph(text), id)         => self.convert_paragraph(text, id),
-            (BlogBody::Text(text), _)              => self.convert_text(text),
-            (BlogBody::Span(span), _)              => self.convert_span(span),
-            (BlogBody::Citation(text), _)          => self.convert_citation(text),
-            (BlogBody::Note(text), _)              => {
-                if let Some((factbox, id)) = self.current_factbox() {
-                    self.convert_factbox_note(&factbox, id.as_ref(), text)
-                } else {
-                    self.convert_note(text)
+            Element::FactBox(factbox)         => self.visit_factbox(ctx, factbox, id),
+            Element::Hoverable(hoverable)     => self.visit_hoverable(ctx, hoverable),
+            Element::Styled(styled)           => self.visit_styled(ctx, styled),
+            Element::Link(link)               => self.visit_link(ctx, link),
+            Element::Header(text, depth)      => self.visit_header(ctx, text, *depth, id),
+            Element::Italics(text)            => self.visit_italics(ctx, text),
+            Element::Bold(text)               => self.visit_bold(ctx, text),
+            Element::Strikethrough(text)      => self.visit_strikethrough(ctx, text),
+            Element::Underline(text)          => self.visit_underline(ctx, text),
+            Element::Highlight(text)          => self.visit_highlight(ctx, text),
+            Element::Superscript(text)        => self.visit_superscript(ctx, text),
+            Element::Subscript(text)          => self.visit_subscript(ctx, text),
+            Element::InlineCode(text)         => self.visit_inline_code(ctx, text),
+            Element::CodeBlock(block)         => self.visit_codeblock(ctx, block, id),
+            Element::InlineMath(tex)          => self.visit_inline_math(ctx, tex),
+            Element::MathBlock(tex)           => self.visit_math_block(ctx, tex, id),
+            Element::Image(image)             => self.visit_image(ctx, image, id),
+            Element::Video(video)             => self.visit_video(ctx, video, id),
+            Element::Audio(audio)             => self.visit_audio(ctx, audio, id),
+            Element::Iframe(iframe)           => self.visit_iframe(ctx, iframe, id),
+            Element::Quote(quote)             => self.visit_quote(ctx, quote, id),
+            Element::Callout(callout)         => self.visit_callout(ctx, callout, id),
+            Element::List(list)               => self.visit_list(ctx, list, id),
+            Element::Table(table)             => self.visit_table(ctx, table, id),
+            Element::DefinitionList(list)     => self.visit_definition_list(ctx, list, id),
+            Element::Paragraph(text)          => self.visit_paragraph(ctx, text, id),
+            Element::Text(text)               => self.visit_text(ctx, text),
+            Element::Span(span)               => self.visit_span(ctx, span),
+            Element::Citation(citation)       => self.visit_citation(ctx, citation),
+            Element::Note(note)               => self.visit_note(ctx, note),
+            Element::CrossReference(kind, id, _) => self.visit_cross_reference(ctx, *kind, id),
+            Element::WikiLink(link)           => self.visit_wiki_link(ctx, link),
+            Element::TOCLocationMarker        => self.visit_toc(ctx),
+            Element::PageBreak                => self.visit_page_break(ctx),
+            Element::Error(raw)               => self.visit_error(ctx, raw),
+        }
+    }
+
+    fn visit_inline(&mut self, ctx: &Context, element: &Element) -> Result<Self::Output> {
+        self.visit_element(ctx, element, &String::new())
+    }
+
+    fn visit_elements(&mut self, ctx: &Context, elements: &[Element]) -> Result<Self::Output> {
+        let mut parts = vec![];
+        for element in elements {
+            parts.push(self.visit_inline(ctx, element)?);
+        }
+        Ok(self.join(parts))
+    }
+
+    fn visit_body(&mut self, ctx: &Context, body: &[(Element, String, SourceSpan)]) -> Result<Self::Output> {
+        let mut parts = vec![];
+        for (element, id, _) in body {
+            parts.push(self.visit_element(ctx, element, id)?);
+        }
+        Ok(self.join(parts))
+    }
+
+    fn visit_factbox(&mut self, ctx: &Context, factbox: &FactBox, id: &String) -> Result<Self::Output> {
+        let ctx = ctx.in_factbox(factbox, id);
+        self.visit_body(&ctx, &factbox.body)
+    }
+
+    fn visit_hoverable(&mut self, ctx: &Context, hoverable: &Alternative) -> Result<Self::Output> {
+        self.visit_inline(ctx, &hoverable.base)
+    }
+
+    fn visit_styled(&mut self, ctx: &Context, styled: &Alternative) -> Result<Self::Output> {
+        self.visit_inline(ctx, &styled.alt)
+    }
+
+    fn visit_link(&mut self, ctx: &Context, link: &Alternative) -> Result<Self::Output> {
+        self.visit_inline(ctx, &link.base)
+    }
+
+    fn visit_header(&mut self, ctx: &Context, text: &Element, _depth: usize, _id: &String) -> Result<Self::Output> {
+        self.visit_inline(ctx, text)
+    }
+
+    fn visit_italics(&mut self, ctx: &Context, text: &Element) -> Result<Self::Output> {
+        self.visit_inline(ctx, text)
+    }
+
+    fn visit_bold(&mut self, ctx: &Context, text: &Element) -> Result<Self::Output> {
+        self.visit_inline(ctx, text)
+    }
+
+    fn visit_strikethrough(&mut self, ctx: &Context, text: &Element) -> Result<Self::Output> {
+        self.visit_inline(ctx, text)
+    }
+
+    fn visit_underline(&mut self, ctx: &Context, text: &Element) -> Result<Self::Output> {
+        self.visit_inline(ctx, text)
+    }
+
+    fn visit_highlight(&mut self, ctx: &Context, text: &Element) -> Result<Self::Output> {
+        self.visit_inline(ctx, text)
+    }
+
+    fn visit_superscript(&mut self, ctx: &Context, text: &Element) -> Result<Self::Output> {
+        self.visit_inline(ctx, text)
+    }
+
+    fn visit_subscript(&mut self, ctx: &Context, text: &Element) -> Result<Self::Output> {
+        self.visit_inline(ctx, text)
+    }
+
+    fn visit_inline_code(&mut self, ctx: &Context, code: &String) -> Result<Self::Output> {
+        self.text(ctx, code)
+    }
+
+    fn visit_codeblock(&mut self, ctx: &Context, block: &CodeBlock, _id: &String) -> Result<Self::Output> {
+        self.text(ctx, &block.code)
+    }
+
+    fn visit_inline_math(&mut self, ctx: &Context, tex: &String) -> Result<Self::Output> {
+        self.text(ctx, tex)
+    }
+
+    fn visit_math_block(&mut self, ctx: &Context, tex: &String, _id: &String) -> Result<Self::Output> {
+        self.text(ctx, tex)
+    }
+
+    fn visit_image(&mut self, ctx: &Context, image: &Image, _id: &String) -> Result<Self::Output> {
+        self.visit_inline(ctx, &image.alt)
+    }
+
+    fn visit_video(&mut self, ctx: &Context, video: &Media, _id: &String) -> Result<Self::Output> {
+        self.visit_inline(ctx, &video.title)
+    }
+
+    fn visit_audio(&mut self, ctx: &Context, audio: &Media, _id: &String) -> Result<Self::Output> {
+        self.visit_inline(ctx, &audio.title)
+    }
+
+    fn visit_iframe(&mut self, ctx: &Context, iframe: &Media, _id: &String) -> Result<Self::Output> {
+        self.visit_inline(ctx, &iframe.title)
+    }
+
+    fn visit_quote(&mut self, ctx: &Context, quote: &Quote, _id: &String) -> Result<Self::Output> {
+        let mut parts = vec![];
+        for Spanned { node: element, .. } in &quote.body {
+            parts.push(self.visit_element(ctx, element, &String::new())?);
+        }
+        if let Some(attribution) = &quote.attribution {
+            parts.push(self.visit_inline(ctx, attribution)?);
+        }
+        Ok(self.join(parts))
+    }
+
+    fn visit_callout(&mut self, ctx: &Context, callout: &Callout, _id: &String) -> Result<Self::Output> {
+        let mut parts = vec![self.visit_inline(ctx, &callout.title)?];
+        for Spanned { node: element, .. } in &callout.body {
+            parts.push(self.visit_element(ctx, element, &String::new())?);
+        }
+        Ok(self.join(parts))
+    }
+
+    fn visit_list(&mut self, ctx: &Context, list: &[ListItem], _id: &String) -> Result<Self::Output> {
+        let mut parts = vec![];
+        for item in list {
+            parts.push(self.visit_list_item(ctx, item)?);
+        }
+        Ok(self.join(parts))
+    }
+
+    fn visit_list_item(&mut self, ctx: &Context, item: &ListItem) -> Result<Self::Output> {
+        match item {
+            ListItem::List(list) => self.visit_list(ctx, list, &String::new()),
+            ListItem::Unordered(text, body)              |
+            ListItem::Numbered(_, text, body)            |
+            ListItem::Alphabetical(_, text, body)        |
+            ListItem::NumberedRounded(_, text, body)     |
+            ListItem::AlphabeticalRounded(_, text, body) => {
+                let mut parts = vec![self.visit_inline(ctx, text)?];
+                for Spanned { node: element, .. } in body {
+                    parts.push(self.visit_element(ctx, element, &String::new())?);
+                }
+                Ok(self.join(parts))
+            },
+            ListItem::Task { checked, text, body } => {
+                let mut parts = vec![
+                    self.text(ctx, if *checked { "\u{2611} " } else { "\u{2610} " })?,
+                    self.visit_inline(ctx, text)?,
+                ];
+                for Spanned { node: element, .. } in body {
+                    parts.push(self.visit_element(ctx, element, &String::new())?);
                 }
+                Ok(self.join(parts))
             },
-            (BlogBody::TOCLocationMarker, _)       => self.convert_toc(),
-            (BlogBody::PageBreak, _)               => self.convert_page_break(),
         }
     }
 
-    fn convert(&mut self, md: &PawsMarkdown) -> Result<String>;
-}
+    fn visit_table(&mut self, ctx: &Context, table: &Table, _id: &String) -> Result<Self::Output> {
+        let mut parts = vec![self.visit_elements(ctx, &table.header)?];
+        for row in &table.rows {
+            parts.push(self.visit_elements(ctx, row)?);
+        }
+        Ok(self.join(parts))
+    }
+
+    fn visit_definition_list(&mut self, ctx: &Context, list: &[Definition], _id: &String) -> Result<Self::Output> {
+        let mut parts = vec![];
+        for definition in list {
+            parts.push(self.visit_inline(ctx, &definition.term)?);
+            parts.push(self.visit_elements(ctx, &definition.definitions)?);
+        }
+        Ok(self.join(parts))
+    }
 
+    fn visit_paragraph(&mut self, ctx: &Context, text: &Element, _id: &String) -> Result<Self::Output> {
+        self.visit_inline(ctx, text)
+    }
 
-pub fn to_string<T>(md: &PawsMarkdown, mut serialiser: T) -> Result<String>
-    where T: PMDSerializer,
-{
-    serialiser.convert(md)
+    fn visit_text(&mut self, ctx: &Context, text: &String) -> Result<Self::Output> {
+        self.text(ctx, text)
+    }
+
+    fn visit_span(&mut self, ctx: &Context, span: &Span) -> Result<Self::Output> {
+        self.visit_elements(ctx, &span.elements)
+    }
+
+    fn visit_citation(&mut self, ctx: &Context, id: &String) -> Result<Self::Output> {
+        if let Some(reference) = ctx.reference(id) {
+            self.text(ctx, to_citation(reference).trim_end())
+        } else {
+            self.text(ctx, "(Missing Source)")
+        }
+    }
+
+    fn visit_cross_reference(&mut self, ctx: &Context, kind: FigureKind, id: &String) -> Result<Self::Output> {
+        match ctx.figure(id) {
+            Some(figure) => self.text(ctx, &figure.label()),
+            None => self.text(ctx, &format!("(Missing {})", kind.name())),
+        }
+    }
+
+    fn visit_wiki_link(&mut self, ctx: &Context, link: &WikiLink) -> Result<Self::Output> {
+        match &link.label {
+            Some(label) => self.visit_inline(ctx, label),
+            None => self.text(ctx, &link.target),
+        }
+    }
+
+    fn visit_note(&mut self, _ctx: &Context, _id: &String) -> Result<Self::Output> {
+        Ok(self.empty())
+    }
+
+    fn visit_toc(&mut self, _ctx: &Context) -> Result<Self::Output> {
+        Ok(self.empty())
+    }
+
+    fn visit_page_break(&mut self, _ctx: &Context) -> Result<Self::Output> {
+        Ok(self.empty())
+    }
+
+    /// by default whatever failed to parse is shown as it was written
+    fn visit_error(&mut self, ctx: &Context, raw: &String) -> Result<Self::Output> {
+        self.text(ctx, raw)
+    }
+
+    fn serialize(&mut self, document: &AssDownDocument) -> Result<Self::Output> {
+        let ctx = Context::new(document);
+        self.visit_body(&ctx, &document.body)
+    }
 }
 
-pub fn to_string_from_boxed<T>(md: &PawsMarkdown, mut serialiser: Box<T>) -> Result<String>
-    where T: PMDSerializer,
+pub fn to_string<T>(md: &AssDownDocument, mut serializer: T) -> Result<String>
+    where T: Serializer<Output = String>,
 {
-    serialiser.convert(md)
+    serializer.serialize(md)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a backend with only the required methods, it writes down every piece of text it's given
+    struct Recorder;
+
+    impl Serializer for Recorder {
+        type Output = Vec<String>;
+
+        fn text(&mut self, _: &Context, text: &str) -> Result<Vec<String>> {
+            Ok(vec![text.to_string()])
+        }
+
+        fn join(&mut self, parts: Vec<Vec<String>>) -> Vec<String> {
+            parts.concat()
+        }
+    }
+
+    #[test]
+    fn default_walker() {
+        let text = "# Title\n\nsome **bold** and ~~gone~~[^1]\n\n- [ ] item\n\n  with a body\n- [[other|a link]]\n\n> quoted\n>\n> more\n\n[^1]: a note\n\n".to_string();
+        let (document, _) = parse(&text, None).unwrap();
+        // blocks in order, each one's inline text before the blocks nested in it, and notes left out
+        assert_eq!(Recorder.serialize(&document).unwrap(), [
+            "Title",
+            "some ", "bold", " and ", "gone", "\n",
+            "\u{2610} ", "item", "with a body\n",
+            "a link",
+            "quoted\n", "more\n",
+        ]);
+    }
 }